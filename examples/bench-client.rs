@@ -0,0 +1,213 @@
+use std::time::{Duration, Instant};
+
+use codecrafters_kafka::{
+    api_versions::ApiVersionsReqeustBodyV4,
+    common_struct::{CompactString, NullableString, TagBuffer},
+    encode::Encode,
+    request_message::RequestHeaderV2,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// 需要先启动 server, 例如:
+// cargo run --release --example bench-client -- --connections 8 --requests 100000 --mix api_versions=2,fetch=1,describe=1
+struct BenchConfig {
+    addr: String,
+    connections: usize,
+    // 所有连接一共发送的请求数
+    requests: usize,
+    // 按权重展开后的请求类型, 每个连接依次循环发送
+    mix: Vec<RequestKind>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RequestKind {
+    ApiVersions,
+    Fetch,
+    DescribeTopicPartitions,
+}
+
+impl RequestKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "api_versions" => Some(RequestKind::ApiVersions),
+            "fetch" => Some(RequestKind::Fetch),
+            "describe" => Some(RequestKind::DescribeTopicPartitions),
+            _ => None,
+        }
+    }
+
+    // (api_key, api_version, body)
+    fn request(&self) -> (i16, i16, Vec<u8>) {
+        match self {
+            RequestKind::ApiVersions => (
+                18,
+                4,
+                ApiVersionsReqeustBodyV4 {
+                    client_id: CompactString::new("bench-client".to_string()),
+                    client_software_version: CompactString::new("0.1".to_string()),
+                    tag_buffer: TagBuffer::default(),
+                }
+                .encode(),
+            ),
+            RequestKind::Fetch => (
+                1,
+                16,
+                [
+                    &0_i32.to_be_bytes()[..], // max_wait_ms
+                    &0_i32.to_be_bytes(),     // min_bytes
+                    &i32::MAX.to_be_bytes(),  // max_bytes
+                    &[0x00],                  // isolation_level
+                    &0_i32.to_be_bytes(),     // session_id
+                    &(-1_i32).to_be_bytes(),  // session_epoch
+                    &[0x01, 0x01, 0x01],      // 空的 topics, forgotten_topics_data, rack_id
+                    &[0x00],                  // tag_buffer
+                ]
+                .concat(),
+            ),
+            RequestKind::DescribeTopicPartitions => (
+                75,
+                0,
+                [
+                    &[0x01][..],            // topics: 空的 compact array
+                    &100_i32.to_be_bytes(), // response_partition_limit
+                    &[0xff],                // cursor: null
+                    &[0x00],                // tag_buffer
+                ]
+                .concat(),
+            ),
+        }
+    }
+}
+
+fn parse_mix(value: &str) -> Option<Vec<RequestKind>> {
+    let mut mix = vec![];
+    for item in value.split(',') {
+        let (name, weight) = item.split_once('=').unwrap_or((item, "1"));
+        let kind = RequestKind::from_name(name.trim())?;
+        let weight = weight.trim().parse::<usize>().ok()?;
+        mix.extend(std::iter::repeat(kind).take(weight));
+    }
+    (!mix.is_empty()).then_some(mix)
+}
+
+impl BenchConfig {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut config = BenchConfig {
+            addr: "127.0.0.1:9092".to_string(),
+            connections: 4,
+            requests: 10_000,
+            mix: vec![RequestKind::ApiVersions],
+        };
+        while let Some(arg) = args.next() {
+            let value = args.next();
+            match (arg.as_str(), value.as_deref()) {
+                ("--addr", Some(addr)) => config.addr = addr.to_string(),
+                ("--connections", Some(n)) => match n.parse() {
+                    Ok(n) if n > 0 => config.connections = n,
+                    _ => panic!("Invalid value for --connections: {}", n),
+                },
+                ("--requests", Some(n)) => match n.parse() {
+                    Ok(n) if n > 0 => config.requests = n,
+                    _ => panic!("Invalid value for --requests: {}", n),
+                },
+                ("--mix", Some(mix)) => {
+                    config.mix =
+                        parse_mix(mix).unwrap_or_else(|| panic!("Invalid value for --mix: {}", mix))
+                }
+                (arg, value) => panic!("Unknown argument: {} {:?}", arg, value),
+            }
+        }
+        config
+    }
+}
+
+fn request_bytes(kind: RequestKind, correlation_id: i32) -> Vec<u8> {
+    let (request_api_key, request_api_version, body) = kind.request();
+    let header = RequestHeaderV2 {
+        request_api_key,
+        request_api_version,
+        correlation_id,
+        client_id: NullableString::new(Some("bench-client".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+    let payload = [header.encode(), body].concat();
+    [(payload.len() as u32).to_be_bytes().to_vec(), payload].concat()
+}
+
+// 每次发送一个请求并等待响应, 返回每个请求的延迟
+async fn run_connection(addr: String, requests: usize, mix: Vec<RequestKind>) -> Vec<Duration> {
+    let mut stream = TcpStream::connect(&addr)
+        .await
+        .unwrap_or_else(|err| panic!("Failed to connect to {}: {}", addr, err));
+    let mut latencies = Vec::with_capacity(requests);
+    let mut response = vec![];
+    for (correlation_id, kind) in mix.iter().cycle().take(requests).enumerate() {
+        let correlation_id = correlation_id as i32;
+        let request = request_bytes(*kind, correlation_id);
+        let start = Instant::now();
+        stream
+            .write_all(&request)
+            .await
+            .expect("Failed to send request");
+        let message_size = stream.read_u32().await.expect("Failed to read size");
+        response.resize(message_size as usize, 0);
+        stream
+            .read_exact(&mut response)
+            .await
+            .expect("Failed to read response");
+        latencies.push(start.elapsed());
+        let response_correlation_id = i32::from_be_bytes(response[..4].try_into().unwrap());
+        assert_eq!(response_correlation_id, correlation_id, "{:?}", kind);
+    }
+    latencies
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let index = (sorted.len() * percent / 100).min(sorted.len() - 1);
+    sorted[index]
+}
+
+#[tokio::main]
+async fn main() {
+    let config = BenchConfig::from_args(std::env::args().skip(1));
+
+    let start = Instant::now();
+    let mut tasks = vec![];
+    for connection in 0..config.connections {
+        // 请求数不能整除时, 前面的连接多发送一个
+        let requests = config.requests / config.connections
+            + usize::from(connection < config.requests % config.connections);
+        tasks.push(tokio::spawn(run_connection(
+            config.addr.clone(),
+            requests,
+            config.mix.clone(),
+        )));
+    }
+    let mut latencies = vec![];
+    for task in tasks {
+        latencies.append(&mut task.await.expect("Connection task panicked"));
+    }
+    let elapsed = start.elapsed();
+
+    latencies.sort();
+    println!(
+        "{} requests over {} connections in {:.2?}",
+        latencies.len(),
+        config.connections,
+        elapsed
+    );
+    println!(
+        "throughput: {:.0} requests/sec",
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    if !latencies.is_empty() {
+        println!(
+            "latency: p50 {:.2?}, p99 {:.2?}",
+            percentile(&latencies, 50),
+            percentile(&latencies, 99)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// 每个 example 只用到其中一部分
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    acl::Session,
+    decode::{Decode, DecodeResult},
+    request_message::{RequestBody, RequestMessage},
+    response_message::{execute_request, ResponseMessage},
+};
+
+// decode 之后 cursor 必须恰好停在 expected_len, 少读或多读都会使后面的字段错位
+pub fn assert_consumes<T>(
+    bytes: &[u8],
+    decode: impl FnOnce(&mut Cursor<&[u8]>) -> DecodeResult<T>,
+    expected_len: usize,
+) -> T {
+    let mut buffer = Cursor::new(bytes);
+    let decoded = decode(&mut buffer).unwrap_or_else(|err| panic!("Failed to decode: {}", err));
+    assert_eq!(
+        buffer.position() as usize,
+        expected_len,
+        "decode consumed {} bytes, expect {}",
+        buffer.position(),
+        expected_len
+    );
+    decoded
+}
+
+// message_size + request header + body
+fn request_frame(header: &[u8], body: &[u8]) -> Vec<u8> {
+    [
+        &((header.len() + body.len()) as u32).to_be_bytes()[..],
+        header,
+        body,
+    ]
+    .concat()
+}
+
+// flexible 版本的请求使用 request header v2
+pub fn request_message(api_key: i16, api_version: i16, body: &[u8]) -> Vec<u8> {
+    let header = [
+        &api_key.to_be_bytes()[..],            // request_api_key
+        &api_version.to_be_bytes(),            // request_api_version
+        &7_i32.to_be_bytes(),                  // correlation_id
+        &[0x00, 0x04, b'd', b'e', b'm', b'o'], // client_id
+        &[0x00],                               // tag_buffer
+    ]
+    .concat();
+    request_frame(&header, body)
+}
+
+// 非 flexible 版本的请求使用 request header v1, 没有 tag_buffer
+pub fn request_message_v1(api_key: i16, api_version: i16, body: &[u8]) -> Vec<u8> {
+    let header = [
+        &api_key.to_be_bytes()[..],            // request_api_key
+        &api_version.to_be_bytes(),            // request_api_version
+        &7_i32.to_be_bytes(),                  // correlation_id
+        &[0x00, 0x04, b'd', b'e', b'm', b'o'], // client_id
+    ]
+    .concat();
+    request_frame(&header, body)
+}
+
+// 请求必须被完整 decode, 并且是 broker 认识的 api
+pub fn decode_request(message: &[u8]) -> RequestMessage {
+    let mut buffer = Cursor::new(message);
+    let request = RequestMessage::decode(&mut buffer).expect("Failed to decode request");
+    assert_eq!(buffer.position() as usize, message.len());
+    assert!(
+        !matches!(request.body, RequestBody::Unknown(_)),
+        "Request body is not decoded: {:?}",
+        request
+    );
+    request
+}
+
+// 返回 execute_request 写回客户端的字节
+pub async fn execute_bytes(request: &RequestMessage) -> Vec<u8> {
+    let mut response = execute_request(request, &Session::default())
+        .await
+        .expect("Failed to execute request");
+    response.as_bytes()
+}
+
+// decode 请求, 经过 execute_request 处理后重新 decode 响应
+pub async fn execute(message: &[u8]) -> ResponseMessage {
+    let request = decode_request(message);
+    let response_bytes = execute_bytes(&request).await;
+    ResponseMessage::decode(
+        &mut Cursor::new(response_bytes.as_slice()),
+        request.header.request_api_key(),
+    )
+    .expect("Failed to decode response")
+}
@@ -0,0 +1,73 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    acl::{AclEntry, AclPermission, AclStore, ANONYMOUS_PRINCIPAL},
+    describe_topic_partitions::TopicAuthorizedOperations,
+};
+
+fn main() {
+    // 没有 ACL 时返回默认的全部操作
+    let acl_store = AclStore::default();
+    assert_eq!(
+        acl_store
+            .authorized_operations(ANONYMOUS_PRINCIPAL, "foo")
+            .bits(),
+        TopicAuthorizedOperations::default().bits()
+    );
+
+    let path = std::env::temp_dir().join("demo-acl.txt");
+    fs::write(
+        &path,
+        "# principal topic operations permission\n\
+         User:alice * Read,Write allow\n\
+         User:alice secret Read deny\n\
+         * public Describe allow\n\
+         User:admin * All allow\n\
+         User:admin audit Alter_Configs deny\n",
+    )
+    .expect("Failed to write acl file");
+    let mut acl_store = AclStore::load(&path).expect("Failed to load acl file");
+
+    // READ/WRITE 隐含 DESCRIBE
+    assert_eq!(
+        acl_store.authorized_operations("User:alice", "foo"),
+        TopicAuthorizedOperations::READ
+            | TopicAuthorizedOperations::WRITE
+            | TopicAuthorizedOperations::DESCRIBE
+    );
+    // deny 优先
+    assert_eq!(
+        acl_store.authorized_operations("User:alice", "secret"),
+        TopicAuthorizedOperations::WRITE | TopicAuthorizedOperations::DESCRIBE
+    );
+    assert_eq!(
+        acl_store.authorized_operations(ANONYMOUS_PRINCIPAL, "public"),
+        TopicAuthorizedOperations::DESCRIBE
+    );
+    assert!(acl_store
+        .authorized_operations(ANONYMOUS_PRINCIPAL, "foo")
+        .is_empty());
+    assert_eq!(
+        acl_store.authorized_operations("User:admin", "foo").bits(),
+        TopicAuthorizedOperations::default().bits()
+    );
+    assert!(!acl_store
+        .authorized_operations("User:admin", "audit")
+        .contains(TopicAuthorizedOperations::ALTER_CONFIGS));
+
+    acl_store.add(AclEntry {
+        principal: ANONYMOUS_PRINCIPAL.to_string(),
+        topic: "foo".to_string(),
+        operations: TopicAuthorizedOperations::ALTER_CONFIGS,
+        permission: AclPermission::Allow,
+    });
+    assert_eq!(
+        acl_store.authorized_operations(ANONYMOUS_PRINCIPAL, "foo"),
+        TopicAuthorizedOperations::ALTER_CONFIGS | TopicAuthorizedOperations::DESCRIBE_CONFIGS
+    );
+
+    assert!(AclStore::load(&path.with_extension("missing")).is_err());
+    fs::write(&path, "User:alice foo Fly allow\n").expect("Failed to write acl file");
+    assert!(AclStore::load(&path).is_err());
+    println!("acl checks passed");
+}
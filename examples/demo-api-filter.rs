@@ -0,0 +1,159 @@
+use codecrafters_kafka::{
+    api_versions::{
+        advertised_apis, cap_api_versions, check_api_version_ranges,
+        max_version_for_metadata_version, ApiKey, MetadataVersion, API_VERSIONS_API_INFO,
+        SUPPORT_APIS,
+    },
+    broker_config::BrokerConfig,
+    common_struct::{CompactString, FeatureLevelRecord, RecordType, RecordValue, TagBuffer},
+    describe_topic_partitions::DESCRIBE_TOPIC_PARTITIONS_API_INFO,
+    fetch::FETCH_API_INFO,
+    metadata_log::{
+        init_internal_states, metadata_version, new_metadata_record, new_metadata_record_batch,
+        MetadataLog, METADATA_VERSION_FEATURE,
+    },
+    request_message::decodable_api_versions,
+};
+
+fn config(args: &[&str]) -> BrokerConfig {
+    BrokerConfig::from_args(args.iter().map(|arg| arg.to_string()))
+}
+
+fn api_keys(config: &BrokerConfig) -> Vec<i16> {
+    advertised_apis(config)
+        .iter()
+        .map(|api_info| api_info.api_key)
+        .collect()
+}
+
+fn main() {
+    // 默认宣告的就是实现的 api
+    let mut implemented: Vec<i16> = SUPPORT_APIS.keys().copied().collect();
+    implemented.sort();
+    assert_eq!(api_keys(&BrokerConfig::default()), implemented);
+
+    // 隐藏已实现的 Fetch
+    let disabled = config(&["--disable-api", "1"]);
+    assert!(!api_keys(&disabled).contains(&FETCH_API_INFO.api_key));
+    assert_eq!(api_keys(&disabled).len(), implemented.len() - 1);
+
+    // 宣告没有实现的 Metadata
+    let enabled = config(&["--enable-api", "3"]);
+    assert!(api_keys(&enabled).contains(&3));
+    // 已实现的 api 保持原来的版本范围
+    let enabled = config(&["--enable-api", "1"]);
+    let fetch = advertised_apis(&enabled)
+        .into_iter()
+        .find(|api_info| api_info.api_key == FETCH_API_INFO.api_key)
+        .expect("Fetch should be advertised");
+    assert_eq!(fetch.max_version, FETCH_API_INFO.max_version);
+
+    // 同一个 api key 以最后的参数为准, 非法的值被忽略
+    let last_wins = config(&[
+        "--disable-api",
+        "1",
+        "--enable-api",
+        "1",
+        "--disable-api",
+        "-5",
+    ]);
+    assert_eq!(api_keys(&last_wins), implemented);
+    let last_wins = config(&["--enable-api", "1", "--disable-api", "1"]);
+    assert!(!api_keys(&last_wins).contains(&1));
+    println!("{:?}", api_keys(&enabled));
+
+    check_implemented_versions();
+    check_metadata_version();
+}
+
+// 宣告的版本范围与能够 decode 的版本完全一致, 启动时的 check_api_version_ranges 也不报错
+fn check_implemented_versions() {
+    for api_info in SUPPORT_APIS.values() {
+        assert_eq!(
+            decodable_api_versions(api_info.api_key),
+            Some((api_info.min_version, api_info.max_version)),
+            "api key {}",
+            api_info.api_key
+        );
+    }
+    assert_eq!(
+        (FETCH_API_INFO.min_version, FETCH_API_INFO.max_version),
+        (16, 16)
+    );
+    check_api_version_ranges().unwrap();
+    println!("advertised versions match implemented versions");
+}
+
+fn metadata_version_log(feature_level: i16) -> MetadataLog {
+    let record = FeatureLevelRecord {
+        frame_version: 1,
+        record_type: RecordType::FEATURE_LEVEL_RECORD,
+        version: 0,
+        name: CompactString::new(METADATA_VERSION_FEATURE.to_string()),
+        feature_level,
+        tag_buffers: TagBuffer::default(),
+    };
+    MetadataLog::new(vec![new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(0, RecordValue::FeatureLevel(record))],
+    )])
+}
+
+fn check_metadata_version() {
+    // 没有 FeatureLevelRecord 时使用最高的 feature level, 不限制任何 api
+    assert_eq!(metadata_version(), MetadataVersion::LATEST);
+    let latest = advertised_apis(&BrokerConfig::default());
+    assert_eq!(
+        version_ranges(&cap_api_versions(latest.clone(), MetadataVersion::LATEST)),
+        version_ranges(&latest)
+    );
+
+    // 低的 metadata.version 降低 Fetch 的最高版本
+    assert_eq!(
+        max_version_for_metadata_version(1, 17, MetadataVersion::IBP_3_5_IV1),
+        Some(15)
+    );
+    assert_eq!(
+        max_version_for_metadata_version(1, 17, MetadataVersion::IBP_3_1_IV0),
+        Some(13)
+    );
+    // 不受限制的 api 保持原来的版本
+    assert_eq!(
+        max_version_for_metadata_version(18, 4, MetadataVersion::IBP_3_0_IV1),
+        Some(4)
+    );
+    // 3.7 之前没有 DescribeTopicPartitions
+    assert_eq!(
+        max_version_for_metadata_version(75, 0, MetadataVersion::IBP_3_5_IV1),
+        None
+    );
+
+    // 从 metadata log 读取 feature level, 只实现了 Fetch v16, 上限为 15 时不再宣告 Fetch
+    init_internal_states(&metadata_version_log(MetadataVersion::IBP_3_5_IV1));
+    assert_eq!(metadata_version(), MetadataVersion::IBP_3_5_IV1);
+    let capped = advertised_apis(&BrokerConfig::default());
+    let capped_keys: Vec<i16> = capped.iter().map(|api_info| api_info.api_key).collect();
+    assert!(!capped_keys.contains(&FETCH_API_INFO.api_key));
+    assert!(!capped_keys.contains(&DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key));
+    let api_versions = capped
+        .iter()
+        .find(|api_info| api_info.api_key == API_VERSIONS_API_INFO.api_key)
+        .expect("ApiVersions should be advertised");
+    assert_eq!(api_versions.max_version, API_VERSIONS_API_INFO.max_version);
+
+    init_internal_states(&metadata_version_log(MetadataVersion::IBP_3_7_IV0));
+    assert_eq!(
+        version_ranges(&advertised_apis(&BrokerConfig::default())),
+        version_ranges(&latest)
+    );
+    println!("metadata.version caps passed");
+}
+
+// ApiKey 的 PartialEq 只比较 api_key, 需要同时比较版本范围
+fn version_ranges(api_keys: &[ApiKey]) -> Vec<(i16, i16, i16)> {
+    api_keys
+        .iter()
+        .map(|api_info| (api_info.api_key, api_info.min_version, api_info.max_version))
+        .collect()
+}
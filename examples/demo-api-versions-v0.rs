@@ -0,0 +1,56 @@
+mod common;
+
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    api_versions::{ApiVersionsResponseBodyV0, ApiVersionsResponseBodyV1, SUPPORT_APIS},
+    decode::Decode,
+    request_message::{request_api_versions, RequestBody, RequestHeader, RequestMessage},
+};
+
+use common::{decode_request, execute_bytes, request_message_v1};
+
+// ApiVersionsRequest v0 到 v2 的 body 为空, 返回 response header 之后的 body
+async fn api_versions(request_api_version: i16) -> Vec<u8> {
+    let request = decode_request(&request_message_v1(18, request_api_version, &[]));
+    assert!(matches!(request.header, RequestHeader::RequestHeaderV1(_)));
+    assert!(matches!(request.body, RequestBody::ApiVersionsV0(_)));
+    let response_bytes = execute_bytes(&request).await;
+    // message_size(4) + correlation_id(4), ApiVersions 的响应使用 v0 header
+    assert_eq!(&response_bytes[4..8], &7_i32.to_be_bytes());
+    response_bytes[8..].to_vec()
+}
+
+#[tokio::main]
+async fn main() {
+    let body = api_versions(0).await;
+    let mut buffer = Cursor::new(body.as_slice());
+    let body_v0 = ApiVersionsResponseBodyV0::decode(&mut buffer).expect("Invalid v0 response");
+    assert_eq!(buffer.position() as usize, body.len());
+    assert_eq!(body_v0.error_code(), 0);
+    let api_keys = body_v0.api_keys().as_ref().unwrap();
+    assert_eq!(api_keys.len(), SUPPORT_APIS.len());
+    assert!(api_keys
+        .iter()
+        .any(|api_key| api_key.api_key == 18 && api_key.min_version == 0));
+
+    // v1, v2 多了 throttle_time_ms
+    for request_api_version in [1, 2] {
+        let body = api_versions(request_api_version).await;
+        let mut buffer = Cursor::new(body.as_slice());
+        let body_v1 = ApiVersionsResponseBodyV1::decode(&mut buffer).expect("Invalid v1 response");
+        assert_eq!(buffer.position() as usize, body.len());
+        assert_eq!(body_v1.error_code(), 0);
+        assert_eq!(body_v1.api_keys().as_ref().unwrap(), api_keys);
+    }
+
+    // 客户端构造的 v0 请求同样使用 v1 header
+    let bytes = request_api_versions(0).as_bytes();
+    let mut buffer = Cursor::new(bytes.as_slice());
+    let request = RequestMessage::decode(&mut buffer).expect("Failed to decode request");
+    assert_eq!(buffer.position() as usize, bytes.len());
+    assert!(matches!(request.header, RequestHeader::RequestHeaderV1(_)));
+    assert!(matches!(request.body, RequestBody::ApiVersionsV0(_)));
+
+    println!("ApiVersions v0 to v2 requests are answered");
+}
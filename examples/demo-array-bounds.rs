@@ -0,0 +1,88 @@
+mod common;
+
+use std::{
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use codecrafters_kafka::{
+    common_struct::{Array, CompactArray, CompactString, VarInt},
+    decode::Decode,
+    encode::Encode,
+    request_message::{RequestBody, RequestMessage},
+};
+
+use common::request_message;
+
+// topics, allow_auto_topic_creation, include_topic_authorized_operations, tag_buffer
+const METADATA_BODY: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+
+fn main() {
+    // 声称有 i32::MAX 个元素, 实际只有 10 个字节, 在 decode 元素之前直接拒绝
+    let bytes = [&i32::MAX.to_be_bytes()[..], &[0x00; 10]].concat();
+    let start = Instant::now();
+    let err = Array::<i32>::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("Array with bogus length should be rejected");
+    assert!(err.is_incomplete());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    let bytes = [VarInt::from_u64(1_000_001).encode().as_slice(), &[0x01; 10]].concat();
+    let start = Instant::now();
+    let err = CompactArray::<CompactString>::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("CompactArray with bogus length should be rejected");
+    assert!(err.is_incomplete());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // 个数没有超过剩余字节数, 但元素不完整时在第一个出错的元素处停止
+    let bytes = [&3_i32.to_be_bytes()[..], &[0x00; 6]].concat();
+    let err = Array::<i32>::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("Array with truncated elements should be rejected");
+    assert!(err.is_incomplete());
+
+    // 正常的数组不受影响
+    let array = Array::from_vec(vec![1_i32, 2, 3]);
+    let bytes = array.encode();
+    let decoded =
+        Array::<i32>::decode(&mut Cursor::new(bytes.as_slice())).expect("Failed to decode array");
+    assert_eq!(decoded, array);
+
+    // 完整的请求中数组长度错误时直接返回错误, 不会等待永远不会到达的字节
+    let bogus_count = [VarInt::from_u64(1_000_001).encode().as_slice(), &[0x00; 3]].concat();
+    let message = request_message(3, 12, &bogus_count);
+    let start = Instant::now();
+    let err = RequestMessage::decode(&mut Cursor::new(message.as_slice()))
+        .expect_err("Request with bogus array length should be rejected");
+    assert!(err.is_other(), "{}", err);
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // 请求还没有完整时仍然等待更多字节
+    let message = request_message(3, 12, &METADATA_BODY);
+    let err = RequestMessage::decode(&mut Cursor::new(&message[..message.len() - 1]))
+        .expect_err("Partial request should be incomplete");
+    assert!(err.is_incomplete());
+
+    // body 比 message_size 短时不会读入下一个请求, 跳过整个请求后下一个请求不受影响
+    let pipelined = [
+        request_message(3, 12, &METADATA_BODY[..3]),
+        request_message(3, 12, &METADATA_BODY),
+    ]
+    .concat();
+    let mut buffer = Cursor::new(pipelined.as_slice());
+    let err = RequestMessage::decode(&mut buffer).expect_err("Short body should be rejected");
+    assert!(err.is_other(), "{}", err);
+    assert_eq!(
+        buffer.position() as usize,
+        request_message(3, 12, &METADATA_BODY[..3]).len()
+    );
+    let request = RequestMessage::decode(&mut buffer).expect("Failed to decode next request");
+    assert!(matches!(request.body, RequestBody::MetadataV12(_)));
+    assert_eq!(buffer.position() as usize, pipelined.len());
+
+    // body 之后还有多余的字节
+    let message = request_message(3, 12, &[&METADATA_BODY[..], &[0x00]].concat());
+    let err = RequestMessage::decode(&mut Cursor::new(message.as_slice()))
+        .expect_err("Trailing bytes should be rejected");
+    assert!(err.is_other(), "{}", err);
+
+    println!("array decoding rejects bogus lengths up front");
+}
@@ -0,0 +1,76 @@
+use codecrafters_kafka::{
+    common_struct::{CompactString, RecordBatch, RecordType, RecordValue, TagBuffer, TopicRecord},
+    metadata_log::{
+        check_batch_offsets, init_internal_states, new_metadata_record, new_metadata_record_batch,
+        MetadataLog, TOPIC_RECORD_BATCH_MAP,
+    },
+};
+use uuid::Uuid;
+
+const TOPIC: &str = "demo-batch-offsets";
+
+// 包含 record_count 个 record 的 batch
+fn record_batch(base_offset: i64, record_count: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 4])))
+            .collect(),
+    )
+}
+
+// 第一个 record 是 TopicRecord, 之后补充 record_count - 1 个其他 record
+fn topic_batch(id: Uuid, record_count: usize) -> RecordBatch {
+    let mut records = vec![new_metadata_record(
+        0,
+        RecordValue::Topic(TopicRecord {
+            frame_version: 1,
+            record_type: RecordType::TOPIC_RECORD,
+            version: 0,
+            name: CompactString::new(TOPIC.to_string()),
+            id,
+            tag_buffers: TagBuffer::default(),
+        }),
+    )];
+    for idx in 1..record_count {
+        records.push(new_metadata_record(
+            idx as i32,
+            RecordValue::Unknown(vec![0xab; 4]),
+        ));
+    }
+    new_metadata_record_batch(0, 0, records)
+}
+
+fn main() {
+    // 每个 batch 的 base_offset 是前一个 batch 的最后一个 offset + 1
+    let batches = vec![record_batch(0, 3), record_batch(3, 2), record_batch(5, 4)];
+    assert_eq!(batches[0].next_offset(), 3);
+    assert_eq!(batches[2].next_offset(), 9);
+    check_batch_offsets(&batches).expect("Consistent batches should be accepted");
+    check_batch_offsets(&[]).expect("Empty log should be accepted");
+
+    // base_offset 按 batch 下标递增, 或者与前一个 batch 重叠, 都是错误的
+    assert!(check_batch_offsets(&[record_batch(0, 3), record_batch(1, 2)]).is_err());
+    assert!(check_batch_offsets(&[record_batch(0, 3), record_batch(2, 2)]).is_err());
+    assert!(check_batch_offsets(&[record_batch(0, 3), record_batch(4, 2)]).is_err());
+
+    // init_internal_states 按 record 的个数累加 base_offset
+    let id = Uuid::new_v4();
+    init_internal_states(&MetadataLog::new(vec![
+        topic_batch(id, 3),
+        topic_batch(id, 2),
+        topic_batch(id, 4),
+    ]));
+    let topic_record_batch_map = TOPIC_RECORD_BATCH_MAP
+        .lock()
+        .expect("Failed to get TOPIC_RECORD_BATCH_MAP lock");
+    let batches = topic_record_batch_map
+        .get(&CompactString::new(TOPIC.to_string()))
+        .expect("Topic should be loaded");
+    let base_offsets: Vec<i64> = batches.iter().map(|batch| batch.base_offset).collect();
+    assert_eq!(base_offsets, [0, 3, 5]);
+    check_batch_offsets(batches).expect("Loaded batches should have consistent offsets");
+
+    println!("batch base offsets follow the record count of previous batches");
+}
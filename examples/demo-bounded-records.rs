@@ -0,0 +1,75 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        MetadataAttributes, RecordBatch, RecordValue, RECORD_BATCH_CRC_START,
+        RECORD_BATCH_RECORDS_START,
+    },
+    crc::crc32c,
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+};
+
+// 一条 value 为 "hello" 的 record, 使用 gzip 压缩, records 的个数本身不压缩
+#[rustfmt::skip]
+const GZIP_BATCH: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_offset: int64
+    0x00, 0x00, 0x00, 0x51,                         // batch_length: int32
+    0x00, 0x00, 0x00, 0x00,                         // partition_leader_epoch: int32
+    0x02,                                           // magic: int8
+    0x08, 0x56, 0xa4, 0x8c,                         // crc: uint32
+    0x00, 0x01,                                     // attributes: int16, gzip
+    0x00, 0x00, 0x00, 0x00,                         // last_offset_delta: int32
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // base_timestamp: int64
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // max_timestamp: int64
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // producer_id: int64
+    0xff, 0xff,                                     // producer_epoch: int16
+    0xff, 0xff, 0xff, 0xff,                         // base_sequence: int32
+    0x00, 0x00, 0x00, 0x01,                         // records 个数: int32
+    // gzip 压缩后的 record, 比原始的 12 个字节更长
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x13, 0x63,
+    0x60, 0x60, 0x60, 0xe4, 0xca, 0x48, 0xcd, 0xc9, 0xc9, 0x67, 0x00, 0x00,
+    0x3e, 0xa9, 0x07, 0x97, 0x0c, 0x00, 0x00, 0x00,
+];
+
+// 把压缩算法改为不支持的 snappy, 并重新计算 crc
+fn snappy_batch() -> Vec<u8> {
+    let mut batch = GZIP_BATCH.to_vec();
+    batch[RECORD_BATCH_CRC_START + 1] = MetadataAttributes::SNAPPY.bits() as u8;
+    let crc = crc32c(&batch[RECORD_BATCH_CRC_START..]);
+    batch[17..21].copy_from_slice(&crc.to_be_bytes());
+    batch
+}
+
+fn main() {
+    let next_batch = new_metadata_record_batch(
+        1,
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 8]))],
+    );
+    let snappy_batch = snappy_batch();
+    let log_content = [snappy_batch.as_slice(), next_batch.encode().as_slice()].concat();
+
+    // 不支持的压缩算法无法解析, 但不会越过 batch 的边界, 后面的 batch 仍然可以 decode
+    let mut buffer = Cursor::new(log_content.as_slice());
+    let err = RecordBatch::decode(&mut buffer).expect_err("Expect compressed batch error");
+    assert!(err.is_other());
+    println!("{}", err);
+    assert_eq!(buffer.position() as usize, snappy_batch.len());
+    let decoded = RecordBatch::decode(&mut buffer).expect("Failed to decode next batch");
+    assert_eq!(decoded.encode(), next_batch.encode());
+    assert_eq!(buffer.position() as usize, log_content.len());
+
+    // records 的个数大于 batch 中实际的 record 时, 在 batch 结尾停止
+    let mut overcounted = next_batch.encode();
+    overcounted[RECORD_BATCH_RECORDS_START..RECORD_BATCH_RECORDS_START + 4]
+        .copy_from_slice(&3_i32.to_be_bytes());
+    let crc = crc32c(&overcounted[RECORD_BATCH_CRC_START..]);
+    overcounted[17..21].copy_from_slice(&crc.to_be_bytes());
+    let log_content = [overcounted.as_slice(), GZIP_BATCH].concat();
+    let mut buffer = Cursor::new(log_content.as_slice());
+    let decoded = RecordBatch::decode(&mut buffer).expect("Failed to decode overcounted batch");
+    assert_eq!(decoded.records.as_ref().map(Vec::len), Some(1));
+    assert_eq!(buffer.position() as usize, overcounted.len());
+}
@@ -0,0 +1,43 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use codecrafters_kafka::{
+    clock::{now_millis, set_clock, Clock, FixedClock, SystemClock},
+    common_struct::{RecordValue, TimestampType},
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+};
+
+fn main() {
+    // 默认使用系统时间
+    let system_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_millis() as i64;
+    assert!((now_millis() - system_now).abs() < 1000);
+    assert!((SystemClock.now_millis() - system_now).abs() < 1000);
+
+    let clock = Arc::new(FixedClock::new(1_726_045_973_899));
+    set_clock(clock.clone());
+    assert_eq!(now_millis(), 1_726_045_973_899);
+    clock.advance(100);
+    assert_eq!(now_millis(), 1_726_045_973_999);
+    clock.set(0);
+    assert_eq!(now_millis(), 0);
+
+    // LogAppendTime 使用注入的时钟, 结果是确定的
+    clock.set(1_726_045_973_899);
+    let mut record_batch = new_metadata_record_batch(
+        0,
+        now_millis() - 5000,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 8]))],
+    );
+    record_batch.apply_timestamp_type(TimestampType::LogAppendTime, now_millis());
+    assert_eq!(record_batch.base_timestamp, 1_726_045_968_899);
+    assert_eq!(record_batch.max_timestamp, 1_726_045_973_899);
+
+    set_clock(Arc::new(SystemClock));
+    assert!((now_millis() - system_now).abs() < 1000);
+    println!("clock checks passed");
+}
@@ -0,0 +1,68 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{CompactArray, CompactMap, CompactNullableString, CompactString},
+    decode::Decode,
+    encode::Encode,
+};
+
+fn key(name: &str) -> CompactString {
+    CompactString::new(name.to_string())
+}
+
+fn value(value: Option<&str>) -> CompactNullableString {
+    CompactNullableString::new(value.map(str::to_string))
+}
+
+fn keys(map: &CompactMap<CompactString, CompactNullableString>) -> Vec<&str> {
+    map.iter().map(|(k, _)| k.as_str()).collect()
+}
+
+fn main() {
+    let mut configs = CompactMap::default();
+    assert!(configs
+        .insert(key("retention.ms"), value(Some("1000")))
+        .is_none());
+    assert!(configs.insert(key("cleanup.policy"), value(None)).is_none());
+    assert!(configs
+        .insert(key("segment.bytes"), value(Some("1024")))
+        .is_none());
+
+    // 已存在的 key 原地替换, 返回旧的值, entry 的顺序不变
+    let replaced = configs.insert(key("retention.ms"), value(Some("2000")));
+    assert_eq!(replaced, Some(value(Some("1000"))));
+    assert_eq!(
+        keys(&configs),
+        ["retention.ms", "cleanup.policy", "segment.bytes"]
+    );
+    assert_eq!(
+        configs.get(&key("retention.ms")),
+        Some(&value(Some("2000")))
+    );
+    assert_eq!(configs.get(&key("cleanup.policy")), Some(&value(None)));
+    assert_eq!(configs.get(&key("missing")), None);
+
+    // 与 CompactArray<(K, V)> 的编码相同
+    let entries = CompactArray::from_vec(configs.iter().cloned().collect());
+    let bytes = configs.encode();
+    assert_eq!(bytes, entries.encode());
+    assert_eq!(bytes.len(), configs.encoded_len());
+
+    // decode 后保持原来的顺序
+    let mut buffer = Cursor::new(bytes.as_slice());
+    let decoded = CompactMap::<CompactString, CompactNullableString>::decode(&mut buffer)
+        .expect("Failed to decode compact map");
+    assert_eq!(buffer.position() as usize, bytes.len());
+    assert_eq!(decoded, configs);
+    assert_eq!(keys(&decoded), keys(&configs));
+
+    // null 的 map 中没有任何 entry
+    let null_map =
+        CompactMap::<CompactString, CompactNullableString>::decode(&mut Cursor::new(&[0x00][..]))
+            .expect("Failed to decode null compact map");
+    assert_eq!(null_map.get(&key("retention.ms")), None);
+    assert_eq!(null_map.iter().count(), 0);
+    assert_eq!(null_map.encode(), [0x00]);
+
+    println!("compact map round-trips and keeps entry order");
+}
@@ -0,0 +1,24 @@
+use codecrafters_kafka::crc::crc32c;
+
+fn main() {
+    // RFC 3720 (iSCSI) B.4 中的 CRC32C 测试向量
+    let ascending: Vec<u8> = (0..32).collect();
+    let descending: Vec<u8> = (0..32).rev().collect();
+    let vectors: [(&str, &[u8], u32); 6] = [
+        ("empty", &[], 0x0000_0000),
+        ("check", b"123456789", 0xe306_9283),
+        ("32 bytes of zeros", &[0x00; 32], 0x8a91_36aa),
+        ("32 bytes of ones", &[0xff; 32], 0x62a8_ab43),
+        ("32 bytes incrementing", &ascending, 0x46dd_794e),
+        ("32 bytes decrementing", &descending, 0x113f_db5c),
+    ];
+    for (name, bytes, expected) in vectors {
+        let crc = crc32c(bytes);
+        assert_eq!(
+            crc, expected,
+            "{}: {:#010x} != {:#010x}",
+            name, crc, expected
+        );
+        println!("{}: {:#010x}", name, crc);
+    }
+}
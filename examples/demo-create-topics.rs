@@ -0,0 +1,102 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::CompactString,
+    create_topics::{INVALID_REPLICATION_FACTOR_ERROR, TOPIC_ALREADY_EXISTS_ERROR},
+    encode::Encode,
+    metadata_log::{init_read_metadata_log, partition_log_path, topic_id},
+    response_message::ResponseBody,
+};
+use uuid::Uuid;
+
+use common::{execute, request_message};
+
+// CreateTopicsRequest v7: 一个 topic, 没有 assignments 和 configs
+// 返回 (error_code, topic_id, num_partitions)
+async fn create_topic(
+    name: &str,
+    num_partitions: i32,
+    replication_factor: i16,
+) -> (i16, Uuid, i32) {
+    let body = [
+        &[0x02][..],                                    // topics
+        &CompactString::new(name.to_string()).encode(), // name
+        &num_partitions.to_be_bytes(),                  // num_partitions
+        &replication_factor.to_be_bytes(),              // replication_factor
+        &[0x01],                                        // assignments
+        &[0x01],                                        // configs
+        &[0x00],                                        // tag_buffer
+        &1000_i32.to_be_bytes(),                        // timeout_ms
+        &[0x00],                                        // validate_only
+        &[0x00],                                        // tag_buffer
+    ]
+    .concat();
+    let response = execute(&request_message(19, 7, &body)).await;
+    let ResponseBody::CreateTopicsV7(body) = response.body() else {
+        panic!("Expect create topics response, got {:?}", response);
+    };
+    let topic = &body.topics().as_ref().unwrap()[0];
+    assert_eq!(topic.name(), name);
+    (topic.error_code(), topic.topic_id(), topic.num_partitions())
+}
+
+// 返回 (error_code, topic_id, partition 数量)
+async fn describe_topic(name: &str) -> (i16, Uuid, usize) {
+    let body = [
+        &[0x02][..],                                    // topics
+        &CompactString::new(name.to_string()).encode(), // name
+        &[0x00],                                        // tag_buffer
+        &100_i32.to_be_bytes(),                         // response_partition_limit
+        &[0xff],                                        // cursor: null
+        &[0x00],                                        // tag_buffer
+    ]
+    .concat();
+    let response = execute(&request_message(75, 0, &body)).await;
+    let ResponseBody::DescribeTopicPartitionsV0(body) = response.body() else {
+        panic!(
+            "Expect describe topic partitions response, got {:?}",
+            response
+        );
+    };
+    let topic = &body.topics().as_ref().unwrap()[0];
+    assert_eq!(topic.name(), name);
+    (topic.error_code(), topic.id(), topic.partition_count())
+}
+
+#[tokio::main]
+async fn main() {
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    // metadata log 会保留之前运行时创建的 topic
+    let topic = format!("demo-create-topics-{}", Uuid::new_v4());
+    let topic = topic.as_str();
+
+    let (error_code, created_id, num_partitions) = create_topic(topic, 3, 1).await;
+    assert_eq!((error_code, num_partitions), (0, 3));
+    assert_eq!(topic_id(topic), Some(created_id));
+    assert_eq!(describe_topic(topic).await, (0, created_id, 3));
+
+    // 重新读取 metadata log 后 topic 仍然存在
+    init_read_metadata_log().expect("Failed to read metadata log");
+    assert_eq!(describe_topic(topic).await, (0, created_id, 3));
+
+    // 已存在的 topic 不会重复创建
+    let (error_code, _, _) = create_topic(topic, 1, 1).await;
+    assert_eq!(error_code, TOPIC_ALREADY_EXISTS_ERROR);
+    assert_eq!(topic_id(topic), Some(created_id));
+
+    // 只有一个 broker
+    let (error_code, _, _) = create_topic("demo-create-topics-replicated", 1, 3).await;
+    assert_eq!(error_code, INVALID_REPLICATION_FACTOR_ERROR);
+    assert_eq!(topic_id("demo-create-topics-replicated"), None);
+
+    println!("created topics are visible to DescribeTopicPartitions");
+}
@@ -1,6 +1,9 @@
 #![allow(unused)]
 
+use std::io::Cursor;
+
 use codecrafters_kafka::{
+    common_struct::{self, Array, CompactArray, CompactString, Presence, TagBuffer, TagSection},
     decode::{self, Decode},
     encode::Encode,
 };
@@ -16,6 +19,280 @@ struct MyStruct1 {
 
 struct MyStruct2(i32, i32);
 
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Pair(i32, i16);
+
+// cached_len 不属于协议内容, decode 时不读取 buffer
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WithSkip {
+    value: i32,
+    #[kafka(skip)]
+    cached_len: usize,
+}
+
+// throttle_time_ms 从 v1 开始出现, cluster_id 只在 v0 到 v2 出现
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Versioned {
+    error_code: i16,
+    #[kafka(since = 1)]
+    throttle_time_ms: i32,
+    #[kafka(since = 0, until = 2)]
+    cluster_id: i8,
+}
+
+// 容器中的 struct 按外层的版本 encode/decode
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct NestedVersioned {
+    compact: CompactArray<Versioned>,
+    array: Array<Versioned>,
+    presence: Presence<Versioned>,
+}
+
+// 生成的 impl 带有 T: Encode / T: Decode 的约束
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Wrapper<T> {
+    items: CompactArray<T>,
+}
+
+// rack_id 为 Some 时写入 tag 0, 为 None 时 tagged fields 为空
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WithTagged {
+    broker_id: i32,
+    #[kafka(tagged = 0)]
+    rack_id: Option<CompactString>,
+}
+
+// 不认识的 tag 保存在 unknown 中, encode 时原样写回
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WithUnknownTags {
+    broker_id: i32,
+    #[kafka(tagged = 1)]
+    rack_id: Option<CompactString>,
+    #[kafka(unknown_tags)]
+    unknown: TagBuffer,
+}
+
+// 没有字段, encode 为空, decode 不读取 buffer
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Empty;
+
+// tag 使用 i16, 未指定 tag 的 variant 使用下标
+#[derive(Debug, PartialEq, Encode, Decode)]
+#[kafka(tag_type = i16)]
+enum MyEnum {
+    #[kafka(tag = -1)]
+    Empty,
+    Pair(i32, i8),
+    #[kafka(tag = 7)]
+    Named {
+        a: i64,
+        b: i16,
+    },
+}
+
 fn main() {
     let a = MyStruct2(1, 2);
+
+    let pair = Pair(7, -1);
+    let encoded = pair.encode();
+    assert_eq!(encoded, [0x00, 0x00, 0x00, 0x07, 0xff, 0xff]);
+    assert_eq!(
+        Pair::decode(&mut Cursor::new(encoded.as_slice())).unwrap(),
+        pair
+    );
+
+    for (value, expected) in [
+        (MyEnum::Empty, vec![0xff, 0xff]),
+        (
+            MyEnum::Pair(5, -2),
+            vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0xfe],
+        ),
+        (
+            MyEnum::Named { a: 9, b: 3 },
+            [
+                &[0x00, 0x07][..],
+                &9_i64.to_be_bytes(),
+                &3_i16.to_be_bytes(),
+            ]
+            .concat(),
+        ),
+    ] {
+        let encoded = value.encode();
+        assert_eq!(encoded, expected, "{:?}", value);
+        assert_eq!(value.encoded_len(), expected.len(), "{:?}", value);
+        let mut out = vec![0xab];
+        value.encode_into(&mut out);
+        assert_eq!(out, [&[0xab][..], &expected].concat(), "{:?}", value);
+        let decoded = MyEnum::decode(&mut Cursor::new(encoded.as_slice())).unwrap();
+        assert_eq!(decoded, value);
+    }
+    let with_skip = WithSkip {
+        value: 3,
+        cached_len: 4,
+    };
+    assert_eq!(with_skip.encode(), [0x00, 0x00, 0x00, 0x03]);
+    assert_eq!(with_skip.encoded_len(), 4);
+    // 后面多出的字节不会被 skip 的字段读取
+    let bytes = [0x00, 0x00, 0x00, 0x03, 0xff, 0xff];
+    let mut buffer = Cursor::new(bytes.as_slice());
+    assert_eq!(
+        WithSkip::decode(&mut buffer).unwrap(),
+        WithSkip {
+            value: 3,
+            cached_len: 0,
+        }
+    );
+    assert_eq!(buffer.position(), 4);
+
+    let versioned = Versioned {
+        error_code: 0,
+        throttle_time_ms: 100,
+        cluster_id: 7,
+    };
+    let v0 = versioned.encode_versioned(0);
+    let v3 = versioned.encode_versioned(3);
+    assert_eq!(v0, [0x00, 0x00, 0x07]);
+    assert_eq!(v3, [0x00, 0x00, 0x00, 0x00, 0x00, 0x64]);
+    assert_eq!(
+        versioned.encode_versioned(1),
+        [0x00, 0x00, 0x00, 0x00, 0x00, 0x64, 0x07]
+    );
+    // 不在版本范围内的字段 decode 为默认值
+    assert_eq!(
+        Versioned::decode_versioned(&mut Cursor::new(v0.as_slice()), 0).unwrap(),
+        Versioned {
+            throttle_time_ms: 0,
+            ..versioned
+        }
+    );
+    assert_eq!(
+        Versioned::decode_versioned(&mut Cursor::new(v3.as_slice()), 3).unwrap(),
+        Versioned {
+            cluster_id: 0,
+            ..versioned
+        }
+    );
+
+    let nested = NestedVersioned {
+        compact: CompactArray::from_vec(vec![Versioned { ..versioned }]),
+        array: Array::new(Some(vec![Versioned { ..versioned }])),
+        presence: Presence::new(Some(Versioned { ..versioned })),
+    };
+    let nested_v0 = nested.encode_versioned(0);
+    assert_eq!(
+        nested_v0,
+        [
+            0x02, 0x00, 0x00, 0x07, // compact
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x07, // array
+            0x01, 0x00, 0x00, 0x07, // presence
+        ]
+    );
+    let nested_v3 = nested.encode_versioned(3);
+    assert_eq!(
+        nested_v3,
+        [
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, // compact
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, // array
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, // presence
+        ]
+    );
+    let expected = |item: Versioned| NestedVersioned {
+        compact: CompactArray::from_vec(vec![Versioned { ..item }]),
+        array: Array::new(Some(vec![Versioned { ..item }])),
+        presence: Presence::new(Some(item)),
+    };
+    assert_eq!(
+        NestedVersioned::decode_versioned(&mut Cursor::new(nested_v0.as_slice()), 0).unwrap(),
+        expected(Versioned {
+            throttle_time_ms: 0,
+            ..versioned
+        })
+    );
+    assert_eq!(
+        NestedVersioned::decode_versioned(&mut Cursor::new(nested_v3.as_slice()), 3).unwrap(),
+        expected(Versioned {
+            cluster_id: 0,
+            ..versioned
+        })
+    );
+
+    // 未知的 tag 返回错误
+    assert!(MyEnum::decode(&mut Cursor::new([0x00, 0x02].as_slice()))
+        .unwrap_err()
+        .is_other());
+    println!("enum derive round trip passed");
+
+    let wrapper = Wrapper {
+        items: CompactArray::from_vec(vec![1_i16, -1]),
+    };
+    let encoded = wrapper.encode();
+    assert_eq!(encoded, [0x03, 0x00, 0x01, 0xff, 0xff]);
+    assert_eq!(wrapper.encoded_len(), encoded.len());
+    assert_eq!(
+        Wrapper::<i16>::decode(&mut Cursor::new(encoded.as_slice())).unwrap(),
+        wrapper
+    );
+    println!("generic derive round trip passed");
+
+    let with_tagged = WithTagged {
+        broker_id: 1,
+        rack_id: Some(CompactString::new("r1".to_string())),
+    };
+    let encoded = with_tagged.encode();
+    assert_eq!(
+        encoded,
+        [
+            0x00, 0x00, 0x00, 0x01, // broker_id
+            0x01, // tagged fields 个数
+            0x00, 0x03, 0x03, b'r', b'1', // tag 0, size 3, rack_id
+        ]
+    );
+    assert_eq!(with_tagged.encoded_len(), encoded.len());
+    assert_eq!(
+        WithTagged::decode(&mut Cursor::new(encoded.as_slice())).unwrap(),
+        with_tagged
+    );
+
+    let without_tagged = WithTagged {
+        broker_id: 1,
+        rack_id: None,
+    };
+    assert_eq!(without_tagged.encode(), [0x00, 0x00, 0x00, 0x01, 0x00]);
+    // 不认识的 tag 被忽略
+    let unknown_tag = [0x00, 0x00, 0x00, 0x01, 0x01, 0x05, 0x01, 0xab];
+    assert_eq!(
+        WithTagged::decode(&mut Cursor::new(unknown_tag.as_slice())).unwrap(),
+        without_tagged
+    );
+    println!("tagged field round trip passed");
+
+    // 新版本客户端加入的 tag 0 和 tag 2 分布在已知的 tag 1 两侧
+    let newer_client = [
+        0x00, 0x00, 0x00, 0x01, // broker_id
+        0x03, // tagged fields 个数
+        0x00, 0x01, 0xaa, // tag 0
+        0x01, 0x03, 0x03, b'r', b'1', // tag 1, rack_id
+        0x02, 0x02, 0xbb, 0xcc, // tag 2
+    ];
+    let decoded = WithUnknownTags::decode(&mut Cursor::new(newer_client.as_slice())).unwrap();
+    assert_eq!(decoded.rack_id, Some(CompactString::new("r1".to_string())));
+    assert_eq!(
+        decoded.unknown,
+        TagBuffer::new(vec![
+            TagSection::new(0, vec![0xaa]),
+            TagSection::new(2, vec![0xbb, 0xcc]),
+        ])
+    );
+    assert_eq!(decoded.encode(), newer_client);
+    assert_eq!(decoded.encoded_len(), newer_client.len());
+    assert_eq!(decoded.encode_versioned(0), newer_client);
+    println!("unknown tags round trip passed");
+
+    assert!(Empty.encode().is_empty());
+    assert_eq!(Empty.encoded_len(), 0);
+    let empty: &[u8] = &[];
+    let mut cursor = Cursor::new(empty);
+    assert_eq!(Empty::decode(&mut cursor).unwrap(), Empty);
+    assert_eq!(cursor.position(), 0);
+    println!("unit struct round trip passed");
 }
@@ -0,0 +1,22 @@
+use codecrafters_kafka::{
+    common_struct::{CompactArray, CompactString},
+    describe_topic_partitions::{TopicAuthorizedOperations, TopicInfo},
+    encode::Encode,
+};
+use uuid::Uuid;
+
+fn main() {
+    // 没有 partition 的 topic, 响应中是空数组而不是 null
+    let mut topic_info = TopicInfo {
+        name: CompactString::new("empty".to_string()),
+        id: Uuid::new_v4(),
+        is_internal: false,
+        partitions_array: CompactArray::null(),
+        topic_authorized_operations: TopicAuthorizedOperations::default(),
+    };
+    assert_eq!(topic_info.response_partitions().encode(), [0x01]);
+
+    topic_info.partitions_array = CompactArray::empty();
+    assert_eq!(topic_info.response_partitions().encode(), [0x01]);
+    println!("empty topic partitions encode as an empty array");
+}
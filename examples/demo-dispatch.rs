@@ -0,0 +1,98 @@
+mod common;
+
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    acl::Session,
+    api_versions::{request_header_version, SUPPORT_APIS},
+    decode::Decode,
+    request_message::{RequestBody, RequestMessage},
+    response_message::{execute_request, ResponseBody},
+};
+
+use common::request_message;
+
+// 每个 api 最新版本的最小请求 body, 新增 api 时需要在这里添加
+fn request_body(api_key: i16) -> Vec<u8> {
+    match api_key {
+        // Produce: transactional_id, acks, timeout_ms, topic_data, tag_buffer
+        0 => [&[0x00][..], &[0x00; 2], &[0x00; 4], &[0x01], &[0x00]].concat(),
+        // Fetch: max_wait_ms, min_bytes, max_bytes, isolation_level, session_id, session_epoch,
+        // topics, forgotten_topics_data, rack_id, tag_buffer
+        1 => [
+            &[0x00; 4][..],
+            &[0x00; 4],
+            &[0x00; 4],
+            &[0x00],
+            &[0x00; 4],
+            &(-1_i32).to_be_bytes(),
+            &[0x01, 0x01, 0x01, 0x00],
+        ]
+        .concat(),
+        // Metadata: topics, allow_auto_topic_creation, include_topic_authorized_operations, tag_buffer
+        3 => vec![0x01, 0x00, 0x00, 0x00],
+        // OffsetCommit: group_id, generation_id_or_member_epoch, member_id, group_instance_id,
+        // topics, tag_buffer
+        8 => [&[0x01][..], &[0x00; 4], &[0x01, 0x00, 0x01, 0x00]].concat(),
+        // OffsetFetch: groups, require_stable, tag_buffer
+        9 => vec![0x01, 0x00, 0x00],
+        // FindCoordinator: key_type, coordinator_keys, tag_buffer
+        10 => vec![0x00, 0x01, 0x00],
+        // ApiVersions: client_id, client_software_version, tag_buffer
+        18 => vec![0x01, 0x01, 0x00],
+        // CreateTopics: topics, timeout_ms, validate_only, tag_buffer
+        19 => [&[0x01][..], &[0x00; 4], &[0x00, 0x00]].concat(),
+        // InitProducerId: transactional_id, transaction_timeout_ms, producer_id, producer_epoch,
+        // tag_buffer
+        22 => [
+            &[0x00][..],
+            &[0x00; 4],
+            &(-1_i64).to_be_bytes(),
+            &(-1_i16).to_be_bytes(),
+            &[0x00],
+        ]
+        .concat(),
+        // OffsetForLeaderEpoch: replica_id, topics, tag_buffer
+        23 => [&(-1_i32).to_be_bytes()[..], &[0x01, 0x00]].concat(),
+        // AlterConfigs, IncrementalAlterConfigs: resources, validate_only, tag_buffer
+        33 | 44 => vec![0x01, 0x00, 0x00],
+        // DescribeTopicPartitions: topics, response_partition_limit, cursor, tag_buffer
+        75 => [&[0x01][..], &[0x00; 4], &[0xff, 0x00]].concat(),
+        api_key => panic!("No sample request for api key {}", api_key),
+    }
+}
+
+fn sample_request(api_key: i16, api_version: i16) -> RequestMessage {
+    // 所有 api 的最新版本都是 flexible 版本, 使用 request header v2
+    assert_eq!(request_header_version(api_key, api_version), 2);
+    let message = request_message(api_key, api_version, &request_body(api_key));
+    let mut buffer = Cursor::new(message.as_slice());
+    let request = RequestMessage::decode(&mut buffer)
+        .unwrap_or_else(|err| panic!("Failed to decode api key {}: {:?}", api_key, err));
+    assert_eq!(buffer.position() as usize, message.len());
+    assert!(
+        !matches!(request.body, RequestBody::Unknown(_)),
+        "api key {} is advertised but its request body is not decoded",
+        api_key
+    );
+    request
+}
+
+#[tokio::main]
+async fn main() {
+    // execute_request 的分支就是 dispatch 的唯一来源, 每个宣告的 api 都要真正经过它处理
+    for api_info in SUPPORT_APIS.values() {
+        let api_key = api_info.api_key;
+        let request = sample_request(api_key, api_info.max_version);
+        let response = execute_request(&request, &Session::default())
+            .await
+            .unwrap_or_else(|err| panic!("api key {} has no handler: {}", api_key, err));
+        assert!(
+            !matches!(response.body(), ResponseBody::Unknown(_)),
+            "api key {} returns an unknown response",
+            api_key
+        );
+    }
+
+    println!("every advertised api is dispatched by execute_request");
+}
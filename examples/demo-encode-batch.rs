@@ -0,0 +1,99 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        Array, MetadataAttributes, RecordBatch, RecordValue, NO_PRODUCER_EPOCH, NO_PRODUCER_ID,
+        NO_SEQUENCE, RECORD_BATCH_MAGIC,
+    },
+    crc::crc32c,
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{new_metadata_record, LOG_OVERHEAD},
+};
+
+// 手动构造的 batch, batch_length 和 crc 都没有设置
+fn hand_built_batch(record_count: usize) -> RecordBatch {
+    RecordBatch {
+        base_offset: 5,
+        batch_length: 0,
+        partition_leader_epoch: 0,
+        magic_byte: RECORD_BATCH_MAGIC,
+        crc: 0,
+        attributes: MetadataAttributes::empty(),
+        last_offset_delta: record_count as i32 - 1,
+        base_timestamp: 0x0191e05af818,
+        max_timestamp: 0x0191e05af818,
+        producer_id: NO_PRODUCER_ID,
+        producer_epoch: NO_PRODUCER_EPOCH,
+        base_sequence: NO_SEQUENCE,
+        records: Array::from_vec(
+            (0..record_count)
+                .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 4])))
+                .collect(),
+        ),
+        compressed_records: None,
+    }
+}
+
+fn main() {
+    for record_count in [1, 3] {
+        let record_batch = hand_built_batch(record_count);
+        let bytes = record_batch.encode();
+        assert_eq!(bytes.len(), record_batch.encoded_len());
+
+        // encode 时根据实际内容计算 batch_length 和 crc
+        let batch_length = i32::from_be_bytes(bytes[8..12].try_into().unwrap());
+        assert_eq!(batch_length as usize, bytes.len() - LOG_OVERHEAD);
+        let crc = u32::from_be_bytes(bytes[17..21].try_into().unwrap());
+        assert_eq!(crc, crc32c(&bytes[21..]));
+        assert_eq!(crc, record_batch.compute_crc());
+
+        let mut buffer = Cursor::new(bytes.as_slice());
+        let decoded = RecordBatch::decode(&mut buffer).expect("Failed to decode record batch");
+        assert_eq!(buffer.position() as usize, bytes.len());
+        assert_eq!(decoded.batch_length, batch_length);
+        assert_eq!(decoded.crc as u32, crc);
+        assert_eq!(decoded.records.as_ref().map(Vec::len), Some(record_count));
+        assert_eq!(decoded.encode(), bytes);
+    }
+
+    // 修改 crc 范围内的字段后不需要手动更新 crc
+    let mut record_batch = hand_built_batch(1);
+    record_batch.attributes |= MetadataAttributes::TIMESTAMP_TYPE;
+    let bytes = record_batch.encode();
+    let decoded = RecordBatch::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect("Failed to decode modified record batch");
+    assert!(decoded
+        .attributes
+        .contains(MetadataAttributes::TIMESTAMP_TYPE));
+
+    // 构造后再追加 record, last_offset_delta 和 batch_length 仍然是构造时的值
+    let mut record_batch = hand_built_batch(1);
+    let mut records = record_batch.records.as_ref().unwrap().clone();
+    records.extend((1..4).map(|idx| new_metadata_record(idx, RecordValue::Unknown(vec![0xcd]))));
+    record_batch.records = Array::from_vec(records);
+    assert_eq!(record_batch.last_offset_delta, 0);
+    assert!(record_batch.try_encode().is_err());
+    let bytes = record_batch.encode();
+    let decoded = RecordBatch::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect("Failed to decode extended record batch");
+    assert_eq!(decoded.last_offset_delta, 3);
+    assert_eq!(decoded.batch_length as usize, bytes.len() - LOG_OVERHEAD);
+    assert_eq!(decoded.records.as_ref().map(Vec::len), Some(4));
+    assert_eq!(decoded.next_offset(), record_batch.base_offset + 4);
+    assert!(decoded.try_encode().is_ok());
+
+    // 不支持的压缩算法不压缩 records, 同时清除 attributes 中的压缩算法
+    let mut record_batch = hand_built_batch(2);
+    record_batch.attributes |= MetadataAttributes::SNAPPY | MetadataAttributes::TIMESTAMP_TYPE;
+    let bytes = record_batch.encode();
+    let decoded = RecordBatch::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect("Failed to decode snappy record batch");
+    assert!(!decoded.is_compressed());
+    assert!(decoded
+        .attributes
+        .contains(MetadataAttributes::TIMESTAMP_TYPE));
+    assert_eq!(decoded.records.as_ref().map(Vec::len), Some(2));
+
+    println!("encoded record batches carry a valid batch_length, last_offset_delta and crc");
+}
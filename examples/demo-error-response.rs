@@ -0,0 +1,82 @@
+mod common;
+
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    acl::Session,
+    api_versions::{POLICY_VIOLATION_ERROR, UNSUPPORTED_VERSION_ERROR},
+    common_struct::CompactString,
+    encode::Encode,
+    request_message::{request_api_versions, RequestBody, RequestMessage},
+    response_message::{error_response, execute_request, ResponseBody, ResponseMessage},
+};
+
+use common::{decode_request, request_message};
+
+const TOPIC: &str = "demo-error-response";
+
+// ProduceRequest: 一个 topic, 两个 records 为 null 的 partition
+fn produce_request(request_api_version: i16) -> RequestMessage {
+    let body = [
+        &[0x00][..],                                     // transactional_id
+        &(-1_i16).to_be_bytes(),                         // acks
+        &1000_i32.to_be_bytes(),                         // timeout_ms
+        &[0x02],                                         // topic_data
+        &CompactString::new(TOPIC.to_string()).encode(), // name
+        &[0x03],                                         // partition_data
+        &0_i32.to_be_bytes(),                            // index
+        &[0x00, 0x00],                                   // records, tag_buffer
+        &1_i32.to_be_bytes(),                            // index
+        &[0x00, 0x00],                                   // records, tag_buffer
+        &[0x00, 0x00],                                   // 两层 tag_buffer
+    ]
+    .concat();
+    let request = decode_request(&request_message(0, request_api_version, &body));
+    assert!(matches!(request.body, RequestBody::ProduceV9(_)));
+    request
+}
+
+// 错误响应按照 Produce 的响应结构解析, 每个 partition 都带有错误码
+fn assert_produce_error(mut response: ResponseMessage, error_code: i16) {
+    assert_eq!(response.header().version(), 1);
+    let response_bytes = response.as_bytes();
+    let response = ResponseMessage::decode(&mut Cursor::new(response_bytes.as_slice()), 0)
+        .expect("Failed to decode produce response");
+    let ResponseBody::ProduceV9(body) = response.body() else {
+        panic!("Expect produce response, got {:?}", response);
+    };
+    let topic_response = &body.responses().as_ref().unwrap()[0];
+    assert_eq!(topic_response.name().as_str(), TOPIC);
+    let partition_responses = topic_response.partition_responses().as_ref().unwrap();
+    let partitions: Vec<(i32, i16)> = partition_responses
+        .iter()
+        .map(|partition| (partition.index(), partition.error_code()))
+        .collect();
+    assert_eq!(partitions, vec![(0, error_code), (1, error_code)]);
+}
+
+#[tokio::main]
+async fn main() {
+    // --read-only 拒绝 produce 时返回 Produce 的响应结构
+    let request = produce_request(9);
+    assert_produce_error(
+        error_response(&request, POLICY_VIOLATION_ERROR),
+        POLICY_VIOLATION_ERROR,
+    );
+
+    // handler 检查出不支持的版本时同样返回 Produce 的响应结构
+    let request = produce_request(10);
+    let response = execute_request(&request, &Session::default())
+        .await
+        .expect("Failed to execute produce request");
+    assert_produce_error(response, UNSUPPORTED_VERSION_ERROR);
+
+    // 只有 ApiVersions 请求返回 ApiVersions 的 body
+    let response = error_response(&request_api_versions(4), UNSUPPORTED_VERSION_ERROR);
+    assert_eq!(response.header().version(), 0);
+    assert!(matches!(response.body(), ResponseBody::ApiVersionsV4(_)));
+    let response = error_response(&request_api_versions(0), UNSUPPORTED_VERSION_ERROR);
+    assert!(matches!(response.body(), ResponseBody::ApiVersionsV0(_)));
+
+    println!("error responses use the response structure of the requested api");
+}
@@ -0,0 +1,107 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    broker_config::BrokerConfig,
+    common_struct::{
+        MetadataAttributes, RecordBatch, RecordValue, COMPRESSION_CODEC_MASK,
+        RECORD_BATCH_RECORDS_START,
+    },
+    decode::Decode,
+    encode::Encode,
+    fetch::compress_record_batches,
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+};
+
+fn record_batch(base_offset: i64) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        (0..8)
+            .map(|idx| new_metadata_record(idx, RecordValue::Unknown(vec![0xab; 64])))
+            .collect(),
+    )
+}
+
+// Record 没有实现 PartialEq, 比较 encode 后的字节
+fn records_bytes(record_batch: &RecordBatch) -> Vec<Vec<u8>> {
+    record_batch
+        .records
+        .as_ref()
+        .expect("Expect records")
+        .iter()
+        .map(Encode::encode)
+        .collect()
+}
+
+fn codec(record_batch: &RecordBatch) -> u16 {
+    record_batch.attributes.bits() & COMPRESSION_CODEC_MASK
+}
+
+fn main() {
+    // 默认不压缩, 无法识别的算法保持之前的值
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let config = BrokerConfig::from_args(args(&[]).into_iter());
+    assert_eq!(config.fetch_compression.bits(), 0);
+    let config = BrokerConfig::from_args(args(&["--fetch-compression", "gzip"]).into_iter());
+    assert_eq!(
+        config.fetch_compression.bits(),
+        MetadataAttributes::GZIP.bits()
+    );
+    let config = BrokerConfig::from_args(
+        args(&[
+            "--fetch-compression",
+            "gzip",
+            "--fetch-compression",
+            "brotli",
+        ])
+        .into_iter(),
+    );
+    assert_eq!(
+        config.fetch_compression.bits(),
+        MetadataAttributes::GZIP.bits()
+    );
+
+    // 不压缩时 batch 保持原样
+    let uncompressed = record_batch(0).encode();
+    let mut record_batches = vec![record_batch(0)];
+    compress_record_batches(&mut record_batches, MetadataAttributes::NO_COMPRESSION);
+    assert_eq!(record_batches[0].encode(), uncompressed);
+
+    // control batch 不压缩
+    let mut control_batch = record_batch(8);
+    control_batch.attributes |= MetadataAttributes::IS_CONTROL_BATCH;
+    let mut record_batches = vec![record_batch(0), control_batch];
+    compress_record_batches(&mut record_batches, MetadataAttributes::GZIP);
+    if cfg!(feature = "gzip") {
+        assert_eq!(codec(&record_batches[0]), MetadataAttributes::GZIP.bits());
+    }
+    assert_eq!(codec(&record_batches[1]), 0);
+
+    // 客户端 decode 时解压, 得到与原始 batch 相同的 records
+    let compressed = record_batches[0].encode();
+    let decoded = RecordBatch::decode(&mut Cursor::new(compressed.as_slice()))
+        .expect("Failed to decode compressed record batch");
+    assert_eq!(records_bytes(&decoded), records_bytes(&record_batch(0)));
+    // encoded_len 和 encode 使用 set_compression 时压缩好的 records, 不会再次压缩
+    assert_eq!(record_batches[0].encoded_len(), compressed.len());
+    if cfg!(feature = "gzip") {
+        assert_eq!(codec(&decoded), MetadataAttributes::GZIP.bits());
+        assert!(compressed.len() < uncompressed.len());
+        let compressed_records = record_batches[0]
+            .compressed_records
+            .as_ref()
+            .expect("Records should be compressed once");
+        assert_eq!(
+            compressed.len(),
+            RECORD_BATCH_RECORDS_START + 4 + compressed_records.len()
+        );
+        assert!(compressed.ends_with(compressed_records));
+    } else {
+        // 没有启用 gzip feature 时退回不压缩
+        assert_eq!(codec(&record_batches[0]), 0);
+        assert!(record_batches[0].compressed_records.is_none());
+        assert_eq!(compressed, uncompressed);
+    }
+
+    println!("fetch compression round-trips through the client decoder");
+}
@@ -0,0 +1,155 @@
+use std::{fs, io::Cursor};
+
+use codecrafters_kafka::{
+    common_struct::{
+        ControlRecordType, MetadataAttributes, Record, RecordBatch, RecordKey, RecordValue, VarInt,
+    },
+    decode::Decode,
+    encode::Encode,
+    fetch::{fetch_partition, FetchPartitionResponse, IsolationLevel},
+    metadata_log::{new_metadata_record, new_metadata_record_batch, partition_log_path},
+};
+
+const TOPIC: &str = "demo-fetch-control";
+
+fn with_key(mut record: Record, key: Vec<u8>) -> Record {
+    record.key = RecordKey::new(Some(key));
+    // length 不包含自身
+    record.length = VarInt::from_i64(0);
+    let length = record.encode().len() - record.length.as_bytes().len();
+    record.length = VarInt::from_i64(length as i64);
+    record
+}
+
+fn batch(
+    base_offset: i64,
+    producer_id: i64,
+    attributes: MetadataAttributes,
+    records: Vec<Record>,
+) -> RecordBatch {
+    let mut record_batch = new_metadata_record_batch(base_offset, 0, records);
+    record_batch.producer_id = producer_id;
+    record_batch.producer_epoch = 0;
+    record_batch.attributes = attributes;
+    record_batch.crc = record_batch.compute_crc() as i32;
+    record_batch
+}
+
+fn data_batch(base_offset: i64, producer_id: i64, record_count: usize) -> RecordBatch {
+    let attributes = if producer_id >= 0 {
+        MetadataAttributes::IS_TRANSACTIONAL
+    } else {
+        MetadataAttributes::empty()
+    };
+    batch(
+        base_offset,
+        producer_id,
+        attributes,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 8])))
+            .collect(),
+    )
+}
+
+// control record 的 key 为 version + type, value 为 version + coordinator_epoch
+fn control_batch(base_offset: i64, producer_id: i64, control_type: i16) -> RecordBatch {
+    let key = [0_i16.to_be_bytes(), control_type.to_be_bytes()].concat();
+    let value = [&0_i16.to_be_bytes()[..], &0_i32.to_be_bytes()].concat();
+    batch(
+        base_offset,
+        producer_id,
+        MetadataAttributes::IS_TRANSACTIONAL | MetadataAttributes::IS_CONTROL_BATCH,
+        vec![with_key(
+            new_metadata_record(0, RecordValue::Unknown(value)),
+            key,
+        )],
+    )
+}
+
+// 重新 decode 响应, 保证 control batch 没有破坏响应的格式
+fn round_trip(response: &FetchPartitionResponse) -> FetchPartitionResponse {
+    let bytes = response.encode();
+    let mut buffer = Cursor::new(bytes.as_slice());
+    let decoded =
+        FetchPartitionResponse::decode(&mut buffer).expect("Failed to decode fetch response");
+    assert_eq!(buffer.position() as usize, bytes.len());
+    assert_eq!(decoded.encode(), bytes);
+    decoded
+}
+
+fn main() {
+    // producer 7 的事务被 abort, producer 8 的事务被 commit, 中间夹着非事务的 batch
+    let record_batches = [
+        data_batch(0, 7, 2),
+        data_batch(2, -1, 1),
+        data_batch(3, 8, 1),
+        control_batch(4, 7, 0),
+        control_batch(5, 8, 1),
+    ];
+    assert!(!record_batches[0].is_control_batch());
+    assert_eq!(record_batches[0].control_record_type(), None);
+    assert!(record_batches[3].is_control_batch());
+    assert_eq!(
+        record_batches[3].control_record_type(),
+        Some(ControlRecordType::Abort)
+    );
+    assert_eq!(
+        record_batches[4].control_record_type(),
+        Some(ControlRecordType::Commit)
+    );
+
+    let log: Vec<u8> = record_batches.iter().flat_map(Encode::encode).collect();
+    let path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(path.parent().unwrap()).expect("Failed to create log dir");
+    fs::write(&path, &log).expect("Failed to write log file");
+
+    // READ_COMMITTED 时返回被 abort 的事务, control batch 原样返回
+    let response = round_trip(&fetch_partition(
+        TOPIC,
+        0,
+        0,
+        usize::MAX,
+        IsolationLevel::READ_COMMITTED,
+    ));
+    assert_eq!(response.error_code(), 0);
+    let aborted: Vec<(i64, i64)> = response
+        .aborted_transactions()
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|transaction| (transaction.producer_id(), transaction.first_offset()))
+        .collect();
+    assert_eq!(aborted, [(7, 0)]);
+    let fetched = response.record_batches().get_inner().as_ref().unwrap();
+    assert_eq!(fetched.len(), record_batches.len());
+    let fetched_log: Vec<u8> = fetched.iter().flat_map(Encode::encode).collect();
+    assert_eq!(fetched_log, log);
+    assert_eq!(
+        fetched[3].control_record_type(),
+        Some(ControlRecordType::Abort)
+    );
+
+    // READ_UNCOMMITTED 时不需要过滤, 不返回 aborted_transactions
+    let response = round_trip(&fetch_partition(
+        TOPIC,
+        0,
+        0,
+        usize::MAX,
+        IsolationLevel::READ_UNCOMMITTED,
+    ));
+    assert!(response
+        .aborted_transactions()
+        .as_ref()
+        .map_or(true, |aborted| aborted.is_empty()));
+    assert_eq!(
+        response
+            .record_batches()
+            .get_inner()
+            .as_ref()
+            .unwrap()
+            .len(),
+        record_batches.len()
+    );
+
+    println!("control batches are returned as transaction markers");
+}
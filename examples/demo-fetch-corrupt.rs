@@ -0,0 +1,69 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    common_struct::RecordValue,
+    encode::Encode,
+    fetch::{fetch_partition, IsolationLevel},
+    metadata_log::{
+        decode_record_batches, new_metadata_record, new_metadata_record_batch, partition_log_path,
+    },
+    produce::CORRUPT_MESSAGE_ERROR,
+};
+
+const TOPIC: &str = "demo-fetch-corrupt";
+
+fn main() {
+    let record_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 16]))],
+    );
+    let good_log = record_batch.encode();
+    // 第二个 batch 只写入了一半
+    let corrupt_log = [good_log.as_slice(), &good_log[..20]].concat();
+
+    for (partition, log) in [(0, &good_log), (1, &corrupt_log)] {
+        let path = partition_log_path(TOPIC, partition);
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create log dir");
+        fs::write(&path, log).expect("Failed to write log file");
+    }
+
+    let _ = fs::remove_file(partition_log_path(TOPIC, 2));
+
+    // 错误信息中带有出错 batch 的起始位置
+    let err = decode_record_batches(&corrupt_log).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains(&format!("at position {}", good_log.len())),
+        "{}",
+        err
+    );
+
+    let good = fetch_partition(TOPIC, 0, 0, usize::MAX, IsolationLevel::READ_UNCOMMITTED);
+    assert_eq!(good.partition_index(), 0);
+    assert_eq!(good.error_code(), 0);
+    assert_eq!(good.record_batches().get_inner().as_ref().unwrap().len(), 1);
+
+    // 损坏的 partition 返回 CORRUPT_MESSAGE 和空的 records, 不会 panic
+    let corrupt = fetch_partition(TOPIC, 1, 0, usize::MAX, IsolationLevel::READ_UNCOMMITTED);
+    assert_eq!(corrupt.partition_index(), 1);
+    assert_eq!(corrupt.error_code(), CORRUPT_MESSAGE_ERROR);
+    assert!(corrupt
+        .record_batches()
+        .get_inner()
+        .as_ref()
+        .unwrap()
+        .is_empty());
+
+    // 没有 log 文件的 partition 返回空的 records
+    let missing = fetch_partition(TOPIC, 2, 0, usize::MAX, IsolationLevel::READ_UNCOMMITTED);
+    assert_eq!(missing.error_code(), 0);
+    assert!(missing
+        .record_batches()
+        .get_inner()
+        .as_ref()
+        .unwrap()
+        .is_empty());
+
+    println!("corrupt partition reported as CORRUPT_MESSAGE");
+}
@@ -0,0 +1,31 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    encode::Encode,
+    fetch::{fetch_partition, FetchPartitionResponse, IsolationLevel},
+    metadata_log::partition_log_path,
+};
+
+fn main() {
+    // 没有数据时 records 是空的 (长度前缀 0x01), 而不是 null (0x00), 消费者会继续 poll
+    let encoded = FetchPartitionResponse::new_empty(0).encode();
+    // partition_index, error_code, high_watermark, last_stable_offset, log_start_offset,
+    // aborted_transactions, preferred_read_replica, records, tag_buffer
+    assert_eq!(encoded.len(), 4 + 2 + 8 + 8 + 8 + 1 + 4 + 1 + 1);
+    assert_eq!(encoded[encoded.len() - 2], 0x01);
+    assert_eq!(encoded[encoded.len() - 1], 0x00);
+
+    // 还没有 produce 过的 partition 同样返回空的 records
+    let _ = fs::remove_file(partition_log_path("demo-fetch-empty", 0));
+    let response = fetch_partition(
+        "demo-fetch-empty",
+        0,
+        0,
+        usize::MAX,
+        IsolationLevel::READ_UNCOMMITTED,
+    );
+    assert_eq!(response.error_code(), 0);
+    assert_eq!(response.record_batches().encode(), [0x01]);
+
+    println!("empty fetch partitions use the empty records form");
+}
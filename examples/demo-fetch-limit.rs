@@ -0,0 +1,50 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    common_struct::RecordValue,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch, read_record_batches_limited},
+};
+
+fn main() {
+    let first_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(
+            0,
+            RecordValue::Unknown(vec![0xab; 460]),
+        )],
+    );
+    let second_batch = new_metadata_record_batch(
+        first_batch.next_offset(),
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xcd; 16]))],
+    );
+    let first_size = first_batch.encode().len();
+    assert!(first_size >= 500, "first batch is {} bytes", first_size);
+
+    let path = std::env::temp_dir().join("demo-fetch-limit.log");
+    fs::write(
+        &path,
+        [first_batch.encode(), second_batch.encode()].concat(),
+    )
+    .expect("Failed to write log file");
+
+    // partition_max_bytes 小于第一个 batch 时仍然返回完整的第一个 batch
+    let record_batches = read_record_batches_limited(&path, 0, 1).expect("Failed to read log");
+    assert_eq!(record_batches.len(), 1);
+    assert_eq!(record_batches[0].encode(), first_batch.encode());
+
+    // 第一个 batch 之后严格遵守限制
+    let record_batches =
+        read_record_batches_limited(&path, 0, first_size).expect("Failed to read log");
+    assert_eq!(record_batches.len(), 1);
+    let record_batches =
+        read_record_batches_limited(&path, 0, first_size + second_batch.encode().len())
+            .expect("Failed to read log");
+    assert_eq!(record_batches.len(), 2);
+    println!(
+        "first batch of {} bytes returned with max bytes 1",
+        first_size
+    );
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{Cursor, Write},
+    time::Duration,
+};
+
+use codecrafters_kafka::{
+    common_struct::{NullableString, RecordBatch, RecordValue, TagBuffer},
+    decode::Decode,
+    encode::Encode,
+    fetch::{execute_fetch, fetch_partition, FetchRequestBodyV16, IsolationLevel},
+    metadata_log::{
+        create_topic, init_read_metadata_log, new_metadata_record, new_metadata_record_batch,
+        partition_log_path, partition_notify, topic_id,
+    },
+    request_message::RequestHeaderV2,
+    response_message::ResponseBody,
+};
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+const TOPIC: &str = "demo-fetch-long-poll";
+
+fn record_batch(base_offset: i64, record_count: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 16])))
+            .collect(),
+    )
+}
+
+// 只请求 partition 0
+fn fetch_request(topic_id: Uuid, fetch_offset: i64, max_wait_ms: i32) -> FetchRequestBodyV16 {
+    let bytes = [
+        &max_wait_ms.to_be_bytes()[..],
+        &1_i32.to_be_bytes(),    // min_bytes
+        &i32::MAX.to_be_bytes(), // max_bytes
+        &[0x00],                 // isolation_level
+        &[0x00; 4],              // session_id
+        &[0xff; 4],              // session_epoch
+        &[0x02],                 // topics
+        topic_id.as_bytes(),
+        &[0x02],                 // partitions
+        &0_i32.to_be_bytes(),    // partition_index
+        &(-1_i32).to_be_bytes(), // current_leader_epoch
+        &fetch_offset.to_be_bytes(),
+        &(-1_i32).to_be_bytes(), // last_fetched_epoch
+        &(-1_i64).to_be_bytes(), // log_start_offset
+        &i32::MAX.to_be_bytes(), // partition_max_bytes
+        &[0x00],                 // partition tag_buffer
+        &[0x00],                 // topic tag_buffer
+        &[0x01, 0x01],           // 空的 forgotten_topics_data, rack_id
+        &[0x00],                 // tag_buffer
+    ]
+    .concat();
+    FetchRequestBodyV16::decode(&mut Cursor::new(bytes.as_slice())).expect("Invalid fetch request")
+}
+
+// 返回 partition 0 的 (high_watermark, 每个 batch 的 base_offset)
+async fn fetch(topic_id: Uuid, fetch_offset: i64, max_wait_ms: i32) -> (i64, Vec<i64>) {
+    let header = RequestHeaderV2 {
+        request_api_key: 1,
+        request_api_version: 16,
+        correlation_id: 7,
+        client_id: NullableString::new(Some("demo".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+    let response =
+        execute_fetch(&header, &fetch_request(topic_id, fetch_offset, max_wait_ms)).await;
+    let ResponseBody::FetchV16(body) = response.body() else {
+        panic!("Expect fetch response, got {:?}", response);
+    };
+    let partition = &body.responses().as_ref().unwrap()[0]
+        .partitions()
+        .as_ref()
+        .unwrap()[0];
+    assert_eq!(partition.error_code(), 0);
+    let base_offsets = partition
+        .record_batches()
+        .get_inner()
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|batch| batch.base_offset)
+        .collect();
+    (partition.high_watermark(), base_offsets)
+}
+
+#[tokio::main]
+async fn main() {
+    // metadata log 不存在时创建空文件, 之后通过 create_topic 追加 topic
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 1).expect("Failed to create topic");
+    let topic_id = topic_id(TOPIC).expect("Topic should exist");
+
+    // offset 0-1, 2
+    let path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(path.parent().unwrap()).expect("Failed to create log dir");
+    fs::write(
+        &path,
+        [record_batch(0, 2).encode(), record_batch(2, 1).encode()].concat(),
+    )
+    .expect("Failed to write log file");
+
+    // high watermark 是 log end offset
+    let response = fetch_partition(TOPIC, 0, 0, usize::MAX, IsolationLevel::READ_UNCOMMITTED);
+    assert_eq!(response.high_watermark(), 3);
+
+    // 还有没有消费的数据时立即返回
+    let start = Instant::now();
+    assert_eq!(fetch(topic_id, 2, 10_000).await, (3, vec![2]));
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // 已经读到末尾的消费者不会重复收到旧的数据, 一直等到 max_wait_ms
+    let start = Instant::now();
+    assert_eq!(fetch(topic_id, 3, 200).await, (3, vec![]));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // 等待期间有新的数据写入时提前返回新的 batch
+    let appender = tokio::spawn(async move {
+        time::sleep(Duration::from_millis(50)).await;
+        OpenOptions::new()
+            .append(true)
+            .open(partition_log_path(TOPIC, 0))
+            .and_then(|mut file| file.write_all(&record_batch(3, 2).encode()))
+            .expect("Failed to append log file");
+        partition_notify(TOPIC, 0).notify_waiters();
+    });
+    let start = Instant::now();
+    assert_eq!(fetch(topic_id, 3, 10_000).await, (5, vec![3]));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    appender.await.expect("Failed to append");
+
+    fs::remove_file(&path).expect("Failed to remove log file");
+    println!("caught-up fetch waits for new data");
+}
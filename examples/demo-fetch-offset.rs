@@ -0,0 +1,77 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    common_struct::{RecordBatch, RecordValue},
+    encode::Encode,
+    fetch::{fetch_partition, IsolationLevel},
+    metadata_log::{
+        new_metadata_record, new_metadata_record_batch, partition_log_path,
+        read_record_batches_limited,
+    },
+};
+
+const TOPIC: &str = "demo-fetch-offset";
+
+fn record_batch(base_offset: i64, record_count: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 16])))
+            .collect(),
+    )
+}
+
+fn fetched_base_offsets(fetch_offset: i64, max_bytes: usize) -> Vec<i64> {
+    let response = fetch_partition(
+        TOPIC,
+        0,
+        fetch_offset,
+        max_bytes,
+        IsolationLevel::READ_UNCOMMITTED,
+    );
+    assert_eq!(response.error_code(), 0);
+    response
+        .record_batches()
+        .get_inner()
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|batch| batch.base_offset)
+        .collect()
+}
+
+fn read_base_offsets(fetch_offset: i64, max_bytes: usize) -> Vec<i64> {
+    read_record_batches_limited(&partition_log_path(TOPIC, 0), fetch_offset, max_bytes)
+        .expect("Failed to read log")
+        .iter()
+        .map(|batch| batch.base_offset)
+        .collect()
+}
+
+fn main() {
+    // offset 0-1, 2-3, 4
+    let log = [record_batch(0, 2), record_batch(2, 2), record_batch(4, 1)]
+        .iter()
+        .flat_map(Encode::encode)
+        .collect::<Vec<u8>>();
+    let path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(path.parent().unwrap()).expect("Failed to create log dir");
+    fs::write(&path, &log).expect("Failed to write log file");
+
+    // 缓存的 segment 和流式读取都从 fetch_offset 所在的 batch 开始
+    for base_offsets in [fetched_base_offsets, read_base_offsets] {
+        // partition_max_bytes 很小时每次只返回一个 batch, 消费者按 offset 依次前进
+        assert_eq!(base_offsets(0, 1), [0]);
+        assert_eq!(base_offsets(2, 1), [2]);
+        assert_eq!(base_offsets(4, 1), [4]);
+        // fetch_offset 在 batch 中间时返回整个 batch, 由客户端跳过已经消费的 record
+        assert_eq!(base_offsets(3, 1), [2]);
+        assert_eq!(base_offsets(1, usize::MAX), [0, 2, 4]);
+        // 已经读到 log 的末尾
+        assert!(base_offsets(5, usize::MAX).is_empty());
+    }
+
+    fs::remove_file(&path).expect("Failed to remove log file");
+    println!("fetch starts from the batch containing fetch_offset");
+}
@@ -0,0 +1,76 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{NullableString, TagBuffer},
+    decode::Decode,
+    encode::Encode,
+    fetch::{
+        execute_fetch, resolve_fetch_session, FetchRequestBodyV16, FetchSessionEpoch,
+        FETCH_SESSION_ID_NOT_FOUND_ERROR, INVALID_FETCH_SESSION_EPOCH_ERROR, NO_FETCH_SESSION_ID,
+    },
+    request_message::RequestHeaderV2,
+};
+
+fn fetch_request(session_id: i32, session_epoch: i32) -> FetchRequestBodyV16 {
+    let bytes = [
+        &0_i32.to_be_bytes()[..],     // max_wait_ms
+        &0_i32.to_be_bytes(),         // min_bytes
+        &i32::MAX.to_be_bytes(),      // max_bytes
+        &[0x00],                      // isolation_level
+        &session_id.to_be_bytes(),    // session_id
+        &session_epoch.to_be_bytes(), // session_epoch
+        &[0x01, 0x01, 0x01],          // 空的 topics, forgotten_topics_data, rack_id
+        &[0x00],                      // tag_buffer
+    ]
+    .concat();
+    FetchRequestBodyV16::decode(&mut Cursor::new(bytes.as_slice())).expect("Invalid fetch request")
+}
+
+// 返回响应中的 error_code 和 session_id
+async fn fetch(session_id: i32, session_epoch: i32) -> (i16, i32) {
+    let header = RequestHeaderV2 {
+        request_api_key: 1,
+        request_api_version: 16,
+        correlation_id: 7,
+        client_id: NullableString::new(Some("demo".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+    let encoded = execute_fetch(&header, &fetch_request(session_id, session_epoch))
+        .await
+        .encode();
+    // correlation_id, header 的 tag buffer, throttle_time_ms 之后是 error_code 和 session_id
+    let mut buffer = Cursor::new(&encoded[9..]);
+    let error_code = i16::decode(&mut buffer).expect("Missing error_code");
+    let session_id = i32::decode(&mut buffer).expect("Missing session_id");
+    (error_code, session_id)
+}
+
+#[tokio::main]
+async fn main() {
+    assert_eq!(
+        resolve_fetch_session(5, FetchSessionEpoch::FINAL_EPOCH),
+        Ok(NO_FETCH_SESSION_ID)
+    );
+    assert_eq!(
+        resolve_fetch_session(0, FetchSessionEpoch::INITIAL_EPOCH),
+        Ok(NO_FETCH_SESSION_ID)
+    );
+    assert_eq!(
+        resolve_fetch_session(5, 3),
+        Err(FETCH_SESSION_ID_NOT_FOUND_ERROR)
+    );
+    assert_eq!(
+        resolve_fetch_session(5, -2),
+        Err(INVALID_FETCH_SESSION_EPOCH_ERROR)
+    );
+
+    // epoch 为 -1 时关闭 session, 按完整 fetch 处理, 响应中不带 session
+    assert_eq!(fetch(5, FetchSessionEpoch::FINAL_EPOCH).await, (0, 0));
+    assert_eq!(fetch(0, FetchSessionEpoch::FINAL_EPOCH).await, (0, 0));
+    assert_eq!(fetch(0, FetchSessionEpoch::INITIAL_EPOCH).await, (0, 0));
+    assert_eq!(
+        fetch(5, 3).await,
+        (FETCH_SESSION_ID_NOT_FOUND_ERROR, NO_FETCH_SESSION_ID)
+    );
+    println!("fetch session epoch checks passed");
+}
@@ -0,0 +1,81 @@
+use std::{io::Cursor, sync::Arc, time::Duration};
+
+use codecrafters_kafka::{
+    common_struct::{NullableString, TagBuffer},
+    decode::Decode,
+    fetch::{execute_fetch, wait_notified, FetchRequestBodyV16},
+    metadata_log::partition_notify,
+    request_message::RequestHeaderV2,
+};
+use tokio::{
+    sync::Notify,
+    time::{self, Instant},
+};
+
+fn fetch_request(max_wait_ms: i32, min_bytes: i32) -> FetchRequestBodyV16 {
+    let bytes = [
+        &max_wait_ms.to_be_bytes()[..],
+        &min_bytes.to_be_bytes(),
+        &i32::MAX.to_be_bytes(), // max_bytes
+        &[0x00],                 // isolation_level
+        &[0x00; 4],              // session_id
+        &[0xff; 4],              // session_epoch
+        &[0x01, 0x01, 0x01],     // 空的 topics, forgotten_topics_data, rack_id
+        &[0x00],                 // tag_buffer
+    ]
+    .concat();
+    FetchRequestBodyV16::decode(&mut Cursor::new(bytes.as_slice())).expect("Invalid fetch request")
+}
+
+#[tokio::main]
+async fn main() {
+    let header = RequestHeaderV2 {
+        request_api_key: 1,
+        request_api_version: 16,
+        correlation_id: 7,
+        client_id: NullableString::new(Some("demo".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+
+    // 没有任何数据时一直等到 max_wait_ms
+    let start = Instant::now();
+    execute_fetch(&header, &fetch_request(200, 1)).await;
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // min_bytes 为 0 时立即返回
+    let start = Instant::now();
+    execute_fetch(&header, &fetch_request(10_000, 0)).await;
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // 到达 deadline 之前被唤醒时提前返回
+    let notify = Arc::new(Notify::new());
+    let mut notified = vec![Box::pin(notify.notified())];
+    notified[0].as_mut().enable();
+    let waker = notify.clone();
+    tokio::spawn(async move {
+        time::sleep(Duration::from_millis(50)).await;
+        waker.notify_waiters();
+    });
+    let start = Instant::now();
+    assert!(wait_notified(&mut notified, start + Duration::from_secs(10)).await);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // 没有被唤醒时在 deadline 返回 false
+    let mut notified = vec![Box::pin(notify.notified())];
+    let start = Instant::now();
+    assert!(!wait_notified(&mut notified, start + Duration::from_millis(100)).await);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // 检查数据之后, await 之前写入的数据不会丢失: 先 enable 再检查, 唤醒会被记录下来
+    let notify = partition_notify("demo-topic", 0);
+    assert!(Arc::ptr_eq(&notify, &partition_notify("demo-topic", 0)));
+    assert!(!Arc::ptr_eq(&notify, &partition_notify("demo-topic", 1)));
+    let mut notified = vec![Box::pin(notify.notified())];
+    notified[0].as_mut().enable();
+    partition_notify("demo-topic", 0).notify_waiters();
+    let start = Instant::now();
+    assert!(wait_notified(&mut notified, start + Duration::from_secs(10)).await);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    println!("fetch wait checks passed");
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use codecrafters_kafka::{
+    alter_configs::INVALID_REQUEST_ERROR,
+    common_struct::CompactString,
+    encode::Encode,
+    find_coordinator::CoordinatorType,
+    response_message::{ResponseBody, ResponseMessage},
+};
+
+use common::{execute, request_message};
+
+// FindCoordinatorRequest v4
+fn find_coordinator_request(key_type: i8, keys: &[&str]) -> Vec<u8> {
+    let mut body = vec![key_type as u8, keys.len() as u8 + 1];
+    for key in keys {
+        body.extend(CompactString::new(key.to_string()).encode());
+    }
+    body.push(0x00); // tag_buffer
+    request_message(10, 4, &body)
+}
+
+async fn find_coordinator(key_type: i8, keys: &[&str]) -> ResponseMessage {
+    execute(&find_coordinator_request(key_type, keys)).await
+}
+
+#[tokio::main]
+async fn main() {
+    for key_type in [CoordinatorType::GROUP, CoordinatorType::TRANSACTION] {
+        let response = find_coordinator(key_type, &["demo-group", "demo-other"]).await;
+        let ResponseBody::FindCoordinatorV4(body) = response.body() else {
+            panic!("Expect find coordinator response, got {:?}", response);
+        };
+        let coordinators = body.coordinators().as_ref().unwrap();
+        assert_eq!(coordinators.len(), 2);
+        for (coordinator, key) in coordinators.iter().zip(["demo-group", "demo-other"]) {
+            assert_eq!(coordinator.key(), key);
+            assert_eq!(coordinator.error_code(), 0);
+            assert_eq!(
+                (
+                    coordinator.node_id(),
+                    coordinator.host(),
+                    coordinator.port()
+                ),
+                (1, "127.0.0.1", 9092)
+            );
+        }
+    }
+
+    // 未知的 key_type
+    let response = find_coordinator(5, &["demo-group"]).await;
+    let ResponseBody::FindCoordinatorV4(body) = response.body() else {
+        panic!("Expect find coordinator response, got {:?}", response);
+    };
+    let coordinator = &body.coordinators().as_ref().unwrap()[0];
+    assert_eq!(coordinator.error_code(), INVALID_REQUEST_ERROR);
+    assert_eq!(coordinator.node_id(), -1);
+
+    println!("find coordinator returns the local broker");
+}
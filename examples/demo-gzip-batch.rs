@@ -0,0 +1,128 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        MetadataAttributes, RecordBatch, RecordValue, COMPRESSION_CODEC_MASK,
+        RECORD_BATCH_CRC_START,
+    },
+    crc::crc32c,
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch, LOG_OVERHEAD},
+};
+
+// 一条 value 为 "hello" 的 record, 使用 gzip 压缩, records 的个数本身不压缩
+#[rustfmt::skip]
+const GZIP_BATCH: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_offset: int64
+    0x00, 0x00, 0x00, 0x51,                         // batch_length: int32
+    0x00, 0x00, 0x00, 0x00,                         // partition_leader_epoch: int32
+    0x02,                                           // magic: int8
+    0x08, 0x56, 0xa4, 0x8c,                         // crc: uint32
+    0x00, 0x01,                                     // attributes: int16, gzip
+    0x00, 0x00, 0x00, 0x00,                         // last_offset_delta: int32
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // base_timestamp: int64
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // max_timestamp: int64
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // producer_id: int64
+    0xff, 0xff,                                     // producer_epoch: int16
+    0xff, 0xff, 0xff, 0xff,                         // base_sequence: int32
+    0x00, 0x00, 0x00, 0x01,                         // records 个数: int32
+    // gzip 压缩后的 record, 比原始的 12 个字节更长
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x13, 0x63,
+    0x60, 0x60, 0x60, 0xe4, 0xca, 0x48, 0xcd, 0xc9, 0xc9, 0x67, 0x00, 0x00,
+    0x3e, 0xa9, 0x07, 0x97, 0x0c, 0x00, 0x00, 0x00,
+];
+
+// Record 没有实现 PartialEq, 比较 encode 后的字节
+fn records_bytes(record_batch: &RecordBatch) -> Vec<Vec<u8>> {
+    record_batch
+        .records
+        .as_ref()
+        .expect("Expect records")
+        .iter()
+        .map(Encode::encode)
+        .collect()
+}
+
+// 不压缩时 batch 的长度
+fn plain_records_len(record_batch: &RecordBatch) -> usize {
+    let mut plain = record_batch.clone();
+    plain.attributes = MetadataAttributes::empty();
+    plain.encoded_len()
+}
+
+fn main() {
+    // 没有压缩的 batch 不受影响
+    let plain_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![
+            new_metadata_record(0, RecordValue::Unknown(vec![0xab; 8])),
+            new_metadata_record(1, RecordValue::Unknown(vec![0xcd; 8])),
+        ],
+    );
+    let bytes = plain_batch.encode();
+    let mut buffer = Cursor::new(bytes.as_slice());
+    let decoded = RecordBatch::decode(&mut buffer).expect("Failed to decode plain batch");
+    assert_eq!(buffer.position() as usize, bytes.len());
+    assert!(!decoded.is_compressed());
+    assert_eq!(decoded.encode(), bytes);
+
+    // gzip 压缩的 batch 解压后 decode records, 后面紧跟的 batch 不受影响
+    let log_content = [GZIP_BATCH, bytes.as_slice()].concat();
+    let mut buffer = Cursor::new(log_content.as_slice());
+    let decoded = RecordBatch::decode(&mut buffer).expect("Failed to decode gzip batch");
+    assert_eq!(buffer.position() as usize, GZIP_BATCH.len());
+    assert_eq!(
+        decoded.attributes.bits() & COMPRESSION_CODEC_MASK,
+        MetadataAttributes::GZIP.bits()
+    );
+    let records = decoded.records.as_ref().expect("Expect gzip records");
+    assert_eq!(records.len(), 1);
+    match records[0].get_value() {
+        RecordValue::Unknown(value) => assert_eq!(value, b"hello"),
+        value => panic!("Expect raw record value, got {:?}", value),
+    }
+    let next = RecordBatch::decode(&mut buffer).expect("Failed to decode next batch");
+    assert_eq!(next.encode(), bytes);
+
+    // 压缩的内容损坏时返回错误
+    let mut corrupted = GZIP_BATCH.to_vec();
+    corrupted.truncate(GZIP_BATCH.len() - 8);
+    let batch_length = (corrupted.len() - LOG_OVERHEAD) as i32;
+    corrupted[8..12].copy_from_slice(&batch_length.to_be_bytes());
+    let crc = crc32c(&corrupted[RECORD_BATCH_CRC_START..]);
+    corrupted[17..21].copy_from_slice(&crc.to_be_bytes());
+    let err = RecordBatch::decode(&mut Cursor::new(corrupted.as_slice()))
+        .expect_err("Expect truncated gzip error");
+    assert!(err.is_other());
+    println!("{}", err);
+
+    // 设置 GZIP 后 encode 会压缩 records, decode 后 attributes 和 records 与原来一致
+    let mut gzip_batch = new_metadata_record_batch(
+        7,
+        0,
+        (0..10)
+            .map(|idx| new_metadata_record(idx, RecordValue::Unknown(vec![0xab; 64])))
+            .collect(),
+    );
+    gzip_batch.attributes |= MetadataAttributes::GZIP;
+    let bytes = gzip_batch.encode();
+    assert_eq!(bytes.len(), gzip_batch.encoded_len());
+    assert!(bytes.len() < plain_records_len(&gzip_batch));
+    let decoded = RecordBatch::decode(&mut Cursor::new(bytes.as_slice()))
+        .expect("Failed to decode encoded gzip batch");
+    assert_eq!(decoded.attributes.bits(), gzip_batch.attributes.bits());
+    assert_eq!(decoded.last_offset_delta, 9);
+    assert_eq!(records_bytes(&decoded), records_bytes(&gzip_batch));
+
+    // decode 得到的 gzip batch 重新 encode 后仍然可以 decode
+    let decoded =
+        RecordBatch::decode(&mut Cursor::new(GZIP_BATCH)).expect("Failed to decode gzip batch");
+    let reencoded = RecordBatch::decode(&mut Cursor::new(decoded.encode().as_slice()))
+        .expect("Failed to decode re-encoded gzip batch");
+    assert_eq!(reencoded.attributes.bits(), decoded.attributes.bits());
+    assert_eq!(records_bytes(&reencoded), records_bytes(&decoded));
+
+    println!("gzip record batches decode and encode");
+}
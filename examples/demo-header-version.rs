@@ -0,0 +1,60 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    acl::Session,
+    api_versions::{
+        request_header_version, response_header_version, FIRST_FLEXIBLE_VERSIONS, SUPPORT_APIS,
+    },
+    common_struct::{NullableString, TagBuffer},
+    decode::Decode,
+    describe_topic_partitions::{
+        execute_describe_topic_partitions, DescribeTopicPartitionsRequestBodyV0,
+    },
+    encode::Encode,
+    request_message::RequestHeaderV2,
+};
+
+fn main() {
+    // 每个实现的 api 都需要登记 flexible 的起始版本
+    for api_key in SUPPORT_APIS.keys() {
+        assert!(
+            FIRST_FLEXIBLE_VERSIONS.contains_key(api_key),
+            "api key {} has no header version",
+            api_key
+        );
+    }
+    assert_eq!(request_header_version(75, 0), 2);
+    assert_eq!(response_header_version(75, 0), 1);
+    assert_eq!(request_header_version(1, 11), 1);
+    assert_eq!(response_header_version(1, 11), 0);
+    // ApiVersions 的响应始终是 v0 header
+    assert_eq!(request_header_version(18, 4), 2);
+    assert_eq!(response_header_version(18, 4), 0);
+
+    let header = RequestHeaderV2 {
+        request_api_key: 75,
+        request_api_version: 0,
+        correlation_id: 7,
+        client_id: NullableString::new(Some("demo".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+    let body = [
+        &[0x01][..],            // topics: 空的 compact array
+        &100_i32.to_be_bytes(), // response_partition_limit
+        &[0xff],                // cursor: null
+        &[0x00],                // tag_buffer
+    ]
+    .concat();
+    let body = DescribeTopicPartitionsRequestBodyV0::decode(&mut Cursor::new(body.as_slice()))
+        .expect("Invalid request body");
+
+    let response = execute_describe_topic_partitions(&header, &body, &Session::default());
+    assert_eq!(response.header().version(), 1);
+    let encoded = response.encode();
+    assert_eq!(&encoded[..4], &7_i32.to_be_bytes()); // correlation_id
+    assert_eq!(encoded[4], 0x00); // header 的 tag buffer
+                                  // header 5 字节, throttle_time 4 字节, topics, next_cursor, tag_buffer 各 1 字节
+    assert_eq!(encoded.len(), 12);
+
+    println!("DescribeTopicPartitions responds with header v1");
+}
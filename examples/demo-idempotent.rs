@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{MetadataAttributes, RecordBatch, RecordValue, NO_PRODUCER_ID},
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+    produce::{
+        ProducerState, SequenceCheck, DUPLICATE_SEQUENCE_NUMBER_ERROR,
+        INVALID_PRODUCER_EPOCH_ERROR, MAX_RETAINED_BATCHES, OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR,
+    },
+};
+
+fn roundtrip(record_batch: &RecordBatch) -> RecordBatch {
+    RecordBatch::decode(&mut Cursor::new(record_batch.encode().as_slice()))
+        .expect("Failed to decode record batch")
+}
+
+fn main() {
+    let record_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 8]))],
+    );
+
+    // producer_id 为 -1
+    let plain = roundtrip(&record_batch);
+    assert_eq!(plain.producer_id, NO_PRODUCER_ID);
+    assert!(!plain.is_idempotent());
+    assert!(!plain.is_transactional());
+
+    // 幂等但不是事务
+    let mut idempotent = record_batch.clone();
+    idempotent.producer_id = 42;
+    idempotent.producer_epoch = 0;
+    idempotent.base_sequence = 0;
+    let idempotent = roundtrip(&idempotent);
+    assert!(idempotent.is_idempotent());
+    assert!(!idempotent.is_transactional());
+
+    // 事务
+    let mut transactional = idempotent.clone();
+    transactional.attributes |= MetadataAttributes::IS_TRANSACTIONAL;
+    let transactional = roundtrip(&transactional);
+    assert!(transactional.is_idempotent());
+    assert!(transactional.is_transactional());
+
+    // is_transactional 只看 attributes, 与 producer_id 无关
+    let mut flag_only = record_batch.clone();
+    flag_only.attributes |= MetadataAttributes::IS_TRANSACTIONAL;
+    let flag_only = roundtrip(&flag_only);
+    assert!(!flag_only.is_idempotent());
+    assert!(flag_only.is_transactional());
+
+    println!("idempotent and transactional checks passed");
+
+    check_sequences();
+    println!("sequence checks passed");
+}
+
+fn error_code(result: Result<SequenceCheck, (i16, String)>) -> i16 {
+    result.expect_err("Expect sequence error").0
+}
+
+fn check_sequences() {
+    // 每个 batch 3 条 record, 依次写在 offset 100, 103, ...
+    let mut state = ProducerState::new(0);
+    assert_eq!(state.check(0, 0, 2), Ok(SequenceCheck::Append));
+    state.append(0, 0, 2, 100);
+    assert_eq!(state.check(0, 3, 5), Ok(SequenceCheck::Append));
+    state.append(0, 3, 5, 103);
+    assert_eq!(state.last_sequence(), Some(5));
+
+    // 重试已写入的 batch 返回之前的 offset
+    assert_eq!(state.check(0, 0, 2), Ok(SequenceCheck::Duplicate(100)));
+    assert_eq!(state.check(0, 3, 5), Ok(SequenceCheck::Duplicate(103)));
+    // 与已写入的 sequence 重叠, 但不是同一个 batch
+    assert_eq!(
+        error_code(state.check(0, 4, 6)),
+        DUPLICATE_SEQUENCE_NUMBER_ERROR
+    );
+    // 中间缺少 sequence 6
+    assert_eq!(
+        error_code(state.check(0, 7, 9)),
+        OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR
+    );
+
+    // 旧的 epoch 被拒绝, 新的 epoch 必须从 0 开始
+    assert_eq!(
+        error_code(state.check(-1, 6, 8)),
+        INVALID_PRODUCER_EPOCH_ERROR
+    );
+    assert_eq!(
+        error_code(state.check(1, 6, 8)),
+        OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR
+    );
+    assert_eq!(state.check(1, 0, 0), Ok(SequenceCheck::Append));
+    state.append(1, 0, 0, 106);
+    assert_eq!(state.producer_epoch(), 1);
+    assert_eq!(state.last_sequence(), Some(0));
+    // 新 epoch 之前的 batch 不再用于去重
+    assert_eq!(
+        error_code(state.check(1, 3, 5)),
+        OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR
+    );
+
+    // 只保留最近的 MAX_RETAINED_BATCHES 个 batch
+    let mut state = ProducerState::new(0);
+    for sequence in 0..=MAX_RETAINED_BATCHES as i32 {
+        state.append(0, sequence, sequence, sequence as i64);
+    }
+    assert_eq!(
+        error_code(state.check(0, 0, 0)),
+        DUPLICATE_SEQUENCE_NUMBER_ERROR
+    );
+    assert_eq!(state.check(0, 1, 1), Ok(SequenceCheck::Duplicate(1)));
+
+    // sequence 超过 i32::MAX 后从 0 开始
+    let record_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![
+            new_metadata_record(0, RecordValue::Unknown(vec![0xab])),
+            new_metadata_record(1, RecordValue::Unknown(vec![0xcd])),
+        ],
+    );
+    let mut wrapping = record_batch.clone();
+    wrapping.base_sequence = i32::MAX;
+    assert_eq!(wrapping.last_sequence(), 0);
+    let mut state = ProducerState::new(0);
+    state.append(0, i32::MAX - 1, i32::MAX - 1, 0);
+    assert_eq!(
+        state.check(0, i32::MAX, wrapping.last_sequence()),
+        Ok(SequenceCheck::Append)
+    );
+    state.append(0, i32::MAX, 0, 1);
+    assert_eq!(state.check(0, 1, 1), Ok(SequenceCheck::Append));
+    assert_eq!(
+        error_code(state.check(0, 0, 0)),
+        DUPLICATE_SEQUENCE_NUMBER_ERROR
+    );
+}
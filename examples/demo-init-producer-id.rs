@@ -0,0 +1,40 @@
+mod common;
+
+use codecrafters_kafka::response_message::ResponseBody;
+
+use common::{execute, request_message};
+
+// InitProducerIdRequest v5: 没有 transactional_id 的新 producer
+fn init_producer_id_request() -> Vec<u8> {
+    let body = [
+        &[0x00][..],              // transactional_id: null
+        &60000_i32.to_be_bytes(), // transaction_timeout_ms
+        &(-1_i64).to_be_bytes(),  // producer_id
+        &(-1_i16).to_be_bytes(),  // producer_epoch
+        &[0x00],                  // tag_buffer
+    ]
+    .concat();
+    request_message(22, 5, &body)
+}
+
+// 返回 (error_code, producer_id, producer_epoch)
+async fn init_producer_id() -> (i16, i64, i16) {
+    let response = execute(&init_producer_id_request()).await;
+    let ResponseBody::InitProducerIdV5(body) = response.body() else {
+        panic!("Expect init producer id response, got {:?}", response);
+    };
+    (body.error_code(), body.producer_id(), body.producer_epoch())
+}
+
+#[tokio::main]
+async fn main() {
+    // 每次分配的 producer id 递增, epoch 从 0 开始
+    let (error_code, first_id, epoch) = init_producer_id().await;
+    assert_eq!((error_code, epoch), (0, 0));
+    assert!(first_id >= 0);
+    for expected_id in first_id + 1..first_id + 4 {
+        assert_eq!(init_producer_id().await, (0, expected_id, 0));
+    }
+
+    println!("init producer id hands out increasing producer ids");
+}
@@ -0,0 +1,97 @@
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::{RecordBatch, RecordValue},
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{
+        create_topic, init_read_metadata_log, new_metadata_record, new_metadata_record_batch,
+        partition_log_path,
+    },
+    offset_for_leader_epoch::{
+        end_offset_for_epoch, epoch_end_offset, UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET,
+    },
+};
+
+const TOPIC: &str = "demo-leader-epoch";
+
+fn record_batch(base_offset: i64, record_count: usize, leader_epoch: i32) -> RecordBatch {
+    let mut record_batch = new_metadata_record_batch(
+        base_offset,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 4])))
+            .collect(),
+    );
+    record_batch.partition_leader_epoch = leader_epoch;
+    record_batch
+}
+
+fn main() {
+    // 只有 epoch 3 的 batch, 请求更早的 epoch 时返回请求的 epoch 和 epoch 3 的起始 offset
+    assert_eq!(end_offset_for_epoch(&[(3, 10)], 3, 15, 1), (1, 10));
+    // metadata 中的 leader epoch 比 log 中的新, 还没有写入 batch
+    let epochs = [(0, 0), (3, 10)];
+    assert_eq!(end_offset_for_epoch(&epochs, 4, 15, 4), (4, 15));
+    assert_eq!(end_offset_for_epoch(&epochs, 4, 15, 3), (3, 15));
+    assert_eq!(end_offset_for_epoch(&epochs, 4, 15, 2), (0, 10));
+
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 1).expect("Failed to create topic");
+
+    // epoch 0: [0, 2), epoch 2: [2, 6), epoch 5: [6, 7)
+    let log_path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(log_path.parent().unwrap()).expect("Failed to create log dir");
+    let log = [
+        record_batch(0, 2, 0),
+        record_batch(2, 3, 2),
+        record_batch(5, 1, 2),
+        record_batch(6, 1, 5),
+    ]
+    .iter()
+    .flat_map(Encode::encode)
+    .collect::<Vec<u8>>();
+    fs::write(&log_path, log).expect("Failed to write log file");
+
+    for (requested_epoch, expected) in [
+        // 最新的 epoch 返回 log end offset
+        (5, (5, 7)),
+        (2, (2, 6)),
+        // 没有 batch 的 epoch 使用不大于它的最大 epoch
+        (3, (2, 6)),
+        (4, (2, 6)),
+        (0, (0, 2)),
+        (1, (0, 2)),
+        // 比最新的 epoch 更大
+        (6, (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET)),
+        (UNDEFINED_EPOCH, (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET)),
+    ] {
+        let response = epoch_end_offset(TOPIC, 0, requested_epoch);
+        assert_eq!(response.error_code(), 0);
+        assert_eq!(
+            (response.leader_epoch(), response.end_offset()),
+            expected,
+            "requested epoch {}",
+            requested_epoch
+        );
+    }
+
+    // 不存在的 topic 和 partition
+    for (topic, partition) in [("demo-leader-epoch-missing", 0), (TOPIC, 1)] {
+        let response = epoch_end_offset(topic, partition, 0);
+        assert_eq!(response.error_code(), UNKNOWN_TOPIC_OR_PARTITION);
+        assert_eq!(
+            (response.leader_epoch(), response.end_offset()),
+            (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET)
+        );
+    }
+
+    println!("offset for leader epoch checks passed");
+}
@@ -0,0 +1,137 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        CompactRecords, CompactString, FeatureLevelRecord, RecordType, RecordValue, Records,
+        TagBuffer, TopicRecord, VarInt,
+    },
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+};
+use uuid::Uuid;
+
+// 原来的实现: 先 encode payload, 再根据实际长度写入前缀
+fn prefixed(length: VarInt, mut payload: Vec<u8>) -> Vec<u8> {
+    let mut encode_res = length.into_bytes();
+    encode_res.append(&mut payload);
+    encode_res
+}
+
+fn check<T: Encode>(name: &str, value: &T, expected: &[u8]) {
+    assert_eq!(value.encode(), expected, "{} encode", name);
+    assert_eq!(value.encoded_len(), expected.len(), "{} encoded_len", name);
+    // encode_into 追加在已有内容后面
+    let mut out = vec![0xee];
+    value.encode_into(&mut out);
+    assert_eq!(&out[1..], expected, "{} encode_into", name);
+}
+
+// decode 之后再 encode 得到相同的字节, 并且用完全部字节
+fn check_roundtrip<T: Encode + Decode>(name: &str, value: &T) {
+    let encoded = value.encode();
+    let mut buffer = Cursor::new(encoded.as_slice());
+    let decoded = T::decode(&mut buffer).unwrap_or_else(|err| panic!("{} decode: {}", name, err));
+    assert_eq!(
+        buffer.position() as usize,
+        encoded.len(),
+        "{} consumed",
+        name
+    );
+    assert_eq!(decoded.encode(), encoded, "{} round trip", name);
+}
+
+fn main() {
+    let topic = TopicRecord {
+        frame_version: 1,
+        record_type: RecordType::TOPIC_RECORD,
+        version: 0,
+        name: CompactString::new("demo".to_string()),
+        id: Uuid::new_v4(),
+        tag_buffers: TagBuffer::default(),
+    };
+    let feature_level = FeatureLevelRecord {
+        frame_version: 1,
+        record_type: RecordType::FEATURE_LEVEL_RECORD,
+        version: 0,
+        name: CompactString::new("metadata.version".to_string()),
+        feature_level: 20,
+        tag_buffers: TagBuffer::default(),
+    };
+    let values = [
+        ("topic", RecordValue::Topic(topic.clone()), topic.encode()),
+        (
+            "feature level",
+            RecordValue::FeatureLevel(feature_level.clone()),
+            feature_level.encode(),
+        ),
+        ("empty", RecordValue::Unknown(vec![]), vec![]),
+        // 长度需要 2 个字节的 varint
+        (
+            "unknown",
+            RecordValue::Unknown(vec![0xab; 200]),
+            vec![0xab; 200],
+        ),
+    ];
+    for (name, value, payload) in values.iter() {
+        let expected = prefixed(VarInt::from_i64(payload.len() as i64), payload.clone());
+        check(name, value, &expected);
+    }
+
+    let first_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(
+            0,
+            RecordValue::FeatureLevel(feature_level),
+        )],
+    );
+    let second_batch = new_metadata_record_batch(
+        first_batch.next_offset(),
+        0,
+        vec![new_metadata_record(
+            0,
+            RecordValue::Unknown(vec![0xcd; 200]),
+        )],
+    );
+    let batches_encode = [first_batch.encode(), second_batch.encode()].concat();
+    let compact_records =
+        CompactRecords::new(Some(vec![first_batch.clone(), second_batch.clone()]));
+    check(
+        "records",
+        &compact_records,
+        &prefixed(
+            VarInt::from_u64((batches_encode.len() + 1) as u64),
+            batches_encode.clone(),
+        ),
+    );
+    check("empty records", &CompactRecords::empty(), &[0x01]);
+    check("null records", &CompactRecords::new(None), &[0x00]);
+    check_roundtrip("records", &compact_records);
+    check_roundtrip("empty records", &CompactRecords::empty());
+    check_roundtrip("null records", &CompactRecords::new(None));
+
+    // 非 flexible 版本的 records 使用 int32 长度前缀
+    let records = Records::new(Some(vec![first_batch, second_batch]));
+    check(
+        "non-compact records",
+        &records,
+        &[
+            &(batches_encode.len() as i32).to_be_bytes()[..],
+            &batches_encode,
+        ]
+        .concat(),
+    );
+    check("empty non-compact records", &Records::empty(), &[0x00; 4]);
+    check("null non-compact records", &Records::new(None), &[0xff; 4]);
+    check_roundtrip("non-compact records", &records);
+    check_roundtrip("empty non-compact records", &Records::empty());
+    check_roundtrip("null non-compact records", &Records::new(None));
+    assert!(
+        Records::decode(&mut Cursor::new((-2_i32).to_be_bytes().as_slice()))
+            .unwrap_err()
+            .is_other()
+    );
+
+    println!("length prefixed encodings are unchanged");
+}
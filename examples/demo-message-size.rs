@@ -0,0 +1,30 @@
+use codecrafters_kafka::{
+    api_versions::{error_response, UNSUPPORTED_VERSION_ERROR},
+    encode::Encode,
+    request_message::request_api_versions,
+};
+
+fn main() {
+    // encode 从 header 开始, 不包含 message_size
+    let mut request = request_api_versions(4);
+    let encoded = request.encode();
+    assert_eq!(&encoded[..2], &18_i16.to_be_bytes()); // request_api_key
+    assert_eq!(&encoded[2..4], &4_i16.to_be_bytes()); // request_api_version
+
+    // as_bytes 在 encode 的结果前面加上 message_size, 多次调用结果一致
+    let framed = request.as_bytes();
+    assert_eq!(&framed[..4], &(encoded.len() as u32).to_be_bytes());
+    assert_eq!(&framed[4..], encoded.as_slice());
+    assert_eq!(request.as_bytes(), framed);
+    assert_eq!(request.message_size as usize, encoded.len());
+
+    let mut response = error_response(7, UNSUPPORTED_VERSION_ERROR);
+    let encoded = response.encode();
+    assert_eq!(&encoded[..4], &7_i32.to_be_bytes()); // correlation_id
+    let framed = response.as_bytes();
+    assert_eq!(&framed[..4], &(encoded.len() as u32).to_be_bytes());
+    assert_eq!(&framed[4..], encoded.as_slice());
+    assert_eq!(response.as_bytes(), framed);
+
+    println!("message_size is only added by as_bytes");
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::CompactString,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata::{MetadataResponseBodyV12, AUTHORIZED_OPERATIONS_OMITTED},
+    metadata_log::{create_topic, init_read_metadata_log, partition_log_path},
+    response_message::{ResponseBody, ResponseMessage},
+};
+
+use common::{execute, request_message};
+
+const TOPIC: &str = "demo-metadata";
+
+// MetadataRequest v12, topics 为 None 时请求所有 topic
+fn metadata_request(topics: Option<&[&str]>) -> Vec<u8> {
+    let mut body = vec![];
+    match topics {
+        None => body.push(0x00),
+        Some(topics) => {
+            body.push(topics.len() as u8 + 1);
+            for topic in topics {
+                body.extend_from_slice(&[0; 16]); // topic_id
+                body.extend(CompactString::new(topic.to_string()).encode()); // name
+                body.push(0x00); // tag_buffer
+            }
+        }
+    }
+    body.extend_from_slice(&[
+        0x00, // allow_auto_topic_creation
+        0x00, // include_topic_authorized_operations
+        0x00, // tag_buffer
+    ]);
+    request_message(3, 12, &body)
+}
+
+async fn metadata(topics: Option<&[&str]>) -> ResponseMessage {
+    execute(&metadata_request(topics)).await
+}
+
+fn metadata_body(response: &ResponseMessage) -> &MetadataResponseBodyV12 {
+    let ResponseBody::MetadataV12(body) = response.body() else {
+        panic!("Expect metadata response, got {:?}", response);
+    };
+    body
+}
+
+#[tokio::main]
+async fn main() {
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 2).expect("Failed to create topic");
+
+    // 只有本地的 broker, 同时也是 controller
+    let response = metadata(None).await;
+    let body = metadata_body(&response);
+    let brokers = body.brokers().as_ref().unwrap();
+    assert_eq!(brokers.len(), 1);
+    assert_eq!(
+        (brokers[0].node_id(), brokers[0].host(), brokers[0].port()),
+        (1, "127.0.0.1", 9092)
+    );
+    assert_eq!(body.controller_id(), 1);
+
+    // topics 为 null 时返回所有 topic
+    let topics = body.topics().as_ref().unwrap();
+    let topic = topics
+        .iter()
+        .find(|topic| topic.name() == Some(TOPIC))
+        .expect("Created topic is missing");
+    assert_eq!(topic.error_code(), 0);
+    assert_eq!(topic.partition_count(), 2);
+    assert_eq!(
+        topic.topic_authorized_operations(),
+        AUTHORIZED_OPERATIONS_OMITTED
+    );
+
+    // 指定的 topic 按请求的顺序返回, 不存在的 topic 返回错误
+    let response = metadata(Some(&["demo-metadata-missing", TOPIC])).await;
+    let body = metadata_body(&response);
+    let topics = body.topics().as_ref().unwrap();
+    assert_eq!(topics.len(), 2);
+    assert_eq!(topics[0].name(), Some("demo-metadata-missing"));
+    assert_eq!(topics[0].error_code(), UNKNOWN_TOPIC_OR_PARTITION);
+    assert_eq!(topics[1].name(), Some(TOPIC));
+    assert_eq!(topics[1].error_code(), 0);
+    assert_eq!(topics[1].topic_id(), topic.topic_id());
+
+    println!("metadata returns the local broker and topics");
+}
@@ -0,0 +1,48 @@
+use std::fs;
+
+use codecrafters_kafka::{
+    common_struct::RecordValue,
+    encode::Encode,
+    metadata_log::{
+        decode_record_batches, new_metadata_record, new_metadata_record_batch, read_log_content,
+        LogContent,
+    },
+};
+
+fn main() {
+    let first_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 64]))],
+    );
+    let second_batch = new_metadata_record_batch(
+        first_batch.next_offset(),
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xcd; 16]))],
+    );
+    let log_bytes = [first_batch.encode(), second_batch.encode()].concat();
+
+    let path = std::env::temp_dir().join("demo-mmap.log");
+    fs::write(&path, &log_bytes).expect("Failed to write log file");
+
+    // mmap 和 fs::read 读到的内容一致
+    let mapped = read_log_content(&path, true).expect("Failed to map log file");
+    assert!(matches!(mapped, LogContent::Mapped(_)));
+    assert_eq!(&mapped[..], log_bytes.as_slice());
+    let owned = read_log_content(&path, false).expect("Failed to read log file");
+    assert!(matches!(owned, LogContent::Owned(_)));
+    assert_eq!(&owned[..], log_bytes.as_slice());
+
+    let record_batches = decode_record_batches(&mapped).expect("Failed to decode mapped log");
+    assert_eq!(record_batches.len(), 2);
+    assert_eq!(record_batches[0].encode(), first_batch.encode());
+    assert_eq!(record_batches[1].encode(), second_batch.encode());
+
+    // 文件不存在时两种方式都返回错误
+    let missing = std::env::temp_dir().join("demo-mmap-missing.log");
+    let _ = fs::remove_file(&missing);
+    assert!(read_log_content(&missing, true).is_err());
+    assert!(read_log_content(&missing, false).is_err());
+
+    println!("mmap read {} bytes", mapped.len());
+}
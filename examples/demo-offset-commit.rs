@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::CompactString,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{create_topic, init_read_metadata_log, partition_log_path},
+    offsets::NO_COMMITTED_OFFSET,
+    response_message::ResponseBody,
+};
+
+use common::{execute, request_message};
+
+const TOPIC: &str = "demo-offset-commit";
+const GROUP: &str = "demo-commit-group";
+
+// OffsetCommitRequest v9: 一个 topic 的一个 partition, 返回 error_code
+async fn commit(topic: &str, partition: i32, offset: i64) -> i16 {
+    let body = [
+        &CompactString::new(GROUP.to_string()).encode()[..], // group_id
+        &1_i32.to_be_bytes(),                                // generation_id_or_member_epoch
+        &CompactString::new("member-1".to_string()).encode(), // member_id
+        &[0x00],                                             // group_instance_id: null
+        &[0x02],                                             // topics
+        &CompactString::new(topic.to_string()).encode(),     // name
+        &[0x02],                                             // partitions
+        &partition.to_be_bytes(),                            // partition_index
+        &offset.to_be_bytes(),                               // committed_offset
+        &(-1_i32).to_be_bytes(),                             // committed_leader_epoch
+        &[0x01],                                             // committed_metadata: ""
+        &[0x00, 0x00, 0x00],                                 // 三层 tag_buffer
+    ]
+    .concat();
+    let response = execute(&request_message(8, 9, &body)).await;
+    let ResponseBody::OffsetCommitV9(body) = response.body() else {
+        panic!("Expect offset commit response, got {:?}", response);
+    };
+    let topic_response = &body.topics().as_ref().unwrap()[0];
+    assert_eq!(topic_response.name(), topic);
+    let partition_response = &topic_response.partitions().as_ref().unwrap()[0];
+    assert_eq!(partition_response.partition_index(), partition);
+    partition_response.error_code()
+}
+
+// OffsetFetchRequest v9: 一个 topic 的一个 partition, 返回 (committed_offset, error_code)
+async fn fetch(topic: &str, partition: i32) -> (i64, i16) {
+    let body = [
+        &[0x02][..],                                     // groups
+        &CompactString::new(GROUP.to_string()).encode(), // group_id
+        &[0x00],                                         // member_id: null
+        &(-1_i32).to_be_bytes(),                         // member_epoch
+        &[0x02],                                         // topics
+        &CompactString::new(topic.to_string()).encode(), // name
+        &[0x02],                                         // partition_indexes
+        &partition.to_be_bytes(),                        // partition_index
+        &[0x00, 0x00],                                   // topic 和 group 的 tag_buffer
+        &[0x00],                                         // require_stable
+        &[0x00],                                         // tag_buffer
+    ]
+    .concat();
+    let response = execute(&request_message(9, 9, &body)).await;
+    let ResponseBody::OffsetFetchV9(body) = response.body() else {
+        panic!("Expect offset fetch response, got {:?}", response);
+    };
+    let group = &body.groups().as_ref().unwrap()[0];
+    let partition_response = &group.topics().as_ref().unwrap()[0]
+        .partitions()
+        .as_ref()
+        .unwrap()[0];
+    (
+        partition_response.committed_offset(),
+        partition_response.error_code(),
+    )
+}
+
+#[tokio::main]
+async fn main() {
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 2).expect("Failed to create topic");
+
+    assert_eq!(fetch(TOPIC, 1).await, (NO_COMMITTED_OFFSET, 0));
+
+    // 提交的 offset 可以通过 OffsetFetch 读回, 再次提交会覆盖
+    assert_eq!(commit(TOPIC, 1, 10).await, 0);
+    assert_eq!(fetch(TOPIC, 1).await, (10, 0));
+    assert_eq!(commit(TOPIC, 1, 25).await, 0);
+    assert_eq!(fetch(TOPIC, 1).await, (25, 0));
+    assert_eq!(fetch(TOPIC, 0).await, (NO_COMMITTED_OFFSET, 0));
+
+    // 不存在的 topic 和 partition
+    assert_eq!(
+        commit("demo-offset-commit-missing", 0, 1).await,
+        UNKNOWN_TOPIC_OR_PARTITION
+    );
+    assert_eq!(commit(TOPIC, 2, 1).await, UNKNOWN_TOPIC_OR_PARTITION);
+
+    println!("committed offsets are read back by offset fetch");
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::CompactString,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{create_topic, init_read_metadata_log, partition_log_path, topic_id},
+    offset_fetch::OffsetFetchResponseBodyV9,
+    offsets::{set_committed, NO_COMMITTED_OFFSET},
+    response_message::{ResponseBody, ResponseMessage},
+};
+
+use common::{execute, request_message};
+
+const TOPIC: &str = "demo-offset-fetch";
+const GROUP: &str = "demo-group";
+
+// OffsetFetchRequest v9: 一个 group, topics 为 None 时请求所有提交过的 partition
+fn offset_fetch_request(group_id: &str, topics: Option<&[(&str, &[i32])]>) -> Vec<u8> {
+    let mut body = vec![0x02]; // groups
+    body.extend(CompactString::new(group_id.to_string()).encode()); // group_id
+    body.push(0x00); // member_id: null
+    body.extend_from_slice(&(-1_i32).to_be_bytes()); // member_epoch
+    match topics {
+        None => body.push(0x00),
+        Some(topics) => {
+            body.push(topics.len() as u8 + 1);
+            for (name, partitions) in topics {
+                body.extend(CompactString::new(name.to_string()).encode());
+                body.push(partitions.len() as u8 + 1);
+                for partition in partitions.iter() {
+                    body.extend_from_slice(&partition.to_be_bytes());
+                }
+                body.push(0x00); // tag_buffer
+            }
+        }
+    }
+    body.extend_from_slice(&[
+        0x00, // group 的 tag_buffer
+        0x00, // require_stable
+        0x00, // tag_buffer
+    ]);
+    request_message(9, 9, &body)
+}
+
+async fn offset_fetch(group_id: &str, topics: Option<&[(&str, &[i32])]>) -> ResponseMessage {
+    execute(&offset_fetch_request(group_id, topics)).await
+}
+
+fn offset_fetch_body(response: &ResponseMessage) -> &OffsetFetchResponseBodyV9 {
+    let ResponseBody::OffsetFetchV9(body) = response.body() else {
+        panic!("Expect offset fetch response, got {:?}", response);
+    };
+    body
+}
+
+// (partition, committed_offset, error_code)
+type PartitionOffset = (i32, i64, i16);
+
+// 返回第一个 group 中每个 topic 的 (name, [(partition, offset, error_code)])
+fn committed(body: &OffsetFetchResponseBodyV9) -> Vec<(String, Vec<PartitionOffset>)> {
+    let group = &body.groups().as_ref().unwrap()[0];
+    assert_eq!(group.group_id(), GROUP);
+    assert_eq!(group.error_code(), 0);
+    group
+        .topics()
+        .iter()
+        .flatten()
+        .map(|topic| {
+            let partitions = topic
+                .partitions()
+                .iter()
+                .flatten()
+                .map(|p| (p.partition_index(), p.committed_offset(), p.error_code()))
+                .collect();
+            (topic.name().to_string(), partitions)
+        })
+        .collect()
+}
+
+#[tokio::main]
+async fn main() {
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 3).expect("Failed to create topic");
+    let id = topic_id(TOPIC).expect("Topic is missing");
+
+    // 还没有提交过 offset
+    let response = offset_fetch(GROUP, None).await;
+    assert!(committed(offset_fetch_body(&response)).is_empty());
+
+    set_committed(GROUP, id, 0, 42);
+    set_committed(GROUP, id, 2, 7);
+    set_committed("demo-other-group", id, 1, 100);
+
+    // 没有提交过的 partition 返回 -1, 不存在的 partition 和 topic 返回错误
+    let response = offset_fetch(
+        GROUP,
+        Some(&[
+            (TOPIC, &[0, 1, 2, 3][..]),
+            ("demo-offset-fetch-missing", &[0][..]),
+        ]),
+    )
+    .await;
+    assert_eq!(
+        committed(offset_fetch_body(&response)),
+        [
+            (
+                TOPIC.to_string(),
+                vec![
+                    (0, 42, 0),
+                    (1, NO_COMMITTED_OFFSET, 0),
+                    (2, 7, 0),
+                    (3, NO_COMMITTED_OFFSET, UNKNOWN_TOPIC_OR_PARTITION),
+                ]
+            ),
+            (
+                "demo-offset-fetch-missing".to_string(),
+                vec![(0, NO_COMMITTED_OFFSET, UNKNOWN_TOPIC_OR_PARTITION)]
+            ),
+        ]
+    );
+
+    // topics 为 null 时只返回这个 group 提交过的 partition
+    let response = offset_fetch(GROUP, None).await;
+    assert_eq!(
+        committed(offset_fetch_body(&response)),
+        [(TOPIC.to_string(), vec![(0, 42, 0), (2, 7, 0)])]
+    );
+
+    println!("offset fetch returns committed offsets");
+}
@@ -0,0 +1,146 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        CompactArray, CompactString, ParitionRecord, RecordType, RecordValue, TagBuffer,
+        TagSection, TopicRecord,
+    },
+    decode::Decode,
+    describe_topic_partitions::RepicaNode,
+    encode::Encode,
+    metadata_log::{
+        init_internal_states, new_metadata_record, new_metadata_record_batch, MetadataLog,
+        TOPIC_INFO_MAP,
+    },
+};
+use uuid::Uuid;
+
+const TOPIC: &str = "demo-partition-elr";
+
+fn replica_nodes(ids: &[i32]) -> CompactArray<RepicaNode> {
+    CompactArray::from_vec(ids.iter().map(|id| RepicaNode::new(*id)).collect())
+}
+
+// RepicaNode 没有实现 PartialEq, 比较 encode 后的字节
+fn assert_nodes(nodes: &CompactArray<RepicaNode>, ids: &[i32]) {
+    assert_eq!(nodes.encode(), replica_nodes(ids).encode());
+}
+
+fn partition_record(version: i8, topic_id: Uuid, tag_sections: Vec<TagSection>) -> ParitionRecord {
+    ParitionRecord {
+        frame_version: 1,
+        record_type: RecordType::PARITION_RECORD,
+        version,
+        parition_id: 0,
+        topic_id,
+        replica_nodes: replica_nodes(&[1, 2, 3]),
+        isr_nodes: replica_nodes(&[1]),
+        removing_replicas_nodes: CompactArray::empty(),
+        adding_replicas_nodes: CompactArray::empty(),
+        leader_id: 1,
+        leader_epoch: 0,
+        partition_epoch: 0,
+        directories: CompactArray::empty(),
+        tag_buffers: TagBuffer::new(tag_sections),
+    }
+}
+
+fn elr_sections(eligible_leader_replicas: &[i32], last_known_elr: &[i32]) -> Vec<TagSection> {
+    vec![
+        TagSection::new(
+            ParitionRecord::ELIGIBLE_LEADER_REPLICAS_TAG,
+            replica_nodes(eligible_leader_replicas).encode(),
+        ),
+        TagSection::new(
+            ParitionRecord::LAST_KNOWN_ELR_TAG,
+            replica_nodes(last_known_elr).encode(),
+        ),
+    ]
+}
+
+fn round_trip(partition: &ParitionRecord) -> ParitionRecord {
+    let bytes = partition.encode();
+    let mut buffer = Cursor::new(bytes.as_slice());
+    let decoded = ParitionRecord::decode(&mut buffer).expect("Failed to decode partition record");
+    assert_eq!(buffer.position() as usize, bytes.len());
+    decoded
+}
+
+fn main() {
+    let topic_id = Uuid::new_v4();
+
+    // version 2 从 tagged fields 中读取 ELR
+    let partition = round_trip(&partition_record(2, topic_id, elr_sections(&[2, 3], &[3])));
+    assert_nodes(&partition.eligible_leader_replicas().unwrap(), &[2, 3]);
+    assert_nodes(&partition.last_known_elr().unwrap(), &[3]);
+
+    // 旧版本没有这两个字段, 即使存在相同的 tag 也返回空数组
+    for version in [0, 1] {
+        let partition = round_trip(&partition_record(
+            version,
+            topic_id,
+            elr_sections(&[2, 3], &[3]),
+        ));
+        assert_nodes(&partition.eligible_leader_replicas().unwrap(), &[]);
+        assert_nodes(&partition.last_known_elr().unwrap(), &[]);
+    }
+
+    // 没有 tag 或者 tag 中是 null 数组时返回空数组
+    let partition = partition_record(
+        2,
+        topic_id,
+        vec![TagSection::new(
+            ParitionRecord::LAST_KNOWN_ELR_TAG,
+            vec![0x00],
+        )],
+    );
+    assert_nodes(&partition.eligible_leader_replicas().unwrap(), &[]);
+    assert_nodes(&partition.last_known_elr().unwrap(), &[]);
+
+    // tag 中的数据不完整时返回错误
+    let partition = partition_record(
+        2,
+        topic_id,
+        vec![TagSection::new(
+            ParitionRecord::ELIGIBLE_LEADER_REPLICAS_TAG,
+            vec![0x03, 0x00, 0x00],
+        )],
+    );
+    assert!(partition.eligible_leader_replicas().is_err());
+
+    // 加载 metadata log 后, DescribeTopicPartitions 使用的 partition 信息中包含 ELR
+    init_internal_states(&MetadataLog::new(vec![new_metadata_record_batch(
+        0,
+        0,
+        vec![
+            new_metadata_record(
+                0,
+                RecordValue::Topic(TopicRecord {
+                    frame_version: 1,
+                    record_type: RecordType::TOPIC_RECORD,
+                    version: 0,
+                    name: CompactString::new(TOPIC.to_string()),
+                    id: topic_id,
+                    tag_buffers: TagBuffer::default(),
+                }),
+            ),
+            new_metadata_record(
+                1,
+                RecordValue::Partition(partition_record(2, topic_id, elr_sections(&[2], &[3]))),
+            ),
+        ],
+    )]));
+    let topic_info_map = TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock");
+    let topic_info = topic_info_map
+        .get(&CompactString::new(TOPIC.to_string()))
+        .expect("Topic should be loaded");
+    let topic_partition = &topic_info.partitions_array.as_ref().unwrap()[0];
+    assert_nodes(&topic_partition.eligible_leader_replicas, &[2]);
+    assert_nodes(&topic_partition.last_known_elr, &[3]);
+    // 已经 decode 的 tag 不会出现在响应的 tagged fields 中
+    assert_eq!(topic_partition.tag_buffer.encode(), [0x00]);
+
+    println!("partition records expose ELR fields from their tagged fields");
+}
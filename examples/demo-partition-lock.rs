@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use codecrafters_kafka::metadata_log::partition_lock;
+
+fn main() {
+    let lock = partition_lock("demo-topic", 0);
+    assert!(Arc::ptr_eq(&lock, &partition_lock("demo-topic", 0)));
+    assert!(!Arc::ptr_eq(&lock, &partition_lock("demo-topic", 1)));
+    assert!(!Arc::ptr_eq(&lock, &partition_lock("other-topic", 0)));
+
+    // produce 持有写锁时, 同一个 partition 的 fetch 和 produce 都需要等待
+    let write_guard = lock.write().expect("Failed to get write lock");
+    assert!(partition_lock("demo-topic", 0).try_read().is_err());
+    assert!(partition_lock("demo-topic", 0).try_write().is_err());
+    // 其他 partition 不受影响
+    assert!(partition_lock("demo-topic", 1).try_write().is_ok());
+    assert!(partition_lock("other-topic", 0).try_read().is_ok());
+    drop(write_guard);
+
+    // 同一个 partition 的多个 fetch 可以同时读取
+    let first_read = lock.read().expect("Failed to get read lock");
+    let other = partition_lock("demo-topic", 0);
+    let second_read = other.try_read();
+    assert!(second_read.is_ok());
+    assert!(partition_lock("demo-topic", 0).try_write().is_err());
+    drop(second_read);
+    drop(first_read);
+    assert!(partition_lock("demo-topic", 0).try_write().is_ok());
+
+    println!("partition lock checks passed");
+}
@@ -0,0 +1,57 @@
+use codecrafters_kafka::{
+    common_struct::{NullableString, TagBuffer},
+    decode::DecodeError,
+    encode::Encode,
+    request_message::{request_api_versions, RequestHeaderV2},
+};
+
+fn main() {
+    let message = request_api_versions(4).as_bytes();
+    let header = RequestHeaderV2::peek_from(&message).expect("Failed to peek header");
+    assert_eq!(
+        header,
+        RequestHeaderV2 {
+            request_api_key: 18,
+            request_api_version: 4,
+            correlation_id: 0,
+            client_id: NullableString::new(Some("myclient".to_string())),
+            tag_buffer: TagBuffer::default(),
+        }
+    );
+
+    // 只有 header 的字节也足够, 不需要完整的 body
+    let header_end = 4 + header.encode().len();
+    assert_eq!(
+        RequestHeaderV2::peek_from(&message[..header_end]).unwrap(),
+        header
+    );
+
+    // header 不完整时返回 Incomplete, 定长字段和 client_id 都会给出还需要的字节数
+    for end in 0..header_end {
+        match RequestHeaderV2::peek_from(&message[..end]) {
+            Err(err @ DecodeError::Incomplete { .. }) => {
+                let needed = err.needed().expect("needed should be known");
+                assert!(end + needed <= header_end, "end {}: {}", end, err);
+            }
+            other => panic!("Expect Incomplete when only {} bytes, got {:?}", end, other),
+        }
+    }
+
+    // message_size 小于 header 的长度时, 读到更多字节也无法解析
+    let mut truncated = message.clone();
+    truncated[..4].copy_from_slice(&6_u32.to_be_bytes());
+    assert!(RequestHeaderV2::peek_from(&truncated)
+        .unwrap_err()
+        .is_other());
+    assert_eq!(header.client_id(), Some("myclient"));
+
+    // client_id 为 null (长度 -1)
+    let mut null_client_id = vec![0x00, 0x00, 0x00, 0x0b];
+    null_client_id.extend_from_slice(&[0x00, 0x12, 0x00, 0x04, 0x00, 0x00, 0x00, 0x07]);
+    null_client_id.extend_from_slice(&[0xff, 0xff, 0x00]);
+    let null_header = RequestHeaderV2::peek_from(&null_client_id).expect("Failed to peek header");
+    assert_eq!(null_header.client_id(), None);
+    assert_eq!(null_header.correlation_id, 7);
+    assert_eq!(null_header.encode(), &null_client_id[4..]);
+    println!("{:?}", header);
+}
@@ -0,0 +1,40 @@
+use codecrafters_kafka::request_message::{request_api_versions, RequestHeader};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// 需要先启动 server, 一次性发送多个请求, 检查响应的顺序与请求一致
+#[tokio::main]
+async fn main() {
+    let mut stream = TcpStream::connect("127.0.0.1:9092")
+        .await
+        .expect("Failed to connect to 127.0.0.1:9092");
+
+    let request_count = 10;
+    let mut requests = vec![];
+    for correlation_id in 0..request_count {
+        let mut request = request_api_versions(4);
+        let RequestHeader::RequestHeaderV2(header) = &mut request.header else {
+            panic!("ApiVersions v4 uses request header v2");
+        };
+        header.correlation_id = correlation_id;
+        requests.append(&mut request.as_bytes());
+    }
+    stream
+        .write_all(&requests)
+        .await
+        .expect("Failed to send requests");
+
+    for correlation_id in 0..request_count {
+        let message_size = stream.read_u32().await.expect("Failed to read size");
+        let mut message = vec![0; message_size as usize];
+        stream
+            .read_exact(&mut message)
+            .await
+            .expect("Failed to read response");
+        let response_correlation_id = i32::from_be_bytes(message[..4].try_into().unwrap());
+        assert_eq!(response_correlation_id, correlation_id);
+        println!("Response {} ({} bytes)", correlation_id, message_size);
+    }
+}
@@ -0,0 +1,41 @@
+use codecrafters_kafka::{
+    broker_config::DEFAULT_MAX_MESSAGE_BYTES,
+    common_struct::RecordValue,
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch},
+    produce::{validate_batch, CORRUPT_MESSAGE_ERROR, MESSAGE_TOO_LARGE_ERROR},
+};
+
+fn main() {
+    let record_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![new_metadata_record(
+            0,
+            RecordValue::Unknown(vec![0xab; 1024]),
+        )],
+    );
+    let batch_size = record_batch.encode().len();
+    assert_eq!(record_batch.encoded_len(), batch_size);
+
+    assert!(validate_batch(0, &record_batch, DEFAULT_MAX_MESSAGE_BYTES).is_ok());
+    assert!(validate_batch(0, &record_batch, batch_size).is_ok());
+
+    // 刚好超过限制 1 个字节
+    let (error_code, message) =
+        validate_batch(0, &record_batch, batch_size - 1).expect_err("Expect MESSAGE_TOO_LARGE");
+    assert_eq!(error_code, MESSAGE_TOO_LARGE_ERROR);
+    println!("{}", message);
+
+    // 大小的检查优先于其他检查
+    let mut corrupted = record_batch.clone();
+    corrupted.crc ^= 1;
+    assert_eq!(
+        validate_batch(0, &corrupted, batch_size).unwrap_err().0,
+        CORRUPT_MESSAGE_ERROR
+    );
+    assert_eq!(
+        validate_batch(0, &corrupted, batch_size - 1).unwrap_err().0,
+        MESSAGE_TOO_LARGE_ERROR
+    );
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::{CompactRecords, CompactString, RecordBatch, RecordValue},
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{
+        create_topic, init_read_metadata_log, log_end_offset, new_metadata_record,
+        new_metadata_record_batch, partition_log_path,
+    },
+    produce::CORRUPT_MESSAGE_ERROR,
+    response_message::ResponseBody,
+};
+
+use common::{execute, request_message};
+
+const TOPIC: &str = "demo-produce-partitions";
+const MISSING_TOPIC: &str = "demo-produce-partitions-missing";
+
+fn record_batch(record_count: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        0,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 4])))
+            .collect(),
+    )
+}
+
+// records 字段的字节, corrupt 时修改最后一个 record 的内容, crc 不再匹配
+fn records(record_batch: &RecordBatch, corrupt: bool) -> Vec<u8> {
+    let mut bytes = CompactRecords::new(Some(vec![record_batch.clone()])).encode();
+    if corrupt {
+        *bytes.last_mut().unwrap() ^= 0xff;
+    }
+    bytes
+}
+
+// 一个 topic 和它的 (partition, records)
+type TopicData<'a> = (&'a str, Vec<(i32, Vec<u8>)>);
+
+// ProduceRequest v9
+fn produce_request(topics: &[TopicData<'_>]) -> Vec<u8> {
+    let mut body = [
+        &[0x00][..],               // transactional_id
+        &(-1_i16).to_be_bytes(),   // acks
+        &1000_i32.to_be_bytes(),   // timeout_ms
+        &[topics.len() as u8 + 1], // topic_data
+    ]
+    .concat();
+    for (topic, partitions) in topics {
+        body.extend_from_slice(&CompactString::new(topic.to_string()).encode());
+        body.push(partitions.len() as u8 + 1); // partition_data
+        for (partition, records) in partitions {
+            body.extend_from_slice(&partition.to_be_bytes());
+            body.extend_from_slice(records);
+            body.push(0x00); // tag_buffer
+        }
+        body.push(0x00); // tag_buffer
+    }
+    body.push(0x00); // tag_buffer
+    request_message(0, 9, &body)
+}
+
+// 返回每个 partition 的 (topic, index, error_code, base_offset)
+async fn produce(topics: &[TopicData<'_>]) -> Vec<(String, i32, i16, i64)> {
+    let response = execute(&produce_request(topics)).await;
+    let ResponseBody::ProduceV9(body) = response.body() else {
+        panic!("Expect produce response, got {:?}", response);
+    };
+    let mut partitions = vec![];
+    for topic_response in body.responses().as_ref().unwrap() {
+        for partition in topic_response.partition_responses().as_ref().unwrap() {
+            partitions.push((
+                topic_response.name().as_str().to_string(),
+                partition.index(),
+                partition.error_code(),
+                partition.base_offset(),
+            ));
+        }
+    }
+    partitions
+}
+
+#[tokio::main]
+async fn main() {
+    // metadata log 不存在时创建空文件, 之后通过 create_topic 追加 topic
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 2).expect("Failed to create topic");
+    for partition in 0..2 {
+        let _ = fs::remove_file(partition_log_path(TOPIC, partition));
+    }
+
+    // 一个请求中的 partition 互不影响: 正常写入, crc 错误, topic 不存在
+    let partitions = produce(&[
+        (
+            TOPIC,
+            vec![
+                (0, records(&record_batch(3), false)),
+                (1, records(&record_batch(2), true)),
+            ],
+        ),
+        (MISSING_TOPIC, vec![(0, records(&record_batch(1), false))]),
+    ])
+    .await;
+    assert_eq!(
+        partitions,
+        vec![
+            (TOPIC.to_string(), 0, 0, 0),
+            (TOPIC.to_string(), 1, CORRUPT_MESSAGE_ERROR, -1),
+            (MISSING_TOPIC.to_string(), 0, UNKNOWN_TOPIC_OR_PARTITION, -1),
+        ]
+    );
+    // 出错的 partition 没有写入
+    assert_eq!(log_end_offset(&partition_log_path(TOPIC, 0)).unwrap(), 3);
+    assert!(!partition_log_path(TOPIC, 1).exists());
+
+    // 下一次 produce 从 log end offset 继续
+    let partitions = produce(&[(TOPIC, vec![(0, records(&record_batch(2), false))])]).await;
+    assert_eq!(partitions, vec![(TOPIC.to_string(), 0, 0, 3)]);
+    assert_eq!(log_end_offset(&partition_log_path(TOPIC, 0)).unwrap(), 5);
+
+    // 只读取 batch 的 header, 最后一个 batch 被截断时仍然返回错误
+    let log_path = partition_log_path(TOPIC, 1);
+    let mut log_content = record_batch(2).encode();
+    log_content.extend_from_slice(&record_batch(1).encode()[..20]);
+    fs::create_dir_all(log_path.parent().unwrap()).expect("Failed to create log dir");
+    fs::write(&log_path, &log_content).expect("Failed to write log file");
+    assert!(log_end_offset(&log_path).is_err());
+    fs::remove_file(&log_path).expect("Failed to remove log file");
+
+    println!("produce returns an error code for each partition");
+}
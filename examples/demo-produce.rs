@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+
+use codecrafters_kafka::{
+    common_struct::{CompactRecords, CompactString, RecordBatch, RecordValue},
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    fetch::{fetch_partition, IsolationLevel},
+    metadata_log::{
+        create_topic, init_read_metadata_log, new_metadata_record, new_metadata_record_batch,
+        partition_log_path,
+    },
+    response_message::ResponseBody,
+};
+
+use common::{execute, request_message};
+
+const TOPIC: &str = "demo-produce";
+
+fn record_batch(record_count: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        0,
+        0,
+        (0..record_count)
+            .map(|idx| new_metadata_record(idx as i32, RecordValue::Unknown(vec![0xab; 4])))
+            .collect(),
+    )
+}
+
+// ProduceRequest v9: 一个 topic, 一个 partition
+fn produce_request(topic: &str, partition: i32, record_batch: &RecordBatch) -> Vec<u8> {
+    let body = [
+        &[0x00][..],                                                     // transactional_id
+        &(-1_i16).to_be_bytes(),                                         // acks
+        &1000_i32.to_be_bytes(),                                         // timeout_ms
+        &[0x02],                                                         // topic_data
+        &CompactString::new(topic.to_string()).encode(),                 // name
+        &[0x02],                                                         // partition_data
+        &partition.to_be_bytes(),                                        // index
+        &CompactRecords::new(Some(vec![record_batch.clone()])).encode(), // records
+        &[0x00, 0x00, 0x00],                                             // 三层 tag_buffer
+    ]
+    .concat();
+    request_message(0, 9, &body)
+}
+
+// 返回 (error_code, base_offset)
+async fn produce(topic: &str, partition: i32, record_batch: &RecordBatch) -> (i16, i64) {
+    let response = execute(&produce_request(topic, partition, record_batch)).await;
+    let ResponseBody::ProduceV9(body) = response.body() else {
+        panic!("Expect produce response, got {:?}", response);
+    };
+    let topic_response = &body.responses().as_ref().unwrap()[0];
+    assert_eq!(topic_response.name().as_str(), topic);
+    let partition_response = &topic_response.partition_responses().as_ref().unwrap()[0];
+    assert_eq!(partition_response.index(), partition);
+    (
+        partition_response.error_code(),
+        partition_response.base_offset(),
+    )
+}
+
+#[tokio::main]
+async fn main() {
+    // metadata log 不存在时创建空文件, 之后通过 create_topic 追加 topic
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    fs::create_dir_all(metadata_log_file.parent().unwrap()).expect("Failed to create log dir");
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&metadata_log_file)
+        .expect("Failed to create metadata log");
+    init_read_metadata_log().expect("Failed to read metadata log");
+    create_topic(TOPIC, 1).expect("Failed to create topic");
+
+    // 已有的 segment 中有 offset 0, 1
+    let log_path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(log_path.parent().unwrap()).expect("Failed to create log dir");
+    fs::write(&log_path, record_batch(2).encode()).expect("Failed to write log file");
+
+    // 新的 batch 从已有 segment 的末尾继续分配 offset
+    assert_eq!(produce(TOPIC, 0, &record_batch(3)).await, (0, 2));
+    assert_eq!(produce(TOPIC, 0, &record_batch(1)).await, (0, 5));
+    let fetched = fetch_partition(TOPIC, 0, 0, usize::MAX, IsolationLevel::READ_UNCOMMITTED);
+    let base_offsets: Vec<i64> = fetched
+        .record_batches()
+        .get_inner()
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|record_batch| record_batch.base_offset)
+        .collect();
+    assert_eq!(base_offsets, [0, 2, 5]);
+
+    // 不存在的 topic 和 partition
+    assert_eq!(
+        produce("demo-produce-missing", 0, &record_batch(1)).await,
+        (UNKNOWN_TOPIC_OR_PARTITION, -1)
+    );
+    assert_eq!(
+        produce(TOPIC, 3, &record_batch(1)).await,
+        (UNKNOWN_TOPIC_OR_PARTITION, -1)
+    );
+
+    println!("produce appends to the partition log");
+}
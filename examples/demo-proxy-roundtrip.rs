@@ -0,0 +1,25 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{common_struct::display_bytes, response_message::ResponseMessage};
+
+fn main() {
+    // 无法识别的 api key 的响应, decode 再 encode 后必须与原始字节完全一致
+    let body = [0x00_u8, 0x00, 0x00, 0x2a, 0x01, 0x02, 0x03];
+    let correlation_id = 7_i32.to_be_bytes();
+    let mut message = ((correlation_id.len() + 1 + body.len()) as u32)
+        .to_be_bytes()
+        .to_vec();
+    message.extend_from_slice(&correlation_id);
+    message.push(0x00); // header 的 tag buffer
+    message.extend_from_slice(&body);
+
+    let mut buffer = Cursor::new(message.as_slice());
+    let mut response =
+        ResponseMessage::decode(&mut buffer, 999).expect("Failed to decode unknown response");
+    println!("{:?}", response);
+    assert_eq!(buffer.position() as usize, message.len());
+
+    let encoded = response.as_bytes();
+    println!("{}", display_bytes(&encoded));
+    assert_eq!(encoded, message);
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        CompactString, FeatureLevelRecord, MetadataAttributes, RecordBatch, RecordType,
+        RecordValue, TagBuffer, RECORD_BATCH_CRC_START,
+    },
+    crc::crc32c,
+    decode::Decode,
+    encode::Encode,
+};
+
+use common::assert_consumes;
+
+// __cluster_metadata-0 中的第一个 batch, 只有一条 FeatureLevelRecord
+#[rustfmt::skip]
+const FEATURE_LEVEL_BATCH: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // base_offset: int64
+    0x00, 0x00, 0x00, 0x4f,                         // batch_length: int32
+    0x00, 0x00, 0x00, 0x01,                         // partition_leader_epoch: int32
+    0x02,                                           // magic: int8
+    0xb0, 0x69, 0x45, 0x7c,                         // crc: uint32
+    0x00, 0x00,                                     // attributes: int16
+    0x00, 0x00, 0x00, 0x00,                         // last_offset_delta: int32
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // base_timestamp: int64
+    0x00, 0x00, 0x01, 0x91, 0xe0, 0x5a, 0xf8, 0x18, // max_timestamp: int64
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // producer_id: int64
+    0xff, 0xff,                                     // producer_epoch: int16
+    0xff, 0xff, 0xff, 0xff,                         // base_sequence: int32
+    0x00, 0x00, 0x00, 0x01,                         // records 个数: int32
+    0x3a,                                           // length: varint, 29
+    0x00,                                           // attributes: int8
+    0x00,                                           // timestamp_delta: varlong
+    0x00,                                           // offset_delta: varint
+    0x01,                                           // key 长度: varint, -1
+    0x2e,                                           // value 长度: varint, 23
+    0x01, 0x0c, 0x00, 0x11,                         // frame_version, type, version, name 长度
+    b'm', b'e', b't', b'a', b'd', b'a', b't', b'a', b'.',
+    b'v', b'e', b'r', b's', b'i', b'o', b'n',
+    0x00, 0x14,                                     // feature_level: int16
+    0x00,                                           // tagged fields
+    0x00,                                           // headers 个数: varint
+];
+
+fn main() {
+    // 后面紧跟下一个 batch 的开头时, 只读取当前 batch
+    let log_content = [FEATURE_LEVEL_BATCH, &FEATURE_LEVEL_BATCH[..12]].concat();
+    let record_batch =
+        assert_consumes(&log_content, RecordBatch::decode, FEATURE_LEVEL_BATCH.len());
+
+    assert_eq!(record_batch.base_offset, 1);
+    assert_eq!(record_batch.batch_length, 0x4f);
+    assert_eq!(
+        record_batch.batch_length as usize,
+        FEATURE_LEVEL_BATCH.len() - 12
+    );
+    assert_eq!(record_batch.partition_leader_epoch, 1);
+    assert_eq!(record_batch.magic_byte, 2);
+    assert_eq!(record_batch.crc as u32, 0xb069457c);
+    assert_eq!(record_batch.crc as u32, record_batch.compute_crc());
+    assert_eq!(
+        record_batch.attributes.bits(),
+        MetadataAttributes::empty().bits()
+    );
+    assert_eq!(record_batch.last_offset_delta, 0);
+    assert_eq!(record_batch.base_timestamp, 0x0191e05af818);
+    assert_eq!(record_batch.max_timestamp, 0x0191e05af818);
+    assert_eq!(record_batch.producer_id, -1);
+    assert_eq!(record_batch.producer_epoch, -1);
+    assert_eq!(record_batch.base_sequence, -1);
+
+    let records = record_batch.records.as_ref().expect("null records");
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.length.as_i64(), 29);
+    assert_eq!(record.attributes, 0);
+    assert_eq!(record.timestamp_delta.as_i128(), 0);
+    assert_eq!(record.offset_delta.as_i64(), 0);
+    assert!(record.key.get_inner().is_none());
+    assert!(record.headers.is_empty());
+    match &record.value {
+        RecordValue::FeatureLevel(feature_level) => assert_eq!(
+            feature_level,
+            &FeatureLevelRecord {
+                frame_version: 1,
+                record_type: RecordType::FEATURE_LEVEL_RECORD,
+                version: 0,
+                name: CompactString::new("metadata.version".to_string()),
+                feature_level: 20,
+                tag_buffers: TagBuffer::default(),
+            }
+        ),
+        value => panic!("Expect FeatureLevelRecord, got {:?}", value),
+    }
+
+    assert_eq!(record_batch.encode(), FEATURE_LEVEL_BATCH);
+
+    // last_offset_delta 与 records 个数不一致时 decode 失败
+    let mut wrong_delta = FEATURE_LEVEL_BATCH.to_vec();
+    wrong_delta[23..27].copy_from_slice(&1_i32.to_be_bytes());
+    let crc = crc32c(&wrong_delta[RECORD_BATCH_CRC_START..]);
+    wrong_delta[17..21].copy_from_slice(&crc.to_be_bytes());
+    let err = RecordBatch::decode(&mut Cursor::new(wrong_delta.as_slice()))
+        .expect_err("Expect last_offset_delta mismatch");
+    assert!(err.is_other());
+    println!("{}", err);
+
+    // crc 覆盖从 attributes 开始的字节, 修改其中任意一个字节都会 decode 失败
+    for position in [RECORD_BATCH_CRC_START, FEATURE_LEVEL_BATCH.len() - 3] {
+        let mut corrupted = FEATURE_LEVEL_BATCH.to_vec();
+        corrupted[position] ^= 0x01;
+        let mut buffer = Cursor::new(corrupted.as_slice());
+        let err = RecordBatch::decode(&mut buffer).expect_err("Expect invalid crc");
+        assert!(err.is_other());
+        assert!(err.to_string().contains("invalid crc"), "{}", err);
+        assert_eq!(buffer.position() as usize, corrupted.len());
+        println!("{}", err);
+    }
+    println!("{:#?}", record_batch);
+}
@@ -0,0 +1,126 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{
+        CompactArray, CompactNullableString, CompactString, RecordBatch, RecordType, RecordValue,
+        RegisterBrokerRecord,
+    },
+    decode::Decode,
+    encode::Encode,
+    metadata_log::{
+        brokers, init_internal_states, new_metadata_record, new_metadata_record_batch, BrokerInfo,
+        MetadataLog,
+    },
+};
+use uuid::Uuid;
+
+// version 3 的 RegisterBrokerRecord, broker 1 监听 localhost:9092
+#[rustfmt::skip]
+const REGISTER_BROKER_V3: &[u8] = &[
+    0x01,                                           // frame_version
+    0x00,                                           // type: RegisterBrokerRecord
+    0x03,                                           // version
+    0x00, 0x00, 0x00, 0x01,                         // broker_id: int32
+    0x00,                                           // is_migrating_zk_broker: bool
+    0x8f, 0x5c, 0x6b, 0x1e, 0x2a, 0x47, 0x4d, 0x0b, // incarnation_id: uuid
+    0x9c, 0x31, 0x5e, 0x64, 0x7a, 0x10, 0x22, 0x93,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, // broker_epoch: int64
+    0x02,                                           // end_points 个数 + 1
+    0x0a, b'P', b'L', b'A', b'I', b'N', b'T', b'E', b'X', b'T',
+    0x0a, b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't',
+    0x23, 0x84,                                     // port: uint16, 9092
+    0x00, 0x00,                                     // security_protocol: int16
+    0x00,                                           // tagged fields
+    0x02,                                           // features 个数 + 1
+    0x11, b'm', b'e', b't', b'a', b'd', b'a', b't', b'a', b'.',
+    b'v', b'e', b'r', b's', b'i', b'o', b'n',
+    0x00, 0x01,                                     // min_supported_version: int16
+    0x00, 0x14,                                     // max_supported_version: int16
+    0x00,                                           // tagged fields
+    0x00,                                           // rack: null
+    0x00,                                           // fenced: bool
+    0x00,                                           // in_controlled_shutdown: bool
+    0x01,                                           // log_dirs: 空数组
+    0x00,                                           // tagged fields
+];
+
+fn main() {
+    // metadata log 中没有 broker 时只有本 broker
+    assert_eq!(
+        brokers(),
+        vec![BrokerInfo {
+            id: 1,
+            host: "127.0.0.1".to_string(),
+            port: 9092,
+            rack: None,
+        }]
+    );
+
+    let mut buffer = Cursor::new(REGISTER_BROKER_V3);
+    let broker_1 = RegisterBrokerRecord::decode(&mut buffer).expect("Invalid RegisterBrokerRecord");
+    assert_eq!(buffer.position() as usize, REGISTER_BROKER_V3.len());
+    assert_eq!(broker_1.broker_id, 1);
+    assert_eq!(broker_1.broker_epoch, 7);
+    assert_eq!(broker_1.encode(), REGISTER_BROKER_V3);
+
+    // version 0 没有 in_controlled_shutdown, is_migrating_zk_broker 和 log_dirs
+    let mut broker_2 = broker_1.clone();
+    broker_2.version = 0;
+    broker_2.broker_id = 2;
+    broker_2.incarnation_id = Uuid::new_v4();
+    broker_2.end_points.as_mut().unwrap()[0].port = 9093;
+    broker_2.rack = CompactNullableString::new(Some("rack-b".to_string()));
+    broker_2.log_dirs = CompactArray::empty();
+    assert_eq!(
+        broker_2.encode().len(),
+        REGISTER_BROKER_V3.len() - 3 + "rack-b".len()
+    );
+
+    // 同一个 broker 再次注册时以最新的记录为准
+    let mut broker_1_moved = broker_1.clone();
+    broker_1_moved.end_points.as_mut().unwrap()[0].host =
+        CompactString::new("broker-1".to_string());
+
+    let record_batch = new_metadata_record_batch(
+        0,
+        0,
+        vec![
+            new_metadata_record(0, RecordValue::Unknown(REGISTER_BROKER_V3.to_vec())),
+            new_metadata_record(1, RecordValue::RegisterBroker(broker_2)),
+            new_metadata_record(2, RecordValue::RegisterBroker(broker_1_moved)),
+        ],
+    );
+    let record_batch = RecordBatch::decode(&mut Cursor::new(record_batch.encode().as_slice()))
+        .expect("Failed to decode record batch");
+    for record in record_batch.get_records().iter().flatten() {
+        match record.get_value() {
+            RecordValue::RegisterBroker(register_broker) => {
+                assert_eq!(
+                    register_broker.record_type,
+                    RecordType::REGISTER_BROKER_RECORD
+                )
+            }
+            value => panic!("Expect RegisterBrokerRecord, got {:?}", value),
+        }
+    }
+
+    init_internal_states(&MetadataLog::new(vec![record_batch]));
+    assert_eq!(
+        brokers(),
+        vec![
+            BrokerInfo {
+                id: 1,
+                host: "broker-1".to_string(),
+                port: 9092,
+                rack: None,
+            },
+            BrokerInfo {
+                id: 2,
+                host: "localhost".to_string(),
+                port: 9093,
+                rack: Some("rack-b".to_string()),
+            },
+        ]
+    );
+    println!("{:#?}", brokers());
+}
@@ -0,0 +1,62 @@
+// cargo run --example demo-reset-state --features test-utils
+use codecrafters_kafka::{
+    api_versions::MetadataVersion,
+    common_struct::{CompactString, RecordType, RecordValue, TagBuffer, TopicRecord},
+    metadata_log::{
+        init_internal_states, metadata_version, new_metadata_record, new_metadata_record_batch,
+        reset_metadata_state, topic_exists, topic_id, MetadataLog, TOPIC_ID_NAME_MAP,
+        TOPIC_INFO_MAP, TOPIC_RECORD_BATCH_MAP,
+    },
+};
+use uuid::Uuid;
+
+fn load_topic(name: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    let record = new_metadata_record(
+        0,
+        RecordValue::Topic(TopicRecord {
+            frame_version: 1,
+            record_type: RecordType::TOPIC_RECORD,
+            version: 0,
+            name: CompactString::new(name.to_string()),
+            id,
+            tag_buffers: TagBuffer::default(),
+        }),
+    );
+    init_internal_states(&MetadataLog::new(vec![new_metadata_record_batch(
+        0,
+        0,
+        vec![record],
+    )]));
+    id
+}
+
+fn assert_empty() {
+    assert!(TOPIC_ID_NAME_MAP.lock().unwrap().is_empty());
+    assert!(TOPIC_INFO_MAP.lock().unwrap().is_empty());
+    assert!(TOPIC_RECORD_BATCH_MAP.lock().unwrap().is_empty());
+    assert_eq!(metadata_version(), MetadataVersion::LATEST);
+}
+
+fn main() {
+    // 第一个测试写入的 topic
+    reset_metadata_state();
+    let first_id = load_topic("first");
+    assert!(topic_exists("first"));
+    assert_eq!(topic_id("first"), Some(first_id));
+
+    // 第二个测试开始前清空, 看不到第一个测试的 topic
+    reset_metadata_state();
+    assert_empty();
+    assert!(!topic_exists("first"));
+
+    let second_id = load_topic("second");
+    assert!(topic_exists("second"));
+    assert!(!topic_exists("first"));
+    assert_eq!(topic_id("second"), Some(second_id));
+    assert_eq!(TOPIC_INFO_MAP.lock().unwrap().len(), 1);
+
+    reset_metadata_state();
+    assert_empty();
+    println!("reset metadata state checks passed");
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::{fmt::Debug, io::Cursor};
+
+use codecrafters_kafka::{
+    alter_configs::{AlterableConfig, IncrementalAlterableConfig},
+    api_versions::ApiKey,
+    common_struct::{FeatureLevelRecord, TagBuffer, TopicRecord},
+    decode::Decode,
+    describe_topic_partitions::TopicRequest,
+    encode::Encode,
+    fetch::{
+        FetchPartitionRequest, FetchPartitionResponse, FetchRequestBodyV16, FetchTopicRequest,
+        ForgottenTopicRequest, NO_PREFERRED_READ_REPLICA,
+    },
+    request_message::RequestHeaderV2,
+    response_message::ResponseHeaderV1,
+};
+
+use common::assert_consumes;
+
+const TOPIC_ID: [u8; 16] = [
+    0x71, 0xa5, 0x9a, 0x51, 0x80, 0x29, 0x4a, 0x8a, 0x9e, 0x2f, 0x6d, 0x1b, 0x4c, 0x1f, 0x9a, 0x3e,
+];
+
+// decode -> encode 得到相同的字节, encoded_len 与实际长度一致, 再次 decode 得到相等的结构体
+// encode_into 只能追加在调用方已有的内容之后, 结果与 encode 相同
+fn roundtrip<T: Encode + Decode + PartialEq + Debug>(name: &str, bytes: &[u8]) {
+    let decoded = assert_consumes(bytes, T::decode, bytes.len());
+
+    let encoded = decoded.encode();
+    assert_eq!(encoded, bytes, "{} encode", name);
+    assert_eq!(decoded.encoded_len(), encoded.len(), "{} encoded_len", name);
+
+    let mut out = vec![0xab, 0xcd];
+    decoded.encode_into(&mut out);
+    assert_eq!(&out[..2], &[0xab, 0xcd], "{} encode_into prefix", name);
+    assert_eq!(&out[2..], bytes, "{} encode_into", name);
+
+    let decoded_again = T::decode(&mut Cursor::new(encoded.as_slice()))
+        .unwrap_or_else(|err| panic!("{} decode again: {}", name, err));
+    assert_eq!(decoded, decoded_again, "{} decode again", name);
+    println!("{} ok", name);
+}
+
+macro_rules! roundtrip_tests {
+    ($($type:ty => $sample:expr),* $(,)?) => {
+        $(
+            roundtrip::<$type>(stringify!($type), &$sample);
+        )*
+    };
+}
+
+fn main() {
+    let fetch_partition: &[u8] = &[
+        0x00, 0x00, 0x00, 0x00, // partition_index
+        0xff, 0xff, 0xff, 0xff, // current_leader_epoch
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // fetch_offset
+        0xff, 0xff, 0xff, 0xff, // last_fetched_epoch
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // log_start_offset
+        0x00, 0x10, 0x00, 0x00, // partition_max_bytes
+        0x00, // tag_buffer
+    ];
+
+    let fetch_topic = [&TOPIC_ID[..], &[0x02], fetch_partition, &[0x00]].concat();
+    let fetch_request = [
+        &[0x00, 0x00, 0x01, 0xf4][..], // max_wait_ms
+        &[0x00, 0x00, 0x00, 0x01],     // min_bytes
+        &[0x03, 0x20, 0x00, 0x00],     // max_bytes
+        &[0x00],                       // isolation_level
+        &[0x00, 0x00, 0x00, 0x00],     // session_id
+        &[0xff, 0xff, 0xff, 0xff],     // session_epoch
+        &[0x02],
+        &fetch_topic[..],
+        &[0x01], // forgotten_topics_data
+        &[0x01], // rack_id
+        // tag_buffer 是最后一个字段, 包含 tag 0 的 cluster_id
+        &[0x01, 0x00, 0x0a, 0x0a],
+        b"cluster-1",
+    ]
+    .concat();
+    let fetch_body = FetchRequestBodyV16::decode(&mut Cursor::new(fetch_request.as_slice()))
+        .expect("Failed to decode fetch request");
+    assert_eq!(
+        fetch_body
+            .cluster_id()
+            .expect("Failed to decode cluster_id"),
+        Some("cluster-1".to_string())
+    );
+
+    // 默认没有 preferred read replica, 不能是 broker 0
+    let empty_partition = FetchPartitionResponse::new_empty(0);
+    assert_eq!(
+        empty_partition.preferred_read_replica(),
+        NO_PREFERRED_READ_REPLICA
+    );
+    // partition_index(4) + error_code(2) + 3 个 i64 + 空的 aborted_transactions(1)
+    assert_eq!(&empty_partition.encode()[31..35], &[0xff; 4]);
+
+    roundtrip_tests! {
+        RequestHeaderV2 => [
+            &[0x00, 0x12, 0x00, 0x04, 0x00, 0x00, 0x00, 0x07][..],
+            &[0x00, 0x03, b'c', b'l', b'i'],
+            &[0x01, 0x00, 0x01, 0xff], // 带一个 tagged field
+        ]
+        .concat(),
+        RequestHeaderV2 => [0x00, 0x12, 0x00, 0x04, 0x00, 0x00, 0x00, 0x07, 0xff, 0xff, 0x00], // client_id 为 null
+        ApiKey => [0x00, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00],
+        TagBuffer => [0x02, 0x01, 0x02, 0xab, 0xcd, 0x02, 0x00],
+        FetchPartitionRequest => fetch_partition,
+        FetchTopicRequest => fetch_topic,
+        FetchRequestBodyV16 => fetch_request,
+        ForgottenTopicRequest => [&TOPIC_ID[..], &[0x00, 0x00, 0x00, 0x01, 0x00]].concat(),
+        TopicRequest => [0x04, b'f', b'o', b'o', 0x00],
+        TopicRecord => [&[0x01, 0x02, 0x00, 0x04, b'f', b'o', b'o'][..], &TOPIC_ID, &[0x00]].concat(),
+        FeatureLevelRecord => [
+            &[0x01, 0x0c, 0x00, 0x11][..],
+            b"metadata.version",
+            &[0x00, 0x14, 0x00],
+        ]
+        .concat(),
+        AlterableConfig => [&[0x0d][..], b"retention.ms", &[0x05], b"1000", &[0x00]].concat(),
+        AlterableConfig => [&[0x0d][..], b"retention.ms", &[0x00, 0x00]].concat(),
+        IncrementalAlterableConfig => [&[0x0f][..], b"cleanup.policy", &[0x02, 0x00, 0x00]].concat(),
+        ResponseHeaderV1 => [0x00, 0x00, 0x00, 0x07, 0x00],
+    }
+}
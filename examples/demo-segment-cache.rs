@@ -0,0 +1,94 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    sync::Arc,
+};
+
+use codecrafters_kafka::{
+    common_struct::{RecordBatch, RecordValue},
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch, partition_log_path},
+    segment_cache::{cached_segment, SegmentCache},
+};
+
+const TOPIC: &str = "demo-segment-cache";
+
+fn record_batch(base_offset: i64) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        vec![new_metadata_record(0, RecordValue::Unknown(vec![0xab; 16]))],
+    )
+}
+
+fn main() {
+    let record_batches = Arc::new(vec![record_batch(0)]);
+    let key = |partition: i32| ("demo".to_string(), partition, 0_i64);
+
+    let mut cache = SegmentCache::new(100);
+    cache.insert(key(0), 40, record_batches.clone());
+    cache.insert(key(1), 40, record_batches.clone());
+    assert_eq!(cache.size(), 80);
+
+    // 访问 partition 0 之后, partition 1 是最久未使用的
+    assert!(cache.get(&key(0), 40).is_some());
+    cache.insert(key(2), 40, record_batches.clone());
+    assert!(cache.contains(&key(0)));
+    assert!(!cache.contains(&key(1)));
+    assert!(cache.contains(&key(2)));
+    assert_eq!(cache.size(), 80);
+
+    // 文件被追加后不再命中, 取出缓存的 batch 补上追加的部分, 重新插入后超过容量, 淘汰 partition 0
+    assert!(cache.get(&key(2), 61).is_none());
+    let (cached, cached_size) = cache.take_appended(&key(2), 61).unwrap();
+    assert_eq!(cached_size, 40);
+    assert!(!cache.contains(&key(2)));
+    assert_eq!(cache.size(), 40);
+    cache.insert(key(2), 61, cached);
+    assert!(!cache.contains(&key(0)));
+    assert_eq!(cache.size(), 61);
+
+    // 超过容量的 segment 不缓存
+    cache.insert(key(3), 101, record_batches.clone());
+    assert!(!cache.contains(&key(3)));
+    assert_eq!(cache.size(), 61);
+
+    // 文件变小时不是追加, 不能复用缓存
+    assert!(cache.take_appended(&key(2), 50).is_none());
+    assert!(cache.contains(&key(2)));
+
+    #[cfg(feature = "metrics")]
+    {
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    // 追加写入后只 decode 新的 batch: 把已缓存的第一个 batch 在磁盘上改坏,
+    // 如果重新读取整个文件会因为 crc 不匹配而失败
+    let path = partition_log_path(TOPIC, 0);
+    fs::create_dir_all(path.parent().unwrap()).expect("Failed to create log dir");
+    let first_batch = record_batch(0).encode();
+    fs::write(&path, &first_batch).expect("Failed to write log file");
+    let segment = cached_segment(TOPIC, 0, 0)
+        .expect("Failed to read segment")
+        .expect("Segment should be cached");
+    assert_eq!(segment.len(), 1);
+
+    let mut corrupt_batch = first_batch.clone();
+    *corrupt_batch.last_mut().unwrap() ^= 0xff;
+    fs::write(&path, &corrupt_batch).expect("Failed to write log file");
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&record_batch(1).encode()))
+        .expect("Failed to append log file");
+    let segment = cached_segment(TOPIC, 0, 0)
+        .expect("Failed to read appended batch")
+        .expect("Segment should be cached");
+    let base_offsets: Vec<i64> = segment.iter().map(|batch| batch.base_offset).collect();
+    assert_eq!(base_offsets, [0, 1]);
+    assert_eq!(segment[0].encode(), first_batch);
+    fs::remove_file(&path).expect("Failed to remove log file");
+
+    println!("segment cache holds {} bytes", cache.size());
+}
@@ -0,0 +1,53 @@
+use codecrafters_kafka::{
+    common_struct::{RecordBatch, RecordValue},
+    encode::Encode,
+    metadata_log::{new_metadata_record, new_metadata_record_batch, split_batches_at_size},
+};
+
+fn record_batch(base_offset: i64, value_len: usize) -> RecordBatch {
+    new_metadata_record_batch(
+        base_offset,
+        0,
+        vec![new_metadata_record(
+            0,
+            RecordValue::Unknown(vec![0xab; value_len]),
+        )],
+    )
+}
+
+// 每个 segment 中 batch 的 base_offset
+fn split(batches: &[RecordBatch], max_bytes: usize) -> Vec<Vec<i64>> {
+    split_batches_at_size(batches.to_vec(), max_bytes)
+        .iter()
+        .map(|segment| segment.iter().map(|batch| batch.base_offset).collect())
+        .collect()
+}
+
+fn main() {
+    let batches = [
+        record_batch(0, 16),
+        record_batch(1, 16),
+        record_batch(2, 16),
+    ];
+    let batch_size = batches[0].encoded_len();
+    assert_eq!(batch_size, batches[0].encode().len());
+
+    // 恰好等于 max_bytes 时仍然放在同一个 segment 中
+    assert_eq!(split(&batches, 2 * batch_size), [vec![0, 1], vec![2]]);
+    assert_eq!(split(&batches, 2 * batch_size - 1), [[0], [1], [2]]);
+    assert_eq!(split(&batches, 3 * batch_size), [[0, 1, 2]]);
+
+    // 超过 max_bytes 的 batch 不会被拆开, 单独占一个 segment
+    let oversized = [
+        record_batch(0, 16),
+        record_batch(1, 4 * batch_size),
+        record_batch(2, 16),
+    ];
+    assert!(oversized[1].encoded_len() > 2 * batch_size);
+    assert_eq!(split(&oversized, 2 * batch_size), [[0], [1], [2]]);
+    assert_eq!(split(&oversized[1..2], batch_size), [[1]]);
+
+    assert!(split(&[], batch_size).is_empty());
+
+    println!("batches are split into segments without crossing max_bytes");
+}
@@ -0,0 +1,73 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{set_strict_tags, TagBuffer, TagSection},
+    decode::{Decode, DecodeResult},
+    encode::Encode,
+};
+
+// 个数, 然后每个字段为 tag, size, data
+fn tag_buffer_bytes(tags: &[(u8, u8)]) -> Vec<u8> {
+    let mut bytes = vec![tags.len() as u8];
+    for (tag, value) in tags {
+        bytes.extend_from_slice(&[*tag, 0x01, *value]);
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8], strict: bool) -> DecodeResult<TagBuffer> {
+    let mut buffer = Cursor::new(bytes);
+    let tag_buffer = TagBuffer::decode_with(&mut buffer, strict)?;
+    assert_eq!(buffer.position() as usize, bytes.len());
+    Ok(tag_buffer)
+}
+
+fn main() {
+    // 递增的 tag 在两种模式下都能 decode, 并且原样 encode
+    let ordered = tag_buffer_bytes(&[(0, 0xa0), (2, 0xa2), (5, 0xa5)]);
+    for strict in [false, true] {
+        let tag_buffer = decode(&ordered, strict).unwrap();
+        assert_eq!(tag_buffer.get(0), Some([0xa0].as_slice()));
+        assert_eq!(tag_buffer.get(2), Some([0xa2].as_slice()));
+        assert_eq!(tag_buffer.get(5), Some([0xa5].as_slice()));
+        assert_eq!(tag_buffer.get(1), None);
+        assert_eq!(tag_buffer.get(6), None);
+        assert_eq!(tag_buffer.encode(), ordered);
+    }
+
+    // 乱序的 tag: strict 模式拒绝, 否则排序后仍然可以查找
+    let unordered = tag_buffer_bytes(&[(5, 0xa5), (0, 0xa0), (2, 0xa2)]);
+    assert!(decode(&unordered, true).unwrap_err().is_other());
+    let tag_buffer = decode(&unordered, false).unwrap();
+    assert_eq!(tag_buffer.get(0), Some([0xa0].as_slice()));
+    assert_eq!(tag_buffer.get(5), Some([0xa5].as_slice()));
+    assert_eq!(tag_buffer.encode(), ordered);
+
+    // 重复的 tag: strict 模式拒绝, 否则保留第一次出现的字段
+    let duplicate = tag_buffer_bytes(&[(1, 0xb0), (1, 0xb1), (3, 0xb3)]);
+    assert!(decode(&duplicate, true).unwrap_err().is_other());
+    let tag_buffer = decode(&duplicate, false).unwrap();
+    assert_eq!(tag_buffer.get(1), Some([0xb0].as_slice()));
+    assert_eq!(
+        tag_buffer.encode(),
+        tag_buffer_bytes(&[(1, 0xb0), (3, 0xb3)])
+    );
+
+    // new 同样保证顺序
+    let tag_buffer = TagBuffer::new(vec![
+        TagSection::new(4, vec![0x04]),
+        TagSection::new(1, vec![0x01]),
+    ]);
+    assert_eq!(tag_buffer.get(1), Some([0x01].as_slice()));
+    assert_eq!(tag_buffer.get(4), Some([0x04].as_slice()));
+
+    // Decode::decode 使用 --strict-tags 的设置
+    assert!(TagBuffer::decode(&mut Cursor::new(unordered.as_slice())).is_ok());
+    set_strict_tags(true);
+    assert!(TagBuffer::decode(&mut Cursor::new(unordered.as_slice()))
+        .unwrap_err()
+        .is_other());
+    set_strict_tags(false);
+
+    println!("tag buffer checks passed");
+}
@@ -0,0 +1,67 @@
+use codecrafters_kafka::{
+    common_struct::{
+        CompactArray, CompactString, ParitionRecord, RecordType, RecordValue, TagBuffer,
+        TopicRecord,
+    },
+    metadata_log::{
+        init_internal_states, new_metadata_record, new_metadata_record_batch, partition_count,
+        topic_exists, topic_id, topic_name, MetadataLog,
+    },
+};
+use uuid::Uuid;
+
+fn main() {
+    let id = Uuid::new_v4();
+    let mut records = vec![new_metadata_record(
+        0,
+        RecordValue::Topic(TopicRecord {
+            frame_version: 1,
+            record_type: RecordType::TOPIC_RECORD,
+            version: 0,
+            name: CompactString::new("orders".to_string()),
+            id,
+            tag_buffers: TagBuffer::default(),
+        }),
+    )];
+    for partition_id in 0..3 {
+        records.push(new_metadata_record(
+            partition_id + 1,
+            RecordValue::Partition(ParitionRecord {
+                frame_version: 1,
+                record_type: RecordType::PARITION_RECORD,
+                version: 0,
+                parition_id: partition_id,
+                topic_id: id,
+                replica_nodes: CompactArray::empty(),
+                isr_nodes: CompactArray::empty(),
+                removing_replicas_nodes: CompactArray::empty(),
+                adding_replicas_nodes: CompactArray::empty(),
+                leader_id: 1,
+                leader_epoch: 0,
+                partition_epoch: 0,
+                directories: CompactArray::empty(),
+                tag_buffers: TagBuffer::default(),
+            }),
+        ));
+    }
+    init_internal_states(&MetadataLog::new(vec![new_metadata_record_batch(
+        0, 0, records,
+    )]));
+
+    assert!(topic_exists("orders"));
+    assert_eq!(partition_count("orders"), Some(3));
+    assert_eq!(topic_id("orders"), Some(id));
+    assert_eq!(
+        topic_name(&id).as_deref().map(String::as_str),
+        Some("orders")
+    );
+
+    assert_eq!(partition_count("missing"), None);
+    assert_eq!(topic_id("missing"), None);
+    assert_eq!(topic_name(&Uuid::nil()), None);
+
+    println!(
+        "topic orders has {:?} partitions",
+        partition_count("orders")
+    );
+}
@@ -0,0 +1,73 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    common_struct::{NullableString, TagBuffer, TagSection},
+    decode::Decode,
+    encode::Encode,
+    request_message::RequestHeaderV2,
+};
+
+// 逐字节增加输入, 完整之前的每个前缀都必须返回 Incomplete, 不能 panic 或返回其他错误
+fn check_prefixes(header: &RequestHeaderV2) {
+    let bytes = header.encode();
+    for end in 0..bytes.len() {
+        let mut buffer = Cursor::new(&bytes[..end]);
+        match RequestHeaderV2::decode(&mut buffer) {
+            Err(err) if err.is_incomplete() => {}
+            other => panic!(
+                "Expect Incomplete with {} of {} bytes, got {:?}",
+                end,
+                bytes.len(),
+                other
+            ),
+        }
+    }
+    let mut buffer = Cursor::new(bytes.as_slice());
+    assert_eq!(&RequestHeaderV2::decode(&mut buffer).unwrap(), header);
+    assert_eq!(buffer.position() as usize, bytes.len());
+}
+
+fn main() {
+    let header = RequestHeaderV2 {
+        request_api_key: 1,
+        request_api_version: 16,
+        correlation_id: 42,
+        client_id: NullableString::new(Some("consumer-1".to_string())),
+        tag_buffer: TagBuffer::default(),
+    };
+    check_prefixes(&header);
+
+    // null 的 client_id, 以及带有 tagged field 的 header
+    check_prefixes(&RequestHeaderV2 {
+        client_id: NullableString::new(None),
+        tag_buffer: TagBuffer::new(vec![TagSection::new(3, vec![0xab; 5])]),
+        ..header
+    });
+
+    // client_id 的长度只有 -1 表示 null, 其他负数直接报错
+    let corrupt = [
+        &1_i16.to_be_bytes()[..],
+        &16_i16.to_be_bytes(),
+        &42_i32.to_be_bytes(),
+        &(-2_i16).to_be_bytes(),
+        &[0x00],
+    ]
+    .concat();
+    let err = RequestHeaderV2::decode(&mut Cursor::new(corrupt.as_slice())).unwrap_err();
+    assert!(err.is_other());
+    assert!(err
+        .to_string()
+        .starts_with("failed to decode field \"client_id\" of RequestHeaderV2: "));
+
+    // 错误信息中带有出错的字段名, 错误类型不变
+    let bytes = header.encode();
+    let err = RequestHeaderV2::decode(&mut Cursor::new(&bytes[..6])).unwrap_err();
+    assert!(err.is_incomplete());
+    assert_eq!(err.needed(), Some(2));
+    assert_eq!(
+        err.to_string(),
+        "stream ended early, need 2 more bytes, failed to decode field \"correlation_id\" of RequestHeaderV2"
+    );
+
+    println!("truncated headers decode as Incomplete");
+}
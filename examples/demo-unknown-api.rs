@@ -0,0 +1,46 @@
+use std::io::Cursor;
+
+use codecrafters_kafka::{
+    acl::Session,
+    api_versions::UNSUPPORTED_VERSION_ERROR,
+    decode::Decode,
+    request_message::{RequestBody, RequestMessage},
+    response_message::execute_request,
+};
+
+#[tokio::main]
+async fn main() {
+    // api key 为 -1 的请求, body 中有 3 个无法识别的字节, 后面紧跟下一个请求的开头
+    let mut message = vec![];
+    let header = [
+        &[0xff, 0xff][..],         // request_api_key
+        &[0x00, 0x00],             // request_api_version
+        &[0x00, 0x00, 0x00, 0x2a], // correlation_id
+        &[0x00, 0x02, b'a', b'b'], // client_id
+        &[0x00],                   // tag_buffer
+    ]
+    .concat();
+    let body = [0x01, 0x02, 0x03];
+    message.extend_from_slice(&((header.len() + body.len()) as u32).to_be_bytes());
+    message.extend_from_slice(&header);
+    message.extend_from_slice(&body);
+    let message_len = message.len();
+    message.extend_from_slice(&[0x00, 0x00, 0x00, 0x10]);
+
+    let mut buffer = Cursor::new(message.as_slice());
+    let request = RequestMessage::decode(&mut buffer).expect("Failed to decode request");
+    assert!(matches!(&request.body, RequestBody::Unknown(bytes) if bytes == &body));
+    assert_eq!(buffer.position() as usize, message_len);
+
+    let mut response = execute_request(&request, &Session::default())
+        .await
+        .expect("Failed to execute request");
+    let response_bytes = response.as_bytes();
+    // message_size(4) + correlation_id(4) + error_code(2)
+    assert_eq!(&response_bytes[4..8], &42_i32.to_be_bytes());
+    assert_eq!(
+        &response_bytes[8..10],
+        &UNSUPPORTED_VERSION_ERROR.to_be_bytes()
+    );
+    println!("{:?}", response);
+}
@@ -1,4 +1,31 @@
-use codecrafters_kafka::common_struct::VarInt;
+mod common;
+
+use std::{fmt::Debug, io::Cursor};
+
+use codecrafters_kafka::{
+    common_struct::{
+        unsigned_varint_len, Array, CompactArray, CompactBytes, CompactNullableBytes,
+        CompactNullableString, CompactString, KafkaBytes, KafkaString, Millis, MillisLong,
+        NullableString, RecordHeader, RecordHeaders, RecordKey, RecordValue, TagBuffer, TagSection,
+        VarInt,
+    },
+    decode::{decode_bounded, Decode},
+    encode::Encode,
+};
+
+use common::assert_consumes;
+
+// 检查 encode 的字节与 Kafka 协议文档一致, 并且 decode 后用完全部字节
+fn check_bytes<T: Encode + Decode + Debug>(name: &str, value: T, expected: &[u8]) {
+    let encoded = value.encode();
+    assert_eq!(encoded, expected, "{} encode", name);
+    assert_eq!(value.encoded_len(), expected.len(), "{} encoded_len", name);
+    // 后面多出的字节属于下一个字段, 不能被读取
+    let trailing = [encoded.as_slice(), &[0xff; 4]].concat();
+    let decoded = assert_consumes(&trailing, T::decode, expected.len());
+    assert_eq!(decoded.encode(), expected, "{} round trip", name);
+    println!("{}: {:02x?}", name, expected);
+}
 
 fn main() {
     let x = VarInt::new(vec![0x96, 0x01]);
@@ -18,4 +45,192 @@ fn main() {
 
     let n = 130_u64;
     dbg!(((n << 1) ^ (n >> 63)) as i64);
+
+    // zigzag: 0 -> 0, -1 -> 1, 1 -> 2, -2 -> 3, 150 -> 300
+    assert_eq!(VarInt::from_i64(0).into_bytes(), [0x00]);
+    assert_eq!(VarInt::from_i64(-1).into_bytes(), [0x01]);
+    assert_eq!(VarInt::from_i64(1).into_bytes(), [0x02]);
+    assert_eq!(VarInt::from_i64(-2).into_bytes(), [0x03]);
+    assert_eq!(VarInt::from_i64(150).into_bytes(), [0xac, 0x02]);
+
+    // unsigned varint, 长度 + 1
+    check_bytes(
+        "CompactString",
+        CompactString::new("abc".to_string()),
+        &[0x04, b'a', b'b', b'c'],
+    );
+    check_bytes(
+        "CompactNullableString(null)",
+        CompactNullableString::new(None),
+        &[0x00],
+    );
+    check_bytes(
+        "CompactNullableString",
+        CompactNullableString::new(Some("a".to_string())),
+        &[0x02, b'a'],
+    );
+    check_bytes(
+        "CompactArray",
+        CompactArray::from_vec(vec![1_i32]),
+        &[0x02, 0x00, 0x00, 0x00, 0x01],
+    );
+    check_bytes("CompactArray(null)", CompactArray::<i32>::null(), &[0x00]);
+    check_bytes("CompactBytes", CompactBytes::new(vec![0xff]), &[0x02, 0xff]);
+    check_bytes(
+        "CompactNullableBytes(null)",
+        CompactNullableBytes::new(None),
+        &[0x00],
+    );
+    // unsigned varint, 没有 +1
+    check_bytes("TagBuffer", TagBuffer::default(), &[0x00]);
+    check_bytes(
+        "TagBuffer(one field)",
+        TagBuffer::new(vec![TagSection::new(3, vec![0xab, 0xcd])]),
+        &[0x01, 0x03, 0x02, 0xab, 0xcd],
+    );
+
+    // encoded_len 不需要 encode 就能得到长度, 长度前缀超过 1 个字节时也一致
+    let long = "x".repeat(200);
+    let long_compact = [&[0xc9, 0x01][..], long.as_bytes()].concat();
+    check_bytes(
+        "CompactString(200)",
+        CompactString::new(long.clone()),
+        &long_compact,
+    );
+    check_bytes(
+        "CompactArray(200)",
+        CompactArray::from_vec(vec![0_u8; 200]),
+        &[&[0xc9, 0x01][..], &[0x00; 200]].concat(),
+    );
+    for (value, len) in [
+        (0, 1),
+        (127, 1),
+        (128, 2),
+        (16383, 2),
+        (16384, 3),
+        (u64::MAX, 10),
+    ] {
+        assert_eq!(unsigned_varint_len(value), len, "{}", value);
+        assert_eq!(VarInt::from_u64(value).encoded_len(), len, "{}", value);
+    }
+
+    // int16 / int32 长度前缀
+    check_bytes(
+        "KafkaString",
+        KafkaString::new("ab".to_string()),
+        &[0x00, 0x02, b'a', b'b'],
+    );
+    check_bytes(
+        "NullableString(null)",
+        NullableString::new(None),
+        &[0xff; 2],
+    );
+    check_bytes(
+        "NullableString",
+        NullableString::new(Some("a".to_string())),
+        &[0x00, 0x01, b'a'],
+    );
+    check_bytes(
+        "Array",
+        Array::from_vec(vec![1_i16, 2]),
+        &[0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02],
+    );
+    check_bytes("Array(null)", Array::<i16>::null(), &[0xff; 4]);
+
+    // signed varint (zigzag), -1 表示 null
+    check_bytes("RecordKey(null)", RecordKey::new(None), &[0x01]);
+    check_bytes(
+        "RecordKey",
+        RecordKey::new(Some(vec![b'k', b'e', b'y'])),
+        &[0x06, b'k', b'e', b'y'],
+    );
+    check_bytes(
+        "RecordValue",
+        RecordValue::Unknown(vec![0xaa, 0xbb]),
+        &[0x04, 0xaa, 0xbb],
+    );
+    check_bytes(
+        "RecordHeaders",
+        RecordHeaders::new(vec![
+            RecordHeader {
+                key: "h".to_string(),
+                value: Some(vec![0x01]),
+            },
+            RecordHeader {
+                key: "n".to_string(),
+                value: None,
+            },
+        ]),
+        &[0x04, 0x02, b'h', 0x02, 0x01, 0x02, b'n', 0x01],
+    );
+    check_bytes("RecordHeaders(empty)", RecordHeaders::default(), &[0x00]);
+
+    // compact 长度为 0 的边界情况
+    assert!(VarInt::from_u64(0).as_len().is_err());
+    assert_eq!(VarInt::from_u64(1).as_len().unwrap(), 0);
+    assert_eq!(VarInt::from_u64(0).as_nullable_len().unwrap(), None);
+    assert!(CompactString::decode(&mut Cursor::new([0x00].as_slice())).is_err());
+    assert!(CompactBytes::decode(&mut Cursor::new([0x00].as_slice())).is_err());
+    assert!(
+        CompactNullableString::decode(&mut Cursor::new([0x00].as_slice()))
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        CompactArray::<i32>::decode(&mut Cursor::new([0x00].as_slice()))
+            .unwrap()
+            .is_none()
+    );
+    check_bytes(
+        "CompactString(empty)",
+        CompactString::new(String::new()),
+        &[0x01],
+    );
+
+    // 毫秒字段与底层整数的 encode 结果相同
+    check_bytes("Millis", Millis(500), &[0x00, 0x00, 0x01, 0xf4]);
+    check_bytes("MillisLong", MillisLong(-1), &[0xff; 8]);
+    assert_eq!(
+        Millis(500).as_duration(),
+        std::time::Duration::from_millis(500)
+    );
+    assert_eq!(MillisLong(-1).as_duration(), std::time::Duration::ZERO);
+
+    // decode_bounded 必须恰好用完指定的字节
+    let bytes = [0x00, 0x00, 0x00, 0x2a, 0xff];
+    let mut buffer = Cursor::new(bytes.as_slice());
+    assert_eq!(decode_bounded::<i32>(&mut buffer, 4).unwrap(), 42);
+    assert_eq!(buffer.position(), 4);
+    let mut buffer = Cursor::new(bytes.as_slice());
+    assert!(decode_bounded::<i32>(&mut buffer, 5)
+        .unwrap_err()
+        .is_other());
+    assert_eq!(buffer.position(), 5);
+    let mut buffer = Cursor::new(bytes.as_slice());
+    assert!(decode_bounded::<i32>(&mut buffer, 3)
+        .unwrap_err()
+        .is_other());
+    let mut buffer = Cursor::new(&bytes[..3]);
+    assert_eq!(
+        decode_bounded::<i32>(&mut buffer, 4).unwrap_err().needed(),
+        Some(1)
+    );
+
+    // KafkaBytes 的长度为负数时报错, 长度超过剩余字节时返回 Incomplete
+    check_bytes(
+        "KafkaBytes",
+        KafkaBytes::new(vec![0xab, 0xcd]),
+        &[0x00, 0x00, 0x00, 0x02, 0xab, 0xcd],
+    );
+    let negative = (-2_i32).to_be_bytes();
+    assert!(KafkaBytes::decode(&mut Cursor::new(negative.as_slice()))
+        .unwrap_err()
+        .is_other());
+    let truncated = [&i32::MAX.to_be_bytes()[..], &[0xab]].concat();
+    assert_eq!(
+        KafkaBytes::decode(&mut Cursor::new(truncated.as_slice()))
+            .unwrap_err()
+            .needed(),
+        Some(i32::MAX as usize - 1)
+    );
 }
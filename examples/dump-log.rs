@@ -0,0 +1,90 @@
+use std::{path::Path, process};
+
+use codecrafters_kafka::{
+    common_struct::{display_bytes, RecordValue},
+    metadata_log::RecordBatchReader,
+};
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: dump-log <path to .log file>");
+        process::exit(2);
+    };
+
+    let mut reader = match RecordBatchReader::open(Path::new(&path)) {
+        Ok(reader) => reader,
+        Err(err) => {
+            eprintln!("Failed to open {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    let mut batch_count = 0;
+    loop {
+        let position = reader.position();
+        let record_batch = match reader.next() {
+            None => break,
+            Some(Ok(record_batch)) => record_batch,
+            Some(Err(err)) => {
+                eprintln!(
+                    "Failed to decode record batch #{} at byte {}: {}",
+                    batch_count, position, err
+                );
+                process::exit(1);
+            }
+        };
+
+        println!(
+            "RecordBatch #{} @ byte {}: base_offset={} batch_length={} leader_epoch={} attributes={:?} producer_id={} records={}",
+            batch_count,
+            position,
+            record_batch.base_offset,
+            record_batch.batch_length,
+            record_batch.partition_leader_epoch,
+            record_batch.attributes,
+            record_batch.producer_id,
+            record_batch.get_records().as_ref().map_or(0, |records| records.len()),
+        );
+
+        // control batch 中是事务标记, 不是普通消息
+        if record_batch.is_control_batch() {
+            println!("  control batch: {:?}", record_batch.control_record_type());
+            batch_count += 1;
+            continue;
+        }
+
+        for record in record_batch.get_records().iter().flatten() {
+            let offset = record_batch.base_offset + record.offset_delta.as_i64();
+            match record.get_value() {
+                RecordValue::RegisterBroker(register_broker) => {
+                    println!(
+                        "  offset {}: RegisterBrokerRecord {:#?}",
+                        offset, register_broker
+                    )
+                }
+                RecordValue::Topic(topic) => {
+                    println!("  offset {}: TopicRecord {:#?}", offset, topic)
+                }
+                RecordValue::Partition(partition) => {
+                    println!("  offset {}: PartitionRecord {:#?}", offset, partition)
+                }
+                RecordValue::FeatureLevel(feature_level) => {
+                    println!(
+                        "  offset {}: FeatureLevelRecord {:#?}",
+                        offset, feature_level
+                    )
+                }
+                RecordValue::Unknown(bytes) => {
+                    println!(
+                        "  offset {}: Unknown record ({} bytes)\n{}",
+                        offset,
+                        bytes.len(),
+                        display_bytes(bytes)
+                    )
+                }
+            }
+        }
+        batch_count += 1;
+    }
+    println!("Decoded {} record batches", batch_count);
+}
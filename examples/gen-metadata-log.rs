@@ -0,0 +1,196 @@
+use std::{fs, path::Path, process};
+
+use codecrafters_kafka::{
+    common_struct::{
+        CompactArray, CompactString, FeatureLevelRecord, ParitionRecord, RecordBatch, RecordType,
+        RecordValue, TagBuffer, TopicRecord,
+    },
+    describe_topic_partitions::RepicaNode,
+    encode::{Encode, EncodeError},
+    metadata_log::{new_metadata_record, new_metadata_record_batch, read_record_batches},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+// 用 JSON 描述 metadata log, 例如:
+// {
+//   "features": [{ "name": "metadata.version", "level": 20 }],
+//   "topics": [{ "name": "foo", "partitions": [{ "id": 0, "leader": 1 }] }]
+// }
+const DEFAULT_FIXTURE: &str = r#"{
+    "features": [{ "name": "metadata.version", "level": 20 }],
+    "topics": [
+        {
+            "name": "foo",
+            "id": "00000000-0000-4000-8000-000000000001",
+            "partitions": [
+                { "id": 0, "leader": 1, "replicas": [1], "isr": [1] },
+                { "id": 1, "leader": 1, "replicas": [1], "isr": [1] }
+            ]
+        },
+        { "name": "bar", "partitions": [{ "id": 0, "leader": 1 }] }
+    ]
+}"#;
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    features: Vec<FeatureFixture>,
+    #[serde(default)]
+    topics: Vec<TopicFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeatureFixture {
+    name: String,
+    level: i16,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopicFixture {
+    name: String,
+    // 不指定时随机生成
+    id: Option<String>,
+    #[serde(default)]
+    partitions: Vec<PartitionFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PartitionFixture {
+    id: i32,
+    leader: i32,
+    #[serde(default)]
+    leader_epoch: i32,
+    // 不指定时只包含 leader
+    replicas: Option<Vec<i32>>,
+    isr: Option<Vec<i32>>,
+}
+
+fn replica_nodes(ids: &Option<Vec<i32>>, leader: i32) -> CompactArray<RepicaNode> {
+    let ids = ids.clone().unwrap_or_else(|| vec![leader]);
+    CompactArray::from_vec(ids.into_iter().map(RepicaNode::new).collect())
+}
+
+// 每个 feature 和每个 topic (连同它的 partition) 各占一个 RecordBatch
+fn build_record_batches(fixture: &Fixture) -> Result<Vec<RecordBatch>, String> {
+    let mut values = vec![];
+    for feature in fixture.features.iter() {
+        values.push(vec![RecordValue::FeatureLevel(FeatureLevelRecord {
+            frame_version: 1,
+            record_type: RecordType::FEATURE_LEVEL_RECORD,
+            version: 0,
+            name: CompactString::new(feature.name.clone()),
+            feature_level: feature.level,
+            tag_buffers: TagBuffer::default(),
+        })]);
+    }
+    for topic in fixture.topics.iter() {
+        let topic_id = match &topic.id {
+            Some(id) => Uuid::parse_str(id).map_err(|err| format!("{}: {}", topic.name, err))?,
+            None => Uuid::new_v4(),
+        };
+        let mut topic_values = vec![RecordValue::Topic(TopicRecord {
+            frame_version: 1,
+            record_type: RecordType::TOPIC_RECORD,
+            version: 0,
+            name: CompactString::new(topic.name.clone()),
+            id: topic_id,
+            tag_buffers: TagBuffer::default(),
+        })];
+        for partition in topic.partitions.iter() {
+            topic_values.push(RecordValue::Partition(ParitionRecord {
+                frame_version: 1,
+                record_type: RecordType::PARITION_RECORD,
+                version: 0,
+                parition_id: partition.id,
+                topic_id,
+                replica_nodes: replica_nodes(&partition.replicas, partition.leader),
+                isr_nodes: replica_nodes(&partition.isr, partition.leader),
+                removing_replicas_nodes: CompactArray::empty(),
+                adding_replicas_nodes: CompactArray::empty(),
+                leader_id: partition.leader,
+                leader_epoch: partition.leader_epoch,
+                partition_epoch: 0,
+                directories: CompactArray::empty(),
+                tag_buffers: TagBuffer::default(),
+            }));
+        }
+        values.push(topic_values);
+    }
+
+    let mut base_offset = 0;
+    let mut record_batches = vec![];
+    for batch_values in values {
+        let records = batch_values
+            .into_iter()
+            .enumerate()
+            .map(|(offset_delta, value)| new_metadata_record(offset_delta as i32, value))
+            .collect();
+        let record_batch = new_metadata_record_batch(base_offset, 0, records);
+        base_offset = record_batch.next_offset();
+        record_batches.push(record_batch);
+    }
+    Ok(record_batches)
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let fixture_json = match args.next() {
+        Some(path) if path != "-" => fs::read_to_string(&path).unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", path, err);
+            process::exit(1);
+        }),
+        _ => DEFAULT_FIXTURE.to_string(),
+    };
+    let output = args
+        .next()
+        .unwrap_or_else(|| "/tmp/__cluster_metadata-0/00000000000000000000.log".to_string());
+
+    let fixture: Fixture = serde_json::from_str(&fixture_json).unwrap_or_else(|err| {
+        eprintln!("Invalid fixture: {}", err);
+        process::exit(2);
+    });
+    let record_batches = build_record_batches(&fixture).unwrap_or_else(|err| {
+        eprintln!("Invalid fixture: {}", err);
+        process::exit(2);
+    });
+
+    // 计数与 records 的个数不一致时不能 encode
+    if let Some(record_batch) = record_batches.first() {
+        let mut wrong_count = record_batch.clone();
+        wrong_count.last_offset_delta += 1;
+        assert!(matches!(
+            wrong_count.try_encode(),
+            Err(EncodeError::CountMismatch { actual, .. }) if actual == record_batch.records.as_ref().map_or(0, |records| records.len())
+        ));
+    }
+
+    let output = Path::new(&output);
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).expect("Failed to create log directory");
+    }
+    let content: Vec<u8> = record_batches
+        .iter()
+        .map(|record_batch| record_batch.try_encode())
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Inconsistent record batch")
+        .concat();
+    fs::write(output, &content).expect("Failed to write log file");
+
+    // 读回来检查 CRC, offset 以及每条 record 都能被识别
+    let decoded = read_record_batches(output).expect("Failed to read back log file");
+    assert_eq!(decoded.len(), record_batches.len());
+    for (expected, actual) in record_batches.iter().zip(decoded.iter()) {
+        assert_eq!(actual.crc, actual.compute_crc() as i32, "crc mismatch");
+        assert_eq!(actual.encode(), expected.encode());
+        let records = actual.records.get_inner().as_ref().expect("null records");
+        assert!(records.iter().all(|record| !record.value.is_unknown()));
+    }
+
+    println!(
+        "Wrote {} record batches ({} bytes) to {}",
+        record_batches.len(),
+        content.len(),
+        output.to_string_lossy()
+    );
+}
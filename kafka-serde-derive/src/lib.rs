@@ -1,16 +1,28 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{DeriveInput, parse_macro_input, punctuated::Punctuated};
+use quote::{ToTokens, quote};
+use syn::{DeriveInput, parse_macro_input, parse_quote, punctuated::Punctuated};
 
-#[proc_macro_derive(Encode)]
+#[proc_macro_derive(Encode, attributes(kafka))]
 pub fn derive_encode(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    let generics = add_trait_bounds(&input.generics, &input.data, parse_quote!(Encode));
+    let (impl_generics, ty_generics, where_clause) = &generics.split_for_impl();
     let name = input.ident;
 
     let expanded = match input.data {
-        syn::Data::Struct(data) => derive_encode_for_struct(&name, data),
+        syn::Data::Struct(data) => {
+            derive_encode_for_struct(&name, impl_generics, ty_generics, where_clause, data)
+        }
+        syn::Data::Enum(data) => derive_encode_for_enum(
+            &name,
+            impl_generics,
+            ty_generics,
+            where_clause,
+            &input.attrs,
+            data,
+        ),
         data => unimplemented!(
-            "Derive Encode only has been implemented for struct, not {:?}",
+            "Derive Encode only has been implemented for struct and enum, not {:?}",
             data
         ),
     };
@@ -18,8 +30,52 @@ pub fn derive_encode(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+// 为字段中用到的每个类型参数加上 T: Encode 或 T: Decode, skip 的字段不需要
+fn add_trait_bounds(generics: &syn::Generics, data: &syn::Data, bound: syn::Path) -> syn::Generics {
+    let field_types: Vec<proc_macro2::TokenStream> = match data {
+        syn::Data::Struct(data) => data.fields.iter().collect::<Vec<_>>(),
+        syn::Data::Enum(data) => data
+            .variants
+            .iter()
+            .flat_map(|variant| variant.fields.iter())
+            .collect(),
+        syn::Data::Union(_) => vec![],
+    }
+    .into_iter()
+    .filter(|field| !is_skipped(field))
+    .map(|field| field.ty.to_token_stream())
+    .collect();
+
+    let mut generics = generics.clone();
+    let used_params: Vec<syn::Ident> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .filter(|ident| {
+            field_types
+                .iter()
+                .any(|ty| mentions_ident(ty.clone(), ident))
+        })
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for ident in used_params {
+        where_clause.predicates.push(parse_quote!(#ident: #bound));
+    }
+    generics
+}
+
+fn mentions_ident(tokens: proc_macro2::TokenStream, ident: &syn::Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(token) => token == *ident,
+        proc_macro2::TokenTree::Group(group) => mentions_ident(group.stream(), ident),
+        _ => false,
+    })
+}
+
 fn derive_encode_for_struct(
     struct_name: &syn::Ident,
+    impl_generics: &syn::ImplGenerics,
+    ty_generics: &syn::TypeGenerics,
+    where_clause: &Option<&syn::WhereClause>,
     data: syn::DataStruct,
 ) -> proc_macro2::TokenStream {
     let fields = match data.fields {
@@ -28,41 +84,301 @@ fn derive_encode_for_struct(
         syn::Fields::Unit => Punctuated::new(),
     };
 
-    let _inner_contents =
-        fields
-            .iter()
-            .enumerate()
-            .map(|(idx, field)| match field.ident.as_ref() {
-                Some(name) => quote! {encode_vec.append(&mut self.#name.encode());},
-                None => {
-                    let _idx = syn::Index::from(idx);
-                    quote! {encode_vec.append(&mut self.#_idx.encode());}
-                }
-            });
+    let accessors: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match field.ident.as_ref() {
+            Some(name) => quote! {self.#name},
+            None => {
+                let idx = syn::Index::from(idx);
+                quote! {self.#idx}
+            }
+        })
+        .collect();
+    // 普通字段, tagged 字段写在所有普通字段之后
+    let plain_fields: Vec<_> = fields
+        .iter()
+        .zip(accessors.iter())
+        .filter(|(field, _)| !is_skipped(field) && !is_tag_section_field(field))
+        .collect();
+    let tagged = tagged_fields(fields.iter().zip(accessors.iter().cloned()));
+    let unknown_tags = unknown_tags_field(&fields).map(|idx| &accessors[idx]);
+
+    let _inner_contents = plain_fields
+        .iter()
+        .map(|(_, accessor)| quote! {#accessor.encode_into(out);});
+    let versioned_contents = plain_fields
+        .iter()
+        .map(|(field, accessor)| encode_versioned_field(field, (*accessor).clone()));
+    let field_lens = plain_fields
+        .iter()
+        .map(|(_, accessor)| quote! {#accessor.encoded_len()});
+    let tagged_contents = encode_tagged_fields(&tagged, unknown_tags);
+    let tagged_len = tagged_fields_len(&tagged, unknown_tags);
 
     quote! {
-        impl Encode for #struct_name {
-            fn encode(&self) -> Vec<u8> {
-                let mut encode_vec = Vec::new();
+        impl #impl_generics Encode for #struct_name #ty_generics #where_clause {
+            fn encode_into(&self, out: &mut Vec<u8>) {
                 #(#_inner_contents)*
+                #tagged_contents
+            }
+
+            fn encode(&self) -> Vec<u8> {
+                let mut encode_vec = Vec::with_capacity(self.encoded_len());
+                self.encode_into(&mut encode_vec);
                 encode_vec
             }
+
+            fn encoded_len(&self) -> usize {
+                0 #(+ #field_lens)* + #tagged_len
+            }
+
+            fn encode_versioned_into(&self, out: &mut Vec<u8>, version: i16) {
+                #(#versioned_contents)*
+                #tagged_contents
+            }
+        }
+    }
+}
+
+// #[kafka(tagged = N)] 的字段按 KIP-482 写入 tagged fields: 个数(uvarint), 每个字段 tag(uvarint) + size(uvarint) + data
+// 字段必须是 Option<T>, None 时不写入, 按 tag 从小到大排列
+struct TaggedField {
+    tag: u32,
+    name: String,
+    accessor: proc_macro2::TokenStream,
+    inner_type: syn::Type,
+}
+
+fn tagged_fields<'a>(
+    fields: impl Iterator<Item = (&'a syn::Field, proc_macro2::TokenStream)>,
+) -> Vec<TaggedField> {
+    let fields: Vec<_> = fields
+        .enumerate()
+        .filter(|(_, (field, _))| !is_skipped(field))
+        .collect();
+    let mut tagged: Vec<TaggedField> = fields
+        .iter()
+        .filter_map(|(idx, (field, accessor))| {
+            field_attrs(field).tagged.map(|tag| TaggedField {
+                tag,
+                name: field_name(field, *idx),
+                accessor: accessor.clone(),
+                inner_type: option_inner_type(&field.ty).clone(),
+            })
+        })
+        .collect();
+    // 两个 tag buffer 会让 decode 错位
+    let has_tag_buffer = fields
+        .iter()
+        .any(|(_, (field, _))| !field_attrs(field).unknown_tags && is_tag_buffer(&field.ty));
+    if !tagged.is_empty() && has_tag_buffer {
+        panic!(
+            "struct with tagged fields must keep unknown tags in a #[kafka(unknown_tags)] field, not a plain TagBuffer"
+        );
+    }
+    tagged.sort_by_key(|field| field.tag);
+    if let Some(pair) = tagged.windows(2).find(|pair| pair[0].tag == pair[1].tag) {
+        panic!("Duplicate tagged field tag {}", pair[0].tag);
+    }
+    tagged
+}
+
+fn is_tag_buffer(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "TagBuffer"),
+        _ => false,
+    }
+}
+
+// #[kafka(unknown_tags)] 的 TagBuffer 字段保存 decode 时不认识的 tag, encode 时与 tagged 字段一起按 tag 顺序写回
+fn unknown_tags_field(fields: &Punctuated<syn::Field, syn::Token![,]>) -> Option<usize> {
+    let mut unknown_fields = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field_attrs(field).unknown_tags);
+    let unknown = unknown_fields.next();
+    if unknown_fields.next().is_some() {
+        panic!("only one field can be #[kafka(unknown_tags)]");
+    }
+    unknown.map(|(idx, field)| {
+        if !is_tag_buffer(&field.ty) {
+            panic!("#[kafka(unknown_tags)] field must be a TagBuffer");
+        }
+        idx
+    })
+}
+
+fn is_tag_section_field(field: &syn::Field) -> bool {
+    let field_attrs = field_attrs(field);
+    field_attrs.tagged.is_some() || field_attrs.unknown_tags
+}
+
+// 与不认识的 tag 合并后的 TagBuffer, tag 相同时使用字段的值
+fn merged_tag_buffer(
+    tagged: &[TaggedField],
+    unknown_tags: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let sections = tagged.iter().map(|field| {
+        let TaggedField { tag, accessor, .. } = field;
+        quote! {
+            if let Some(value) = &#accessor {
+                __tag_sections.push(crate::common_struct::TagSection::new(#tag, value.encode()));
+            }
+        }
+    });
+    quote! {
+        {
+            let mut __tag_sections = Vec::new();
+            #(#sections)*
+            #unknown_tags.merged(__tag_sections)
+        }
+    }
+}
+
+fn option_inner_type(ty: &syn::Type) -> &syn::Type {
+    if let syn::Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == "Option" {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                        return inner;
+                    }
+                }
+            }
+        }
+    }
+    panic!("tagged field must be an Option<T>");
+}
+
+// 没有 tagged 字段时不写入任何内容
+fn encode_tagged_fields(
+    tagged: &[TaggedField],
+    unknown_tags: Option<&proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    if let Some(unknown_tags) = unknown_tags {
+        let tag_buffer = merged_tag_buffer(tagged, unknown_tags);
+        return quote! { #tag_buffer.encode_into(out); };
+    }
+    if tagged.is_empty() {
+        return quote! {};
+    }
+    let accessors = tagged.iter().map(|field| &field.accessor);
+    let sections = tagged.iter().map(|field| {
+        let TaggedField { tag, accessor, .. } = field;
+        quote! {
+            if let Some(value) = &#accessor {
+                crate::common_struct::VarInt::from_u64(#tag as u64).encode_into(out);
+                crate::common_struct::VarInt::from_u64(value.encoded_len() as u64).encode_into(out);
+                value.encode_into(out);
+            }
+        }
+    });
+    quote! {
+        let __tagged_count = 0 #(+ usize::from(#accessors.is_some()))*;
+        crate::common_struct::VarInt::from_u64(__tagged_count as u64).encode_into(out);
+        #(#sections)*
+    }
+}
+
+fn tagged_fields_len(
+    tagged: &[TaggedField],
+    unknown_tags: Option<&proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    if let Some(unknown_tags) = unknown_tags {
+        let tag_buffer = merged_tag_buffer(tagged, unknown_tags);
+        return quote! { #tag_buffer.encoded_len() };
+    }
+    if tagged.is_empty() {
+        return quote! { 0 };
+    }
+    let sections = tagged.iter().map(|field| {
+        let TaggedField { tag, accessor, .. } = field;
+        quote! {
+            if let Some(value) = &#accessor {
+                let value_len = value.encoded_len();
+                __tagged_count += 1;
+                __tagged_len += crate::common_struct::unsigned_varint_len(#tag as u64)
+                    + crate::common_struct::unsigned_varint_len(value_len as u64)
+                    + value_len;
+            }
+        }
+    });
+    quote! {
+        {
+            let mut __tagged_count = 0_usize;
+            let mut __tagged_len = 0_usize;
+            #(#sections)*
+            crate::common_struct::unsigned_varint_len(__tagged_count as u64) + __tagged_len
+        }
+    }
+}
+
+// 只取出声明的 tag, 不认识的 tag 保存到 #[kafka(unknown_tags)] 字段, 没有该字段时直接忽略
+fn decode_tagged_fields(
+    tagged: &[TaggedField],
+    bindings: &[syn::Ident],
+    unknown_tags: Option<&syn::Ident>,
+    owner: &str,
+) -> proc_macro2::TokenStream {
+    if tagged.is_empty() && unknown_tags.is_none() {
+        return quote! {};
+    }
+    let unknown_decode = unknown_tags.map(|binding| {
+        let tags = tagged.iter().map(|field| field.tag);
+        quote! { let #binding = __tag_buffer.without_tags(&[#(#tags),*]); }
+    });
+    let decodes = tagged.iter().zip(bindings).map(|(field, binding)| {
+        let TaggedField {
+            tag,
+            name,
+            inner_type,
+            ..
+        } = field;
+        let decode = with_field_context(
+            quote! { <#inner_type as Decode>::decode(&mut std::io::Cursor::new(data)) },
+            name,
+            owner,
+        );
+        quote! {
+            let #binding = match __tag_buffer.get(#tag) {
+                Some(data) => Some(#decode),
+                None => None,
+            };
         }
+    });
+    quote! {
+        let __tag_buffer = <crate::common_struct::TagBuffer as Decode>::decode(buffer)?;
+        #(#decodes)*
+        #unknown_decode
     }
 }
 
-#[proc_macro_derive(Decode)]
+#[proc_macro_derive(Decode, attributes(kafka))]
 pub fn derive_decode(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let (impl_generics, ty_generics, where_clause) = &input.generics.split_for_impl();
+    let generics = add_trait_bounds(&input.generics, &input.data, parse_quote!(Decode));
+    let (impl_generics, ty_generics, where_clause) = &generics.split_for_impl();
     let name = &input.ident;
 
     let expanded = match input.data {
         syn::Data::Struct(data) => {
             derive_decode_for_struct(name, impl_generics, ty_generics, where_clause, data)
         }
+        syn::Data::Enum(data) => derive_decode_for_enum(
+            name,
+            impl_generics,
+            ty_generics,
+            where_clause,
+            &input.attrs,
+            data,
+        ),
         data => unimplemented!(
-            "Derive Decode only has been implemented for struct, not {:?}",
+            "Derive Decode only has been implemented for struct and enum, not {:?}",
             data
         ),
     };
@@ -77,40 +393,432 @@ fn derive_decode_for_struct(
     where_clause: &Option<&syn::WhereClause>,
     data: syn::DataStruct,
 ) -> proc_macro2::TokenStream {
-    match &data.fields {
-        syn::Fields::Named(fields) => {
-            let field_decodes = fields.named.iter().map(|field| {
-                let field_name = field.ident.as_ref().unwrap();
-                let field_type = &field.ty;
-                quote! { #field_name: <#field_type as Decode>::decode(buffer)? }
-            });
+    let unit_fields = Punctuated::new();
+    let fields = match &data.fields {
+        syn::Fields::Named(fields) => &fields.named,
+        syn::Fields::Unnamed(fields) => &fields.unnamed,
+        syn::Fields::Unit => &unit_fields,
+    };
+    // 先按顺序 decode 普通字段, tagged 字段在最后统一 decode
+    // 使用 field_{idx} 绑定, 避免字段名与 buffer, version 参数冲突
+    let bindings: Vec<syn::Ident> = (0..fields.len())
+        .map(|idx| syn::Ident::new(&format!("field_{}", idx), proc_macro2::Span::call_site()))
+        .collect();
+    let plain_fields: Vec<_> = fields
+        .iter()
+        .zip(bindings.iter())
+        .enumerate()
+        .filter(|(_, (field, _))| is_skipped(field) || !is_tag_section_field(field))
+        .map(|(idx, (field, binding))| (idx, field, binding))
+        .collect();
+    let tagged = tagged_fields(
+        fields
+            .iter()
+            .zip(bindings.iter())
+            .map(|(field, binding)| (field, quote! {#binding})),
+    );
+    let tagged_bindings: Vec<syn::Ident> = tagged
+        .iter()
+        .map(|field| syn::parse2(field.accessor.clone()).unwrap())
+        .collect();
+    let owner = struct_name.to_string();
+    let unknown_tags = unknown_tags_field(fields).map(|idx| &bindings[idx]);
+    let tagged_decodes = decode_tagged_fields(&tagged, &tagged_bindings, unknown_tags, &owner);
 
-            quote! {
-                impl #impl_generics Decode for #struct_name #ty_generics #where_clause {
-                    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> Result<Self, crate::decode::DecodeError> {
-                        Ok(Self {
-                            #(#field_decodes,)*
-                        })
-                    }
+    let field_decodes = plain_fields.iter().map(|(idx, field, binding)| {
+        let decode = decode_field(field, *idx, &owner);
+        quote! { let #binding = #decode; }
+    });
+    let versioned_decodes = plain_fields.iter().map(|(idx, field, binding)| {
+        let decode = decode_versioned_field(field, *idx, &owner);
+        quote! { let #binding = #decode; }
+    });
+    let construct = match &data.fields {
+        syn::Fields::Named(_) => {
+            let field_names = fields.iter().map(|field| field.ident.as_ref().unwrap());
+            quote! { Self { #(#field_names: #bindings),* } }
+        }
+        syn::Fields::Unnamed(_) => quote! { Self( #(#bindings),* ) },
+        // 没有字段的 struct 不读取 buffer
+        syn::Fields::Unit => quote! { Self },
+    };
+
+    quote! {
+        impl #impl_generics Decode for #struct_name #ty_generics #where_clause {
+            fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> Result<Self, crate::decode::DecodeError> {
+                #(#field_decodes)*
+                #tagged_decodes
+                Ok(#construct)
+            }
+
+            fn decode_versioned(buffer: &mut std::io::Cursor<&[u8]>, version: i16) -> Result<Self, crate::decode::DecodeError> {
+                #(#versioned_decodes)*
+                #tagged_decodes
+                Ok(#construct)
+            }
+        }
+    }
+}
+
+// #[kafka(skip)] 的字段不属于协议内容: encode 时跳过, decode 时不读取 buffer, 使用 Default::default()
+// #[kafka(since = N, until = M)] 的字段只在版本 N 到 M (包含 M) 之间出现, 只影响 encode_versioned 和 decode_versioned
+// #[kafka(tagged = N)] 的字段写入 tagged fields, 只能用于 struct
+// #[kafka(unknown_tags)] 的字段保存不认识的 tagged fields, 只能用于 struct
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    since: Option<i16>,
+    until: Option<i16>,
+    tagged: Option<u32>,
+    unknown_tags: bool,
+}
+
+fn field_attrs(field: &syn::Field) -> FieldAttrs {
+    let mut field_attrs = FieldAttrs::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("kafka"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                field_attrs.skip = true;
+            } else if meta.path.is_ident("since") {
+                field_attrs.since = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("until") {
+                field_attrs.until = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("unknown_tags") {
+                field_attrs.unknown_tags = true;
+            } else if meta.path.is_ident("tagged") {
+                field_attrs.tagged = Some(meta.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+            } else {
+                return Err(meta.error("unsupported kafka attribute on field"));
+            }
+            Ok(())
+        })
+        .unwrap_or_else(|err| panic!("{}", err));
+    }
+    if let (Some(since), Some(until)) = (field_attrs.since, field_attrs.until) {
+        if since > until {
+            panic!("since({}) is greater than until({})", since, until);
+        }
+    }
+    field_attrs
+}
+
+fn is_skipped(field: &syn::Field) -> bool {
+    field_attrs(field).skip
+}
+
+// 没有版本范围时返回 None
+fn version_condition(field: &syn::Field) -> Option<proc_macro2::TokenStream> {
+    let field_attrs = field_attrs(field);
+    match (field_attrs.since, field_attrs.until) {
+        (None, None) => None,
+        (Some(since), None) => Some(quote! { version >= #since }),
+        (None, Some(until)) => Some(quote! { version <= #until }),
+        (Some(since), Some(until)) => Some(quote! { (#since..=#until).contains(&version) }),
+    }
+}
+
+fn decode_field(field: &syn::Field, idx: usize, owner: &str) -> proc_macro2::TokenStream {
+    let field_type = &field.ty;
+    if is_skipped(field) {
+        quote! { <#field_type as Default>::default() }
+    } else {
+        with_field_context(
+            quote! { <#field_type as Decode>::decode(buffer) },
+            &field_name(field, idx),
+            owner,
+        )
+    }
+}
+
+// decode 失败时在错误信息中加上字段名和所属的类型, tuple 的字段使用下标
+fn with_field_context(
+    decode: proc_macro2::TokenStream,
+    name: &str,
+    owner: &str,
+) -> proc_macro2::TokenStream {
+    let context = format!("failed to decode field \"{}\" of {}", name, owner);
+    quote! { #decode.map_err(|err| err.context(#context))? }
+}
+
+fn field_name(field: &syn::Field, idx: usize) -> String {
+    match field.ident.as_ref() {
+        Some(ident) => ident.to_string(),
+        None => idx.to_string(),
+    }
+}
+
+fn encode_versioned_field(
+    field: &syn::Field,
+    accessor: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let encode = quote! { #accessor.encode_versioned_into(out, version); };
+    match version_condition(field) {
+        Some(condition) => quote! { if #condition { #encode } },
+        None => encode,
+    }
+}
+
+// 不在版本范围内的字段不读取 buffer, 使用 Default::default()
+fn decode_versioned_field(field: &syn::Field, idx: usize, owner: &str) -> proc_macro2::TokenStream {
+    let field_type = &field.ty;
+    if is_skipped(field) {
+        return quote! { <#field_type as Default>::default() };
+    }
+    let decode = with_field_context(
+        quote! { <#field_type as Decode>::decode_versioned(buffer, version) },
+        &field_name(field, idx),
+        owner,
+    );
+    match version_condition(field) {
+        Some(condition) => quote! {
+            if #condition { #decode } else { <#field_type as Default>::default() }
+        },
+        None => decode,
+    }
+}
+
+// enum 先写入 variant 的 tag, 再依次写入 variant 的字段
+// #[kafka(tag_type = i8 | i16)] 指定 tag 的类型, 默认为 i8
+// #[kafka(tag = N)] 指定 variant 的 tag, 默认为 variant 的下标
+fn enum_tag_type(attrs: &[syn::Attribute]) -> syn::Ident {
+    let mut tag_type = syn::Ident::new("i8", proc_macro2::Span::call_site());
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("kafka")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag_type") {
+                tag_type = meta.value()?.parse()?;
+                if tag_type != "i8" && tag_type != "i16" {
+                    return Err(meta.error("tag_type must be i8 or i16"));
                 }
+                Ok(())
+            } else {
+                Err(meta.error("unsupported kafka attribute on enum"))
             }
+        })
+        .unwrap_or_else(|err| panic!("{}", err));
+    }
+    tag_type
+}
+
+fn variant_tags(tag_type: &syn::Ident, data: &syn::DataEnum) -> Vec<proc_macro2::Literal> {
+    let (min, max) = if tag_type == "i8" {
+        (i8::MIN as i64, i8::MAX as i64)
+    } else {
+        (i16::MIN as i64, i16::MAX as i64)
+    };
+    let mut tags: Vec<i64> = vec![];
+    for (idx, variant) in data.variants.iter().enumerate() {
+        let mut tag = idx as i64;
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("kafka"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = meta.value()?.parse::<syn::LitInt>()?.base10_parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported kafka attribute on enum variant"))
+                }
+            })
+            .unwrap_or_else(|err| panic!("{}", err));
+        }
+        if tag < min || tag > max {
+            panic!(
+                "Tag {} of variant {} does not fit in {}",
+                tag, variant.ident, tag_type
+            );
         }
-        syn::Fields::Unnamed(fields) => {
-            let field_decodes = fields.unnamed.iter().map(|field| {
-                let field_type = &field.ty;
-                quote! {  <#field_type as Decode>::decode(buffer)? }
+        if tags.contains(&tag) {
+            panic!("Duplicate tag {} on variant {}", tag, variant.ident);
+        }
+        tags.push(tag);
+    }
+    tags.into_iter()
+        .map(proc_macro2::Literal::i64_unsuffixed)
+        .collect()
+}
+
+// variant 的字段在 match 中绑定的名字, skip 的字段不绑定
+fn variant_bindings(fields: &syn::Fields) -> Vec<Option<syn::Ident>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            if is_tag_section_field(field) {
+                panic!("tagged fields are not supported in enum variants");
+            }
+            (!is_skipped(field)).then(|| match field.ident.as_ref() {
+                Some(name) => name.clone(),
+                None => syn::Ident::new(&format!("field_{}", idx), proc_macro2::Span::call_site()),
+            })
+        })
+        .collect()
+}
+
+fn variant_pattern(
+    fields: &syn::Fields,
+    bindings: &[Option<syn::Ident>],
+) -> proc_macro2::TokenStream {
+    let patterns =
+        fields
+            .iter()
+            .zip(bindings)
+            .map(|(field, binding)| match (field.ident.as_ref(), binding) {
+                (_, Some(binding)) => quote! { #binding },
+                (Some(name), None) => quote! { #name: _ },
+                (None, None) => quote! { _ },
             });
+    match fields {
+        syn::Fields::Named(_) => quote! { { #(#patterns),* } },
+        syn::Fields::Unnamed(_) => quote! { ( #(#patterns),* ) },
+        syn::Fields::Unit => quote! {},
+    }
+}
+
+fn derive_encode_for_enum(
+    enum_name: &syn::Ident,
+    impl_generics: &syn::ImplGenerics,
+    ty_generics: &syn::TypeGenerics,
+    where_clause: &Option<&syn::WhereClause>,
+    attrs: &[syn::Attribute],
+    data: syn::DataEnum,
+) -> proc_macro2::TokenStream {
+    let tag_type = enum_tag_type(attrs);
+    let tags = variant_tags(&tag_type, &data);
+
+    let mut encode_arms = vec![];
+    let mut len_arms = vec![];
+    let mut versioned_arms = vec![];
+    for (variant, tag) in data.variants.iter().zip(tags.iter()) {
+        let variant_name = &variant.ident;
+        let bindings = variant_bindings(&variant.fields);
+        let pattern = variant_pattern(&variant.fields, &bindings);
+        let versioned_contents =
+            variant
+                .fields
+                .iter()
+                .zip(bindings.iter())
+                .filter_map(|(field, binding)| {
+                    binding
+                        .as_ref()
+                        .map(|binding| encode_versioned_field(field, quote! {#binding}))
+                });
+        versioned_arms.push(quote! {
+            Self::#variant_name #pattern => {
+                (#tag as #tag_type).encode_into(out);
+                #(#versioned_contents)*
+            }
+        });
+        let bindings: Vec<_> = bindings.iter().flatten().collect();
+        encode_arms.push(quote! {
+            Self::#variant_name #pattern => {
+                (#tag as #tag_type).encode_into(out);
+                #(#bindings.encode_into(out);)*
+            }
+        });
+        len_arms.push(quote! {
+            Self::#variant_name #pattern => {
+                std::mem::size_of::<#tag_type>() #(+ #bindings.encoded_len())*
+            }
+        });
+    }
+
+    quote! {
+        impl #impl_generics Encode for #enum_name #ty_generics #where_clause {
+            fn encode_into(&self, out: &mut Vec<u8>) {
+                match self {
+                    #(#encode_arms)*
+                }
+            }
+
+            fn encode(&self) -> Vec<u8> {
+                let mut encode_vec = Vec::with_capacity(self.encoded_len());
+                self.encode_into(&mut encode_vec);
+                encode_vec
+            }
 
-            quote! {
-                impl #impl_generics Decode for #struct_name #ty_generics #where_clause {
-                    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> Result<Self, crate::decode::DecodeError> {
-                        Self (
-                            #(#field_decodes,)*
-                        )
+            fn encoded_len(&self) -> usize {
+                match self {
+                    #(#len_arms)*
+                }
+            }
+
+            fn encode_versioned_into(&self, out: &mut Vec<u8>, version: i16) {
+                match self {
+                    #(#versioned_arms)*
+                }
+            }
+        }
+    }
+}
+
+fn derive_decode_for_enum(
+    enum_name: &syn::Ident,
+    impl_generics: &syn::ImplGenerics,
+    ty_generics: &syn::TypeGenerics,
+    where_clause: &Option<&syn::WhereClause>,
+    attrs: &[syn::Attribute],
+    data: syn::DataEnum,
+) -> proc_macro2::TokenStream {
+    let tag_type = enum_tag_type(attrs);
+    let tags = variant_tags(&tag_type, &data);
+
+    let decode_arms = |decode_field: fn(&syn::Field, usize, &str) -> proc_macro2::TokenStream| {
+        data.variants
+            .iter()
+            .zip(tags.iter())
+            .map(|(variant, tag)| {
+                let variant_name = &variant.ident;
+                let owner = format!("{}::{}", enum_name, variant_name);
+                let field_decodes = variant
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, field)| decode_field(field, idx, &owner));
+                match &variant.fields {
+                    syn::Fields::Named(fields) => {
+                        let field_names = fields
+                            .named
+                            .iter()
+                            .map(|field| field.ident.as_ref().unwrap());
+                        quote! { #tag => Ok(Self::#variant_name { #(#field_names: #field_decodes,)* }), }
                     }
+                    syn::Fields::Unnamed(_) => {
+                        quote! { #tag => Ok(Self::#variant_name( #(#field_decodes,)* )), }
+                    }
+                    syn::Fields::Unit => quote! { #tag => Ok(Self::#variant_name), },
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let unknown_tag = quote! {
+        tag => Err(crate::decode::DecodeError::Other(
+            format!("Unknown tag {} for {}", tag, stringify!(#enum_name)).into(),
+        )),
+    };
+    let field_arms = decode_arms(decode_field);
+    let versioned_arms = decode_arms(decode_versioned_field);
+
+    quote! {
+        impl #impl_generics Decode for #enum_name #ty_generics #where_clause {
+            fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> Result<Self, crate::decode::DecodeError> {
+                match <#tag_type as Decode>::decode(buffer)? {
+                    #(#field_arms)*
+                    #unknown_tag
+                }
+            }
+
+            fn decode_versioned(buffer: &mut std::io::Cursor<&[u8]>, version: i16) -> Result<Self, crate::decode::DecodeError> {
+                match <#tag_type as Decode>::decode(buffer)? {
+                    #(#versioned_arms)*
+                    #unknown_tag
                 }
             }
         }
-        syn::Fields::Unit => unimplemented!(),
     }
 }
@@ -0,0 +1,168 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::describe_topic_partitions::TopicAuthorizedOperations;
+
+// 没有认证时所有连接都使用这个 principal
+pub const ANONYMOUS_PRINCIPAL: &str = "User:ANONYMOUS";
+pub const ACL_WILDCARD: &str = "*";
+
+lazy_static! {
+    pub static ref ACL_STORE: Arc<Mutex<AclStore>> = Arc::new(Mutex::new(AclStore::default()));
+}
+
+// 每个连接的会话状态
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub principal: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            principal: ANONYMOUS_PRINCIPAL.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclPermission {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct AclEntry {
+    pub principal: String, // "*" 匹配所有 principal
+    pub topic: String,     // "*" 匹配所有 topic
+    pub operations: TopicAuthorizedOperations,
+    pub permission: AclPermission,
+}
+
+impl AclEntry {
+    fn matches(&self, principal: &str, topic: &str) -> bool {
+        (self.principal == ACL_WILDCARD || self.principal == principal)
+            && (self.topic == ACL_WILDCARD || self.topic == topic)
+    }
+
+    // 格式: <principal> <topic> <operation>[,<operation>...] <allow|deny>
+    fn parse(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [principal, topic, operations, permission] = fields.as_slice() else {
+            return Err(format!("Expect 4 fields in acl entry: {}", line));
+        };
+        let mut flags = TopicAuthorizedOperations::empty();
+        for operation in operations.split(',') {
+            flags |= TopicAuthorizedOperations::from_name(&operation.to_uppercase())
+                .ok_or(format!("Unknown acl operation: {}", operation))?;
+        }
+        let permission = match permission.to_lowercase().as_str() {
+            "allow" => AclPermission::Allow,
+            "deny" => AclPermission::Deny,
+            _ => return Err(format!("Unknown acl permission: {}", permission)),
+        };
+        Ok(AclEntry {
+            principal: principal.to_string(),
+            topic: topic.to_string(),
+            operations: flags,
+            permission,
+        })
+    }
+}
+
+// 与 Kafka 一致, 允许 READ/WRITE/DELETE/ALTER 时隐含 DESCRIBE, 允许 ALTER_CONFIGS 时隐含 DESCRIBE_CONFIGS
+fn expand_allowed(operations: TopicAuthorizedOperations) -> TopicAuthorizedOperations {
+    if operations.contains(TopicAuthorizedOperations::ALL) {
+        return TopicAuthorizedOperations::default();
+    }
+    let mut expanded = operations;
+    if operations.intersects(
+        TopicAuthorizedOperations::READ
+            | TopicAuthorizedOperations::WRITE
+            | TopicAuthorizedOperations::DELETE
+            | TopicAuthorizedOperations::ALTER,
+    ) {
+        expanded |= TopicAuthorizedOperations::DESCRIBE;
+    }
+    if operations.contains(TopicAuthorizedOperations::ALTER_CONFIGS) {
+        expanded |= TopicAuthorizedOperations::DESCRIBE_CONFIGS;
+    }
+    expanded
+}
+
+fn expand_denied(operations: TopicAuthorizedOperations) -> TopicAuthorizedOperations {
+    if operations.contains(TopicAuthorizedOperations::ALL) {
+        TopicAuthorizedOperations::default()
+    } else {
+        operations
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AclStore {
+    entries: Vec<AclEntry>,
+}
+
+impl AclStore {
+    pub fn new(entries: Vec<AclEntry>) -> Self {
+        Self { entries }
+    }
+
+    // 空行和 # 开头的行会被忽略
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let entries = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(AclEntry::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(entries))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn add(&mut self, entry: AclEntry) {
+        self.entries.push(entry);
+    }
+
+    // 没有配置 ACL 时允许默认的全部操作, 否则 deny 优先于 allow
+    pub fn authorized_operations(&self, principal: &str, topic: &str) -> TopicAuthorizedOperations {
+        if self.is_empty() {
+            return TopicAuthorizedOperations::default();
+        }
+        let mut allowed = TopicAuthorizedOperations::empty();
+        let mut denied = TopicAuthorizedOperations::empty();
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.matches(principal, topic))
+        {
+            match entry.permission {
+                AclPermission::Allow => allowed |= expand_allowed(entry.operations),
+                AclPermission::Deny => denied |= expand_denied(entry.operations),
+            }
+        }
+        allowed.difference(denied) & TopicAuthorizedOperations::default()
+    }
+}
+
+pub fn load_acl_store(path: &Path) -> Result<(), String> {
+    let acl_store = AclStore::load(path)?;
+    *ACL_STORE.lock().expect("Failed to get ACL_STORE lock") = acl_store;
+    Ok(())
+}
+
+pub fn topic_authorized_operations(session: &Session, topic: &str) -> TopicAuthorizedOperations {
+    ACL_STORE
+        .lock()
+        .expect("Failed to get ACL_STORE lock")
+        .authorized_operations(&session.principal, topic)
+}
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    configs::{mark_config_store_dirty, resource_exists, ConfigMap, ResourceType, CONFIG_STORE},
+    decode::Decode,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+pub const INVALID_CONFIG_ERROR: i16 = 40;
+pub const INVALID_REQUEST_ERROR: i16 = 42;
+
+lazy_static! {
+    pub static ref ALTER_CONFIGS_API_INFO: ApiKey = ApiKey::new(33, 2, 2, TagBuffer::default());
+    pub static ref INCREMENTAL_ALTER_CONFIGS_API_INFO: ApiKey =
+        ApiKey::new(44, 1, 1, TagBuffer::default());
+}
+
+pub struct AlterConfigOp;
+
+impl AlterConfigOp {
+    pub const SET: i8 = 0;
+    pub const DELETE: i8 = 1;
+    pub const APPEND: i8 = 2;
+    pub const SUBTRACT: i8 = 3;
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct AlterConfigsRequestBodyV2 {
+    resources: CompactArray<AlterConfigsResource>,
+    validate_only: bool,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct AlterConfigsResource {
+    resource_type: i8,
+    resource_name: CompactString,
+    configs: CompactArray<AlterableConfig>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct AlterableConfig {
+    name: CompactString,
+    value: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IncrementalAlterConfigsRequestBodyV1 {
+    resources: CompactArray<IncrementalAlterConfigsResource>,
+    validate_only: bool,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IncrementalAlterConfigsResource {
+    resource_type: i8,
+    resource_name: CompactString,
+    configs: CompactArray<IncrementalAlterableConfig>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct IncrementalAlterableConfig {
+    name: CompactString,
+    config_operation: i8,
+    value: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct AlterConfigsResponseBody {
+    throttle_time_ms: Millis,
+    responses: CompactArray<AlterConfigsResourceResponse>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct AlterConfigsResourceResponse {
+    error_code: i16,
+    error_message: CompactNullableString,
+    resource_type: i8,
+    resource_name: CompactString,
+    tag_buffer: TagBuffer,
+}
+
+impl AlterConfigsResponseBody {
+    // 请求的每个 resource 都返回同一个错误码
+    fn error<R>(
+        resources: &CompactArray<R>,
+        resource_key: impl Fn(&R) -> (i8, CompactString),
+        error_code: i16,
+    ) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            responses: resources
+                .iter()
+                .flatten()
+                .map(|resource| {
+                    let (resource_type, resource_name) = resource_key(resource);
+                    AlterConfigsResourceResponse {
+                        error_code,
+                        error_message: CompactNullableString::new(None),
+                        resource_type,
+                        resource_name,
+                        tag_buffer: TagBuffer::default(),
+                    }
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn alter_configs_error(body: &AlterConfigsRequestBodyV2, error_code: i16) -> Self {
+        Self::error(
+            &body.resources,
+            |resource| (resource.resource_type, resource.resource_name.clone()),
+            error_code,
+        )
+    }
+
+    pub fn incremental_alter_configs_error(
+        body: &IncrementalAlterConfigsRequestBodyV1,
+        error_code: i16,
+    ) -> Self {
+        Self::error(
+            &body.resources,
+            |resource| (resource.resource_type, resource.resource_name.clone()),
+            error_code,
+        )
+    }
+}
+
+impl AlterConfigsResourceResponse {
+    pub fn new(
+        resource_type: i8,
+        resource_name: CompactString,
+        result: Result<(), (i16, String)>,
+    ) -> Self {
+        let (error_code, error_message) = match result {
+            Ok(()) => (0, None),
+            Err((error_code, error_message)) => (error_code, Some(error_message)),
+        };
+        Self {
+            error_code,
+            error_message: CompactNullableString::new(error_message),
+            resource_type,
+            resource_name,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+}
+
+fn check_resource(resource_type: i8, resource_name: &str) -> Result<(), (i16, String)> {
+    match resource_type {
+        ResourceType::TOPIC | ResourceType::BROKER => {
+            if resource_exists(resource_type, resource_name) {
+                Ok(())
+            } else {
+                Err((
+                    UNKNOWN_TOPIC_OR_PARTITION,
+                    format!("Unknown resource: {}", resource_name),
+                ))
+            }
+        }
+        resource_type => Err((
+            INVALID_REQUEST_ERROR,
+            format!("Unsupported resource type: {}", resource_type),
+        )),
+    }
+}
+
+fn alter_resource_configs(
+    config_map: &mut ConfigMap,
+    resource: &AlterConfigsResource,
+) -> Result<(), (i16, String)> {
+    check_resource(resource.resource_type, resource.resource_name.as_str())?;
+
+    // AlterConfigs 会用请求中的配置整体替换原有配置
+    let mut configs = HashMap::new();
+    if let Some(alterable_configs) = resource.configs.as_ref() {
+        for config in alterable_configs {
+            if let Some(value) = config.value.as_ref() {
+                configs.insert(config.name.to_string(), value.clone());
+            }
+        }
+    }
+    config_map.insert(
+        (resource.resource_type, resource.resource_name.to_string()),
+        configs,
+    );
+    Ok(())
+}
+
+fn split_list_config(value: Option<&String>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn incremental_alter_resource_configs(
+    config_map: &mut ConfigMap,
+    resource: &IncrementalAlterConfigsResource,
+) -> Result<(), (i16, String)> {
+    check_resource(resource.resource_type, resource.resource_name.as_str())?;
+
+    let configs = config_map
+        .entry((resource.resource_type, resource.resource_name.to_string()))
+        .or_default();
+    if let Some(alterable_configs) = resource.configs.as_ref() {
+        for config in alterable_configs {
+            let name = config.name.to_string();
+            match (config.config_operation, config.value.as_ref()) {
+                (AlterConfigOp::SET, Some(value)) => {
+                    configs.insert(name, value.clone());
+                }
+                (AlterConfigOp::DELETE, _) => {
+                    configs.remove(&name);
+                }
+                (AlterConfigOp::APPEND, Some(value)) => {
+                    let mut items = split_list_config(configs.get(&name));
+                    for item in value.split(',').filter(|item| !item.is_empty()) {
+                        if !items.iter().any(|exist| exist == item) {
+                            items.push(item.to_string());
+                        }
+                    }
+                    configs.insert(name, items.join(","));
+                }
+                (AlterConfigOp::SUBTRACT, Some(value)) => {
+                    if configs.contains_key(&name) {
+                        let removed: Vec<&str> = value.split(',').collect();
+                        let items: Vec<String> = split_list_config(configs.get(&name))
+                            .into_iter()
+                            .filter(|item| !removed.contains(&item.as_str()))
+                            .collect();
+                        configs.insert(name, items.join(","));
+                    }
+                }
+                (AlterConfigOp::SET | AlterConfigOp::APPEND | AlterConfigOp::SUBTRACT, None) => {
+                    return Err((
+                        INVALID_CONFIG_ERROR,
+                        format!("Config {} requires a value", name),
+                    ));
+                }
+                (op, _) => {
+                    return Err((
+                        INVALID_REQUEST_ERROR,
+                        format!("Unknown config operation {} for config {}", op, name),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_config_changes<R>(
+    resources: &CompactArray<R>,
+    validate_only: bool,
+    alter: impl Fn(&mut ConfigMap, &R) -> Result<(), (i16, String)>,
+    resource_key: impl Fn(&R) -> (i8, CompactString),
+) -> Vec<AlterConfigsResourceResponse> {
+    let mut config_store = CONFIG_STORE
+        .lock()
+        .expect("Failed to get CONFIG_STORE lock");
+    let mut config_map = config_store.clone();
+    let mut responses = vec![];
+    if let Some(resources) = resources.as_ref() {
+        for resource in resources {
+            // 每个 resource 独立生效, 失败时不影响其他 resource
+            let mut resource_config_map = config_map.clone();
+            let result = alter(&mut resource_config_map, resource);
+            if result.is_ok() {
+                config_map = resource_config_map;
+            }
+            let (resource_type, resource_name) = resource_key(resource);
+            responses.push(AlterConfigsResourceResponse::new(
+                resource_type,
+                resource_name,
+                result,
+            ));
+        }
+    }
+
+    if !validate_only {
+        *config_store = config_map;
+        mark_config_store_dirty();
+    }
+    responses
+}
+
+pub fn execute_alter_configs(
+    header: &RequestHeaderV2,
+    body: &AlterConfigsRequestBodyV2,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < ALTER_CONFIGS_API_INFO.min_version
+        || request_api_version > ALTER_CONFIGS_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::AlterConfigsV2(AlterConfigsResponseBody::alter_configs_error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let responses = apply_config_changes(
+        &body.resources,
+        body.validate_only,
+        alter_resource_configs,
+        |resource| (resource.resource_type, resource.resource_name.clone()),
+    );
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::AlterConfigsV2(AlterConfigsResponseBody {
+            throttle_time_ms: Millis(0),
+            responses: CompactArray::from_vec(responses),
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
+
+pub fn execute_incremental_alter_configs(
+    header: &RequestHeaderV2,
+    body: &IncrementalAlterConfigsRequestBodyV1,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < INCREMENTAL_ALTER_CONFIGS_API_INFO.min_version
+        || request_api_version > INCREMENTAL_ALTER_CONFIGS_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::IncrementalAlterConfigsV1(
+                AlterConfigsResponseBody::incremental_alter_configs_error(
+                    body,
+                    UNSUPPORTED_VERSION_ERROR,
+                ),
+            ),
+        );
+    }
+
+    let responses = apply_config_changes(
+        &body.resources,
+        body.validate_only,
+        incremental_alter_resource_configs,
+        |resource| (resource.resource_type, resource.resource_name.clone()),
+    );
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::IncrementalAlterConfigsV1(AlterConfigsResponseBody {
+            throttle_time_ms: Millis(0),
+            responses: CompactArray::from_vec(responses),
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
@@ -3,18 +3,57 @@ use std::collections::HashMap;
 use lazy_static::lazy_static;
 
 use crate::{
-    common_struct::{CompactArray, CompactString, TagBuffer},
+    alter_configs::{ALTER_CONFIGS_API_INFO, INCREMENTAL_ALTER_CONFIGS_API_INFO},
+    broker_config::{BrokerConfig, BROKER_CONFIG},
+    common_struct::{Array, CompactArray, CompactString, Millis, TagBuffer},
+    create_topics::CREATE_TOPICS_API_INFO,
     decode::Decode,
     describe_topic_partitions::DESCRIBE_TOPIC_PARTITIONS_API_INFO,
     encode::Encode,
     fetch::FETCH_API_INFO,
-    request_message::RequestHeaderV2,
+    find_coordinator::FIND_COORDINATOR_API_INFO,
+    init_producer_id::INIT_PRODUCER_ID_API_INFO,
+    metadata::METADATA_API_INFO,
+    metadata_log::metadata_version,
+    offset_fetch::OFFSET_FETCH_API_INFO,
+    offset_for_leader_epoch::OFFSET_FOR_LEADER_EPOCH_API_INFO,
+    offsets::OFFSET_COMMIT_API_INFO,
+    produce::PRODUCE_API_INFO,
+    request_message::{decodable_api_versions, RequestHeaderV1, RequestHeaderV2},
     response_message::{ResponseBody, ResponseHeader, ResponseMessage},
 };
 
 pub const UNSUPPORTED_VERSION_ERROR: i16 = 35;
+pub const POLICY_VIOLATION_ERROR: i16 = 44;
+
+// --read-only 模式下拒绝的 api
+pub const MUTATING_API_KEYS: [i16; 8] = [
+    0,  // Produce
+    19, // CreateTopics
+    20, // DeleteTopics
+    21, // DeleteRecords
+    33, // AlterConfigs
+    37, // CreatePartitions
+    44, // IncrementalAlterConfigs
+    45, // AlterPartitionReassignments
+];
+
+// metadata.version 的 feature level, 与 Kafka 的 MetadataVersion 对应
+pub struct MetadataVersion;
+
+impl MetadataVersion {
+    pub const IBP_3_0_IV1: i16 = 1;
+    pub const IBP_3_1_IV0: i16 = 2;
+    pub const IBP_3_5_IV0: i16 = 9;
+    pub const IBP_3_5_IV1: i16 = 10;
+    pub const IBP_3_7_IV0: i16 = 15;
+    pub const IBP_3_8_IV0: i16 = 20;
+    // metadata log 中没有 metadata.version 时使用
+    pub const LATEST: i16 = MetadataVersion::IBP_3_8_IV0;
+}
 
 lazy_static! {
+    // v0 到 v2 使用 request header v1 和空的 body, v3 开始是 flexible 版本
     pub static ref API_VERSIONS_API_INFO: ApiKey = ApiKey::new(18, 0, 4, TagBuffer::default());
     pub static ref SUPPORT_APIS: HashMap<i16, ApiKey> = HashMap::from([
         (FETCH_API_INFO.api_key, FETCH_API_INFO.clone()),
@@ -23,6 +62,55 @@ lazy_static! {
             DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key,
             DESCRIBE_TOPIC_PARTITIONS_API_INFO.clone(),
         ),
+        (
+            ALTER_CONFIGS_API_INFO.api_key,
+            ALTER_CONFIGS_API_INFO.clone()
+        ),
+        (
+            INCREMENTAL_ALTER_CONFIGS_API_INFO.api_key,
+            INCREMENTAL_ALTER_CONFIGS_API_INFO.clone(),
+        ),
+        (PRODUCE_API_INFO.api_key, PRODUCE_API_INFO.clone()),
+        (
+            OFFSET_FOR_LEADER_EPOCH_API_INFO.api_key,
+            OFFSET_FOR_LEADER_EPOCH_API_INFO.clone(),
+        ),
+        (METADATA_API_INFO.api_key, METADATA_API_INFO.clone()),
+        (CREATE_TOPICS_API_INFO.api_key, CREATE_TOPICS_API_INFO.clone()),
+        (FIND_COORDINATOR_API_INFO.api_key, FIND_COORDINATOR_API_INFO.clone()),
+        (OFFSET_FETCH_API_INFO.api_key, OFFSET_FETCH_API_INFO.clone()),
+        (OFFSET_COMMIT_API_INFO.api_key, OFFSET_COMMIT_API_INFO.clone()),
+        (INIT_PRODUCER_ID_API_INFO.api_key, INIT_PRODUCER_ID_API_INFO.clone()),
+    ]);
+    // 每个 api 从哪个版本开始使用 flexible 编码, 决定 request/response header 的版本
+    pub static ref FIRST_FLEXIBLE_VERSIONS: HashMap<i16, i16> = HashMap::from([
+        (0, 9),   // Produce
+        (1, 12),  // Fetch
+        (3, 9),   // Metadata
+        (8, 8),   // OffsetCommit
+        (9, 6),   // OffsetFetch
+        (10, 3),  // FindCoordinator
+        (18, 3),  // ApiVersions
+        (19, 5),  // CreateTopics
+        (22, 2),  // InitProducerId
+        (23, 4),  // OffsetForLeaderEpoch
+        (33, 2),  // AlterConfigs
+        (44, 1),  // IncrementalAlterConfigs
+        (75, 0),  // DescribeTopicPartitions
+    ]);
+    // (metadata.version, 最高版本), 从该 feature level 开始支持的最高版本, 没有列出的 api 不受限制
+    pub static ref METADATA_VERSION_MAX_VERSIONS: HashMap<i16, Vec<(i16, i16)>> = HashMap::from([
+        (
+            1, // Fetch
+            vec![
+                (MetadataVersion::IBP_3_0_IV1, 12),
+                (MetadataVersion::IBP_3_1_IV0, 13),
+                (MetadataVersion::IBP_3_5_IV0, 14),
+                (MetadataVersion::IBP_3_5_IV1, 15),
+                (MetadataVersion::IBP_3_7_IV0, 16),
+            ],
+        ),
+        (75, vec![(MetadataVersion::IBP_3_7_IV0, 0)]), // DescribeTopicPartitions
     ]);
 }
 
@@ -33,11 +121,50 @@ pub struct ApiVersionsReqeustBodyV4 {
     pub tag_buffer: TagBuffer,
 }
 
+// v0 到 v2 的 request body 为空
+#[derive(Debug, Decode, Encode)]
+pub struct ApiVersionsRequestBodyV0;
+
+// v0 没有 throttle_time_ms
+#[derive(Debug, Encode, Decode)]
+pub struct ApiVersionsResponseBodyV0 {
+    error_code: i16,
+    api_keys: Array<ApiKeyV0>,
+}
+
+// v1, v2 的结构相同
+#[derive(Debug, Encode, Decode)]
+pub struct ApiVersionsResponseBodyV1 {
+    error_code: i16,
+    api_keys: Array<ApiKeyV0>,
+    throttle_time_ms: Millis,
+}
+
+impl ApiVersionsResponseBodyV0 {
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn api_keys(&self) -> &Array<ApiKeyV0> {
+        &self.api_keys
+    }
+}
+
+impl ApiVersionsResponseBodyV1 {
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn api_keys(&self) -> &Array<ApiKeyV0> {
+        &self.api_keys
+    }
+}
+
 #[derive(Debug, Encode, Decode)]
 pub struct ApiVersionsResponseBodyV4 {
     error_code: i16,
     api_keys: CompactArray<ApiKey>,
-    throttle_time_ms: i32,
+    throttle_time_ms: Millis,
     tag_buffer: TagBuffer,
 }
 
@@ -51,7 +178,7 @@ impl ApiVersionsResponseBodyV4 {
         Self {
             error_code,
             api_keys,
-            throttle_time_ms,
+            throttle_time_ms: Millis(throttle_time_ms),
             tag_buffer,
         }
     }
@@ -76,6 +203,24 @@ impl ApiKey {
     }
 }
 
+// 非 flexible 版本中的 ApiKey, 没有 tag_buffer
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct ApiKeyV0 {
+    pub api_key: i16,
+    pub min_version: i16,
+    pub max_version: i16,
+}
+
+impl From<&ApiKey> for ApiKeyV0 {
+    fn from(api_info: &ApiKey) -> Self {
+        Self {
+            api_key: api_info.api_key,
+            min_version: api_info.min_version,
+            max_version: api_info.max_version,
+        }
+    }
+}
+
 impl PartialEq for ApiKey {
     fn eq(&self, other: &Self) -> bool {
         self.api_key == other.api_key
@@ -84,7 +229,7 @@ impl PartialEq for ApiKey {
 
 impl PartialOrd for ApiKey {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.api_key.cmp(&other.api_key))
+        Some(self.cmp(other))
     }
 }
 
@@ -96,6 +241,130 @@ impl Ord for ApiKey {
     }
 }
 
+pub fn is_flexible_version(api_key: i16, api_version: i16) -> bool {
+    FIRST_FLEXIBLE_VERSIONS
+        .get(&api_key)
+        .is_some_and(|first_flexible| api_version >= *first_flexible)
+}
+
+pub fn request_header_version(api_key: i16, api_version: i16) -> i16 {
+    if is_flexible_version(api_key, api_version) {
+        2
+    } else {
+        1
+    }
+}
+
+// ApiVersions 的响应始终使用 v0 header, 客户端需要在不知道 broker 支持的版本时解析它
+pub fn response_header_version(api_key: i16, api_version: i16) -> i16 {
+    if api_key != API_VERSIONS_API_INFO.api_key && is_flexible_version(api_key, api_version) {
+        1
+    } else {
+        0
+    }
+}
+
+// 只用于 flexible 版本的 ApiVersions 请求, 其他 api 使用各自的响应结构返回错误
+pub fn error_response(correlation_id: i32, error_code: i16) -> ResponseMessage {
+    ResponseMessage::new(
+        ResponseHeader::new_v0(correlation_id),
+        ResponseBody::ApiVersionsV4(ApiVersionsResponseBodyV4::new(
+            error_code,
+            CompactArray::empty(),
+            0,
+            TagBuffer::default(),
+        )),
+    )
+}
+
+pub fn check_api_version_ranges() -> Result<(), String> {
+    let mut errors = vec![];
+    for api_info in SUPPORT_APIS.values() {
+        match decodable_api_versions(api_info.api_key) {
+            Some((min_version, max_version)) => {
+                if api_info.min_version < min_version || api_info.max_version > max_version {
+                    errors.push(format!(
+                        "api key {} advertises versions [{}, {}] but only [{}, {}] can be decoded",
+                        api_info.api_key,
+                        api_info.min_version,
+                        api_info.max_version,
+                        min_version,
+                        max_version
+                    ));
+                }
+            }
+            None => errors.push(format!(
+                "api key {} is advertised but cannot be decoded",
+                api_info.api_key
+            )),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        errors.sort();
+        Err(errors.join("\n"))
+    }
+}
+
+// 根据 --disable-api 和 --enable-api 调整宣告的 api, 默认与实现的 api 完全一致
+// 最高版本受 metadata log 中的 metadata.version 限制
+// 宣告了但没有实现的 api 在 dispatch 时返回 UNSUPPORTED_VERSION
+pub fn advertised_apis(config: &BrokerConfig) -> Vec<ApiKey> {
+    let mut api_keys: Vec<ApiKey> = SUPPORT_APIS
+        .values()
+        .filter(|api_info| !config.disabled_apis.contains(&api_info.api_key))
+        .cloned()
+        .collect();
+    for api_key in config.enabled_apis.iter() {
+        if !SUPPORT_APIS.contains_key(api_key) {
+            api_keys.push(ApiKey::new(*api_key, 0, 0, TagBuffer::default()));
+        }
+    }
+    api_keys.sort();
+    api_keys.dedup();
+    cap_api_versions(api_keys, metadata_version())
+}
+
+// None 表示该 metadata.version 下还不支持这个 api
+pub fn max_version_for_metadata_version(
+    api_key: i16,
+    max_version: i16,
+    metadata_version: i16,
+) -> Option<i16> {
+    match METADATA_VERSION_MAX_VERSIONS.get(&api_key) {
+        None => Some(max_version),
+        Some(levels) => levels
+            .iter()
+            .rev()
+            .find(|(level, _)| *level <= metadata_version)
+            .map(|(_, level_max_version)| max_version.min(*level_max_version)),
+    }
+}
+
+// 按集群的 metadata.version 降低宣告的最高版本, 低于实现的最低版本时不再宣告
+pub fn cap_api_versions(api_keys: Vec<ApiKey>, metadata_version: i16) -> Vec<ApiKey> {
+    api_keys
+        .into_iter()
+        .filter_map(|mut api_info| {
+            let max_version = max_version_for_metadata_version(
+                api_info.api_key,
+                api_info.max_version,
+                metadata_version,
+            )?;
+            if max_version < api_info.min_version {
+                return None;
+            }
+            api_info.max_version = max_version;
+            Some(api_info)
+        })
+        .collect()
+}
+
+pub fn is_api_disabled(api_key: i16) -> bool {
+    BROKER_CONFIG.disabled_apis.contains(&api_key)
+}
+
 pub fn execute_api_verions(
     header: &RequestHeaderV2,
     _body: &ApiVersionsReqeustBodyV4,
@@ -105,7 +374,7 @@ pub fn execute_api_verions(
     let (error_code, mut api_keys) = if request_api_version >= API_VERSIONS_API_INFO.min_version
         && request_api_version <= API_VERSIONS_API_INFO.max_version
     {
-        (0, SUPPORT_APIS.values().cloned().collect())
+        (0, advertised_apis(&BROKER_CONFIG))
     } else {
         (UNSUPPORTED_VERSION_ERROR, vec![])
     };
@@ -115,9 +384,58 @@ pub fn execute_api_verions(
         ResponseHeader::new_v0(correlation_id),
         ResponseBody::ApiVersionsV4(ApiVersionsResponseBodyV4::new(
             error_code,
-            CompactArray::new(Some(api_keys)),
+            CompactArray::from_vec(api_keys),
             0,
             TagBuffer::default(),
         )),
     )
 }
+
+// v0 到 v2 的请求没有 tagged fields, 响应同样使用 v0 header
+pub fn execute_api_versions_v0(
+    header: &RequestHeaderV1,
+    _body: &ApiVersionsRequestBodyV0,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let (error_code, mut api_keys) = if request_api_version >= API_VERSIONS_API_INFO.min_version
+        && request_api_version <= API_VERSIONS_API_INFO.max_version
+    {
+        (0, advertised_apis(&BROKER_CONFIG))
+    } else {
+        (UNSUPPORTED_VERSION_ERROR, vec![])
+    };
+    api_keys.sort();
+    api_versions_v0_response(
+        header,
+        error_code,
+        api_keys.iter().map(ApiKeyV0::from).collect(),
+    )
+}
+
+// ApiVersions v0 到 v2 的请求出错时不返回 api_keys
+pub fn error_response_v0(header: &RequestHeaderV1, error_code: i16) -> ResponseMessage {
+    api_versions_v0_response(header, error_code, Array::empty())
+}
+
+fn api_versions_v0_response(
+    header: &RequestHeaderV1,
+    error_code: i16,
+    api_keys: Array<ApiKeyV0>,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+    // 不支持的版本使用 v0 的结构返回错误
+    let body = if request_api_version <= 0 {
+        ResponseBody::ApiVersionsV0(ApiVersionsResponseBodyV0 {
+            error_code,
+            api_keys,
+        })
+    } else {
+        ResponseBody::ApiVersionsV1(ApiVersionsResponseBodyV1 {
+            error_code,
+            api_keys,
+            throttle_time_ms: Millis(0),
+        })
+    };
+    ResponseMessage::new(ResponseHeader::new_v0(correlation_id), body)
+}
@@ -0,0 +1,176 @@
+use lazy_static::lazy_static;
+
+use crate::common_struct::MetadataAttributes;
+
+lazy_static! {
+    pub static ref BROKER_CONFIG: BrokerConfig = BrokerConfig::from_args(std::env::args().skip(1));
+}
+
+pub const DEFAULT_WORKER_QUEUE_DEPTH: usize = 128;
+pub const DEFAULT_NUM_PARTITIONS: i32 = 1;
+pub const DEFAULT_FLUSH_INTERVAL_MS: u64 = 5000;
+// 与 Kafka 的 message.max.bytes 默认值一致
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 1048588;
+pub const DEFAULT_SEGMENT_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct BrokerConfig {
+    pub wait_for_metadata: bool,
+    pub read_only: bool,
+    // None 时每个连接 spawn 一个 task, Some(n) 时由 n 个 worker 从有界队列中取连接处理
+    pub worker_pool_size: Option<usize>,
+    pub worker_queue_depth: usize,
+    // 默认关闭, 关闭时请求不存在的 topic 返回 UNKNOWN_TOPIC_OR_PARTITION
+    pub auto_create_topics: bool,
+    pub num_partitions: i32,
+    pub flush_interval_ms: u64,
+    // produce 时单个 record batch 的最大字节数
+    pub max_message_bytes: usize,
+    // None 时每个连接分配新的读缓冲区, Some(n) 时最多缓存 n 个关闭连接的缓冲区
+    pub buffer_pool_size: Option<usize>,
+    // 测试客户端协商用: 隐藏已实现的 api, 或者宣告没有实现的 api
+    pub disabled_apis: Vec<i16>,
+    pub enabled_apis: Vec<i16>,
+    // None 时不做权限控制, 所有 topic 都返回默认的 authorized operations
+    pub acl_file: Option<String>,
+    // 读取 log 文件时使用 mmap, 失败时退回 fs::read
+    pub mmap: bool,
+    // fetch 缓存 decode 后的 segment, 按文件大小计算总占用
+    pub segment_cache_bytes: usize,
+    // tagged fields 的 tag 乱序或重复时拒绝请求
+    pub strict_tags: bool,
+    // fetch 返回前使用的压缩算法, 没有编译对应 feature 的算法在 encode 时退回不压缩
+    pub fetch_compression: MetadataAttributes,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self {
+            wait_for_metadata: true,
+            read_only: false,
+            worker_pool_size: None,
+            worker_queue_depth: DEFAULT_WORKER_QUEUE_DEPTH,
+            auto_create_topics: false,
+            num_partitions: DEFAULT_NUM_PARTITIONS,
+            flush_interval_ms: DEFAULT_FLUSH_INTERVAL_MS,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            buffer_pool_size: None,
+            disabled_apis: vec![],
+            enabled_apis: vec![],
+            acl_file: None,
+            mmap: false,
+            segment_cache_bytes: DEFAULT_SEGMENT_CACHE_BYTES,
+            strict_tags: false,
+            fetch_compression: MetadataAttributes::NO_COMPRESSION,
+        }
+    }
+}
+
+fn parse_api_key(flag: &str, value: Option<String>) -> Option<i16> {
+    match value.as_deref().map(str::parse::<i16>) {
+        Some(Ok(api_key)) if api_key >= 0 => Some(api_key),
+        _ => {
+            tracing::warn!("Invalid value for {}: {:?}", flag, value);
+            None
+        }
+    }
+}
+
+fn parse_positive(flag: &str, value: Option<String>) -> Option<usize> {
+    match value.as_deref().map(str::parse::<usize>) {
+        Some(Ok(n)) if n > 0 => Some(n),
+        _ => {
+            tracing::warn!("Invalid value for {}: {:?}", flag, value);
+            None
+        }
+    }
+}
+
+fn parse_compression(flag: &str, value: Option<String>) -> Option<MetadataAttributes> {
+    match value.as_deref() {
+        Some("none") => Some(MetadataAttributes::NO_COMPRESSION),
+        Some("gzip") => Some(MetadataAttributes::GZIP),
+        Some("snappy") => Some(MetadataAttributes::SNAPPY),
+        Some("lz4") => Some(MetadataAttributes::LZ4),
+        Some("zstd") => Some(MetadataAttributes::ZSTD),
+        _ => {
+            tracing::warn!("Invalid value for {}: {:?}", flag, value);
+            None
+        }
+    }
+}
+
+impl BrokerConfig {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut config = BrokerConfig::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-wait-for-metadata" => config.wait_for_metadata = false,
+                "--read-only" => config.read_only = true,
+                "--worker-pool-size" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.worker_pool_size = Some(n);
+                    }
+                }
+                "--worker-queue-depth" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.worker_queue_depth = n;
+                    }
+                }
+                "--mmap" => config.mmap = true,
+                "--strict-tags" => config.strict_tags = true,
+                "--segment-cache-bytes" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.segment_cache_bytes = n;
+                    }
+                }
+                "--auto-create-topics" => config.auto_create_topics = true,
+                "--num-partitions" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.num_partitions = i32::try_from(n).unwrap_or(i32::MAX);
+                    }
+                }
+                "--flush-interval-ms" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.flush_interval_ms = n as u64;
+                    }
+                }
+                "--max-message-bytes" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.max_message_bytes = n;
+                    }
+                }
+                "--buffer-pool-size" => {
+                    if let Some(n) = parse_positive(&arg, args.next()) {
+                        config.buffer_pool_size = Some(n);
+                    }
+                }
+                // 同一个 api key 以最后出现的参数为准
+                "--disable-api" => {
+                    if let Some(api_key) = parse_api_key(&arg, args.next()) {
+                        config.enabled_apis.retain(|key| *key != api_key);
+                        config.disabled_apis.push(api_key);
+                    }
+                }
+                "--enable-api" => {
+                    if let Some(api_key) = parse_api_key(&arg, args.next()) {
+                        config.disabled_apis.retain(|key| *key != api_key);
+                        config.enabled_apis.push(api_key);
+                    }
+                }
+                "--fetch-compression" => {
+                    if let Some(codec) = parse_compression(&arg, args.next()) {
+                        config.fetch_compression = codec;
+                    }
+                }
+                "--acl-file" => match args.next() {
+                    Some(path) => config.acl_file = Some(path),
+                    None => tracing::warn!("Missing value for {}", arg),
+                },
+                // codecrafters 会传入 server.properties 的路径, 忽略无法识别的参数
+                arg => tracing::debug!("Ignore unknown argument: {}", arg),
+            }
+        }
+        config
+    }
+}
@@ -1,8 +1,11 @@
 use std::{
+    borrow::Cow,
     cmp::min,
-    io::{Cursor, Read, Seek},
+    io::{Cursor, Read},
     mem,
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 
 use bitflags::bitflags;
@@ -10,9 +13,11 @@ use bytes::Buf;
 use uuid::Uuid;
 
 use crate::{
-    decode::{Decode, DecodeError, DecodeResult},
+    crc::crc32c,
+    decode::{decode_bounded_with, Decode, DecodeError, DecodeResult},
     describe_topic_partitions::RepicaNode,
-    encode::Encode,
+    encode::{Encode, EncodeError, EncodeResult},
+    metadata_log::LOG_OVERHEAD,
 };
 
 const VARINTS_MASK: u8 = 0x7f;
@@ -71,6 +76,22 @@ impl VarInt {
         zigzag_decode_64bit(n)
     }
 
+    // compact 类型的长度为实际长度 + 1, 0 只能表示 null, 对非 null 的类型是非法值
+    pub fn as_len(&self) -> DecodeResult<usize> {
+        let n = self.as_u64();
+        n.checked_sub(1)
+            .and_then(|length| usize::try_from(length).ok())
+            .ok_or_else(|| DecodeError::Other(format!("Invalid compact length: {}", n).into()))
+    }
+
+    pub fn as_nullable_len(&self) -> DecodeResult<Option<usize>> {
+        if self.as_u64() == 0 {
+            Ok(None)
+        } else {
+            self.as_len().map(Some)
+        }
+    }
+
     pub fn as_bytes(&self) -> &Vec<u8> {
         &self.bytes
     }
@@ -81,8 +102,12 @@ impl VarInt {
 }
 
 impl Encode for VarInt {
-    fn encode(&self) -> Vec<u8> {
-        self.bytes.clone()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.bytes);
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.bytes.len()
     }
 }
 
@@ -165,8 +190,12 @@ impl VarLong {
 }
 
 impl Encode for VarLong {
-    fn encode(&self) -> Vec<u8> {
-        self.bytes.clone()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.bytes);
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.bytes.len()
     }
 }
 
@@ -186,6 +215,84 @@ impl Decode for VarLong {
     }
 }
 
+// 以毫秒为单位的 int32 字段, encode 结果与 i32 完全相同
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millis(pub i32);
+
+// 以毫秒为单位的 int64 字段, encode 结果与 i64 完全相同
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MillisLong(pub i64);
+
+macro_rules! impl_millis {
+    ($($type:ident($inner:ty)),*) => {
+        $(
+            impl $type {
+                // 负数 (例如 -1 表示不限制) 按 0 处理
+                pub fn as_duration(&self) -> Duration {
+                    Duration::from_millis(self.0.max(0) as u64)
+                }
+            }
+
+            impl From<$inner> for $type {
+                fn from(value: $inner) -> Self {
+                    $type(value)
+                }
+            }
+
+            impl Encode for $type {
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    self.0.encode_into(out);
+                }
+
+                fn encoded_len(&self) -> usize {
+                    mem::size_of::<$inner>()
+                }
+            }
+
+            impl Decode for $type {
+                fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+                where
+                    Self: Sized,
+                {
+                    Ok($type(<$inner>::decode(buffer)?))
+                }
+            }
+        )*
+    };
+}
+impl_millis!(Millis(i32), MillisLong(i64));
+
+// unsigned varint 的字节数, 每个字节保存 7 位
+pub fn unsigned_varint_len(value: u64) -> usize {
+    ((u64::BITS - value.leading_zeros()).max(1) as usize).div_ceil(7)
+}
+
+// 每个元素至少占用 1 个字节, 元素个数超过剩余字节数时无需逐个 decode
+// 流式 decode 时返回 Incomplete; 请求已经完整时由 decode_bounded_with 转换为其他错误
+pub(crate) fn check_array_length(length: u64, buffer: &Cursor<&[u8]>) -> DecodeResult<()> {
+    if length > buffer.remaining() as u64 {
+        Err(DecodeError::incomplete(Some(
+            format!(
+                "Array length({}) is greater than remaining bytes({})",
+                length,
+                buffer.remaining()
+            )
+            .into(),
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+// 读取到 end 为止的所有字节, 用于跳过或原样保留无法识别的 body
+pub(crate) fn read_bytes_until(buffer: &mut Cursor<&[u8]>, end: u64) -> DecodeResult<Vec<u8>> {
+    let remaining = end.saturating_sub(buffer.position());
+    check_array_length(remaining, buffer)?;
+    let mut inner = vec![0; remaining as usize];
+    buffer.read_exact(&mut inner)?;
+    Ok(inner)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Array<T> {
     inner: Option<Vec<T>>,
@@ -197,10 +304,10 @@ impl<T> Array<T> {
     }
 }
 
-impl<T: Encode> Encode for Array<T> {
-    fn encode(&self) -> Vec<u8> {
+impl<T: Encode> Array<T> {
+    fn encode_with(&self, out: &mut Vec<u8>, encode_item: impl Fn(&T, &mut Vec<u8>)) {
         match &self.inner {
-            None => vec![0xff; 4],
+            None => (-1i32).encode_into(out),
             Some(array) => {
                 if array.len() >= i32::MAX as usize {
                     panic!(
@@ -209,27 +316,47 @@ impl<T: Encode> Encode for Array<T> {
                         i32::MAX
                     );
                 } else {
-                    let mut encode_res = (array.len() as i32).to_be_bytes().to_vec();
+                    (array.len() as i32).encode_into(out);
                     for item in array.iter() {
-                        encode_res.append(&mut item.encode());
+                        encode_item(item, out);
                     }
-                    encode_res
                 }
             }
         }
     }
 }
 
-impl<T: Decode> Decode for Array<T> {
-    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<Self>
-    where
-        Self: Sized,
-    {
+impl<T: Encode> Encode for Array<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.encode_with(out, T::encode_into);
+    }
+
+    // 元素按相同的版本 encode, 嵌套 struct 中的 since/until 才能生效
+    fn encode_versioned_into(&self, out: &mut Vec<u8>, version: i16) {
+        self.encode_with(out, |item, out| item.encode_versioned_into(out, version));
+    }
+
+    fn encoded_len(&self) -> usize {
+        4 + self
+            .inner
+            .iter()
+            .flatten()
+            .map(Encode::encoded_len)
+            .sum::<usize>()
+    }
+}
+
+impl<T: Decode> Array<T> {
+    fn decode_with(
+        buffer: &mut std::io::Cursor<&[u8]>,
+        decode_item: impl Fn(&mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<T>,
+    ) -> crate::decode::DecodeResult<Self> {
         let length = i32::decode(buffer)?;
         let inner = if length >= 0 {
-            let mut decode_res = vec![];
+            check_array_length(length as u64, buffer)?;
+            let mut decode_res = Vec::with_capacity(length as usize);
             for _ in 0..length {
-                let item = T::decode(buffer)?;
+                let item = decode_item(buffer)?;
                 decode_res.push(item);
             }
             Some(decode_res)
@@ -240,6 +367,25 @@ impl<T: Decode> Decode for Array<T> {
     }
 }
 
+impl<T: Decode> Decode for Array<T> {
+    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, T::decode)
+    }
+
+    fn decode_versioned(
+        buffer: &mut std::io::Cursor<&[u8]>,
+        version: i16,
+    ) -> crate::decode::DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, |buffer| T::decode_versioned(buffer, version))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CompactArray<T> {
     inner: Option<Vec<T>>,
@@ -251,31 +397,51 @@ impl<T> CompactArray<T> {
     }
 }
 
-impl<T: Encode> Encode for CompactArray<T> {
-    fn encode(&self) -> Vec<u8> {
+impl<T: Encode> CompactArray<T> {
+    fn encode_with(&self, out: &mut Vec<u8>, encode_item: impl Fn(&T, &mut Vec<u8>)) {
         match &self.inner {
-            None => vec![0x00],
+            None => out.push(0x00),
             Some(array) => {
-                let mut encode_res = VarInt::from_u64((array.len() + 1) as u64).into_bytes();
+                // unsigned varint, 长度 + 1, 0 表示 null
+                VarInt::from_u64((array.len() + 1) as u64).encode_into(out);
                 for item in array.iter() {
-                    encode_res.append(&mut item.encode());
+                    encode_item(item, out);
                 }
-                encode_res
             }
         }
     }
 }
 
-impl<T: Decode> Decode for CompactArray<T> {
-    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<Self>
-    where
-        Self: Sized,
-    {
-        let length = VarInt::decode(buffer)?.as_u64();
-        let inner = if length > 0 {
-            let mut decode_res = vec![];
-            for _ in 0..length - 1 {
-                let item = T::decode(buffer)?;
+impl<T: Encode> Encode for CompactArray<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.encode_with(out, T::encode_into);
+    }
+
+    fn encode_versioned_into(&self, out: &mut Vec<u8>, version: i16) {
+        self.encode_with(out, |item, out| item.encode_versioned_into(out, version));
+    }
+
+    fn encoded_len(&self) -> usize {
+        match &self.inner {
+            None => 1,
+            Some(array) => {
+                unsigned_varint_len((array.len() + 1) as u64)
+                    + array.iter().map(Encode::encoded_len).sum::<usize>()
+            }
+        }
+    }
+}
+
+impl<T: Decode> CompactArray<T> {
+    fn decode_with(
+        buffer: &mut std::io::Cursor<&[u8]>,
+        decode_item: impl Fn(&mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<T>,
+    ) -> crate::decode::DecodeResult<Self> {
+        let inner = if let Some(length) = VarInt::decode(buffer)?.as_nullable_len()? {
+            check_array_length(length as u64, buffer)?;
+            let mut decode_res = Vec::with_capacity(length);
+            for _ in 0..length {
+                let item = decode_item(buffer)?;
                 decode_res.push(item);
             }
             Some(decode_res)
@@ -286,6 +452,25 @@ impl<T: Decode> Decode for CompactArray<T> {
     }
 }
 
+impl<T: Decode> Decode for CompactArray<T> {
+    fn decode(buffer: &mut std::io::Cursor<&[u8]>) -> crate::decode::DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, T::decode)
+    }
+
+    fn decode_versioned(
+        buffer: &mut std::io::Cursor<&[u8]>,
+        version: i16,
+    ) -> crate::decode::DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, |buffer| T::decode_versioned(buffer, version))
+    }
+}
+
 macro_rules! impl_deref_for_array {
     ($($type:tt<$gen:tt>),*) => {
         $(
@@ -332,6 +517,35 @@ macro_rules! impl_empty_for_array {
 }
 impl_empty_for_array!(Array<T>, CompactArray<T>);
 
+macro_rules! impl_from_vec_for_array {
+    ($($type:tt<$gen:tt>),*) => {
+        $(
+            impl<$gen> $type<$gen> {
+                pub fn null() -> Self {
+                    Self::new(None)
+                }
+
+                pub fn from_vec(inner: Vec<$gen>) -> Self {
+                    Self::new(Some(inner))
+                }
+            }
+
+            impl<$gen> From<Vec<$gen>> for $type<$gen> {
+                fn from(inner: Vec<$gen>) -> Self {
+                    Self::from_vec(inner)
+                }
+            }
+
+            impl<$gen> FromIterator<$gen> for $type<$gen> {
+                fn from_iter<I: IntoIterator<Item = $gen>>(iter: I) -> Self {
+                    Self::from_vec(iter.into_iter().collect())
+                }
+            }
+        )*
+    };
+}
+impl_from_vec_for_array!(Array<T>, CompactArray<T>);
+
 macro_rules! impl_inner_for_array {
     ($($type:tt<$gen:tt>),*) => {
         $(
@@ -345,6 +559,66 @@ macro_rules! impl_inner_for_array {
 }
 impl_inner_for_array!(Array<T>, CompactArray<T>);
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CompactMap<K, V> {
+    inner: CompactArray<(K, V)>,
+}
+
+impl<K: PartialEq, V> CompactMap<K, V> {
+    pub fn new(inner: CompactArray<(K, V)>) -> Self {
+        Self { inner }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.inner
+            .as_ref()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    // 已存在的 key 原地替换, 保持 entry 的顺序
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let entries = self.inner.get_or_insert_with(Vec::new);
+        match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(mem::replace(v, value)),
+            None => {
+                entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(K, V)> {
+        self.inner.iter().flatten()
+    }
+
+    pub fn get_inner(&self) -> &CompactArray<(K, V)> {
+        &self.inner
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for CompactMap<K, V> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.inner.encode_into(out);
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.inner.encoded_len()
+    }
+}
+
+impl<K: Decode, V: Decode> Decode for CompactMap<K, V> {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Ok(CompactMap {
+            inner: CompactArray::decode(buffer)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct KafkaString {
     inner: String,
@@ -357,7 +631,7 @@ impl KafkaString {
 }
 
 impl Encode for KafkaString {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         if self.inner.len() > i16::MAX as usize {
             panic!(
                 "KafkaString length({}) is bigger than i16::MAX({})",
@@ -365,11 +639,14 @@ impl Encode for KafkaString {
                 i16::MAX
             );
         } else {
-            let mut encode_res = (self.inner.len() as i16).to_be_bytes().to_vec();
-            encode_res.extend(self.inner.as_bytes());
-            encode_res
+            (self.inner.len() as i16).encode_into(out);
+            out.extend_from_slice(self.inner.as_bytes());
         }
     }
+
+    fn encoded_len(&self) -> usize {
+        mem::size_of::<i16>() + self.inner.len()
+    }
 }
 
 impl Decode for KafkaString {
@@ -378,10 +655,16 @@ impl Decode for KafkaString {
         Self: Sized,
     {
         let length = i16::decode(buffer)?;
-        assert!(
-            length >= 0,
-            "KafkaString's length cannot smaller than 0 when decoding"
-        );
+        if length < 0 {
+            return Err(DecodeError::Other(
+                format!("KafkaString's length cannot be negative: {}", length).into(),
+            ));
+        }
+        if buffer.remaining() < length as usize {
+            return Err(DecodeError::incomplete_needed(
+                length as usize - buffer.remaining(),
+            ));
+        }
         let mut string_buffer = vec![0; length as usize]; //TODO 是否需要预先置零
         buffer.read_exact(&mut string_buffer)?;
         let s = String::from_utf8(string_buffer)?;
@@ -401,10 +684,14 @@ impl CompactString {
 }
 
 impl Encode for CompactString {
-    fn encode(&self) -> Vec<u8> {
-        let mut encode_res = VarInt::from_u64((self.inner.len() + 1) as u64).into_bytes();
-        encode_res.extend(self.inner.as_bytes());
-        encode_res
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        // unsigned varint, 长度 + 1
+        VarInt::from_u64((self.inner.len() + 1) as u64).encode_into(out);
+        out.extend_from_slice(self.inner.as_bytes());
+    }
+
+    fn encoded_len(&self) -> usize {
+        unsigned_varint_len((self.inner.len() + 1) as u64) + self.inner.len()
     }
 }
 
@@ -413,12 +700,9 @@ impl Decode for CompactString {
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_u64();
-        assert!(
-            length > 0,
-            "CompactString's length must bigger than 0 when decoding"
-        );
-        let mut string_buffer = vec![0; (length - 1) as usize]; //TODO 是否需要预先置零
+        let length = VarInt::decode(buffer)?.as_len()?;
+        check_array_length(length as u64, buffer)?;
+        let mut string_buffer = vec![0; length]; //TODO 是否需要预先置零
         buffer.read_exact(&mut string_buffer)?;
         let s = String::from_utf8(string_buffer)?;
         Ok(CompactString::new(s))
@@ -457,9 +741,9 @@ impl NullableString {
 }
 
 impl Encode for NullableString {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            None => vec![0xff; mem::size_of::<i16>()],
+            None => (-1i16).encode_into(out),
             Some(s) => {
                 if s.len() > i16::MAX as usize {
                     panic!(
@@ -468,13 +752,16 @@ impl Encode for NullableString {
                         i16::MAX
                     );
                 } else {
-                    let mut encode_res = (s.len() as i16).to_be_bytes().to_vec();
-                    encode_res.extend(s.as_bytes());
-                    encode_res
+                    (s.len() as i16).encode_into(out);
+                    out.extend_from_slice(s.as_bytes());
                 }
             }
         }
     }
+
+    fn encoded_len(&self) -> usize {
+        mem::size_of::<i16>() + self.inner.as_ref().map_or(0, String::len)
+    }
 }
 
 impl Decode for NullableString {
@@ -483,7 +770,18 @@ impl Decode for NullableString {
         Self: Sized,
     {
         let length = i16::decode(buffer)?;
+        // 只有 -1 表示 null, 其他负数说明长度字段已损坏
+        if length < -1 {
+            return Err(DecodeError::Other(
+                format!("NullableString's length cannot be {}", length).into(),
+            ));
+        }
         let inner = if length >= 0 {
+            if buffer.remaining() < length as usize {
+                return Err(DecodeError::incomplete_needed(
+                    length as usize - buffer.remaining(),
+                ));
+            }
             let mut string_buffer = vec![0; length as usize]; //TODO 是否需要预先置零
             buffer.read_exact(&mut string_buffer)?;
             let s = String::from_utf8(string_buffer)?;
@@ -507,16 +805,23 @@ impl CompactNullableString {
 }
 
 impl Encode for CompactNullableString {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            None => vec![0x00],
+            None => out.push(0x00),
             Some(s) => {
-                let mut encode_res = VarInt::from_u64((s.len() + 1) as u64).into_bytes();
-                encode_res.extend(s.as_bytes());
-                encode_res
+                // unsigned varint, 长度 + 1, 0 表示 null
+                VarInt::from_u64((s.len() + 1) as u64).encode_into(out);
+                out.extend_from_slice(s.as_bytes());
             }
         }
     }
+
+    fn encoded_len(&self) -> usize {
+        match &self.inner {
+            None => 1,
+            Some(s) => unsigned_varint_len((s.len() + 1) as u64) + s.len(),
+        }
+    }
 }
 
 impl Decode for CompactNullableString {
@@ -524,9 +829,9 @@ impl Decode for CompactNullableString {
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_u64();
-        let inner = if length > 0 {
-            let mut string_buffer = vec![0; (length - 1) as usize]; //TODO 是否需要预先置零
+        let inner = if let Some(length) = VarInt::decode(buffer)?.as_nullable_len()? {
+            check_array_length(length as u64, buffer)?;
+            let mut string_buffer = vec![0; length]; //TODO 是否需要预先置零
             buffer.read_exact(&mut string_buffer)?;
             let s = String::from_utf8(string_buffer)?;
             Some(s)
@@ -537,6 +842,113 @@ impl Decode for CompactNullableString {
     }
 }
 
+macro_rules! impl_deref_for_nullable_string {
+    ($($type:ty),*) => {
+        $(
+            impl Deref for $type {
+                type Target = Option<String>;
+                fn deref(&self) -> &Self::Target {
+                    &self.inner
+                }
+            }
+
+            impl DerefMut for $type {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    &mut self.inner
+                }
+            }
+        )*
+    };
+}
+impl_deref_for_nullable_string!(NullableString, CompactNullableString);
+
+// Presence<T> 用 1 个字节(0 或 1)表示字段是否存在, 存在时紧跟 T 的编码.
+// 它不是 Kafka 的 nullable 约定: NullableString/NullableBytes/Array 用长度 -1 表示 null,
+// Compact* 类型用长度 0 表示 null. 只有协议里明确是 "存在标记 + 内容" 的字段才使用 Presence<T>.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Presence<T> {
+    inner: Option<T>,
+}
+
+impl<T> Presence<T> {
+    pub fn new(inner: Option<T>) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.inner
+    }
+}
+
+impl<T> Deref for Presence<T> {
+    type Target = Option<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Presence<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: Encode> Presence<T> {
+    fn encode_with(&self, out: &mut Vec<u8>, encode_item: impl Fn(&T, &mut Vec<u8>)) {
+        match &self.inner {
+            None => out.push(0x00),
+            Some(item) => {
+                out.push(0x01);
+                encode_item(item, out);
+            }
+        }
+    }
+}
+
+impl<T: Encode> Encode for Presence<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.encode_with(out, T::encode_into);
+    }
+
+    fn encode_versioned_into(&self, out: &mut Vec<u8>, version: i16) {
+        self.encode_with(out, |item, out| item.encode_versioned_into(out, version));
+    }
+}
+
+impl<T: Decode> Presence<T> {
+    fn decode_with(
+        buffer: &mut Cursor<&[u8]>,
+        decode_item: impl Fn(&mut Cursor<&[u8]>) -> DecodeResult<T>,
+    ) -> DecodeResult<Self> {
+        let inner = match u8::decode(buffer)? {
+            0 => None,
+            1 => Some(decode_item(buffer)?),
+            x => {
+                return Err(DecodeError::Other(
+                    format!("Found {} when decoding presence flag", x).into(),
+                ))
+            }
+        };
+        Ok(Presence::new(inner))
+    }
+}
+
+impl<T: Decode> Decode for Presence<T> {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, T::decode)
+    }
+
+    fn decode_versioned(buffer: &mut Cursor<&[u8]>, version: i16) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(buffer, |buffer| T::decode_versioned(buffer, version))
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct KafkaBytes {
     inner: Vec<u8>,
@@ -549,7 +961,7 @@ impl KafkaBytes {
 }
 
 impl Encode for KafkaBytes {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         if self.inner.len() >= i32::MAX as usize {
             panic!(
                 "KafkaBytes length({}) is greater then i32::MAX({})",
@@ -557,9 +969,8 @@ impl Encode for KafkaBytes {
                 i32::MAX
             );
         } else {
-            let mut encode_res = (self.inner.len() as i32).to_be_bytes().to_vec();
-            encode_res.append(&mut self.inner.clone());
-            encode_res
+            (self.inner.len() as i32).encode_into(out);
+            out.extend_from_slice(&self.inner);
         }
     }
 }
@@ -570,6 +981,17 @@ impl Decode for KafkaBytes {
         Self: Sized,
     {
         let length = i32::decode(buffer)?;
+        if length < 0 {
+            return Err(DecodeError::Other(
+                format!("KafkaBytes's length cannot be negative: {}", length).into(),
+            ));
+        }
+        // 先检查剩余字节, 避免按损坏的长度分配内存
+        if buffer.remaining() < length as usize {
+            return Err(DecodeError::incomplete_needed(
+                length as usize - buffer.remaining(),
+            ));
+        }
         let mut bytes = vec![0_u8; length as usize];
         buffer.read_exact(&mut bytes)?;
         Ok(KafkaBytes::new(bytes))
@@ -588,10 +1010,10 @@ impl CompactBytes {
 }
 
 impl Encode for CompactBytes {
-    fn encode(&self) -> Vec<u8> {
-        let mut encode_res = VarInt::from_u64((self.inner.len() + 1) as u64).into_bytes();
-        encode_res.extend_from_slice(&self.inner);
-        encode_res
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        // unsigned varint, 长度 + 1
+        VarInt::from_u64((self.inner.len() + 1) as u64).encode_into(out);
+        out.extend_from_slice(&self.inner);
     }
 }
 
@@ -600,12 +1022,9 @@ impl Decode for CompactBytes {
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_u64();
-        assert!(
-            length > 0,
-            "CompactBytes's length must bigger than 0 when decoding"
-        );
-        let mut inner = vec![0; (length - 1) as usize]; //TODO 是否需要预先置零
+        let length = VarInt::decode(buffer)?.as_len()?;
+        check_array_length(length as u64, buffer)?;
+        let mut inner = vec![0; length]; //TODO 是否需要预先置零
         buffer.read_exact(&mut inner)?;
         Ok(CompactBytes::new(inner))
     }
@@ -623,9 +1042,9 @@ impl NullableBytes {
 }
 
 impl Encode for NullableBytes {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            None => vec![0xff; mem::size_of::<i32>()],
+            None => (-1i32).encode_into(out),
             Some(array) => {
                 if array.len() > i32::MAX as usize {
                     panic!(
@@ -634,9 +1053,8 @@ impl Encode for NullableBytes {
                         i32::MAX
                     );
                 } else {
-                    let mut encode_res = (array.len() as i32).to_be_bytes().to_vec();
-                    encode_res.extend_from_slice(array);
-                    encode_res
+                    (array.len() as i32).encode_into(out);
+                    out.extend_from_slice(array);
                 }
             }
         }
@@ -669,16 +1087,20 @@ impl CompactNullableBytes {
     pub fn new(inner: Option<Vec<u8>>) -> Self {
         Self { inner }
     }
+
+    pub fn get_inner(&self) -> &Option<Vec<u8>> {
+        &self.inner
+    }
 }
 
 impl Encode for CompactNullableBytes {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            None => vec![0x00],
+            None => out.push(0x00),
             Some(array) => {
-                let mut encode_res = VarInt::from_u64((array.len() + 1) as u64).into_bytes();
-                encode_res.extend_from_slice(array);
-                encode_res
+                // unsigned varint, 长度 + 1, 0 表示 null
+                VarInt::from_u64((array.len() + 1) as u64).encode_into(out);
+                out.extend_from_slice(array);
             }
         }
     }
@@ -689,9 +1111,9 @@ impl Decode for CompactNullableBytes {
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_u64();
-        let inner = if length > 0 {
-            let mut inner = vec![0; (length - 1) as usize]; //TODO 是否需要预先置零
+        let inner = if let Some(length) = VarInt::decode(buffer)?.as_nullable_len()? {
+            check_array_length(length as u64, buffer)?;
+            let mut inner = vec![0; length]; //TODO 是否需要预先置零
             buffer.read_exact(&mut inner)?;
             Some(inner)
         } else {
@@ -701,52 +1123,573 @@ impl Decode for CompactNullableBytes {
     }
 }
 
-#[derive(Debug, Clone, Encode, Decode, Default)]
+// tagged fields: 字段个数(uvarint), 每个字段为 tag(uvarint) + size(uvarint) + data
+// 与 CompactArray 不同, 字段个数和 size 都没有 +1
+// fields 按 tag 严格递增排列, get 依赖这个顺序二分查找
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct TagBuffer {
-    fields: CompactArray<TagSection>,
+    fields: Vec<TagSection>,
+}
+
+// --strict-tags: tag 乱序或重复时 decode 失败, 否则排序并保留每个 tag 第一次出现的字段
+static STRICT_TAGS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_tags(strict: bool) {
+    STRICT_TAGS.store(strict, Ordering::Relaxed);
 }
 
-#[derive(Debug, Clone, Encode, Decode, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct TagSection {
-    tag: u8,
-    data: CompactArray<u8>,
+    tag: u32,
+    data: Vec<u8>,
 }
 
 impl TagBuffer {
-    pub fn new(fields: CompactArray<TagSection>) -> Self {
+    // tag 重复时保留第一个
+    pub fn new(mut fields: Vec<TagSection>) -> Self {
+        fields.sort_by_key(|section| section.tag);
+        fields.dedup_by_key(|section| section.tag);
         Self { fields }
     }
-}
 
-impl TagSection {
-    pub fn new(tag: u8, data: Option<Vec<u8>>) -> Self {
+    pub fn get(&self, tag: u32) -> Option<&[u8]> {
+        self.fields
+            .binary_search_by_key(&tag, |section| section.tag)
+            .ok()
+            .map(|idx| self.fields[idx].data.as_slice())
+    }
+
+    // 去掉已经 decode 到字段中的 tag, 剩下不认识的 tag
+    pub fn without_tags(&self, tags: &[u32]) -> Self {
         Self {
-            tag,
-            data: CompactArray::new(data),
+            fields: self
+                .fields
+                .iter()
+                .filter(|section| !tags.contains(&section.tag))
+                .cloned()
+                .collect(),
         }
     }
-}
 
-#[derive(Debug, Clone, Encode, Decode)]
-pub struct RecordBatch {
-    pub base_offset: i64,
-    pub batch_length: i32,
-    pub partition_leader_epoch: i32,
-    pub magic_byte: i8,
-    pub crc: i32,
-    pub attributes: MetadataAttributes,
-    pub last_offset_data: i32,
-    pub base_timestamp: i64,
-    pub max_timestamp: i64,
-    pub producer_id: i64,
-    pub producer_epoch: i16,
-    pub base_sequence: i32,
-    pub records: Array<Record>,
+    // 加入字段的 tag 后按 tag 排序, tag 相同时使用 sections 中的字段
+    pub fn merged(&self, mut sections: Vec<TagSection>) -> Self {
+        sections.extend(self.fields.iter().cloned());
+        Self::new(sections)
+    }
+
+    pub fn decode_with(buffer: &mut Cursor<&[u8]>, strict: bool) -> DecodeResult<Self> {
+        let length = VarInt::decode(buffer)?.as_u64();
+        check_array_length(length, buffer)?;
+        let mut fields: Vec<TagSection> = Vec::with_capacity(length as usize);
+        for _ in 0..length {
+            let section = TagSection::decode(buffer)?;
+            if strict {
+                if let Some(last) = fields.last() {
+                    if section.tag <= last.tag {
+                        return Err(DecodeError::Other(
+                            format!(
+                                "Tag {} follows tag {}, tags must be unique and ascending",
+                                section.tag, last.tag
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
+            fields.push(section);
+        }
+        Ok(TagBuffer::new(fields))
+    }
 }
 
-impl RecordBatch {
-    pub fn get_records(&self) -> &Array<Record> {
-        &self.records
+impl TagSection {
+    pub fn new(tag: u32, data: Vec<u8>) -> Self {
+        Self { tag, data }
+    }
+}
+
+impl Encode for TagBuffer {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        VarInt::from_u64(self.fields.len() as u64).encode_into(out);
+        for section in self.fields.iter() {
+            section.encode_into(out);
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        unsigned_varint_len(self.fields.len() as u64)
+            + self.fields.iter().map(Encode::encoded_len).sum::<usize>()
+    }
+}
+
+impl Decode for TagBuffer {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        TagBuffer::decode_with(buffer, STRICT_TAGS.load(Ordering::Relaxed))
+    }
+}
+
+impl Encode for TagSection {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        VarInt::from_u64(self.tag as u64).encode_into(out);
+        VarInt::from_u64(self.data.len() as u64).encode_into(out);
+        out.extend_from_slice(&self.data);
+    }
+
+    fn encoded_len(&self) -> usize {
+        unsigned_varint_len(self.tag as u64)
+            + unsigned_varint_len(self.data.len() as u64)
+            + self.data.len()
+    }
+}
+
+impl Decode for TagSection {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let tag = VarInt::decode(buffer)?.as_u64();
+        let tag = u32::try_from(tag)
+            .map_err(|_| DecodeError::Other(format!("Invalid tag: {}", tag).into()))?;
+        let size = VarInt::decode(buffer)?.as_u64();
+        check_array_length(size, buffer)?;
+        let mut data = vec![0; size as usize];
+        buffer.read_exact(&mut data)?;
+        Ok(TagSection::new(tag, data))
+    }
+}
+
+// base_offset(i64) + batch_length(i32) + partition_leader_epoch(i32) + magic(i8) + crc(u32)
+// crc 覆盖从 attributes 开始到 batch 结束的所有字节
+pub const RECORD_BATCH_CRC_START: usize = 21;
+// records 的个数所在的位置, 之后直到 batch 结束都是 record (可能被压缩)
+pub const RECORD_BATCH_RECORDS_START: usize = 57;
+pub const RECORD_BATCH_MAGIC: i8 = 2;
+// 非幂等 producer 写入的 batch 中 producer_id, producer_epoch, base_sequence 都为 -1
+pub const NO_PRODUCER_ID: i64 = -1;
+pub const NO_PRODUCER_EPOCH: i16 = -1;
+pub const NO_SEQUENCE: i32 = -1;
+
+#[derive(Debug, Clone)]
+pub struct RecordBatch {
+    pub base_offset: i64,               // int64
+    pub batch_length: i32,              // int32, 不包含 base_offset 和自身, encode 时重新计算
+    pub partition_leader_epoch: i32,    // int32
+    pub magic_byte: i8,                 // int8
+    pub crc: i32,                       // uint32, 按位保存在 i32 中, encode 时重新计算
+    pub attributes: MetadataAttributes, // int16
+    pub last_offset_delta: i32,         // int32, 最后一条 record 的 offset delta, encode 时重新计算
+    pub base_timestamp: i64,            // int64
+    pub max_timestamp: i64,             // int64
+    pub producer_id: i64,               // int64
+    pub producer_epoch: i16,            // int16
+    pub base_sequence: i32,             // int32
+    pub records: Array<Record>,         // int32 个数 + records
+    // set_compression 时压缩好的 records, encode 时直接使用, 不会每次 encode 都重新压缩
+    // 为 None 时在 encode 时压缩, 修改 records 后需要重新调用 set_compression
+    pub compressed_records: Option<Vec<u8>>,
+}
+
+impl RecordBatch {
+    pub fn get_records(&self) -> &Array<Record> {
+        &self.records
+    }
+
+    pub fn next_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64 + 1
+    }
+
+    // sequence 超过 i32::MAX 后从 0 重新开始
+    pub fn last_sequence(&self) -> i32 {
+        if self.base_sequence > i32::MAX - self.last_offset_delta {
+            self.last_offset_delta - (i32::MAX - self.base_sequence) - 1
+        } else {
+            self.base_sequence + self.last_offset_delta
+        }
+    }
+
+    pub fn compute_crc(&self) -> u32 {
+        crc32c(&self.encode()[RECORD_BATCH_CRC_START..])
+    }
+
+    #[deprecated(note = "use the last_offset_delta field")]
+    pub fn last_offset_data(&self) -> i32 {
+        self.last_offset_delta
+    }
+
+    // last_offset_delta 是最后一条 record 的 offset delta, 必须与 records 的个数一致
+    pub fn check_record_count(&self) -> EncodeResult<()> {
+        let actual = self.records.as_ref().map_or(0, |records| records.len());
+        if self.last_offset_delta as i64 + 1 != actual as i64 {
+            return Err(EncodeError::CountMismatch {
+                field: "RecordBatch.last_offset_delta",
+                declared: self.last_offset_delta as i64 + 1,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    // 写入磁盘或发送前使用, encode 会按 records 重新计算, 字段不一致说明调用方的状态有误
+    pub fn try_encode(&self) -> EncodeResult<Vec<u8>> {
+        self.check_record_count()?;
+        Ok(self.encode())
+    }
+
+    pub fn timestamp_type(&self) -> TimestampType {
+        if self.attributes.contains(MetadataAttributes::TIMESTAMP_TYPE) {
+            TimestampType::LogAppendTime
+        } else {
+            TimestampType::CreateTime
+        }
+    }
+
+    pub fn is_control_batch(&self) -> bool {
+        self.attributes
+            .contains(MetadataAttributes::IS_CONTROL_BATCH)
+    }
+
+    pub fn is_idempotent(&self) -> bool {
+        self.producer_id > NO_PRODUCER_ID
+    }
+
+    // 只看 attributes 中的标志位, 事务 batch 一定是幂等的, 反过来不成立
+    pub fn is_transactional(&self) -> bool {
+        self.attributes
+            .contains(MetadataAttributes::IS_TRANSACTIONAL)
+    }
+
+    // control batch 中只有一个 control record, key 为 version(i16) + type(i16)
+    pub fn control_record_type(&self) -> Option<ControlRecordType> {
+        if !self.is_control_batch() {
+            return None;
+        }
+        let record = self.records.as_ref()?.first()?;
+        let key = record.key.get_inner().as_ref()?;
+        let mut buffer = Cursor::new(key.as_slice());
+        let _version = i16::decode(&mut buffer).ok()?;
+        match i16::decode(&mut buffer).ok()? {
+            0 => Some(ControlRecordType::Abort),
+            1 => Some(ControlRecordType::Commit),
+            _ => None,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.attributes.bits() & COMPRESSION_CODEC_MASK != 0
+    }
+
+    // 替换 attributes 中的压缩算法并立即压缩 records
+    // 无法压缩时 (例如没有启用对应的 feature) 保持不压缩
+    pub fn set_compression(&mut self, codec: MetadataAttributes) {
+        self.attributes = MetadataAttributes::from_bits_retain(
+            (self.attributes.bits() & !COMPRESSION_CODEC_MASK)
+                | (codec.bits() & COMPRESSION_CODEC_MASK),
+        );
+        self.compressed_records = None;
+        self.compressed_records = self.compress_records();
+        if self.compressed_records.is_none() {
+            self.attributes = MetadataAttributes::from_bits_retain(
+                self.attributes.bits() & !COMPRESSION_CODEC_MASK,
+            );
+        }
+    }
+
+    // buffer 只包含 records 的个数和 record, 个数多于实际的 record 时在 batch 结尾停止
+    fn decode_records(&self, buffer: &mut Cursor<&[u8]>) -> DecodeResult<Array<Record>> {
+        let count = i32::decode(buffer)?;
+        if count < 0 {
+            return Ok(Array::new(None));
+        }
+        if !self.is_compressed() {
+            return self.decode_record_list(count as usize, buffer);
+        }
+        // 个数之后直到 batch 结束都是压缩后的 records, 解压后同样不能有多余的字节
+        let bytes: &[u8] = buffer.get_ref();
+        let decompressed =
+            decompress_records(self.attributes, &bytes[buffer.position() as usize..])?;
+        buffer.set_position(bytes.len() as u64);
+        decode_bounded_with(
+            &mut Cursor::new(decompressed.as_slice()),
+            decompressed.len(),
+            |records_buffer| self.decode_record_list(count as usize, records_buffer),
+        )
+    }
+
+    // encode 时使用的压缩后的 records, 优先使用 set_compression 时已经压缩好的结果
+    fn encoded_compressed_records(&self) -> Option<Cow<'_, [u8]>> {
+        if !self.is_compressed() {
+            return None;
+        }
+        match &self.compressed_records {
+            Some(compressed_records) => Some(Cow::Borrowed(compressed_records)),
+            None => self.compress_records().map(Cow::Owned),
+        }
+    }
+
+    // 按 attributes 中的压缩算法压缩个数之后的所有 record, 不需要压缩或无法压缩时返回 None
+    fn compress_records(&self) -> Option<Vec<u8>> {
+        if !self.is_compressed() {
+            return None;
+        }
+        let records = self.records.as_ref()?;
+        let mut records_bytes = vec![];
+        for record in records.iter() {
+            record.encode_into(&mut records_bytes);
+        }
+        let compressed_records = compress_records(self.attributes, &records_bytes);
+        if compressed_records.is_none() {
+            tracing::warn!(
+                "Record batch at offset {} is written uncompressed, attributes: {:#06x}",
+                self.base_offset,
+                self.attributes.bits()
+            );
+        }
+        compressed_records
+    }
+
+    fn decode_record_list(
+        &self,
+        count: usize,
+        buffer: &mut Cursor<&[u8]>,
+    ) -> DecodeResult<Array<Record>> {
+        let mut records = vec![];
+        while records.len() < count && buffer.has_remaining() {
+            records.push(Record::decode(buffer)?);
+        }
+        if records.len() < count {
+            tracing::warn!(
+                "Record batch at offset {} declares {} records, but only {} fit in the batch",
+                self.base_offset,
+                count,
+                records.len()
+            );
+        }
+        Ok(Array::from_vec(records))
+    }
+
+    // LogAppendTime 时使用 broker 接收的时间覆盖 record 的时间戳
+    pub fn apply_timestamp_type(&mut self, timestamp_type: TimestampType, append_time: i64) {
+        match timestamp_type {
+            TimestampType::CreateTime => {
+                self.attributes.remove(MetadataAttributes::TIMESTAMP_TYPE);
+            }
+            TimestampType::LogAppendTime => {
+                self.attributes.insert(MetadataAttributes::TIMESTAMP_TYPE);
+                self.max_timestamp = append_time;
+            }
+        }
+    }
+}
+
+impl Encode for RecordBatch {
+    // batch_length, last_offset_delta 和 crc 根据实际 encode 的内容计算,
+    // 字段中的值只反映 decode 时读到的内容
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        let compressed_records = self.encoded_compressed_records();
+        // 无法压缩时不压缩 records, 同时清除 attributes 中的压缩算法, 保证输出可以被 decode
+        let attributes = if compressed_records.is_none()
+            && self.is_compressed()
+            && self.records.as_ref().is_some()
+        {
+            MetadataAttributes::from_bits_retain(self.attributes.bits() & !COMPRESSION_CODEC_MASK)
+        } else {
+            self.attributes
+        };
+        self.base_offset.encode_into(out);
+        0_i32.encode_into(out); // batch_length, 最后回填
+        self.partition_leader_epoch.encode_into(out);
+        self.magic_byte.encode_into(out);
+        0_i32.encode_into(out); // crc, 最后回填
+        attributes.encode_into(out);
+        let record_count = self.records.as_ref().map_or(0, |records| records.len());
+        (record_count as i32 - 1).encode_into(out); // last_offset_delta
+        self.base_timestamp.encode_into(out);
+        self.max_timestamp.encode_into(out);
+        self.producer_id.encode_into(out);
+        self.producer_epoch.encode_into(out);
+        self.base_sequence.encode_into(out);
+        match compressed_records {
+            // records 的个数不压缩
+            Some(compressed_records) => {
+                (record_count as i32).encode_into(out);
+                out.extend_from_slice(&compressed_records);
+            }
+            None => self.records.encode_into(out),
+        }
+
+        let batch_length = (out.len() - start - LOG_OVERHEAD) as i32;
+        out[start + 8..start + LOG_OVERHEAD].copy_from_slice(&batch_length.to_be_bytes());
+        let crc = crc32c(&out[start + RECORD_BATCH_CRC_START..]);
+        out[start + RECORD_BATCH_CRC_START - 4..start + RECORD_BATCH_CRC_START]
+            .copy_from_slice(&crc.to_be_bytes());
+    }
+
+    fn encoded_len(&self) -> usize {
+        match (self.is_compressed(), &self.compressed_records) {
+            // records 的个数不压缩
+            (true, Some(compressed_records)) => {
+                RECORD_BATCH_RECORDS_START + 4 + compressed_records.len()
+            }
+            // 还没有压缩时只能压缩后才知道长度
+            (true, None) => self.encode().len(),
+            (false, _) => RECORD_BATCH_RECORDS_START + self.records.encoded_len(),
+        }
+    }
+}
+
+impl Decode for RecordBatch {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        check_batch_crc(buffer)?;
+        let mut record_batch = RecordBatch {
+            base_offset: i64::decode(buffer)?,
+            batch_length: i32::decode(buffer)?,
+            partition_leader_epoch: i32::decode(buffer)?,
+            magic_byte: i8::decode(buffer)?,
+            crc: i32::decode(buffer)?,
+            attributes: MetadataAttributes::decode(buffer)?,
+            last_offset_delta: i32::decode(buffer)?,
+            base_timestamp: i64::decode(buffer)?,
+            max_timestamp: i64::decode(buffer)?,
+            producer_id: i64::decode(buffer)?,
+            producer_epoch: i16::decode(buffer)?,
+            base_sequence: i32::decode(buffer)?,
+            records: Array::new(None),
+            compressed_records: None,
+        };
+        // records 只能在 batch_length 范围内 decode, 个数字段不可信时也不会越过 batch 的边界
+        let records_size = (LOG_OVERHEAD + record_batch.batch_length.max(0) as usize)
+            .checked_sub(RECORD_BATCH_RECORDS_START)
+            .ok_or_else(|| {
+                DecodeError::Other(
+                    format!("Invalid batch length: {}", record_batch.batch_length).into(),
+                )
+            })?;
+        record_batch.records = decode_bounded_with(buffer, records_size, |records_buffer| {
+            record_batch.decode_records(records_buffer)
+        })?;
+        // 不一致说明 batch 被截断或已损坏
+        if record_batch.records.is_some() {
+            record_batch
+                .check_record_count()
+                .map_err(|err| DecodeError::Other(err.into()))?;
+        }
+        Ok(record_batch)
+    }
+}
+
+// 在 decode header 之前按原始字节检查 crc, crc 范围内的字段 (例如 attributes) 损坏时也能跳过整个 batch
+// batch 不完整或 batch_length 无效时交给 RecordBatch::decode 返回对应的错误
+fn check_batch_crc(buffer: &mut Cursor<&[u8]>) -> DecodeResult<()> {
+    let start = buffer.position() as usize;
+    let bytes: &[u8] = buffer.get_ref();
+    let Some(header) = bytes.get(start..start + RECORD_BATCH_CRC_START) else {
+        return Ok(());
+    };
+    let batch_length = i32::decode(&mut Cursor::new(&header[8..]))?;
+    let batch_end = start + LOG_OVERHEAD + batch_length.max(0) as usize;
+    let Some(batch_bytes) = bytes
+        .get(start..batch_end)
+        .filter(|batch_bytes| batch_bytes.len() >= RECORD_BATCH_RECORDS_START)
+    else {
+        return Ok(());
+    };
+    let expected = i32::decode(&mut Cursor::new(&header[RECORD_BATCH_CRC_START - 4..]))? as u32;
+    let crc = crc32c(&batch_bytes[RECORD_BATCH_CRC_START..]);
+    if crc != expected {
+        // 与 records 无法解析时一样, 跳过整个 batch
+        buffer.set_position(batch_end as u64);
+        return Err(DecodeError::Other(
+            format!(
+                "Record batch has invalid crc {:#010x}, expected {:#010x}",
+                expected, crc
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlRecordType {
+    Abort,
+    Commit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampType {
+    #[default]
+    CreateTime,
+    LogAppendTime,
+}
+
+impl TimestampType {
+    pub fn from_config(value: &str) -> Option<Self> {
+        match value {
+            "CreateTime" => Some(TimestampType::CreateTime),
+            "LogAppendTime" => Some(TimestampType::LogAppendTime),
+            _ => None,
+        }
+    }
+}
+
+// attributes 的低 3 位表示压缩算法
+pub const COMPRESSION_CODEC_MASK: u16 = 0b111;
+
+// 压缩 records 部分, 其他压缩算法在这里添加, 与 decompress_records 对应
+fn compress_records(attributes: MetadataAttributes, bytes: &[u8]) -> Option<Vec<u8>> {
+    // 没有启用任何压缩算法时 bytes 不会被使用
+    #[cfg(not(feature = "gzip"))]
+    let _ = bytes;
+    match attributes.bits() & COMPRESSION_CODEC_MASK {
+        #[cfg(feature = "gzip")]
+        codec if codec == MetadataAttributes::GZIP.bits() => {
+            use std::io::Write;
+
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(bytes)
+                .expect("Failed to compress records into memory");
+            Some(
+                encoder
+                    .finish()
+                    .expect("Failed to compress records into memory"),
+            )
+        }
+        _ => None,
+    }
+}
+
+// 解压 records 部分, 其他压缩算法在这里添加
+fn decompress_records(attributes: MetadataAttributes, bytes: &[u8]) -> DecodeResult<Vec<u8>> {
+    // 没有启用任何压缩算法时 bytes 不会被使用
+    #[cfg(not(feature = "gzip"))]
+    let _ = bytes;
+    match attributes.bits() & COMPRESSION_CODEC_MASK {
+        #[cfg(feature = "gzip")]
+        codec if codec == MetadataAttributes::GZIP.bits() => {
+            let mut decompressed = vec![];
+            flate2::read::GzDecoder::new(bytes)
+                .read_to_end(&mut decompressed)
+                .map_err(|err| {
+                    DecodeError::Other(format!("Failed to decompress gzip records: {}", err).into())
+                })?;
+            Ok(decompressed)
+        }
+        _ => Err(DecodeError::Other(
+            format!(
+                "Compressed record batch is not supported, attributes: {:#06x}",
+                attributes.bits()
+            )
+            .into(),
+        )),
     }
 }
 
@@ -766,8 +1709,12 @@ bitflags! {
 }
 
 impl Encode for MetadataAttributes {
-    fn encode(&self) -> Vec<u8> {
-        self.bits().encode()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.bits().encode_into(out);
+    }
+
+    fn encoded_len(&self) -> usize {
+        mem::size_of::<u16>()
     }
 }
 
@@ -786,6 +1733,7 @@ impl Decode for MetadataAttributes {
 pub struct RecordType;
 
 impl RecordType {
+    pub const REGISTER_BROKER_RECORD: i8 = 0x00;
     pub const TOPIC_RECORD: i8 = 0x02;
     pub const PARITION_RECORD: i8 = 0x03;
     pub const FEATURE_LEVEL_RECORD: i8 = 0x0c;
@@ -793,13 +1741,13 @@ impl RecordType {
 
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Record {
-    pub length: VarInt, // signed
-    pub attributes: i8,
-    pub timestamp_delta: VarLong,
-    pub offset_delta: VarInt,
-    pub key: RecordKey,
-    pub value: RecordValue,
-    pub headers_array_count: CompactArray<RecordHeader>,
+    pub length: VarInt,           // signed varint (zigzag), 不包含自身
+    pub attributes: i8,           // int8, 目前没有使用
+    pub timestamp_delta: VarLong, // signed varlong (zigzag)
+    pub offset_delta: VarInt,     // signed varint (zigzag)
+    pub key: RecordKey,           // signed varint (zigzag) 长度 + bytes
+    pub value: RecordValue,       // signed varint (zigzag) 长度 + bytes
+    pub headers: RecordHeaders,   // signed varint (zigzag) 个数 + headers
 }
 
 impl Record {
@@ -823,38 +1771,48 @@ impl RecordKey {
     }
 }
 
-impl Encode for RecordKey {
-    fn encode(&self) -> Vec<u8> {
-        match &self.inner {
-            None => vec![0x01],
-            Some(array) => {
-                let mut encode_res = VarInt::from_i64(array.len() as i64).into_bytes();
-                encode_res.extend_from_slice(array);
-                encode_res
-            }
+// record 内部的长度都是 signed varint (zigzag), -1 表示 null,
+// 与 Compact* 类型使用的 unsigned varint (长度 +1, 0 表示 null) 不同
+fn encode_varint_bytes(bytes: Option<&[u8]>, out: &mut Vec<u8>) {
+    match bytes {
+        None => VarInt::from_i64(-1).encode_into(out),
+        Some(bytes) => {
+            VarInt::from_i64(bytes.len() as i64).encode_into(out);
+            out.extend_from_slice(bytes);
         }
     }
 }
 
+fn decode_varint_bytes(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Option<Vec<u8>>> {
+    let length = VarInt::decode(buffer)?.as_i64();
+    if length >= 0 {
+        check_array_length(length as u64, buffer)?;
+        let mut decode_res = vec![0_u8; length as usize];
+        buffer.read_exact(&mut decode_res)?;
+        Ok(Some(decode_res))
+    } else {
+        Ok(None)
+    }
+}
+
+impl Encode for RecordKey {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_varint_bytes(self.inner.as_deref(), out);
+    }
+}
+
 impl Decode for RecordKey {
     fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_i64();
-        let inner = if length >= 0 {
-            let mut decode_res = vec![0_u8; length as usize];
-            buffer.read_exact(&mut decode_res)?;
-            Some(decode_res)
-        } else {
-            None
-        };
-        Ok(RecordKey::new(inner))
+        Ok(RecordKey::new(decode_varint_bytes(buffer)?))
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum RecordValue {
+    RegisterBroker(RegisterBrokerRecord),
     Topic(TopicRecord),
     Partition(ParitionRecord),
     FeatureLevel(FeatureLevelRecord),
@@ -865,34 +1823,35 @@ impl RecordValue {
     pub fn is_unknown(&self) -> bool {
         matches!(self, RecordValue::Unknown(_))
     }
+
+    // 不包含长度前缀
+    fn payload_len(&self) -> usize {
+        match self {
+            RecordValue::RegisterBroker(record) => record.encoded_len(),
+            RecordValue::Topic(record) => record.encoded_len(),
+            RecordValue::Partition(record) => record.encoded_len(),
+            RecordValue::FeatureLevel(record) => record.encoded_len(),
+            RecordValue::Unknown(record_encode) => record_encode.len(),
+        }
+    }
 }
 
+// value 的长度为 signed varint (zigzag)
 impl Encode for RecordValue {
-    fn encode(&self) -> Vec<u8> {
-        match &self {
-            RecordValue::Topic(record) => {
-                let mut record_encode = record.encode();
-                let mut encode_res = VarInt::from_i64(record_encode.len() as i64).into_bytes();
-                encode_res.append(&mut record_encode);
-                encode_res
-            }
-            RecordValue::Partition(record) => {
-                let mut record_encode = record.encode();
-                let mut encode_res = VarInt::from_i64(record_encode.len() as i64).into_bytes();
-                encode_res.append(&mut record_encode);
-                encode_res
-            }
-            RecordValue::FeatureLevel(record) => {
-                let mut record_encode = record.encode();
-                let mut encode_res = VarInt::from_i64(record_encode.len() as i64).into_bytes();
-                encode_res.append(&mut record_encode);
-                encode_res
-            }
-            RecordValue::Unknown(record_encode) => {
-                let mut encode_res = VarInt::from_i64(record_encode.len() as i64).into_bytes();
-                encode_res.extend_from_slice(record_encode);
-                encode_res
-            }
+    fn encoded_len(&self) -> usize {
+        let payload_len = self.payload_len();
+        VarInt::from_i64(payload_len as i64).encoded_len() + payload_len
+    }
+
+    // 先根据 encoded_len 写入长度前缀, 再把 payload 直接写在后面
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        VarInt::from_i64(self.payload_len() as i64).encode_into(out);
+        match self {
+            RecordValue::RegisterBroker(record) => record.encode_into(out),
+            RecordValue::Topic(record) => record.encode_into(out),
+            RecordValue::Partition(record) => record.encode_into(out),
+            RecordValue::FeatureLevel(record) => record.encode_into(out),
+            RecordValue::Unknown(record_encode) => out.extend_from_slice(record_encode),
         }
     }
 }
@@ -902,29 +1861,45 @@ impl Decode for RecordValue {
     where
         Self: Sized,
     {
-        let value_length = VarInt::decode(buffer)?;
-        let _frame_version = i8::decode(buffer)?;
-        let record_type = i8::decode(buffer)?;
-
-        buffer.seek_relative(-2).expect("Failed to seek");
-        let position = buffer.position();
-
-        let record_value = match parse_known_record(record_type, buffer) {
-            Ok(record_value) => record_value,
+        let value_length = VarInt::decode(buffer)?.as_i64();
+        if value_length < 0 {
+            return Err(DecodeError::Other(
+                format!("Invalid record value length: {}", value_length).into(),
+            ));
+        }
+        let value_length = value_length as usize;
+        if buffer.remaining() < value_length {
+            return Err(DecodeError::incomplete_needed(
+                value_length - buffer.remaining(),
+            ));
+        }
+        let value_start = buffer.position() as usize;
+        let bytes: &[u8] = buffer.get_ref();
+        let record_encode = &bytes[value_start..value_start + value_length];
+
+        // 只在 value 的范围内解析, 普通消息和 control record 的 value 不是 metadata record,
+        // 解析失败或没有恰好用完 value 时保留原始字节, 保证重新 encode 后内容不变
+        let Some(record_type) = record_encode.get(1).copied() else {
+            buffer.advance(value_length);
+            return Ok(RecordValue::Unknown(record_encode.to_vec()));
+        };
+        match decode_bounded_with(buffer, value_length, |value_buffer| {
+            parse_known_record(record_type as i8, value_buffer)
+        }) {
+            Ok(record_value) => Ok(record_value),
             Err(err) => {
-                tracing::error!("{}", err);
-                buffer.set_position(position);
-                let mut record_encode = vec![0x00; value_length.as_i64() as usize];
-                buffer.read_exact(&mut record_encode)?;
-                RecordValue::Unknown(record_encode)
+                tracing::debug!("{}", err);
+                Ok(RecordValue::Unknown(record_encode.to_vec()))
             }
-        };
-        Ok(record_value)
+        }
     }
 }
 
 fn parse_known_record(record_type: i8, buffer: &mut Cursor<&[u8]>) -> DecodeResult<RecordValue> {
     match record_type {
+        RecordType::REGISTER_BROKER_RECORD => Ok(RecordValue::RegisterBroker(
+            RegisterBrokerRecord::decode(buffer)?,
+        )),
         RecordType::TOPIC_RECORD => Ok(RecordValue::Topic(TopicRecord::decode(buffer)?)),
         RecordType::PARITION_RECORD => Ok(RecordValue::Partition(ParitionRecord::decode(buffer)?)),
         RecordType::FEATURE_LEVEL_RECORD => Ok(RecordValue::FeatureLevel(
@@ -936,7 +1911,117 @@ fn parse_known_record(record_type: i8, buffer: &mut Cursor<&[u8]>) -> DecodeResu
     }
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct BrokerEndpoint {
+    pub name: CompactString,
+    pub host: CompactString,
+    pub port: u16,
+    pub security_protocol: i16,
+    pub tag_buffers: TagBuffer,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct BrokerFeature {
+    pub name: CompactString,
+    pub min_supported_version: i16,
+    pub max_supported_version: i16,
+    pub tag_buffers: TagBuffer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterBrokerRecord {
+    pub frame_version: i8,
+    pub record_type: i8,
+    pub version: i8,
+    pub broker_id: i32,
+    pub is_migrating_zk_broker: bool, // version >= 2
+    pub incarnation_id: Uuid,
+    pub broker_epoch: i64,
+    pub end_points: CompactArray<BrokerEndpoint>,
+    pub features: CompactArray<BrokerFeature>,
+    pub rack: CompactNullableString,
+    pub fenced: bool,
+    pub in_controlled_shutdown: bool, // version >= 1
+    pub log_dirs: CompactArray<Uuid>, // version >= 3
+    pub tag_buffers: TagBuffer,
+}
+
+impl Encode for RegisterBrokerRecord {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.frame_version.encode_into(out);
+        self.record_type.encode_into(out);
+        self.version.encode_into(out);
+        self.broker_id.encode_into(out);
+        if self.version >= 2 {
+            self.is_migrating_zk_broker.encode_into(out);
+        }
+        self.incarnation_id.encode_into(out);
+        self.broker_epoch.encode_into(out);
+        self.end_points.encode_into(out);
+        self.features.encode_into(out);
+        self.rack.encode_into(out);
+        self.fenced.encode_into(out);
+        if self.version >= 1 {
+            self.in_controlled_shutdown.encode_into(out);
+        }
+        if self.version >= 3 {
+            self.log_dirs.encode_into(out);
+        }
+        self.tag_buffers.encode_into(out);
+    }
+}
+
+impl Decode for RegisterBrokerRecord {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let frame_version = i8::decode(buffer)?;
+        let record_type = i8::decode(buffer)?;
+        let version = i8::decode(buffer)?;
+        let broker_id = i32::decode(buffer)?;
+        let is_migrating_zk_broker = if version >= 2 {
+            bool::decode(buffer)?
+        } else {
+            false
+        };
+        let incarnation_id = Uuid::decode(buffer)?;
+        let broker_epoch = i64::decode(buffer)?;
+        let end_points = CompactArray::decode(buffer)?;
+        let features = CompactArray::decode(buffer)?;
+        let rack = CompactNullableString::decode(buffer)?;
+        let fenced = bool::decode(buffer)?;
+        let in_controlled_shutdown = if version >= 1 {
+            bool::decode(buffer)?
+        } else {
+            false
+        };
+        let log_dirs = if version >= 3 {
+            CompactArray::decode(buffer)?
+        } else {
+            CompactArray::empty()
+        };
+        let tag_buffers = TagBuffer::decode(buffer)?;
+        Ok(RegisterBrokerRecord {
+            frame_version,
+            record_type,
+            version,
+            broker_id,
+            is_migrating_zk_broker,
+            incarnation_id,
+            broker_epoch,
+            end_points,
+            features,
+            rack,
+            fenced,
+            in_controlled_shutdown,
+            log_dirs,
+            tag_buffers,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
 pub struct TopicRecord {
     pub frame_version: i8,
     pub record_type: i8,
@@ -946,7 +2031,7 @@ pub struct TopicRecord {
     pub tag_buffers: TagBuffer,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone)]
 pub struct ParitionRecord {
     pub frame_version: i8,
     pub record_type: i8,
@@ -960,29 +2045,193 @@ pub struct ParitionRecord {
     pub leader_id: i32,
     pub leader_epoch: i32,
     pub partition_epoch: i32,
-    pub directories: CompactArray<Directory>,
+    pub directories: CompactArray<Directory>, // version >= 1
     pub tag_buffers: TagBuffer,
 }
 
+impl ParitionRecord {
+    // version >= 2 时 eligible_leader_replicas 和 last_known_elr 以 tagged field 的形式出现
+    pub const ELIGIBLE_LEADER_REPLICAS_TAG: u32 = 1;
+    pub const LAST_KNOWN_ELR_TAG: u32 = 2;
+
+    pub fn eligible_leader_replicas(&self) -> DecodeResult<CompactArray<RepicaNode>> {
+        self.tagged_replica_nodes(Self::ELIGIBLE_LEADER_REPLICAS_TAG)
+    }
+
+    pub fn last_known_elr(&self) -> DecodeResult<CompactArray<RepicaNode>> {
+        self.tagged_replica_nodes(Self::LAST_KNOWN_ELR_TAG)
+    }
+
+    fn tagged_replica_nodes(&self, tag: u32) -> DecodeResult<CompactArray<RepicaNode>> {
+        if self.version < 2 {
+            return Ok(CompactArray::empty());
+        }
+        match self.tag_buffers.get(tag) {
+            None => Ok(CompactArray::empty()),
+            Some(data) => {
+                let replica_nodes = CompactArray::<RepicaNode>::decode(&mut Cursor::new(data))?;
+                // null 与空数组在 DescribeTopicPartitions 的响应中含义相同
+                if replica_nodes.is_none() {
+                    Ok(CompactArray::empty())
+                } else {
+                    Ok(replica_nodes)
+                }
+            }
+        }
+    }
+}
+
+impl Encode for ParitionRecord {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.frame_version.encode_into(out);
+        self.record_type.encode_into(out);
+        self.version.encode_into(out);
+        self.parition_id.encode_into(out);
+        self.topic_id.encode_into(out);
+        self.replica_nodes.encode_into(out);
+        self.isr_nodes.encode_into(out);
+        self.removing_replicas_nodes.encode_into(out);
+        self.adding_replicas_nodes.encode_into(out);
+        self.leader_id.encode_into(out);
+        self.leader_epoch.encode_into(out);
+        self.partition_epoch.encode_into(out);
+        if self.version >= 1 {
+            self.directories.encode_into(out);
+        }
+        self.tag_buffers.encode_into(out);
+    }
+}
+
+impl Decode for ParitionRecord {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let frame_version = i8::decode(buffer)?;
+        let record_type = i8::decode(buffer)?;
+        let version = i8::decode(buffer)?;
+        let parition_id = i32::decode(buffer)?;
+        let topic_id = Uuid::decode(buffer)?;
+        let replica_nodes = CompactArray::decode(buffer)?;
+        let isr_nodes = CompactArray::decode(buffer)?;
+        let removing_replicas_nodes = CompactArray::decode(buffer)?;
+        let adding_replicas_nodes = CompactArray::decode(buffer)?;
+        let leader_id = i32::decode(buffer)?;
+        let leader_epoch = i32::decode(buffer)?;
+        let partition_epoch = i32::decode(buffer)?;
+        let directories = if version >= 1 {
+            CompactArray::decode(buffer)?
+        } else {
+            CompactArray::empty()
+        };
+        let tag_buffers = TagBuffer::decode(buffer)?;
+        Ok(ParitionRecord {
+            frame_version,
+            record_type,
+            version,
+            parition_id,
+            topic_id,
+            replica_nodes,
+            isr_nodes,
+            removing_replicas_nodes,
+            adding_replicas_nodes,
+            leader_id,
+            leader_epoch,
+            partition_epoch,
+            directories,
+            tag_buffers,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Directory {
     id: Uuid,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
 pub struct FeatureLevelRecord {
-    frame_version: i8,
-    record_type: i8,
-    version: i8,
-    name: CompactString,
-    feature_level: i16,
-    tag_buffers: TagBuffer,
+    pub frame_version: i8,
+    pub record_type: i8,
+    pub version: i8,
+    pub name: CompactString,
+    pub feature_level: i16,
+    pub tag_buffers: TagBuffer,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+// headers 的个数也是 signed varint (zigzag), 不能用 CompactArray
+#[derive(Debug, Clone, Default)]
+pub struct RecordHeaders {
+    inner: Vec<RecordHeader>,
+}
+
+impl RecordHeaders {
+    pub fn new(inner: Vec<RecordHeader>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Deref for RecordHeaders {
+    type Target = Vec<RecordHeader>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Encode for RecordHeaders {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        VarInt::from_i64(self.inner.len() as i64).encode_into(out);
+        for header in self.inner.iter() {
+            header.encode_into(out);
+        }
+    }
+}
+
+impl Decode for RecordHeaders {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let length = VarInt::decode(buffer)?.as_i64();
+        if length < 0 {
+            return Err(DecodeError::Other(
+                format!("Invalid record headers count: {}", length).into(),
+            ));
+        }
+        check_array_length(length as u64, buffer)?;
+        let mut inner = Vec::with_capacity(length as usize);
+        for _ in 0..length {
+            inner.push(RecordHeader::decode(buffer)?);
+        }
+        Ok(RecordHeaders::new(inner))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RecordHeader {
-    key: CompactString,
-    value: CompactArray<u8>,
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+impl Encode for RecordHeader {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_varint_bytes(Some(self.key.as_bytes()), out);
+        encode_varint_bytes(self.value.as_deref(), out);
+    }
+}
+
+impl Decode for RecordHeader {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let key = decode_varint_bytes(buffer)?
+            .ok_or_else(|| DecodeError::Other("Record header key must not be null".into()))?;
+        let key = String::from_utf8(key)?;
+        let value = decode_varint_bytes(buffer)?;
+        Ok(RecordHeader { key, value })
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -995,6 +2244,10 @@ impl CompactRecords {
         Self { inner }
     }
 
+    pub fn get_inner(&self) -> &Option<Vec<RecordBatch>> {
+        &self.inner
+    }
+
     pub fn empty() -> Self {
         Self {
             inner: Some(vec![]),
@@ -1002,19 +2255,32 @@ impl CompactRecords {
     }
 }
 
+// 长度前缀只包含 record batch 的总长度
+fn records_len(array: &[RecordBatch]) -> usize {
+    array.iter().map(Encode::encoded_len).sum()
+}
+
 impl Encode for CompactRecords {
-    fn encode(&self) -> Vec<u8> {
+    fn encoded_len(&self) -> usize {
+        match &self.inner {
+            None => 1,
+            Some(array) => {
+                let records_len = records_len(array);
+                unsigned_varint_len((records_len + 1) as u64) + records_len
+            }
+        }
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            None => vec![0x00],
+            None => out.push(0x00),
             Some(array) => {
-                let mut records_encode: Vec<u8> = array
-                    .iter()
-                    .flat_map(|record_batch| record_batch.encode())
-                    .collect();
-                let mut encode_res =
-                    VarInt::from_u64((records_encode.len() + 1) as u64).into_bytes();
-                encode_res.append(&mut records_encode);
-                encode_res
+                // unsigned varint, 长度 + 1, 0 表示 null
+                let records_len = records_len(array);
+                VarInt::from_u64((records_len + 1) as u64).encode_into(out);
+                for record_batch in array.iter() {
+                    record_batch.encode_into(out);
+                }
             }
         }
     }
@@ -1025,16 +2291,8 @@ impl Decode for CompactRecords {
     where
         Self: Sized,
     {
-        let length = VarInt::decode(buffer)?.as_u64();
-        let inner = if length > 0 {
-            let mut inner_buffer = vec![0x00; (length - 1) as usize];
-            buffer.read_exact(&mut inner_buffer)?;
-            let mut inner_buffer = Cursor::new(inner_buffer.as_slice());
-            let mut record_batches = vec![];
-            while inner_buffer.has_remaining() {
-                record_batches.push(RecordBatch::decode(&mut inner_buffer)?);
-            }
-            Some(record_batches)
+        let inner = if let Some(length) = VarInt::decode(buffer)?.as_nullable_len()? {
+            Some(decode_bounded_with(buffer, length, decode_all_batches)?)
         } else {
             None
         };
@@ -1042,6 +2300,85 @@ impl Decode for CompactRecords {
     }
 }
 
+// 长度前缀之后的所有字节都是 record batch
+fn decode_all_batches(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Vec<RecordBatch>> {
+    let mut record_batches = vec![];
+    while buffer.has_remaining() {
+        record_batches.push(RecordBatch::decode(buffer)?);
+    }
+    Ok(record_batches)
+}
+
+// 非 flexible 版本 (例如 Fetch v11 及以前) 使用的 records, 长度前缀为 int32, -1 表示 null
+#[derive(Debug, Clone, Default)]
+pub struct Records {
+    inner: Option<Vec<RecordBatch>>,
+}
+
+impl Records {
+    pub fn new(inner: Option<Vec<RecordBatch>>) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_inner(&self) -> &Option<Vec<RecordBatch>> {
+        &self.inner
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            inner: Some(vec![]),
+        }
+    }
+}
+
+impl Encode for Records {
+    fn encoded_len(&self) -> usize {
+        4 + self.inner.as_deref().map_or(0, records_len)
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match &self.inner {
+            None => (-1_i32).encode_into(out),
+            Some(array) => {
+                let records_len = records_len(array);
+                if records_len > i32::MAX as usize {
+                    panic!(
+                        "Records length({}) is greater then i32::MAX({})",
+                        records_len,
+                        i32::MAX
+                    );
+                }
+                (records_len as i32).encode_into(out);
+                for record_batch in array.iter() {
+                    record_batch.encode_into(out);
+                }
+            }
+        }
+    }
+}
+
+impl Decode for Records {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let inner = match i32::decode(buffer)? {
+            -1 => None,
+            length if length < 0 => {
+                return Err(DecodeError::Other(
+                    format!("Records's length cannot be negative: {}", length).into(),
+                ))
+            }
+            length => Some(decode_bounded_with(
+                buffer,
+                length as usize,
+                decode_all_batches,
+            )?),
+        };
+        Ok(Records::new(inner))
+    }
+}
+
 pub fn display_bytes(bytes: &[u8]) -> String {
     let mut s = String::new();
 
@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+
+use crate::{common_struct::TimestampType, metadata_log::topic_exists};
+
+pub const CONFIG_STORE_FILE: &str = "/tmp/kraft-combined-logs/__configs.bin";
+pub const MESSAGE_TIMESTAMP_TYPE_CONFIG: &str = "message.timestamp.type";
+
+pub type ConfigResource = (i8, String);
+pub type ConfigMap = HashMap<ConfigResource, HashMap<String, String>>;
+
+lazy_static! {
+    pub static ref CONFIG_STORE: Arc<Mutex<ConfigMap>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+// 修改 CONFIG_STORE 后只标记为 dirty, 由定时任务统一写入磁盘
+static CONFIG_STORE_DIRTY: AtomicBool = AtomicBool::new(false);
+
+pub fn mark_config_store_dirty() {
+    CONFIG_STORE_DIRTY.store(true, Ordering::Release);
+}
+
+pub struct ResourceType;
+
+impl ResourceType {
+    pub const UNKNOWN: i8 = 0;
+    pub const TOPIC: i8 = 2;
+    pub const BROKER: i8 = 4;
+}
+
+pub fn resource_exists(resource_type: i8, resource_name: &str) -> bool {
+    match resource_type {
+        ResourceType::TOPIC => topic_exists(resource_name),
+        ResourceType::BROKER => true,
+        _ => false,
+    }
+}
+
+pub fn get_config(resource_type: i8, resource_name: &str, key: &str) -> Option<String> {
+    CONFIG_STORE
+        .lock()
+        .expect("Failed to get CONFIG_STORE lock")
+        .get(&(resource_type, resource_name.to_string()))
+        .and_then(|configs| configs.get(key).cloned())
+}
+
+pub fn topic_timestamp_type(topic: &str) -> TimestampType {
+    match get_config(ResourceType::TOPIC, topic, MESSAGE_TIMESTAMP_TYPE_CONFIG) {
+        Some(value) => TimestampType::from_config(&value).unwrap_or_else(|| {
+            tracing::warn!(
+                "Unknown {} for topic {}: {}",
+                MESSAGE_TIMESTAMP_TYPE_CONFIG,
+                topic,
+                value
+            );
+            TimestampType::default()
+        }),
+        None => TimestampType::default(),
+    }
+}
+
+pub fn load_config_store() -> Result<(), String> {
+    let path = Path::new(CONFIG_STORE_FILE);
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = fs::read(path).map_err(|err| err.to_string())?;
+    let (config_map, _): (ConfigMap, usize) =
+        bincode::serde::decode_from_slice(&content, bincode::config::standard())
+            .map_err(|err| err.to_string())?;
+    *CONFIG_STORE
+        .lock()
+        .expect("Failed to get CONFIG_STORE lock") = config_map;
+    Ok(())
+}
+
+pub fn persist_config_store(config_map: &ConfigMap) -> Result<(), String> {
+    let content = bincode::serde::encode_to_vec(config_map, bincode::config::standard())
+        .map_err(|err| err.to_string())?;
+    fs::write(CONFIG_STORE_FILE, content).map_err(|err| err.to_string())
+}
+
+pub fn flush_config_store() -> Result<(), String> {
+    // 持有锁期间写入, 保证不会和修改同时进行
+    let config_store = CONFIG_STORE
+        .lock()
+        .expect("Failed to get CONFIG_STORE lock");
+    if CONFIG_STORE_DIRTY.swap(false, Ordering::AcqRel) {
+        if let Err(err) = persist_config_store(&config_store) {
+            mark_config_store_dirty();
+            return Err(err);
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,11 @@
-use std::io::Cursor;
+use std::{io::Cursor, sync::Mutex};
 
-use crate::{decode::DecodeResult, response_message::ResponseMessage};
+use crate::{
+    acl::Session, broker_config::BROKER_CONFIG, decode::DecodeResult,
+    response_message::ResponseMessage,
+};
 use bytes::{Buf, BytesMut};
+use lazy_static::lazy_static;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufWriter},
     net::TcpStream,
@@ -12,46 +16,144 @@ use crate::{
     request_message::RequestMessage,
 };
 
+pub const CONNECTION_BUFFER_CAPACITY: usize = 4096;
+// 处理过大请求后容量变大的缓冲区不放回池中, 避免一直占用内存
+const MAX_POOLED_BUFFER_CAPACITY: usize = 1 << 20;
+
+lazy_static! {
+    // 只有指定 --buffer-pool-size 时才复用缓冲区
+    pub static ref BUFFER_POOL: Option<BufferPool> =
+        BROKER_CONFIG.buffer_pool_size.map(BufferPool::new);
+}
+
+// 连接关闭后回收读缓冲区, 减少频繁建立连接时的内存分配
+pub struct BufferPool {
+    buffers: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+        }
+    }
+
+    pub fn acquire(&self) -> BytesMut {
+        self.buffers
+            .lock()
+            .expect("Failed to get buffer pool lock")
+            .pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(CONNECTION_BUFFER_CAPACITY))
+    }
+
+    pub fn release(&self, mut buffer: BytesMut) {
+        if buffer.capacity() > MAX_POOLED_BUFFER_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        let mut buffers = self.buffers.lock().expect("Failed to get buffer pool lock");
+        if buffers.len() < self.max_buffers {
+            buffers.push(buffer);
+        }
+    }
+}
+
 pub struct Connection {
     socket: BufWriter<TcpStream>,
     buffer: BytesMut,
+    // 上一次解析时 decode 给出的还需要的字节数, 读够之前不再重新解析
+    needed: usize,
+    session: Session,
 }
 
 impl Connection {
     pub fn new(socket: TcpStream) -> Self {
+        Self::with_buffer(socket, BytesMut::with_capacity(CONNECTION_BUFFER_CAPACITY))
+    }
+
+    // 使用已有的缓冲区, 缓冲区中残留的内容会被清空
+    pub fn with_buffer(socket: TcpStream, mut buffer: BytesMut) -> Self {
+        buffer.clear();
+        buffer.reserve(CONNECTION_BUFFER_CAPACITY);
         Connection {
             socket: BufWriter::new(socket),
-            buffer: BytesMut::with_capacity(4096),
+            buffer,
+            needed: 0,
+            session: Session::default(),
         }
     }
 
+    // 连接关闭后取回缓冲区, 放回 BUFFER_POOL 复用
+    pub fn into_buffer(self) -> BytesMut {
+        self.buffer
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
     pub async fn read_request(&mut self) -> crate::Result<Option<RequestMessage>> {
         loop {
             if let Some(request) = self.parse_request()? {
                 return Ok(Some(request));
-            } else if 0 == self.socket.read_buf(&mut self.buffer).await? {
+            } else if !self.read_needed().await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    // 至少读取 needed 个字节, 连接正常关闭时返回 false
+    async fn read_needed(&mut self) -> crate::Result<bool> {
+        let target = self.buffer.len() + self.needed.max(1);
+        self.buffer.reserve(target - self.buffer.len());
+        while self.buffer.len() < target {
+            if 0 == self.socket.read_buf(&mut self.buffer).await? {
                 if self.buffer.is_empty() {
-                    return Ok(None);
+                    return Ok(false);
                 } else {
                     return Err("connection reset by peer".into());
                 }
             }
         }
+        Ok(true)
     }
 
-    fn parse_request(&mut self) -> DecodeResult<Option<RequestMessage>> {
-        let mut buffer = Cursor::new(self.buffer.as_ref());
-        match RequestMessage::decode(&mut buffer) {
-            Ok(request) => {
-                let pos = buffer.position() as usize;
-                self.buffer.advance(pos);
-                Ok(Some(request))
+    fn update_needed<T>(&mut self, result: DecodeResult<T>) -> DecodeResult<Option<T>> {
+        match result {
+            Ok(message) => {
+                self.needed = 0;
+                Ok(Some(message))
+            }
+            Err(err @ DecodeError::Incomplete { .. }) => {
+                self.needed = err.needed().unwrap_or(0);
+                Ok(None)
             }
-            Err(DecodeError::Incomplete(_err)) => Ok(None),
             Err(err) => Err(err),
         }
     }
 
+    // 不读 socket, 只解析缓冲区中已经完整的请求, 用于批量处理客户端连续发送的请求
+    pub fn read_buffered_requests(&mut self) -> DecodeResult<Vec<RequestMessage>> {
+        let mut requests = vec![];
+        while let Some(request) = self.parse_request()? {
+            requests.push(request);
+        }
+        Ok(requests)
+    }
+
+    fn parse_request(&mut self) -> DecodeResult<Option<RequestMessage>> {
+        let mut buffer = Cursor::new(self.buffer.as_ref());
+        let result = RequestMessage::decode(&mut buffer);
+        let pos = buffer.position() as usize;
+        let request = self.update_needed(result)?;
+        if request.is_some() {
+            self.buffer.advance(pos);
+        }
+        Ok(request)
+    }
+
     pub async fn write_request(&mut self, response: &mut RequestMessage) -> crate::Result<()> {
         let encode_request = response.as_bytes();
         self.socket.write_all(&encode_request).await?;
@@ -66,28 +168,35 @@ impl Connection {
         loop {
             if let Some(response) = self.parse_response(request_api_key)? {
                 return Ok(Some(response));
-            } else if 0 == self.socket.read_buf(&mut self.buffer).await? {
-                if self.buffer.is_empty() {
-                    return Ok(None);
-                } else {
-                    return Err("connection reset by peer".into());
-                }
+            } else if !self.read_needed().await? {
+                return Ok(None);
             }
         }
     }
 
     fn parse_response(&mut self, request_api_key: i16) -> DecodeResult<Option<ResponseMessage>> {
         let mut buffer = Cursor::new(self.buffer.as_ref());
-        match ResponseMessage::decode(&mut buffer, request_api_key) {
-            Ok(response) => Ok(Some(response)),
-            Err(DecodeError::Incomplete(_err)) => Ok(None),
-            Err(err) => Err(err),
-        }
+        let result = ResponseMessage::decode(&mut buffer, request_api_key);
+        self.update_needed(result)
+    }
+
+    pub async fn write_response(&mut self, response: &mut ResponseMessage) -> crate::Result<usize> {
+        let resp_bytes = self.write_response_unflushed(response).await?;
+        self.flush().await?;
+        Ok(resp_bytes)
     }
 
-    pub async fn write_response(&mut self, response: &mut ResponseMessage) -> crate::Result<()> {
+    // 只写入 BufWriter, 需要调用 flush 才会发送
+    pub async fn write_response_unflushed(
+        &mut self,
+        response: &mut ResponseMessage,
+    ) -> crate::Result<usize> {
         let encode_response = response.as_bytes();
         self.socket.write_all(&encode_response).await?;
+        Ok(encode_response.len())
+    }
+
+    pub async fn flush(&mut self) -> crate::Result<()> {
         self.socket.flush().await?;
         Ok(())
     }
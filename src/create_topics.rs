@@ -0,0 +1,264 @@
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    broker_config::BROKER_CONFIG,
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    decode::Decode,
+    describe_topic_partitions::COORDINATOR_LOAD_IN_PROGRESS,
+    encode::Encode,
+    metadata_log::{create_topic, is_metadata_ready, topic_exists},
+    produce::KAFKA_STORAGE_ERROR,
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+pub const TOPIC_ALREADY_EXISTS_ERROR: i16 = 36;
+pub const INVALID_PARTITIONS_ERROR: i16 = 37;
+pub const INVALID_REPLICATION_FACTOR_ERROR: i16 = 38;
+
+// num_partitions, replication_factor 为 -1 时使用 broker 的默认值
+const DEFAULT_FROM_BROKER: i32 = -1;
+
+lazy_static! {
+    pub static ref CREATE_TOPICS_API_INFO: ApiKey = ApiKey::new(19, 7, 7, TagBuffer::default());
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreateTopicsRequestBodyV7 {
+    topics: CompactArray<CreatableTopic>,
+    timeout_ms: Millis,
+    validate_only: bool,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreatableTopic {
+    name: CompactString,
+    num_partitions: i32,
+    replication_factor: i16,
+    assignments: CompactArray<CreatableReplicaAssignment>,
+    configs: CompactArray<CreatableTopicConfig>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreatableReplicaAssignment {
+    partition_index: i32,
+    broker_ids: CompactArray<i32>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreatableTopicConfig {
+    name: CompactString,
+    value: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreateTopicsResponseBodyV7 {
+    throttle_time_ms: Millis,
+    topics: CompactArray<CreatableTopicResult>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreatableTopicResult {
+    name: CompactString,
+    topic_id: Uuid,
+    error_code: i16,
+    error_message: CompactNullableString,
+    num_partitions: i32,
+    replication_factor: i16,
+    configs: CompactArray<CreatableTopicConfigs>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct CreatableTopicConfigs {
+    name: CompactString,
+    value: CompactNullableString,
+    read_only: bool,
+    config_source: i8,
+    is_sensitive: bool,
+    tag_buffer: TagBuffer,
+}
+
+impl CreateTopicsResponseBodyV7 {
+    // 请求的每个 topic 都返回同一个错误码
+    pub fn error(body: &CreateTopicsRequestBodyV7, error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            topics: body
+                .topics
+                .iter()
+                .flatten()
+                .map(|topic| CreatableTopicResult {
+                    name: topic.name.clone(),
+                    topic_id: Uuid::nil(),
+                    error_code,
+                    error_message: CompactNullableString::new(None),
+                    num_partitions: DEFAULT_FROM_BROKER,
+                    replication_factor: DEFAULT_FROM_BROKER as i16,
+                    configs: CompactArray::empty(),
+                    tag_buffer: TagBuffer::default(),
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn topics(&self) -> &CompactArray<CreatableTopicResult> {
+        &self.topics
+    }
+}
+
+impl CreatableTopicResult {
+    fn new_error(name: &CompactString, error_code: i16, error_message: String) -> Self {
+        Self {
+            name: name.clone(),
+            topic_id: Uuid::nil(),
+            error_code,
+            error_message: CompactNullableString::new(Some(error_message)),
+            num_partitions: DEFAULT_FROM_BROKER,
+            replication_factor: DEFAULT_FROM_BROKER as i16,
+            configs: CompactArray::empty(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn num_partitions(&self) -> i32 {
+        self.num_partitions
+    }
+}
+
+// 只有一个 broker, 指定了 assignments 时以其数量作为 partition 数
+fn num_partitions(topic: &CreatableTopic) -> Result<i32, (i16, String)> {
+    if let Some(assignments) = topic.assignments.as_ref().filter(|a| !a.is_empty()) {
+        if topic.num_partitions != DEFAULT_FROM_BROKER {
+            return Err((
+                INVALID_PARTITIONS_ERROR,
+                "Both numPartitions and replicaAssignments were set".to_string(),
+            ));
+        }
+        return Ok(assignments.len() as i32);
+    }
+    match topic.num_partitions {
+        DEFAULT_FROM_BROKER => Ok(BROKER_CONFIG.num_partitions),
+        n if n > 0 => Ok(n),
+        n => Err((
+            INVALID_PARTITIONS_ERROR,
+            format!("Number of partitions was set to an invalid non-positive value {n}"),
+        )),
+    }
+}
+
+fn create_one_topic(topic: &CreatableTopic, validate_only: bool) -> CreatableTopicResult {
+    let name = &topic.name;
+    if !is_metadata_ready() {
+        return CreatableTopicResult::new_error(
+            name,
+            COORDINATOR_LOAD_IN_PROGRESS,
+            "Metadata log is still loading".to_string(),
+        );
+    }
+    let num_partitions = match num_partitions(topic) {
+        Ok(n) => n,
+        Err((error_code, error_message)) => {
+            return CreatableTopicResult::new_error(name, error_code, error_message)
+        }
+    };
+    if topic.replication_factor != DEFAULT_FROM_BROKER as i16 && topic.replication_factor != 1 {
+        return CreatableTopicResult::new_error(
+            name,
+            INVALID_REPLICATION_FACTOR_ERROR,
+            format!(
+                "Replication factor {} is larger than the number of brokers 1",
+                topic.replication_factor
+            ),
+        );
+    }
+
+    let topic_id = if validate_only {
+        if topic_exists(name.as_str()) {
+            None
+        } else {
+            Some(Uuid::nil())
+        }
+    } else {
+        match create_topic(name.as_str(), num_partitions) {
+            Ok(topic_id) => topic_id,
+            Err(err) => {
+                tracing::error!("Failed to create topic {}: {}", name.as_str(), err);
+                return CreatableTopicResult::new_error(name, KAFKA_STORAGE_ERROR, err);
+            }
+        }
+    };
+    match topic_id {
+        Some(topic_id) => CreatableTopicResult {
+            name: name.clone(),
+            topic_id,
+            error_code: 0,
+            error_message: CompactNullableString::new(None),
+            num_partitions,
+            replication_factor: 1,
+            configs: CompactArray::empty(),
+            tag_buffer: TagBuffer::default(),
+        },
+        None => CreatableTopicResult::new_error(
+            name,
+            TOPIC_ALREADY_EXISTS_ERROR,
+            format!("Topic '{}' already exists.", name.as_str()),
+        ),
+    }
+}
+
+pub fn execute_create_topics(
+    header: &RequestHeaderV2,
+    body: &CreateTopicsRequestBodyV7,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < CREATE_TOPICS_API_INFO.min_version
+        || request_api_version > CREATE_TOPICS_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::CreateTopicsV7(CreateTopicsResponseBodyV7::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let topics = body
+        .topics
+        .iter()
+        .flatten()
+        .map(|topic| create_one_topic(topic, body.validate_only))
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::CreateTopicsV7(CreateTopicsResponseBodyV7 {
+            throttle_time_ms: Millis(0),
+            topics,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
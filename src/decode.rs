@@ -14,6 +14,15 @@ pub trait Decode {
     fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
     where
         Self: Sized;
+
+    // 按 api 版本 decode, 该版本中不存在的字段使用默认值, 与版本无关的类型等同于 decode
+    // 容器类型需要把 version 传给元素, 与 Encode::encode_versioned_into 对应
+    fn decode_versioned(buffer: &mut Cursor<&[u8]>, _version: i16) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::decode(buffer)
+    }
 }
 
 // 使用宏为所有整数类型实现 Encode
@@ -23,7 +32,9 @@ macro_rules! impl_decode_for_integers {
             impl Decode for $type {
                 fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self> {
                     if buffer.remaining() < std::mem::size_of::<$type>() {
-                        Err(DecodeError::Incomplete(None))
+                        Err(DecodeError::incomplete_needed(
+                            std::mem::size_of::<$type>() - buffer.remaining(),
+                        ))
                     } else {
                         paste! { Ok(buffer.[<get_ $type>]()) }
                     }
@@ -52,16 +63,112 @@ impl Decode for bool {
 
 #[derive(Debug)]
 pub enum DecodeError {
-    Incomplete(Option<Box<dyn std::error::Error + Send + Sync>>),
+    // needed: 至少还需要多少字节, None 表示未知
+    Incomplete {
+        needed: Option<usize>,
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    Incomplete,
+    Other,
+}
+
+impl DecodeError {
+    pub fn kind(&self) -> DecodeErrorKind {
+        match self {
+            DecodeError::Incomplete { .. } => DecodeErrorKind::Incomplete,
+            DecodeError::Other(_) => DecodeErrorKind::Other,
+        }
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.kind() == DecodeErrorKind::Incomplete
+    }
+
+    pub fn is_other(&self) -> bool {
+        self.kind() == DecodeErrorKind::Other
+    }
+
+    pub fn incomplete(source: Option<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        DecodeError::Incomplete {
+            needed: None,
+            source,
+        }
+    }
+
+    pub fn incomplete_needed(needed: usize) -> Self {
+        DecodeError::Incomplete {
+            needed: Some(needed),
+            source: None,
+        }
+    }
+
+    // 在错误信息中加上出错的字节位置, 不改变错误的类型
+    pub fn at_position(self, position: u64) -> Self {
+        match self {
+            DecodeError::Incomplete { needed, source } => DecodeError::Incomplete {
+                needed,
+                source: Some(
+                    match source {
+                        Some(err) => format!(
+                            ", at position {}: {}",
+                            position,
+                            err.to_string().trim_start_matches(", ")
+                        ),
+                        None => format!(", at position {}", position),
+                    }
+                    .into(),
+                ),
+            },
+            DecodeError::Other(err) => {
+                DecodeError::Other(format!("at position {}: {}", position, err).into())
+            }
+        }
+    }
+
+    // 在错误信息前加上正在 decode 的内容 (例如 derive 生成的字段名), 不改变错误的类型
+    // 嵌套时外层的 context 在前
+    pub fn context(self, context: impl Display) -> Self {
+        match self {
+            DecodeError::Incomplete { needed, source } => DecodeError::Incomplete {
+                needed,
+                source: Some(
+                    match source {
+                        Some(err) => format!(
+                            ", {}: {}",
+                            context,
+                            err.to_string().trim_start_matches(", ")
+                        ),
+                        None => format!(", {}", context),
+                    }
+                    .into(),
+                ),
+            },
+            DecodeError::Other(err) => DecodeError::Other(format!("{}: {}", context, err).into()),
+        }
+    }
+
+    pub fn needed(&self) -> Option<usize> {
+        match self {
+            DecodeError::Incomplete { needed, .. } => *needed,
+            DecodeError::Other(_) => None,
+        }
+    }
+}
+
 impl Display for DecodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            DecodeError::Incomplete(err) => {
+            DecodeError::Incomplete { needed, source } => {
                 "stream ended early".fmt(f)?;
-                match err {
+                if let Some(needed) = needed {
+                    write!(f, ", need {} more bytes", needed)?;
+                }
+                match source {
                     Some(err) => err.fmt(f),
                     None => Ok(()),
                 }
@@ -99,7 +206,7 @@ macro_rules! impl_decode_imcomplete_error_from {
         $(
             impl From<$type> for DecodeError {
                 fn from(value: $type) -> Self {
-                    DecodeError::Incomplete(Some(value.into()))
+                    DecodeError::incomplete(Some(value.into()))
                 }
             }
         )*
@@ -110,6 +217,54 @@ impl_decode_imcomplete_error_from!(std::io::Error);
 
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
+// 只在接下来的 len 个字节内 decode 一个 T, 没有恰好用完这些字节时返回错误
+pub fn decode_bounded<T: Decode>(buffer: &mut Cursor<&[u8]>, len: usize) -> DecodeResult<T> {
+    decode_bounded_with(buffer, len, T::decode)
+}
+
+// 只要 len 个字节都已经在 buffer 中, 无论 decode 是否成功, buffer 都会跳过这些字节
+pub fn decode_bounded_with<T>(
+    buffer: &mut Cursor<&[u8]>,
+    len: usize,
+    decode: impl FnOnce(&mut Cursor<&[u8]>) -> DecodeResult<T>,
+) -> DecodeResult<T> {
+    if buffer.remaining() < len {
+        return Err(DecodeError::incomplete_needed(len - buffer.remaining()));
+    }
+    let start = buffer.position() as usize;
+    let bytes: &[u8] = buffer.get_ref();
+    let mut region = Cursor::new(&bytes[start..start + len]);
+    buffer.advance(len);
+
+    match decode(&mut region) {
+        // 整个区域都已经读入, 仍然不够说明长度前缀与内容不一致, 读取更多字节也无法解决
+        Err(err) if err.is_incomplete() => Err(DecodeError::Other(
+            format!("Decode past the end of a {} bytes region: {}", len, err).into(),
+        )),
+        Err(err) => Err(err),
+        Ok(_) if region.has_remaining() => Err(DecodeError::Other(
+            format!(
+                "Decode only consumed {} of a {} bytes region",
+                region.position(),
+                len
+            )
+            .into(),
+        )),
+        Ok(value) => Ok(value),
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
+    where
+        Self: Sized,
+    {
+        let a = A::decode(buffer)?;
+        let b = B::decode(buffer)?;
+        Ok((a, b))
+    }
+}
+
 impl Decode for Uuid {
     fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self>
     where
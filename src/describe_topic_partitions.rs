@@ -5,16 +5,18 @@ use lazy_static::lazy_static;
 use uuid::Uuid;
 
 use crate::{
-    api_versions::{ApiKey, ApiVersionsResponseBodyV4, UNSUPPORTED_VERSION_ERROR},
-    common_struct::{CompactArray, CompactString, TagBuffer},
+    acl::{topic_authorized_operations, Session},
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactString, Millis, TagBuffer},
     decode::{Decode, DecodeError, DecodeResult},
     encode::Encode,
-    metadata_log::TOPIC_INFO_MAP,
+    metadata_log::{is_metadata_ready, TOPIC_INFO_MAP},
     request_message::RequestHeaderV2,
     response_message::{ResponseBody, ResponseHeader, ResponseMessage},
 };
 
 pub const UNKNOWN_TOPIC_OR_PARTITION: i16 = 3; //TODO 考虑怎么把错误码和数据结构结合到一起
+pub const COORDINATOR_LOAD_IN_PROGRESS: i16 = 14;
 
 lazy_static! {
     pub static ref DESCRIBE_TOPIC_PARTITIONS_API_INFO: ApiKey =
@@ -29,6 +31,16 @@ pub struct TopicInfo {
     pub topic_authorized_operations: TopicAuthorizedOperations,
 }
 
+impl TopicInfo {
+    // partition 还没有写入 metadata log 时返回空数组, 部分客户端不接受 null
+    pub fn response_partitions(&self) -> CompactArray<TopicPartition> {
+        match self.partitions_array.as_ref() {
+            Some(_) => self.partitions_array.clone(),
+            None => CompactArray::empty(),
+        }
+    }
+}
+
 #[derive(Debug, Decode, Encode)]
 pub struct DescribeTopicPartitionsRequestBodyV0 {
     topics: CompactArray<TopicRequest>,
@@ -37,7 +49,7 @@ pub struct DescribeTopicPartitionsRequestBodyV0 {
     tag_buffer: TagBuffer,
 }
 
-#[derive(Debug, Decode, Encode)]
+#[derive(Debug, PartialEq, Decode, Encode)]
 pub struct TopicRequest {
     //TODO 考虑是否需要修改名称
     name: CompactString,
@@ -69,10 +81,10 @@ impl Default for OptionTopicCursor {
 }
 
 impl Encode for OptionTopicCursor {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match &self.inner {
-            Some(cursor) => cursor.encode(),
-            None => vec![0xff],
+            Some(cursor) => cursor.encode_into(out),
+            None => out.push(0xff),
         }
     }
 }
@@ -94,7 +106,7 @@ impl Decode for OptionTopicCursor {
 
 #[derive(Debug, Encode, Decode)]
 pub struct DescribeTopicPartitionsResponseBodyV0 {
-    throttle_time: i32,
+    throttle_time: Millis,
     topic_array: CompactArray<TopicResponse>,
     next_curor: OptionTopicCursor,
     tag_buffer: TagBuffer,
@@ -111,6 +123,59 @@ pub struct TopicResponse {
     tag_buffer: TagBuffer,
 }
 
+impl DescribeTopicPartitionsResponseBodyV0 {
+    // 请求的每个 topic 都返回同一个错误码
+    pub fn error(body: &DescribeTopicPartitionsRequestBodyV0, error_code: i16) -> Self {
+        Self {
+            throttle_time: Millis(0),
+            topic_array: body
+                .topics
+                .iter()
+                .flatten()
+                .map(|topic| TopicResponse::new_error(&topic.name, error_code))
+                .collect(),
+            next_curor: OptionTopicCursor::default(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn topics(&self) -> &CompactArray<TopicResponse> {
+        &self.topic_array
+    }
+}
+
+impl TopicResponse {
+    fn new_error(name: &CompactString, error_code: i16) -> Self {
+        Self {
+            error_code,
+            name: name.clone(),
+            id: Uuid::nil(),
+            is_internal: false,
+            partitions_array: CompactArray::empty(),
+            topic_authorized_operations: TopicAuthorizedOperations::default(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn partition_count(&self) -> usize {
+        self.partitions_array
+            .as_ref()
+            .map_or(0, |partitions| partitions.len())
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct TopicPartition {
     pub error_code: i16,
@@ -137,7 +202,7 @@ impl RepicaNode {
 }
 
 bitflags! {
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct TopicAuthorizedOperations: u32{
         const UNKNOWN = 1 << 0;
         const ANY = 1 << 1;
@@ -164,8 +229,8 @@ impl Default for TopicAuthorizedOperations {
 }
 
 impl Encode for TopicAuthorizedOperations {
-    fn encode(&self) -> Vec<u8> {
-        self.bits().encode()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.bits().encode_into(out);
     }
 }
 
@@ -188,28 +253,29 @@ impl Decode for TopicAuthorizedOperations {
 pub fn execute_describe_topic_partitions(
     header: &RequestHeaderV2,
     body: &DescribeTopicPartitionsRequestBodyV0,
+    session: &Session,
 ) -> ResponseMessage {
     let request_api_version = header.request_api_version;
-    let correlation_id = header.correlation_id;
 
     if request_api_version < DESCRIBE_TOPIC_PARTITIONS_API_INFO.min_version
         || request_api_version > DESCRIBE_TOPIC_PARTITIONS_API_INFO.max_version
     {
         return ResponseMessage::new(
-            ResponseHeader::new_v0(correlation_id),
-            ResponseBody::ApiVersionsV4(ApiVersionsResponseBodyV4::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsResponseBodyV0::error(
+                body,
                 UNSUPPORTED_VERSION_ERROR,
-                CompactArray::new(Some(vec![])),
-                0,
-                TagBuffer::default(),
             )),
         );
     }
 
+    let metadata_ready = is_metadata_ready();
     let mut describe_topics = vec![];
     if let Some(topics) = body.topics.as_ref() {
         for request_topic in topics.iter() {
-            let resp_topic = if let Some(topic_info) = TOPIC_INFO_MAP
+            let resp_topic = if !metadata_ready {
+                TopicResponse::new_error(&request_topic.name, COORDINATOR_LOAD_IN_PROGRESS)
+            } else if let Some(topic_info) = TOPIC_INFO_MAP
                 .lock()
                 .expect("Failed to get TOPIC_PARTITIONS")
                 .get(&request_topic.name)
@@ -219,30 +285,25 @@ pub fn execute_describe_topic_partitions(
                     name: topic_info.name.clone(),
                     id: topic_info.id,
                     is_internal: topic_info.is_internal,
-                    partitions_array: topic_info.partitions_array.clone(),
-                    topic_authorized_operations: topic_info.topic_authorized_operations,
+                    partitions_array: topic_info.response_partitions(),
+                    topic_authorized_operations: topic_authorized_operations(
+                        session,
+                        &topic_info.name,
+                    ),
                     tag_buffer: TagBuffer::default(),
                 }
             } else {
-                TopicResponse {
-                    error_code: UNKNOWN_TOPIC_OR_PARTITION,
-                    name: request_topic.name.clone(),
-                    id: Uuid::nil(),
-                    is_internal: false,
-                    partitions_array: CompactArray::empty(),
-                    topic_authorized_operations: TopicAuthorizedOperations::default(),
-                    tag_buffer: TagBuffer::default(),
-                }
+                TopicResponse::new_error(&request_topic.name, UNKNOWN_TOPIC_OR_PARTITION)
             };
             describe_topics.push(resp_topic);
         }
     }
 
     ResponseMessage::new(
-        ResponseHeader::new_v1(correlation_id),
+        ResponseHeader::for_request(header),
         ResponseBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsResponseBodyV0 {
-            throttle_time: 0,
-            topic_array: CompactArray::new(Some(describe_topics)),
+            throttle_time: Millis(0),
+            topic_array: CompactArray::from_vec(describe_topics),
             next_curor: OptionTopicCursor::default(),
             tag_buffer: TagBuffer::default(),
         }),
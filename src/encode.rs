@@ -1,18 +1,78 @@
+use std::fmt::Display;
+
 use uuid::Uuid;
 
 pub use kafka_serde_derive::Encode;
 
 pub trait Encode {
-    fn encode(&self) -> Vec<u8>;
+    // 追加写入调用方的 buffer, 嵌套结构 encode 时不再为每个字段分配 Vec
+    fn encode_into(&self, out: &mut Vec<u8>);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut encode_vec = Vec::new();
+        self.encode_into(&mut encode_vec);
+        encode_vec
+    }
+
+    // encode 后的准确长度, 默认实现会分配内存, 定长类型和 derive 生成的实现不会
+    fn encoded_len(&self) -> usize {
+        self.encode().len()
+    }
+
+    // 按 api 版本 encode, 只包含该版本中存在的字段, 与版本无关的类型等同于 encode_into
+    // 容器类型需要把 version 传给元素, 否则嵌套 struct 的 since/until 不生效
+    fn encode_versioned_into(&self, out: &mut Vec<u8>, _version: i16) {
+        self.encode_into(out)
+    }
+
+    fn encode_versioned(&self, version: i16) -> Vec<u8> {
+        let mut encode_vec = Vec::new();
+        self.encode_versioned_into(&mut encode_vec, version);
+        encode_vec
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    // 冗余的计数字段与数组的实际长度不一致, 按原样 encode 会被客户端拒绝
+    CountMismatch {
+        field: &'static str,
+        declared: i64,
+        actual: usize,
+    },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::CountMismatch {
+                field,
+                declared,
+                actual,
+            } => write!(
+                f,
+                "{} declares {} elements, but the array has {}",
+                field, declared, actual
+            ),
+        }
+    }
 }
 
+impl std::error::Error for EncodeError {}
+
+pub type EncodeResult<T> = Result<T, EncodeError>;
+
 // 使用宏为所有整数类型实现 Encode
 macro_rules! impl_encode_for_integers {
     ($($type:ty),*) => {
         $(
             impl Encode for $type {
-                fn encode(&self) -> Vec<u8> {
-                    self.to_be_bytes().to_vec() //TODO 减少一次 copy
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn encoded_len(&self) -> usize {
+                    std::mem::size_of::<$type>()
                 }
             }
         )*
@@ -22,13 +82,32 @@ macro_rules! impl_encode_for_integers {
 impl_encode_for_integers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, isize, i128);
 
 impl Encode for bool {
-    fn encode(&self) -> Vec<u8> {
-        u8::from(*self).encode()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.0.encode_into(out);
+        self.1.encode_into(out);
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len() + self.1.encoded_len()
     }
 }
 
 impl Encode for Uuid {
-    fn encode(&self) -> Vec<u8> {
-        self.as_bytes().to_vec()
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn encoded_len(&self) -> usize {
+        16
     }
 }
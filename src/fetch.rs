@@ -1,26 +1,78 @@
+use std::{
+    collections::HashMap,
+    future::{poll_fn, Future},
+    io::Cursor,
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+};
+
 use lazy_static::lazy_static;
+use tokio::{
+    sync::{futures::Notified, Notify},
+    time::{self, Instant},
+};
 use uuid::Uuid;
 
 use crate::{
-    api_versions::{ApiKey, ApiVersionsResponseBodyV4, UNSUPPORTED_VERSION_ERROR},
-    common_struct::{CompactArray, CompactRecords, CompactString, TagBuffer},
-    decode::Decode,
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    broker_config::BROKER_CONFIG,
+    common_struct::{
+        CompactArray, CompactNullableString, CompactRecords, CompactString, ControlRecordType,
+        MetadataAttributes, Millis, RecordBatch, TagBuffer, COMPRESSION_CODEC_MASK,
+    },
+    decode::{Decode, DecodeResult},
+    describe_topic_partitions::COORDINATOR_LOAD_IN_PROGRESS,
     encode::Encode,
-    metadata_log::{read_record_batches, TOPIC_ID_NAME_MAP},
+    metadata_log::{
+        is_metadata_ready, log_end_offset, partition_lock, partition_log_path, partition_notify,
+        read_record_batches_limited, take_record_batches, topic_name, FIRST_SEGMENT_BASE_OFFSET,
+        LOG_OVERHEAD,
+    },
+    produce::CORRUPT_MESSAGE_ERROR,
     request_message::RequestHeaderV2,
     response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+    segment_cache::cached_segment,
 };
 
 pub const INVALID_FETCH_SIZE_ERROR: i16 = 4;
 pub const UNKNOWN_TOPIC_ID_ERROR: i16 = 100;
+// 没有 preferred read replica, 客户端继续从 leader 读取
+pub const NO_PREFERRED_READ_REPLICA: i32 = -1;
+// 不超过客户端默认的 request.timeout.ms (30s), 避免客户端在 broker 响应前超时
+pub const MAX_FETCH_WAIT_MS: Millis = Millis(30_000);
+
+pub const FETCH_SESSION_ID_NOT_FOUND_ERROR: i16 = 70;
+pub const INVALID_FETCH_SESSION_EPOCH_ERROR: i16 = 71;
+// 不使用 fetch session 时响应中的 session_id
+pub const NO_FETCH_SESSION_ID: i32 = 0;
+
+// fetch session 的 epoch 状态机:
+//   INITIAL_EPOCH (0): 创建新的 session, 本次是完整 fetch, 之后的 epoch 从 1 开始递增
+//   epoch > 0: 增量 fetch, 必须与 session 期望的下一个 epoch 一致
+//   FINAL_EPOCH (-1): 关闭 session_id 对应的 session (如果存在), 本次按完整 fetch 处理
+//   其他负数: 非法
+pub struct FetchSessionEpoch;
+
+impl FetchSessionEpoch {
+    pub const INITIAL_EPOCH: i32 = 0;
+    pub const FINAL_EPOCH: i32 = -1;
+}
+
+pub struct IsolationLevel;
+
+impl IsolationLevel {
+    pub const READ_UNCOMMITTED: i8 = 0;
+    pub const READ_COMMITTED: i8 = 1;
+}
 
 lazy_static! {
-    pub static ref FETCH_API_INFO: ApiKey = ApiKey::new(1, 0, 16, TagBuffer::default());
+    pub static ref FETCH_API_INFO: ApiKey = ApiKey::new(1, 16, 16, TagBuffer::default());
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct FetchRequestBodyV16 {
-    max_wait_ms: i32,
+    max_wait_ms: Millis,
     min_bytes: i32,
     max_bytes: i32,
     isolation_level: i8,
@@ -29,17 +81,32 @@ pub struct FetchRequestBodyV16 {
     topics: CompactArray<FetchTopicRequest>,
     forgotten_topics_data: CompactArray<ForgottenTopicRequest>,
     rack_id: CompactString,
-    tag_buffer: TagBuffer,
+    tag_buffer: TagBuffer, // tag 0: cluster_id, tag 1: replica_state
 }
 
-#[derive(Debug, Encode, Decode)]
+impl FetchRequestBodyV16 {
+    pub const CLUSTER_ID_TAG: u32 = 0;
+
+    // KRaft 的 follower 才会设置 cluster_id, 普通 consumer 不会设置
+    pub fn cluster_id(&self) -> DecodeResult<Option<String>> {
+        match self.tag_buffer.get(Self::CLUSTER_ID_TAG) {
+            None => Ok(None),
+            Some(data) => {
+                let cluster_id = CompactNullableString::decode(&mut Cursor::new(data))?;
+                Ok((*cluster_id).clone())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct FetchTopicRequest {
     topic_id: Uuid,
     partitions: CompactArray<FetchPartitionRequest>,
     tag_buffer: TagBuffer,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct FetchPartitionRequest {
     partition_index: i32,
     current_leader_epoch: i32,
@@ -50,7 +117,7 @@ pub struct FetchPartitionRequest {
     tag_buffer: TagBuffer,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct ForgottenTopicRequest {
     topic_id: Uuid,
     partitions: i32,
@@ -59,13 +126,34 @@ pub struct ForgottenTopicRequest {
 
 #[derive(Debug, Encode, Decode)]
 pub struct FetchResponseBodyV16 {
-    throttle_time_ms: i32,
+    throttle_time_ms: Millis,
     error_code: i16,
     session_id: i32,
     responses: CompactArray<FetchTopicResponse>,
     tag_buffer: TagBuffer,
 }
 
+impl FetchResponseBodyV16 {
+    // 整个请求出错时只返回顶层的错误码
+    pub fn error(error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            error_code,
+            session_id: NO_FETCH_SESSION_ID,
+            responses: CompactArray::empty(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn responses(&self) -> &CompactArray<FetchTopicResponse> {
+        &self.responses
+    }
+}
+
 #[derive(Debug, Encode, Decode)]
 pub struct FetchTopicResponse {
     topic_id: Uuid,
@@ -73,6 +161,12 @@ pub struct FetchTopicResponse {
     tag_buffer: TagBuffer,
 }
 
+impl FetchTopicResponse {
+    pub fn partitions(&self) -> &CompactArray<FetchPartitionResponse> {
+        &self.partitions
+    }
+}
+
 #[derive(Debug, Encode, Decode)]
 pub struct FetchPartitionResponse {
     partition_index: i32,
@@ -95,11 +189,35 @@ impl FetchPartitionResponse {
             last_stable_offset: 0,
             log_start_offset: 0,
             aborted_transactions: CompactArray::empty(),
-            preferred_read_replica: 0,
+            preferred_read_replica: NO_PREFERRED_READ_REPLICA,
             record_batches: CompactRecords::empty(),
             tag_buffer: TagBuffer::default(),
         }
     }
+
+    pub fn preferred_read_replica(&self) -> i32 {
+        self.preferred_read_replica
+    }
+
+    pub fn partition_index(&self) -> i32 {
+        self.partition_index
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn high_watermark(&self) -> i64 {
+        self.high_watermark
+    }
+
+    pub fn record_batches(&self) -> &CompactRecords {
+        &self.record_batches
+    }
+
+    pub fn aborted_transactions(&self) -> &CompactArray<Transaction> {
+        &self.aborted_transactions
+    }
 }
 
 #[derive(Debug, Encode, Decode)]
@@ -109,54 +227,163 @@ pub struct Transaction {
     tag_buffer: TagBuffer,
 }
 
-pub fn execute_fetch(header: &RequestHeaderV2, body: &FetchRequestBodyV16) -> ResponseMessage {
-    let request_api_version = header.request_api_version;
-    let correlation_id = header.correlation_id;
+impl Transaction {
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
 
-    if request_api_version < FETCH_API_INFO.min_version
-        || request_api_version > FETCH_API_INFO.max_version
+    pub fn first_offset(&self) -> i64 {
+        self.first_offset
+    }
+}
+
+// control batch 作为事务标记原样返回给客户端, 由客户端跳过, 不会作为普通消息交付
+// READ_COMMITTED 时还需要告诉客户端哪些事务被 abort 了, 以便过滤其中的消息
+fn collect_aborted_transactions(record_batches: &[RecordBatch]) -> Vec<Transaction> {
+    let mut ongoing_transactions: HashMap<i64, i64> = HashMap::new();
+    let mut aborted_transactions = vec![];
+    for record_batch in record_batches {
+        let producer_id = record_batch.producer_id;
+        if record_batch.is_control_batch() {
+            let first_offset = ongoing_transactions.remove(&producer_id);
+            if let (Some(ControlRecordType::Abort), Some(first_offset)) =
+                (record_batch.control_record_type(), first_offset)
+            {
+                aborted_transactions.push(Transaction {
+                    producer_id,
+                    first_offset,
+                    tag_buffer: TagBuffer::default(),
+                });
+            }
+        } else if record_batch.is_transactional() {
+            ongoing_transactions
+                .entry(producer_id)
+                .or_insert(record_batch.base_offset);
+        }
+    }
+    aborted_transactions
+}
+
+// 还没有 produce 过的 partition 没有 log 文件
+// 从 fetch_offset 所在的 batch 开始返回, 之前的 batch 客户端已经消费过
+// 与 Kafka 一致, partition_max_bytes 小于第一个 batch 时仍然返回完整的第一个 batch
+// 同时返回 high watermark, 目前没有副本同步, 就是 log end offset
+fn read_partition(
+    topic_name: &str,
+    partition_index: i32,
+    fetch_offset: i64,
+    partition_max_bytes: usize,
+) -> DecodeResult<(Vec<RecordBatch>, i64)> {
+    let path = partition_log_path(topic_name, partition_index);
+    if !path.exists() {
+        return Ok((vec![], 0));
+    }
+    match cached_segment(topic_name, partition_index, FIRST_SEGMENT_BASE_OFFSET)? {
+        Some(segment) => {
+            // segment 中的 batch 按 offset 递增排列
+            let start = segment.partition_point(|batch| batch.next_offset() <= fetch_offset);
+            let record_batches = take_record_batches(
+                segment[start..].iter().cloned().map(Ok),
+                partition_max_bytes,
+            )?;
+            let high_watermark = segment.last().map_or(0, RecordBatch::next_offset);
+            Ok((record_batches, high_watermark))
+        }
+        // 不能缓存的 segment 逐个 batch 读取, 读够 partition_max_bytes 后停止
+        None => Ok((
+            read_record_batches_limited(&path, fetch_offset, partition_max_bytes)?,
+            log_end_offset(&path)?,
+        )),
+    }
+}
+
+// --fetch-compression 指定的压缩算法, 已经压缩的 batch 和 control batch 保持原样
+pub fn compress_record_batches(record_batches: &mut [RecordBatch], codec: MetadataAttributes) {
+    if codec.bits() & COMPRESSION_CODEC_MASK == 0 {
+        return;
+    }
+    for record_batch in record_batches
+        .iter_mut()
+        .filter(|record_batch| !record_batch.is_compressed() && !record_batch.is_control_batch())
     {
-        return ResponseMessage::new(
-            ResponseHeader::new_v0(correlation_id),
-            ResponseBody::ApiVersionsV4(ApiVersionsResponseBodyV4::new(
-                UNSUPPORTED_VERSION_ERROR,
-                CompactArray::new(Some(vec![])),
-                0,
-                TagBuffer::default(),
-            )),
-        );
+        record_batch.set_compression(codec);
+    }
+}
+
+// 一个 partition 的 log 损坏时只有这个 partition 返回 CORRUPT_MESSAGE, 不影响其他 partition
+pub fn fetch_partition(
+    topic_name: &str,
+    partition_index: i32,
+    fetch_offset: i64,
+    partition_max_bytes: usize,
+    isolation_level: i8,
+) -> FetchPartitionResponse {
+    let partition_lock = partition_lock(topic_name, partition_index);
+    let _guard = partition_lock
+        .read()
+        .expect("Failed to get partition read lock");
+    let (mut record_batches, high_watermark) = match read_partition(
+        topic_name,
+        partition_index,
+        fetch_offset,
+        partition_max_bytes,
+    ) {
+        Ok(partition) => partition,
+        Err(err) => {
+            tracing::error!(
+                "Corrupt log for {}-{}: {}",
+                topic_name,
+                partition_index,
+                err
+            );
+            return FetchPartitionResponse {
+                partition_index,
+                ..FetchPartitionResponse::new_empty(CORRUPT_MESSAGE_ERROR)
+            };
+        }
+    };
+    let aborted_transactions = if isolation_level == IsolationLevel::READ_COMMITTED {
+        CompactArray::from_vec(collect_aborted_transactions(&record_batches))
+    } else {
+        CompactArray::default()
+    };
+    compress_record_batches(&mut record_batches, BROKER_CONFIG.fetch_compression);
+    FetchPartitionResponse {
+        partition_index,
+        error_code: 0,
+        high_watermark,
+        last_stable_offset: 0,
+        log_start_offset: 0,
+        aborted_transactions,
+        preferred_read_replica: NO_PREFERRED_READ_REPLICA,
+        record_batches: CompactRecords::new(Some(record_batches)),
+        tag_buffer: TagBuffer::default(),
     }
+}
 
+// 按请求中的 topic 和 partition 读取 record batch
+fn fetch_topic_responses(body: &FetchRequestBodyV16) -> Vec<FetchTopicResponse> {
+    let metadata_ready = is_metadata_ready();
     let mut fetch_topics = vec![];
     if let Some(topics) = body.topics.as_ref() {
         for request_topic in topics.iter() {
-            let partitions_inner = if let Some(topic_name) = TOPIC_ID_NAME_MAP
-                .lock()
-                .expect("Failed to get TOPIC_ID_NAME_MAP")
-                .get(&request_topic.topic_id)
-            {
+            // 读取 log 时不持有 TOPIC_ID_NAME_MAP 的锁, 只持有对应 partition 的读锁
+            let topic_name = topic_name(&request_topic.topic_id);
+            let partitions_inner = if !metadata_ready {
+                Some(vec![FetchPartitionResponse::new_empty(
+                    COORDINATOR_LOAD_IN_PROGRESS,
+                )])
+            } else if let Some(topic_name) = topic_name {
                 if let Some(partitions) = request_topic.partitions.as_ref() {
                     let mut partitions_inner = vec![];
                     for partition in partitions {
-                        let topic_log_file = format!(
-                            "/tmp/kraft-combined-logs/{}-{}/00000000000000000000.log",
+                        partitions_inner.push(fetch_partition(
                             topic_name.as_str(),
-                            partition.partition_index
-                        );
-                        let record_batches = read_record_batches(topic_log_file.as_ref())
-                            .expect("Failed to read topic log file");
-                        // let record_batches = vec![record_batches[0].clone()];
-                        partitions_inner.push(FetchPartitionResponse {
-                            partition_index: partition.partition_index,
-                            error_code: 0,
-                            high_watermark: 0,
-                            last_stable_offset: 0,
-                            log_start_offset: 0,
-                            aborted_transactions: CompactArray::default(),
-                            preferred_read_replica: 0,
-                            record_batches: CompactRecords::new(Some(record_batches)),
-                            tag_buffer: TagBuffer::default(),
-                        });
+                            partition.partition_index,
+                            partition.fetch_offset,
+                            partition.partition_max_bytes.max(0) as usize,
+                            body.isolation_level,
+                        ));
                     }
                     Some(partitions_inner)
                 } else {
@@ -174,14 +401,141 @@ pub fn execute_fetch(header: &RequestHeaderV2, body: &FetchRequestBodyV16) -> Re
             });
         }
     }
+    fetch_topics
+}
+
+fn partition_responses(
+    fetch_topics: &[FetchTopicResponse],
+) -> impl Iterator<Item = &FetchPartitionResponse> {
+    fetch_topics
+        .iter()
+        .filter_map(|topic| topic.partitions.as_ref())
+        .flatten()
+}
+
+// 返回的 batch 都包含 fetch_offset 之后的 record, 已经消费过的 batch 不会被计入,
+// 客户端已经读到 log 末尾时为 0, 会一直等待新的数据
+fn fetched_bytes(fetch_topics: &[FetchTopicResponse]) -> usize {
+    partition_responses(fetch_topics)
+        .filter_map(|partition| partition.record_batches.get_inner().as_ref())
+        .flatten()
+        .map(|record_batch| LOG_OVERHEAD + record_batch.batch_length.max(0) as usize)
+        .sum()
+}
+
+fn partition_notifies(body: &FetchRequestBodyV16) -> Vec<Arc<Notify>> {
+    body.topics
+        .iter()
+        .flatten()
+        .filter_map(|topic| {
+            let topic_name = topic_name(&topic.topic_id)?;
+            Some(
+                topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(move |partition| partition_notify(&topic_name, partition.partition_index)),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+// 返回响应中的 session_id, 或者 session 相关的错误码
+// 目前不缓存 session, 所有的 fetch 都是完整 fetch: 新建 session 时返回 NO_FETCH_SESSION_ID,
+// 客户端会继续发送完整 fetch; FINAL_EPOCH 时没有需要关闭的 session; 增量 fetch 找不到 session
+pub fn resolve_fetch_session(session_id: i32, session_epoch: i32) -> Result<i32, i16> {
+    match session_epoch {
+        FetchSessionEpoch::FINAL_EPOCH | FetchSessionEpoch::INITIAL_EPOCH => {
+            Ok(NO_FETCH_SESSION_ID)
+        }
+        epoch if epoch > 0 => {
+            tracing::debug!(
+                "Fetch session {} with epoch {} is not found",
+                session_id,
+                epoch
+            );
+            Err(FETCH_SESSION_ID_NOT_FOUND_ERROR)
+        }
+        _ => Err(INVALID_FETCH_SESSION_EPOCH_ERROR),
+    }
+}
+
+// 任意一个 partition 被唤醒时返回 true, 到达 deadline 时返回 false
+pub async fn wait_notified(notified: &mut [Pin<Box<Notified<'_>>>], deadline: Instant) -> bool {
+    let any_notified = poll_fn(|cx| {
+        if notified
+            .iter_mut()
+            .any(|notified| notified.as_mut().poll(cx).is_ready())
+        {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    });
+    tokio::select! {
+        _ = any_notified => true,
+        _ = time::sleep_until(deadline) => false,
+    }
+}
+
+// 数据不足 min_bytes 时最多等待 max_wait_ms, 期间有 produce 写入请求的 partition 时提前返回
+pub async fn execute_fetch(
+    header: &RequestHeaderV2,
+    body: &FetchRequestBodyV16,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < FETCH_API_INFO.min_version
+        || request_api_version > FETCH_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::FetchV16(FetchResponseBodyV16::error(UNSUPPORTED_VERSION_ERROR)),
+        );
+    }
+
+    let session_id = match resolve_fetch_session(body.session_id, body.session_epoch) {
+        Ok(session_id) => session_id,
+        Err(error_code) => {
+            return ResponseMessage::new(
+                ResponseHeader::new_v1(correlation_id),
+                ResponseBody::FetchV16(FetchResponseBodyV16::error(error_code)),
+            )
+        }
+    };
+
+    let max_wait = body.max_wait_ms.min(MAX_FETCH_WAIT_MS).as_duration();
+    let deadline = Instant::now() + max_wait;
+    let min_bytes = body.min_bytes.max(0) as usize;
+    let fetch_topics = loop {
+        // 先注册再读取, 避免错过读取之后的 produce
+        let notifies = partition_notifies(body);
+        let mut notified: Vec<_> = notifies
+            .iter()
+            .map(|notify| Box::pin(notify.notified()))
+            .collect();
+        for notified in notified.iter_mut() {
+            notified.as_mut().enable();
+        }
+
+        let fetch_topics = fetch_topic_responses(body);
+        let has_error =
+            partition_responses(&fetch_topics).any(|partition| partition.error_code != 0);
+        if has_error || fetched_bytes(&fetch_topics) >= min_bytes || Instant::now() >= deadline {
+            break fetch_topics;
+        }
+        wait_notified(&mut notified, deadline).await;
+    };
 
     ResponseMessage::new(
         ResponseHeader::new_v1(correlation_id),
         ResponseBody::FetchV16(FetchResponseBodyV16 {
-            throttle_time_ms: 0,
+            throttle_time_ms: Millis(0),
             error_code: 0,
-            session_id: 0,
-            responses: CompactArray::new(Some(fetch_topics)),
+            session_id,
+            responses: CompactArray::from_vec(fetch_topics),
             tag_buffer: TagBuffer::default(),
         }),
     )
@@ -0,0 +1,162 @@
+use lazy_static::lazy_static;
+
+use crate::{
+    alter_configs::INVALID_REQUEST_ERROR,
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    decode::Decode,
+    encode::Encode,
+    metadata_log::BrokerInfo,
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+lazy_static! {
+    pub static ref FIND_COORDINATOR_API_INFO: ApiKey = ApiKey::new(10, 4, 4, TagBuffer::default());
+}
+
+pub struct CoordinatorType;
+
+impl CoordinatorType {
+    pub const GROUP: i8 = 0;
+    pub const TRANSACTION: i8 = 1;
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct FindCoordinatorRequestBodyV4 {
+    key_type: i8,
+    coordinator_keys: CompactArray<CompactString>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct FindCoordinatorResponseBodyV4 {
+    throttle_time_ms: Millis,
+    coordinators: CompactArray<Coordinator>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct Coordinator {
+    key: CompactString,
+    node_id: i32,
+    host: CompactString,
+    port: i32,
+    error_code: i16,
+    error_message: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+impl FindCoordinatorResponseBodyV4 {
+    // 请求的每个 key 都返回同一个错误码
+    pub fn error(body: &FindCoordinatorRequestBodyV4, error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            coordinators: body
+                .coordinator_keys
+                .iter()
+                .flatten()
+                .map(|key| Coordinator {
+                    key: key.clone(),
+                    node_id: -1,
+                    host: CompactString::new(String::new()),
+                    port: -1,
+                    error_code,
+                    error_message: CompactNullableString::new(None),
+                    tag_buffer: TagBuffer::default(),
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn coordinators(&self) -> &CompactArray<Coordinator> {
+        &self.coordinators
+    }
+}
+
+impl Coordinator {
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+
+    pub fn node_id(&self) -> i32 {
+        self.node_id
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    pub fn port(&self) -> i32 {
+        self.port
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+}
+
+// 只有一个 broker, 所有 group 和 transaction 的 coordinator 都是它自己
+fn find_coordinator(key_type: i8, key: &CompactString) -> Coordinator {
+    if key_type != CoordinatorType::GROUP && key_type != CoordinatorType::TRANSACTION {
+        return Coordinator {
+            key: key.clone(),
+            node_id: -1,
+            host: CompactString::new(String::new()),
+            port: -1,
+            error_code: INVALID_REQUEST_ERROR,
+            error_message: CompactNullableString::new(Some(format!(
+                "Unknown coordinator type {}",
+                key_type
+            ))),
+            tag_buffer: TagBuffer::default(),
+        };
+    }
+    let broker = BrokerInfo::local();
+    Coordinator {
+        key: key.clone(),
+        node_id: broker.id,
+        host: CompactString::new(broker.host),
+        port: broker.port,
+        error_code: 0,
+        error_message: CompactNullableString::new(None),
+        tag_buffer: TagBuffer::default(),
+    }
+}
+
+pub fn execute_find_coordinator(
+    header: &RequestHeaderV2,
+    body: &FindCoordinatorRequestBodyV4,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < FIND_COORDINATOR_API_INFO.min_version
+        || request_api_version > FIND_COORDINATOR_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::FindCoordinatorV4(FindCoordinatorResponseBodyV4::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let coordinators = body
+        .coordinator_keys
+        .iter()
+        .flatten()
+        .map(|key| find_coordinator(body.key_type, key))
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::FindCoordinatorV4(FindCoordinatorResponseBodyV4 {
+            throttle_time_ms: Millis(0),
+            coordinators,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
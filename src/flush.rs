@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use crate::configs::flush_config_store;
+
+// 所有需要持久化的状态在这里统一写入磁盘, 目前只有 config store
+// committed offset (offsets::COMMITTED_OFFSETS) 和 producer id 计数器 (init_producer_id::NEXT_PRODUCER_ID)
+// 只保存在内存中, 重启后丢失, 不在这里 flush. 之后持久化它们时需要像 config store 一样标记 dirty 并在这里加入
+pub fn flush_persisted_state() {
+    if let Err(err) = flush_config_store() {
+        tracing::error!("Failed to flush config store: {}", err);
+    }
+}
+
+pub async fn run_flush_timer(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    // 第一次 tick 会立即返回
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tokio::task::spawn_blocking(flush_persisted_state).await {
+            tracing::error!("Flush task failed: {}", err);
+        }
+    }
+}
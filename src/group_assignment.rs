@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::metadata_log::partition_count;
+
+pub const RANGE_STRATEGY: &str = "range";
+pub const ROUND_ROBIN_STRATEGY: &str = "roundrobin";
+
+// member id -> 订阅的 topic
+pub type Subscriptions = BTreeMap<String, Vec<String>>;
+// member id -> 分配到的 (topic, partition)
+pub type Assignments = BTreeMap<String, Vec<(String, i32)>>;
+
+pub trait AssignmentStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn assign(
+        &self,
+        subscriptions: &Subscriptions,
+        partition_counts: &HashMap<String, i32>,
+    ) -> Assignments;
+}
+
+fn empty_assignments(subscriptions: &Subscriptions) -> Assignments {
+    subscriptions
+        .keys()
+        .map(|member_id| (member_id.clone(), vec![]))
+        .collect()
+}
+
+fn subscribed_topics(subscriptions: &Subscriptions) -> BTreeSet<&String> {
+    subscriptions.values().flatten().collect()
+}
+
+// 对每个 topic 单独分配, 按 member id 排序后每个 member 分到连续的一段 partition,
+// 不能整除时排在前面的 member 多分一个
+pub struct RangeAssignor;
+
+impl AssignmentStrategy for RangeAssignor {
+    fn name(&self) -> &'static str {
+        RANGE_STRATEGY
+    }
+
+    fn assign(
+        &self,
+        subscriptions: &Subscriptions,
+        partition_counts: &HashMap<String, i32>,
+    ) -> Assignments {
+        let mut assignments = empty_assignments(subscriptions);
+        for topic in subscribed_topics(subscriptions) {
+            let Some(&partition_count) = partition_counts.get(topic) else {
+                continue;
+            };
+            let members: Vec<&String> = subscriptions
+                .iter()
+                .filter(|(_, topics)| topics.contains(topic))
+                .map(|(member_id, _)| member_id)
+                .collect();
+            let member_count = members.len() as i32;
+            let per_member = partition_count / member_count;
+            let extra = partition_count % member_count;
+            for (i, member_id) in members.into_iter().enumerate() {
+                let i = i as i32;
+                let start = per_member * i + i.min(extra);
+                let length = per_member + if i < extra { 1 } else { 0 };
+                let assignment = assignments
+                    .get_mut(member_id)
+                    .expect("Member must be in assignments");
+                assignment
+                    .extend((start..start + length).map(|partition| (topic.clone(), partition)));
+            }
+        }
+        assignments
+    }
+}
+
+// 所有 topic 的 partition 排序后轮流分配给订阅了该 topic 的 member
+pub struct RoundRobinAssignor;
+
+impl AssignmentStrategy for RoundRobinAssignor {
+    fn name(&self) -> &'static str {
+        ROUND_ROBIN_STRATEGY
+    }
+
+    fn assign(
+        &self,
+        subscriptions: &Subscriptions,
+        partition_counts: &HashMap<String, i32>,
+    ) -> Assignments {
+        let mut assignments = empty_assignments(subscriptions);
+        let members: Vec<&String> = subscriptions.keys().collect();
+        if members.is_empty() {
+            return assignments;
+        }
+        let mut next_member = 0;
+        for topic in subscribed_topics(subscriptions) {
+            let Some(&partition_count) = partition_counts.get(topic) else {
+                continue;
+            };
+            for partition in 0..partition_count {
+                // 跳过没有订阅该 topic 的 member, subscribed_topics 保证至少有一个 member 订阅
+                while !subscriptions[members[next_member]].contains(topic) {
+                    next_member = (next_member + 1) % members.len();
+                }
+                assignments
+                    .get_mut(members[next_member])
+                    .expect("Member must be in assignments")
+                    .push((topic.clone(), partition));
+                next_member = (next_member + 1) % members.len();
+            }
+        }
+        assignments
+    }
+}
+
+pub fn assignment_strategy(name: &str) -> Option<Box<dyn AssignmentStrategy>> {
+    match name {
+        RANGE_STRATEGY => Some(Box::new(RangeAssignor)),
+        ROUND_ROBIN_STRATEGY => Some(Box::new(RoundRobinAssignor)),
+        _ => None,
+    }
+}
+
+// JoinGroup 中 member 按优先级给出 protocol 名称, 选择第一个支持的策略, 都不支持时使用 range
+pub fn select_assignment_strategy<'a>(
+    protocol_names: impl IntoIterator<Item = &'a str>,
+) -> Box<dyn AssignmentStrategy> {
+    protocol_names
+        .into_iter()
+        .find_map(assignment_strategy)
+        .unwrap_or_else(|| Box::new(RangeAssignor))
+}
+
+pub fn topic_partition_counts<'a>(
+    topics: impl IntoIterator<Item = &'a String>,
+) -> HashMap<String, i32> {
+    topics
+        .into_iter()
+        .filter_map(|topic| {
+            partition_count(topic).map(|partition_count| (topic.clone(), partition_count as i32))
+        })
+        .collect()
+}
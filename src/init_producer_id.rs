@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactNullableString, Millis, TagBuffer},
+    decode::Decode,
+    encode::Encode,
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+lazy_static! {
+    pub static ref INIT_PRODUCER_ID_API_INFO: ApiKey = ApiKey::new(22, 5, 5, TagBuffer::default());
+}
+
+// 每次请求分配一个新的 producer id, 不会复用
+// 计数器不经过 flush_persisted_state 持久化, 重启后从 0 开始
+static NEXT_PRODUCER_ID: AtomicI64 = AtomicI64::new(0);
+
+// producer_id 和 producer_epoch 为 -1 表示新的 producer, 否则是要求 bump epoch
+// 这里都按新的 producer 处理
+#[derive(Debug, Encode, Decode)]
+pub struct InitProducerIdRequestBodyV5 {
+    transactional_id: CompactNullableString,
+    transaction_timeout_ms: Millis,
+    producer_id: i64,
+    producer_epoch: i16,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct InitProducerIdResponseBodyV5 {
+    throttle_time_ms: Millis,
+    error_code: i16,
+    producer_id: i64,
+    producer_epoch: i16,
+    tag_buffer: TagBuffer,
+}
+
+impl InitProducerIdResponseBodyV5 {
+    pub fn error(error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            error_code,
+            producer_id: -1,
+            producer_epoch: -1,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
+}
+
+pub fn allocate_producer_id() -> i64 {
+    NEXT_PRODUCER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn execute_init_producer_id(
+    header: &RequestHeaderV2,
+    _body: &InitProducerIdRequestBodyV5,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < INIT_PRODUCER_ID_API_INFO.min_version
+        || request_api_version > INIT_PRODUCER_ID_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::InitProducerIdV5(InitProducerIdResponseBodyV5::error(
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::InitProducerIdV5(InitProducerIdResponseBodyV5 {
+            throttle_time_ms: Millis(0),
+            error_code: 0,
+            producer_id: allocate_producer_id(),
+            producer_epoch: 0,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
@@ -1,10 +1,27 @@
+pub mod acl;
+pub mod alter_configs;
 pub mod api_versions;
+pub mod broker_config;
+pub mod clock;
 pub mod common_struct;
+pub mod configs;
+pub mod crc;
+pub mod create_topics;
 pub mod decode;
 pub mod describe_topic_partitions;
 pub mod encode;
 pub mod fetch;
+pub mod find_coordinator;
+pub mod flush;
+pub mod group_assignment;
+pub mod init_producer_id;
+pub mod metadata;
 pub mod metadata_log;
+pub mod offset_fetch;
+pub mod offset_for_leader_epoch;
+pub mod offsets;
+pub mod produce;
 pub mod request_message;
 pub mod response_message;
+pub mod segment_cache;
 pub mod utils;
@@ -1,48 +1,153 @@
 #![allow(dead_code)]
 
-use tokio::net::{TcpListener, TcpStream};
+use std::{path::Path, sync::Arc, time::Duration};
 
-use crate::connection::Connection;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+};
 
+use crate::{
+    broker_config::BROKER_CONFIG,
+    connection::{Connection, BUFFER_POOL},
+};
+
+mod acl;
+mod alter_configs;
 mod api_versions;
+mod broker_config;
+mod clock;
 mod common_struct;
+mod configs;
 mod connection;
+mod crc;
+mod create_topics;
 mod decode;
 mod describe_topic_partitions;
 mod encode;
 mod fetch;
+mod find_coordinator;
+mod flush;
+mod group_assignment;
+mod init_producer_id;
+mod metadata;
 mod metadata_log;
+mod offset_fetch;
+mod offset_for_leader_epoch;
+mod offsets;
+mod produce;
 mod request_message;
 mod response_message;
+mod segment_cache;
 mod utils;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
 
 async fn process(socket: TcpStream) {
-    let mut connection = Connection::new(socket);
+    let mut connection = match BUFFER_POOL.as_ref() {
+        Some(pool) => Connection::with_buffer(socket, pool.acquire()),
+        None => Connection::new(socket),
+    };
     while let Some(request) = connection
         .read_request()
         .await
         .expect("Failed to read content from socket")
     {
-        tracing::debug!("Receive Request:\n{:?}", request);
+        // 一次处理缓冲区中所有完整的请求, 按请求的顺序写入响应, 最后只 flush 一次
+        let mut requests = vec![request];
+        requests.extend(
+            connection
+                .read_buffered_requests()
+                .expect("Failed to parse buffered requests"),
+        );
+
+        for request in requests {
+            let req_bytes = request.message_size as usize + 4;
+            tracing::debug!(req_bytes, "Receive Request:\n{:?}", request);
 
-        let mut response = response_message::execute_request(&request)
-            .await
-            .expect("Failed to execute request");
+            let mut response = response_message::execute_request(&request, connection.session())
+                .await
+                .expect("Failed to execute request");
 
-        tracing::debug!("Response:\n{:?}", response);
+            let resp_bytes = connection
+                .write_response_unflushed(&mut response)
+                .await
+                .expect("Failed to write response");
 
-        connection
-            .write_response(&mut response)
-            .await
-            .expect("Failed to write response");
+            tracing::debug!(req_bytes, resp_bytes, "Response:\n{:?}", response);
+        }
+        connection.flush().await.expect("Failed to flush responses");
+    }
+    if let Some(pool) = BUFFER_POOL.as_ref() {
+        pool.release(connection.into_buffer());
+    }
+}
+
+async fn accept_per_connection(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((socket, _addr)) => {
+                tracing::info!("Connect with {:?}", socket);
+                tokio::spawn(process(socket));
+            }
+            Err(err) => tracing::error!("Connect error: {:?}", err),
+        }
+    }
+}
+
+async fn worker(id: usize, receiver: Arc<Mutex<mpsc::Receiver<TcpStream>>>) {
+    loop {
+        let socket = receiver.lock().await.recv().await;
+        let Some(socket) = socket else {
+            break;
+        };
+        // process 出错时会 panic, 放到单独的 task 中避免 worker 退出
+        if let Err(err) = tokio::spawn(process(socket)).await {
+            tracing::error!(worker = id, "Connection task failed: {}", err);
+        }
+    }
+}
+
+// 队列满时 accept 循环会阻塞在 send 上, 形成背压
+async fn accept_with_worker_pool(listener: TcpListener, pool_size: usize, queue_depth: usize) {
+    tracing::info!(pool_size, queue_depth, "Start worker pool");
+    let (sender, receiver) = mpsc::channel(queue_depth);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for id in 0..pool_size {
+        tokio::spawn(worker(id, receiver.clone()));
+    }
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, _addr)) => {
+                tracing::info!("Connect with {:?}", socket);
+                if sender.send(socket).await.is_err() {
+                    tracing::error!("All workers exited");
+                    break;
+                }
+            }
+            Err(err) => tracing::error!("Connect error: {:?}", err),
+        }
     }
 }
 
 fn init() {
-    metadata_log::init_read_metadata_log().expect("Failed to read metadata log");
+    common_struct::set_strict_tags(BROKER_CONFIG.strict_tags);
+    if let Err(err) = api_versions::check_api_version_ranges() {
+        panic!(
+            "Advertised api versions do not match the implementation:\n{}",
+            err
+        );
+    }
+    if let Err(err) = configs::load_config_store() {
+        tracing::error!("Failed to load config store: {}", err);
+    }
+    if let Some(acl_file) = BROKER_CONFIG.acl_file.as_ref() {
+        if let Err(err) = acl::load_acl_store(Path::new(acl_file)) {
+            tracing::error!("Failed to load acl file {}: {}", acl_file, err);
+        }
+    }
 }
 
 #[tokio::main]
@@ -56,13 +161,46 @@ async fn main() {
 
     init();
 
-    loop {
-        match listener.accept().await {
-            Ok((socket, _addr)) => {
-                tracing::info!("Connect with {:?}", socket);
-                tokio::spawn(process(socket));
+    tokio::spawn(async {
+        match tokio::task::spawn_blocking(metadata_log::init_read_metadata_log).await {
+            Ok(Ok(())) => tracing::info!("Metadata log loaded"),
+            Ok(Err(err)) => {
+                tracing::error!("Failed to read metadata log: {}", err);
+                std::process::exit(1);
             }
-            Err(err) => tracing::error!("Connect error: {:?}", err),
+            Err(err) => {
+                tracing::error!("Metadata log loading task failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+    });
+    if BROKER_CONFIG.wait_for_metadata {
+        metadata_log::wait_metadata_ready().await;
+    }
+
+    tokio::spawn(flush::run_flush_timer(Duration::from_millis(
+        BROKER_CONFIG.flush_interval_ms,
+    )));
+
+    let accept_loop = async {
+        match BROKER_CONFIG.worker_pool_size {
+            None => accept_per_connection(listener).await,
+            Some(pool_size) => {
+                accept_with_worker_pool(listener, pool_size, BROKER_CONFIG.worker_queue_depth).await
+            }
+        }
+    };
+    let shutdown = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            // 无法监听信号时一直运行下去
+            tracing::error!("Failed to listen for shutdown signal: {}", err);
+            std::future::pending::<()>().await;
         }
+    };
+    tokio::select! {
+        _ = accept_loop => {}
+        _ = shutdown => tracing::info!("Shutting down"),
     }
+    // 退出前最后写入一次, 避免丢失最近一次定时写入之后的修改
+    flush::flush_persisted_state();
 }
@@ -0,0 +1,342 @@
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::{
+    acl::{topic_authorized_operations, Session},
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    broker_config::BROKER_CONFIG,
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    decode::Decode,
+    describe_topic_partitions::{
+        RepicaNode, TopicInfo, COORDINATOR_LOAD_IN_PROGRESS, UNKNOWN_TOPIC_OR_PARTITION,
+    },
+    encode::Encode,
+    metadata_log::{
+        brokers, create_topic, is_metadata_ready, topic_exists, topic_name, LOCAL_BROKER_ID,
+        TOPIC_INFO_MAP,
+    },
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+// 没有请求 topic authorized operations 时返回 i32::MIN
+pub const AUTHORIZED_OPERATIONS_OMITTED: i32 = i32::MIN;
+
+lazy_static! {
+    pub static ref METADATA_API_INFO: ApiKey = ApiKey::new(3, 12, 12, TagBuffer::default());
+}
+
+// topics 为 null 时返回所有 topic
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataRequestBodyV12 {
+    topics: CompactArray<MetadataRequestTopic>,
+    allow_auto_topic_creation: bool,
+    include_topic_authorized_operations: bool,
+    tag_buffer: TagBuffer,
+}
+
+// v10 开始可以只指定 topic_id, 此时 name 为 null
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataRequestTopic {
+    topic_id: Uuid,
+    name: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataResponseBodyV12 {
+    throttle_time_ms: Millis,
+    brokers: CompactArray<MetadataResponseBroker>,
+    cluster_id: CompactNullableString,
+    controller_id: i32,
+    topics: CompactArray<MetadataResponseTopic>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataResponseBroker {
+    node_id: i32,
+    host: CompactString,
+    port: i32,
+    rack: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataResponseTopic {
+    error_code: i16,
+    name: CompactNullableString,
+    topic_id: Uuid,
+    is_internal: bool,
+    partitions: CompactArray<MetadataResponsePartition>,
+    topic_authorized_operations: i32,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct MetadataResponsePartition {
+    error_code: i16,
+    partition_index: i32,
+    leader_id: i32,
+    leader_epoch: i32,
+    replica_nodes: CompactArray<RepicaNode>,
+    isr_nodes: CompactArray<RepicaNode>,
+    offline_replicas: CompactArray<RepicaNode>,
+    tag_buffer: TagBuffer,
+}
+
+impl MetadataResponseBodyV12 {
+    // 请求的每个 topic 都返回同一个错误码, 请求所有 topic 时返回空数组
+    pub fn error(body: &MetadataRequestBodyV12, error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            brokers: CompactArray::empty(),
+            cluster_id: CompactNullableString::new(None),
+            controller_id: -1,
+            topics: body
+                .topics
+                .iter()
+                .flatten()
+                .map(|topic| {
+                    MetadataResponseTopic::new_error(
+                        error_code,
+                        topic.name.as_deref().map(str::to_string),
+                        topic.topic_id,
+                    )
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn brokers(&self) -> &CompactArray<MetadataResponseBroker> {
+        &self.brokers
+    }
+
+    pub fn controller_id(&self) -> i32 {
+        self.controller_id
+    }
+
+    pub fn topics(&self) -> &CompactArray<MetadataResponseTopic> {
+        &self.topics
+    }
+}
+
+impl MetadataResponseBroker {
+    pub fn node_id(&self) -> i32 {
+        self.node_id
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    pub fn port(&self) -> i32 {
+        self.port
+    }
+}
+
+impl MetadataResponseTopic {
+    fn new_error(error_code: i16, name: Option<String>, topic_id: Uuid) -> Self {
+        Self {
+            error_code,
+            name: CompactNullableString::new(name),
+            topic_id,
+            is_internal: false,
+            partitions: CompactArray::empty(),
+            topic_authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    fn from_topic_info(
+        topic_info: &TopicInfo,
+        session: &Session,
+        include_authorized_operations: bool,
+    ) -> Self {
+        let partitions = topic_info
+            .response_partitions()
+            .iter()
+            .flatten()
+            .map(|partition| MetadataResponsePartition {
+                error_code: partition.error_code,
+                partition_index: partition.index,
+                leader_id: partition.leader_id,
+                leader_epoch: partition.leader_epoch,
+                replica_nodes: partition.repica_nodes.clone(),
+                isr_nodes: partition.isr_nodes.clone(),
+                offline_replicas: partition.offline_replicas.clone(),
+                tag_buffer: TagBuffer::default(),
+            })
+            .collect();
+        Self {
+            error_code: 0,
+            name: CompactNullableString::new(Some(topic_info.name.as_str().to_string())),
+            topic_id: topic_info.id,
+            is_internal: topic_info.is_internal,
+            partitions,
+            topic_authorized_operations: if include_authorized_operations {
+                topic_authorized_operations(session, &topic_info.name).bits() as i32
+            } else {
+                AUTHORIZED_OPERATIONS_OMITTED
+            },
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn partition_count(&self) -> usize {
+        self.partitions
+            .as_ref()
+            .map_or(0, |partitions| partitions.len())
+    }
+
+    pub fn topic_authorized_operations(&self) -> i32 {
+        self.topic_authorized_operations
+    }
+}
+
+// 按名称或 topic_id 查找, 都不存在时返回 UNKNOWN_TOPIC_OR_PARTITION
+fn metadata_topic(
+    request_topic: &MetadataRequestTopic,
+    allow_auto_topic_creation: bool,
+    session: &Session,
+    include_authorized_operations: bool,
+) -> MetadataResponseTopic {
+    let name = match request_topic.name.as_deref() {
+        Some(name) => Some(CompactString::new(name.to_string())),
+        None => topic_name(&request_topic.topic_id),
+    };
+    let Some(name) = name else {
+        return MetadataResponseTopic::new_error(
+            UNKNOWN_TOPIC_OR_PARTITION,
+            None,
+            request_topic.topic_id,
+        );
+    };
+    if allow_auto_topic_creation && BROKER_CONFIG.auto_create_topics && !topic_exists(name.as_str())
+    {
+        if let Err(err) = create_topic(name.as_str(), BROKER_CONFIG.num_partitions) {
+            tracing::error!("Failed to create topic {}: {}", name.as_str(), err);
+        }
+    }
+    match TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .get(&name)
+    {
+        Some(topic_info) => MetadataResponseTopic::from_topic_info(
+            topic_info,
+            session,
+            include_authorized_operations,
+        ),
+        None => MetadataResponseTopic::new_error(
+            UNKNOWN_TOPIC_OR_PARTITION,
+            Some(name.as_str().to_string()),
+            request_topic.topic_id,
+        ),
+    }
+}
+
+fn metadata_topics(body: &MetadataRequestBodyV12, session: &Session) -> Vec<MetadataResponseTopic> {
+    let include_authorized_operations = body.include_topic_authorized_operations;
+    if !is_metadata_ready() {
+        return body
+            .topics
+            .iter()
+            .flatten()
+            .map(|request_topic| {
+                MetadataResponseTopic::new_error(
+                    COORDINATOR_LOAD_IN_PROGRESS,
+                    request_topic.name.as_deref().map(str::to_string),
+                    request_topic.topic_id,
+                )
+            })
+            .collect();
+    }
+    match body.topics.as_ref() {
+        Some(request_topics) => request_topics
+            .iter()
+            .map(|request_topic| {
+                metadata_topic(
+                    request_topic,
+                    body.allow_auto_topic_creation,
+                    session,
+                    include_authorized_operations,
+                )
+            })
+            .collect(),
+        None => {
+            let topic_info_map = TOPIC_INFO_MAP
+                .lock()
+                .expect("Failed to get TOPIC_INFO_MAP lock");
+            let mut topics: Vec<&TopicInfo> = topic_info_map.values().collect();
+            topics.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+            topics
+                .into_iter()
+                .map(|topic_info| {
+                    MetadataResponseTopic::from_topic_info(
+                        topic_info,
+                        session,
+                        include_authorized_operations,
+                    )
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn execute_metadata(
+    header: &RequestHeaderV2,
+    body: &MetadataRequestBodyV12,
+    session: &Session,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < METADATA_API_INFO.min_version
+        || request_api_version > METADATA_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::MetadataV12(MetadataResponseBodyV12::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let brokers = brokers()
+        .into_iter()
+        .map(|broker| MetadataResponseBroker {
+            node_id: broker.id,
+            host: CompactString::new(broker.host),
+            port: broker.port,
+            rack: CompactNullableString::new(broker.rack),
+            tag_buffer: TagBuffer::default(),
+        })
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::MetadataV12(MetadataResponseBodyV12 {
+            throttle_time_ms: Millis(0),
+            brokers,
+            cluster_id: CompactNullableString::new(None),
+            controller_id: LOCAL_BROKER_ID,
+            topics: CompactArray::from_vec(metadata_topics(body, session)),
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
@@ -1,21 +1,40 @@
 use std::{
     collections::HashMap,
-    fs,
-    io::Cursor,
-    path::Path,
-    sync::{Arc, Mutex},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    mem,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicI16, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 
 use bytes::Buf;
 use lazy_static::lazy_static;
+use memmap2::Mmap;
+use tokio::sync::Notify;
 use uuid::Uuid;
 
 use crate::{
-    common_struct::{display_bytes, CompactArray, CompactString, RecordBatch, RecordValue},
+    api_versions::MetadataVersion,
+    broker_config::BROKER_CONFIG,
+    clock::now_millis,
+    common_struct::{
+        Array, CompactArray, CompactString, MetadataAttributes, ParitionRecord, Record,
+        RecordBatch, RecordHeaders, RecordKey, RecordType, RecordValue, RegisterBrokerRecord,
+        TagBuffer, TopicRecord, VarInt, VarLong, NO_PRODUCER_EPOCH, NO_PRODUCER_ID, NO_SEQUENCE,
+        RECORD_BATCH_CRC_START, RECORD_BATCH_MAGIC, RECORD_BATCH_RECORDS_START,
+    },
     decode::{Decode, DecodeError, DecodeResult},
-    describe_topic_partitions::{TopicAuthorizedOperations, TopicInfo, TopicPartition},
+    describe_topic_partitions::{RepicaNode, TopicAuthorizedOperations, TopicInfo, TopicPartition},
+    encode::Encode,
 };
 
+// produce 持有写锁, fetch 持有读锁
+pub type PartitionLock = Arc<RwLock<()>>;
+
 lazy_static! {
     pub static ref TOPIC_ID_NAME_MAP: Arc<Mutex<HashMap<Uuid, CompactString>>> =
         Arc::new(Mutex::new(HashMap::new()));
@@ -23,6 +42,96 @@ lazy_static! {
         Arc::new(Mutex::new(HashMap::new()));
     pub static ref TOPIC_RECORD_BATCH_MAP: Arc<Mutex<HashMap<CompactString, Vec<RecordBatch>>>> =
         Arc::new(Mutex::new(HashMap::new()));
+    // RegisterBrokerRecord 中的 broker, 以 broker id 为 key
+    pub static ref BROKERS: Arc<Mutex<HashMap<i32, BrokerInfo>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    static ref METADATA_LOADED: Notify = Notify::new();
+    // 创建 topic 时需要串行写入 metadata log, 避免同一个 topic 被创建两次
+    static ref CREATE_TOPIC_LOCK: Mutex<()> = Mutex::new(());
+    // 每个 partition 追加数据后唤醒等待中的 fetch
+    static ref PARTITION_NOTIFY_MAP: Mutex<HashMap<(String, i32), Arc<Notify>>> =
+        Mutex::new(HashMap::new());
+    // 同一个 partition 的 produce 串行执行以保证 offset 有序, fetch 之间可以并发, 不同 partition 互不影响
+    static ref PARTITION_LOCK_MAP: Mutex<HashMap<(String, i32), PartitionLock>> =
+        Mutex::new(HashMap::new());
+}
+
+// server.properties 中的 node.id, 自动创建的 partition 都由本 broker 负责
+pub const LOCAL_BROKER_ID: i32 = 1;
+const LOCAL_BROKER_HOST: &str = "127.0.0.1";
+const LOCAL_BROKER_PORT: i32 = 9092;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerInfo {
+    pub id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+impl BrokerInfo {
+    pub fn local() -> Self {
+        Self {
+            id: LOCAL_BROKER_ID,
+            host: LOCAL_BROKER_HOST.to_string(),
+            port: LOCAL_BROKER_PORT,
+            rack: None,
+        }
+    }
+
+    // 有多个 listener 时使用第一个
+    fn from_record(record: &RegisterBrokerRecord) -> Option<Self> {
+        let end_point = record.end_points.as_ref()?.first()?;
+        Some(Self {
+            id: record.broker_id,
+            host: end_point.host.as_str().to_string(),
+            port: end_point.port as i32,
+            rack: record.rack.as_deref().map(str::to_string),
+        })
+    }
+}
+
+// 按 broker id 排序, metadata log 中没有 broker 时只返回本 broker
+pub fn brokers() -> Vec<BrokerInfo> {
+    let mut brokers: Vec<BrokerInfo> = BROKERS
+        .lock()
+        .expect("Failed to get BROKERS lock")
+        .values()
+        .cloned()
+        .collect();
+    if brokers.is_empty() {
+        brokers.push(BrokerInfo::local());
+    }
+    brokers.sort_by_key(|broker| broker.id);
+    brokers
+}
+
+static METADATA_READY: AtomicBool = AtomicBool::new(false);
+
+pub const METADATA_VERSION_FEATURE: &str = "metadata.version";
+
+// 以 metadata log 中最后一条 metadata.version 的 FeatureLevelRecord 为准
+static METADATA_VERSION: AtomicI16 = AtomicI16::new(MetadataVersion::LATEST);
+
+pub fn metadata_version() -> i16 {
+    METADATA_VERSION.load(Ordering::Acquire)
+}
+
+pub fn is_metadata_ready() -> bool {
+    METADATA_READY.load(Ordering::Acquire)
+}
+
+pub async fn wait_metadata_ready() {
+    loop {
+        let notified = METADATA_LOADED.notified();
+        tokio::pin!(notified);
+        // 先注册再检查, 避免错过 init_read_metadata_log 中的 notify_waiters
+        notified.as_mut().enable();
+        if is_metadata_ready() {
+            return;
+        }
+        notified.await;
+    }
 }
 
 #[derive(Debug)]
@@ -40,7 +149,7 @@ impl MetadataLog {
     }
 }
 
-fn init_internal_states(metadata_log: &MetadataLog) {
+pub fn init_internal_states(metadata_log: &MetadataLog) {
     let mut topic_info_array = vec![];
     for record_batch in metadata_log.get_record_batches() {
         let mut found = false;
@@ -54,6 +163,20 @@ fn init_internal_states(metadata_log: &MetadataLog) {
         if let Some(records) = record_batch.get_records().get_inner() {
             for record in records {
                 match record.get_value() {
+                    RecordValue::RegisterBroker(register_broker) => {
+                        match BrokerInfo::from_record(register_broker) {
+                            Some(broker) => {
+                                BROKERS
+                                    .lock()
+                                    .expect("Failed to get BROKERS lock")
+                                    .insert(broker.id, broker);
+                            }
+                            None => tracing::warn!(
+                                "Broker {} is registered without any endpoint",
+                                register_broker.broker_id
+                            ),
+                        }
+                    }
                     RecordValue::Topic(topic) => {
                         found = true;
                         topic_info.name = topic.name.clone();
@@ -61,6 +184,23 @@ fn init_internal_states(metadata_log: &MetadataLog) {
                     }
                     RecordValue::Partition(partition) => {
                         found = true;
+                        let eligible_leader_replicas =
+                            partition.eligible_leader_replicas().unwrap_or_else(|err| {
+                                tracing::warn!(
+                                    "Invalid eligible_leader_replicas of partition {}: {}",
+                                    partition.parition_id,
+                                    err
+                                );
+                                CompactArray::empty()
+                            });
+                        let last_known_elr = partition.last_known_elr().unwrap_or_else(|err| {
+                            tracing::warn!(
+                                "Invalid last_known_elr of partition {}: {}",
+                                partition.parition_id,
+                                err
+                            );
+                            CompactArray::empty()
+                        });
                         let topic_partition = TopicPartition {
                             error_code: 0,                //TODO 包含在哪里
                             index: partition.parition_id, //TODO 是否是同一个属性
@@ -68,10 +208,11 @@ fn init_internal_states(metadata_log: &MetadataLog) {
                             leader_epoch: partition.leader_epoch,
                             repica_nodes: partition.replica_nodes.clone(),
                             isr_nodes: partition.isr_nodes.clone(),
-                            eligible_leader_replicas: CompactArray::empty(), //TODO 包含在哪里
-                            last_known_elr: CompactArray::empty(),           //TODO 包含在哪里
-                            offline_replicas: CompactArray::empty(),         //TODO 包含在哪里
-                            tag_buffer: partition.tag_buffers.clone(),
+                            eligible_leader_replicas,
+                            last_known_elr,
+                            offline_replicas: CompactArray::empty(), //TODO 包含在哪里
+                            // record 中的 tagged fields 不属于响应, 不能直接复制
+                            tag_buffer: TagBuffer::default(),
                         };
                         topic_info
                             .partitions_array
@@ -79,6 +220,11 @@ fn init_internal_states(metadata_log: &MetadataLog) {
                             .unwrap()
                             .push(topic_partition);
                     }
+                    RecordValue::FeatureLevel(feature_level)
+                        if feature_level.name.as_str() == METADATA_VERSION_FEATURE =>
+                    {
+                        METADATA_VERSION.store(feature_level.feature_level, Ordering::Release);
+                    }
                     _ => {}
                 }
             }
@@ -88,13 +234,17 @@ fn init_internal_states(metadata_log: &MetadataLog) {
                 .lock()
                 .expect("Failed to get TOPIC_RECORD_BATCH_MAP lock");
             let mut record_batch = record_batch.clone();
+            // base_offset 是 record 的 offset, 而不是 batch 的下标
             match topic_record_batch_map.get_mut(&topic_info.name) {
                 None => {
                     record_batch.base_offset = 0;
                     topic_record_batch_map.insert(topic_info.name.clone(), vec![record_batch]);
                 }
                 Some(array) => {
-                    record_batch.base_offset = array.len() as i64;
+                    record_batch.base_offset = array
+                        .last()
+                        .map(|last_batch| last_batch.next_offset())
+                        .unwrap_or(0);
                     array.push(record_batch);
                 }
             }
@@ -115,20 +265,86 @@ fn init_internal_states(metadata_log: &MetadataLog) {
     }
 }
 
+pub fn check_batch_offsets(record_batches: &[RecordBatch]) -> DecodeResult<()> {
+    for (prev_batch, batch) in record_batches.iter().zip(record_batches.iter().skip(1)) {
+        if batch.base_offset != prev_batch.next_offset() {
+            return Err(DecodeError::Other(
+                format!(
+                    "Inconsistent base offset {}, previous batch starts at {} and ends at {}",
+                    batch.base_offset,
+                    prev_batch.base_offset,
+                    prev_batch.next_offset() - 1
+                )
+                .into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub enum LogContent {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for LogContent {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            LogContent::Mapped(mmap) => mmap,
+            LogContent::Owned(content) => content,
+        }
+    }
+}
+
+// log 文件只会追加写入, 不会被截断, 映射期间读取的内容始终有效
+fn map_log_file(path: &Path) -> Option<Mmap> {
+    match File::open(path).and_then(|file| unsafe { Mmap::map(&file) }) {
+        Ok(mmap) => Some(mmap),
+        Err(err) => {
+            tracing::debug!(
+                "Failed to mmap {}, fall back to read: {}",
+                path.to_string_lossy(),
+                err
+            );
+            None
+        }
+    }
+}
+
+// use_mmap 为 true 时尝试 mmap, 失败时 (例如平台不支持) 退回 fs::read
+pub fn read_log_content(path: &Path, use_mmap: bool) -> std::io::Result<LogContent> {
+    if let Some(mmap) = use_mmap.then(|| map_log_file(path)).flatten() {
+        return Ok(LogContent::Mapped(mmap));
+    }
+    Ok(LogContent::Owned(fs::read(path)?)) //TODO 支持异步
+}
+
+pub fn decode_record_batches(log_content: &[u8]) -> DecodeResult<Vec<RecordBatch>> {
+    let mut buffer = Cursor::new(log_content);
+    let mut record_batches = vec![];
+    while buffer.has_remaining() {
+        let position = buffer.position();
+        let record_batch =
+            RecordBatch::decode(&mut buffer).map_err(|err| err.at_position(position))?; // loop 循环 decode
+        record_batches.push(record_batch);
+    }
+    Ok(record_batches)
+}
+
 pub fn read_record_batches(path: &Path) -> DecodeResult<Vec<RecordBatch>> {
     if path.exists() {
-        let log_content = fs::read(path)?; //TODO 支持异步
+        let log_content = read_log_content(path, BROKER_CONFIG.mmap)?;
         // tracing::debug!(
         //     "Read: {:?}\nContent:\n{}",
         //     path,
         //     display_bytes(&log_content)
         // );
 
-        let mut buffer = Cursor::new(log_content.as_ref());
-        let mut record_batches = vec![];
-        while buffer.has_remaining() {
-            let record_batch = RecordBatch::decode(&mut buffer)?; // loop 循环 decode
-            record_batches.push(record_batch);
+        let record_batches = decode_record_batches(&log_content)?;
+        if let Err(err) = check_batch_offsets(&record_batches) {
+            tracing::warn!("{}: {}", path.to_string_lossy(), err);
         }
         Ok(record_batches)
     } else {
@@ -138,13 +354,451 @@ pub fn read_record_batches(path: &Path) -> DecodeResult<Vec<RecordBatch>> {
     }
 }
 
+// base_offset(i64) + batch_length(i32)
+pub const LOG_OVERHEAD: usize = 12;
+
+pub const LOG_DIR: &str = "/tmp/kraft-combined-logs";
+
+// 目前每个 partition 只有一个 segment
+pub const FIRST_SEGMENT_BASE_OFFSET: i64 = 0;
+
+pub fn partition_log_path(topic: &str, partition: i32) -> PathBuf {
+    segment_log_path(topic, partition, FIRST_SEGMENT_BASE_OFFSET)
+}
+
+// segment 文件以 base offset 补齐 20 位命名
+pub fn segment_log_path(topic: &str, partition: i32, base_offset: i64) -> PathBuf {
+    Path::new(LOG_DIR)
+        .join(format!("{}-{}", topic, partition))
+        .join(format!("{:020}.log", base_offset))
+}
+
+pub fn partition_notify(topic: &str, partition: i32) -> Arc<Notify> {
+    PARTITION_NOTIFY_MAP
+        .lock()
+        .expect("Failed to get PARTITION_NOTIFY_MAP lock")
+        .entry((topic.to_string(), partition))
+        .or_default()
+        .clone()
+}
+
+// produce 持有写锁, fetch 持有读锁
+pub fn partition_lock(topic: &str, partition: i32) -> PartitionLock {
+    PARTITION_LOCK_MAP
+        .lock()
+        .expect("Failed to get PARTITION_LOCK_MAP lock")
+        .entry((topic.to_string(), partition))
+        .or_default()
+        .clone()
+}
+
+// base_offset, batch_length, partition_leader_epoch, magic, crc, attributes, last_offset_delta
+const BATCH_HEADER_LEN: usize = RECORD_BATCH_CRC_START + 6;
+
+// 不 decode records 就能从 header 中得到的信息, 用来跳过 batch
+struct BatchHeader {
+    batch_length: i32,
+    next_offset: i64,
+}
+
+impl BatchHeader {
+    fn decode(header: &[u8]) -> DecodeResult<Self> {
+        let base_offset = i64::decode(&mut Cursor::new(&header[..8]))?;
+        let batch_length = i32::decode(&mut Cursor::new(&header[8..LOG_OVERHEAD]))?;
+        let last_offset_delta =
+            i32::decode(&mut Cursor::new(&header[RECORD_BATCH_CRC_START + 2..]))?;
+        // 长度不足一个 header 时认为 log 已损坏
+        if batch_length < (RECORD_BATCH_RECORDS_START - LOG_OVERHEAD) as i32 {
+            return Err(DecodeError::Other(
+                format!("Invalid batch length {}", batch_length).into(),
+            ));
+        }
+        Ok(Self {
+            batch_length,
+            next_offset: base_offset + last_offset_delta as i64 + 1,
+        })
+    }
+
+    fn batch_size(&self) -> u64 {
+        LOG_OVERHEAD as u64 + self.batch_length as u64
+    }
+}
+
+// 下一条 record 的 offset, 文件不存在时从 0 开始
+// 只读取每个 batch 的 header 并跳过 records, produce 时不需要 decode 整个 log
+pub fn log_end_offset(path: &Path) -> DecodeResult<i64> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut header = [0_u8; BATCH_HEADER_LEN];
+    let mut position = 0;
+    let mut end_offset = 0;
+    while position < file_len {
+        reader.read_exact(&mut header)?;
+        let batch_header = BatchHeader::decode(&header).map_err(|err| err.at_position(position))?;
+        let batch_end = position + batch_header.batch_size();
+        // 超出文件结尾 (写入被中断) 时认为 log 已损坏
+        if batch_end > file_len {
+            return Err(DecodeError::Other(
+                format!("Invalid batch length {}", batch_header.batch_length).into(),
+            )
+            .at_position(position));
+        }
+        reader.seek_relative((batch_end - position) as i64 - header.len() as i64)?;
+        end_offset = batch_header.next_offset;
+        position = batch_end;
+    }
+    Ok(end_offset)
+}
+
+// 跳过 log 开头所有 offset 都小于 fetch_offset 的 batch, 返回第一个需要读取的 batch 的位置
+// 只读取 batch header, 被跳过的 batch 不会被 decode
+fn skip_batches_before(log_content: &[u8], fetch_offset: i64) -> DecodeResult<usize> {
+    let mut position = 0;
+    while let Some(header) = log_content.get(position..position + BATCH_HEADER_LEN) {
+        let batch_header =
+            BatchHeader::decode(header).map_err(|err| err.at_position(position as u64))?;
+        if batch_header.next_offset > fetch_offset {
+            break;
+        }
+        position += batch_header.batch_size() as usize;
+    }
+    Ok(position.min(log_content.len()))
+}
+
+pub struct RecordBatchReader {
+    reader: BufReader<File>,
+    position: u64,
+}
+
+impl RecordBatchReader {
+    pub fn open(path: &Path) -> DecodeResult<Self> {
+        Self::open_at(path, 0)
+    }
+
+    // 从文件的 position 处开始读取, position 必须是某个 batch 的起始位置
+    pub fn open_at(path: &Path, position: u64) -> DecodeResult<Self> {
+        if path.exists() {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(position))?;
+            Ok(Self {
+                reader: BufReader::new(file),
+                position,
+            })
+        } else {
+            Err(DecodeError::Other(
+                format!("Cannot find log file: {}", path.to_string_lossy()).into(),
+            ))
+        }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    // 跳过所有 offset 都小于 fetch_offset 的 batch, 只读取 batch header
+    pub fn skip_batches_before(&mut self, fetch_offset: i64) -> DecodeResult<()> {
+        let mut header = [0_u8; BATCH_HEADER_LEN];
+        while !self.reader.fill_buf()?.is_empty() {
+            let position = self.position;
+            self.reader
+                .read_exact(&mut header)
+                .map_err(|err| DecodeError::from(err).at_position(position))?;
+            let batch_header =
+                BatchHeader::decode(&header).map_err(|err| err.at_position(position))?;
+            if batch_header.next_offset > fetch_offset {
+                self.reader.seek_relative(-(header.len() as i64))?;
+                break;
+            }
+            self.reader
+                .seek_relative(batch_header.batch_size() as i64 - header.len() as i64)?;
+            self.position += batch_header.batch_size();
+        }
+        Ok(())
+    }
+
+    fn read_next(&mut self) -> DecodeResult<Option<RecordBatch>> {
+        let position = self.position;
+        self.read_batch().map_err(|err| err.at_position(position))
+    }
+
+    fn read_batch(&mut self) -> DecodeResult<Option<RecordBatch>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let mut batch_buffer = vec![0_u8; LOG_OVERHEAD];
+        self.reader.read_exact(&mut batch_buffer)?;
+        let batch_length = i32::decode(&mut Cursor::new(&batch_buffer[8..]))?;
+        if batch_length < 0 {
+            return Err(DecodeError::Other(
+                format!("Invalid batch length {}", batch_length).into(),
+            ));
+        }
+        batch_buffer.resize(LOG_OVERHEAD + batch_length as usize, 0);
+        self.reader.read_exact(&mut batch_buffer[LOG_OVERHEAD..])?;
+
+        let record_batch = RecordBatch::decode(&mut Cursor::new(batch_buffer.as_slice()))?;
+        self.position += batch_buffer.len() as u64;
+        Ok(Some(record_batch))
+    }
+}
+
+impl Iterator for RecordBatchReader {
+    type Item = DecodeResult<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_next().transpose()
+    }
+}
+
+// 从 fetch_offset 所在的 batch 开始逐个 batch 读取, 直到累计大小达到 max_bytes, 不会把整个文件读入内存
+// 第一个 batch 即使超过 max_bytes 也会返回, 否则消费者永远无法越过这个 batch
+pub fn read_record_batches_limited(
+    path: &Path,
+    fetch_offset: i64,
+    max_bytes: usize,
+) -> DecodeResult<Vec<RecordBatch>> {
+    if BROKER_CONFIG.mmap && path.exists() {
+        if let Some(mmap) = map_log_file(path) {
+            let start = skip_batches_before(&mmap, fetch_offset)?;
+            let mut buffer = Cursor::new(&mmap[start..]);
+            let batches = std::iter::from_fn(|| {
+                buffer
+                    .has_remaining()
+                    .then(|| RecordBatch::decode(&mut buffer))
+            });
+            return take_record_batches(batches, max_bytes);
+        }
+    }
+    let mut reader = RecordBatchReader::open(path)?;
+    reader.skip_batches_before(fetch_offset)?;
+    take_record_batches(reader, max_bytes)
+}
+
+pub fn take_record_batches(
+    batches: impl Iterator<Item = DecodeResult<RecordBatch>>,
+    max_bytes: usize,
+) -> DecodeResult<Vec<RecordBatch>> {
+    let mut record_batches = vec![];
+    let mut total_bytes = 0;
+    for record_batch in batches {
+        let record_batch = record_batch?;
+        let batch_size = LOG_OVERHEAD + record_batch.batch_length as usize;
+        if !record_batches.is_empty() && total_bytes + batch_size > max_bytes {
+            break;
+        }
+        total_bytes += batch_size;
+        record_batches.push(record_batch);
+    }
+    Ok(record_batches)
+}
+
+pub fn split_batches_at_size(batches: Vec<RecordBatch>, max_bytes: usize) -> Vec<Vec<RecordBatch>> {
+    let mut segments = vec![];
+    let mut segment = vec![];
+    let mut segment_size = 0;
+    for batch in batches {
+        // encoded_len 不需要重新 encode batch
+        let batch_size = batch.encoded_len();
+        // batch 不能跨 segment, 超过 max_bytes 的 batch 单独占一个 segment
+        if !segment.is_empty() && segment_size + batch_size > max_bytes {
+            segments.push(mem::take(&mut segment));
+            segment_size = 0;
+        }
+        segment_size += batch_size;
+        segment.push(batch);
+    }
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+// 只用于测试: 清空 init_internal_states 写入的全局状态, 使每个测试从空的 metadata 开始
+// 服务运行时调用会让正在处理的请求看到不一致的 topic, 不能在 broker 中使用
+// 加锁顺序与 init_internal_states 相同, 避免与并发的读取死锁
+#[cfg(feature = "test-utils")]
+pub fn reset_metadata_state() {
+    TOPIC_RECORD_BATCH_MAP
+        .lock()
+        .expect("Failed to get TOPIC_RECORD_BATCH_MAP lock")
+        .clear();
+    let mut topic_id_name_map = TOPIC_ID_NAME_MAP
+        .lock()
+        .expect("Failed to get TOPIC_ID_NAME_MAP lock");
+    let mut topic_info_map = TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock");
+    topic_id_name_map.clear();
+    topic_info_map.clear();
+    drop(topic_info_map);
+    drop(topic_id_name_map);
+    BROKERS.lock().expect("Failed to get BROKERS lock").clear();
+    METADATA_VERSION.store(MetadataVersion::LATEST, Ordering::Release);
+}
+
 pub fn init_read_metadata_log() -> DecodeResult<()> {
-    let metadata_log_file =
-        Path::new("/tmp/kraft-combined-logs/__cluster_metadata-0/00000000000000000000.log");
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
     // let metadata_log_file = Path::new("tmp/demo.bin");
-    let record_batches = read_record_batches(metadata_log_file)?;
+    let record_batches = read_record_batches(&metadata_log_file)?;
     let metadata_log = MetadataLog::new(record_batches);
     init_internal_states(&metadata_log);
 
+    METADATA_READY.store(true, Ordering::Release);
+    METADATA_LOADED.notify_waiters();
     Ok(())
 }
+
+pub fn topic_exists(name: &str) -> bool {
+    TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .contains_key(&CompactString::new(name.to_string()))
+}
+
+// 以下的访问函数只在读取时持有锁, 只复制需要的字段
+pub fn partition_count(topic: &str) -> Option<usize> {
+    TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .get(&CompactString::new(topic.to_string()))
+        .map(|topic_info| {
+            topic_info
+                .partitions_array
+                .as_ref()
+                .map_or(0, |partitions| partitions.len())
+        })
+}
+
+// partition 不存在时返回 None
+pub fn partition_leader_epoch(topic: &str, partition: i32) -> Option<i32> {
+    TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .get(&CompactString::new(topic.to_string()))
+        .and_then(|topic_info| topic_info.partitions_array.as_ref())
+        .and_then(|partitions| partitions.iter().find(|p| p.index == partition))
+        .map(|partition| partition.leader_epoch)
+}
+
+pub fn topic_id(name: &str) -> Option<Uuid> {
+    TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .get(&CompactString::new(name.to_string()))
+        .map(|topic_info| topic_info.id)
+}
+
+pub fn topic_name(id: &Uuid) -> Option<CompactString> {
+    TOPIC_ID_NAME_MAP
+        .lock()
+        .expect("Failed to get TOPIC_ID_NAME_MAP lock")
+        .get(id)
+        .cloned()
+}
+
+pub fn new_metadata_record(offset_delta: i32, value: RecordValue) -> Record {
+    let mut record = Record {
+        length: VarInt::from_i64(0),
+        attributes: 0,
+        timestamp_delta: VarLong::from_i128(0),
+        offset_delta: VarInt::from_i64(offset_delta as i64),
+        key: RecordKey::new(None),
+        value,
+        headers: RecordHeaders::default(),
+    };
+    // length 不包含自身
+    let length = record.encode().len() - record.length.as_bytes().len();
+    record.length = VarInt::from_i64(length as i64);
+    record
+}
+
+pub fn new_metadata_record_batch(
+    base_offset: i64,
+    timestamp: i64,
+    records: Vec<Record>,
+) -> RecordBatch {
+    let mut record_batch = RecordBatch {
+        base_offset,
+        batch_length: 0,
+        partition_leader_epoch: 0,
+        magic_byte: RECORD_BATCH_MAGIC,
+        crc: 0,
+        attributes: MetadataAttributes::empty(),
+        last_offset_delta: records.len() as i32 - 1,
+        base_timestamp: timestamp,
+        max_timestamp: timestamp,
+        producer_id: NO_PRODUCER_ID,
+        producer_epoch: NO_PRODUCER_EPOCH,
+        base_sequence: NO_SEQUENCE,
+        records: Array::from_vec(records),
+        compressed_records: None,
+    };
+    record_batch.batch_length = (record_batch.encode().len() - LOG_OVERHEAD) as i32;
+    record_batch.crc = record_batch.compute_crc() as i32;
+    record_batch
+}
+
+// 将 TopicRecord 和 PartitionRecord 追加到 metadata log, 并更新内存中的状态
+// 返回新 topic 的 id, topic 已存在时返回 None
+pub fn create_topic(name: &str, num_partitions: i32) -> Result<Option<Uuid>, String> {
+    let _guard = CREATE_TOPIC_LOCK
+        .lock()
+        .expect("Failed to get CREATE_TOPIC_LOCK lock");
+    if topic_exists(name) {
+        return Ok(None);
+    }
+
+    let topic_id = Uuid::new_v4();
+    let mut records = vec![new_metadata_record(
+        0,
+        RecordValue::Topic(TopicRecord {
+            frame_version: 1,
+            record_type: RecordType::TOPIC_RECORD,
+            version: 0,
+            name: CompactString::new(name.to_string()),
+            id: topic_id,
+            tag_buffers: TagBuffer::default(),
+        }),
+    )];
+    for partition_id in 0..num_partitions {
+        records.push(new_metadata_record(
+            partition_id + 1,
+            RecordValue::Partition(ParitionRecord {
+                frame_version: 1,
+                record_type: RecordType::PARITION_RECORD,
+                version: 0,
+                parition_id: partition_id,
+                topic_id,
+                replica_nodes: CompactArray::from_vec(vec![RepicaNode::new(LOCAL_BROKER_ID)]),
+                isr_nodes: CompactArray::from_vec(vec![RepicaNode::new(LOCAL_BROKER_ID)]),
+                removing_replicas_nodes: CompactArray::empty(),
+                adding_replicas_nodes: CompactArray::empty(),
+                leader_id: LOCAL_BROKER_ID,
+                leader_epoch: 0,
+                partition_epoch: 0,
+                directories: CompactArray::empty(),
+                tag_buffers: TagBuffer::default(),
+            }),
+        ));
+    }
+
+    let metadata_log_file = partition_log_path("__cluster_metadata", 0);
+    let timestamp = now_millis();
+    let base_offset = log_end_offset(&metadata_log_file).map_err(|err| err.to_string())?;
+    let record_batch = new_metadata_record_batch(base_offset, timestamp, records);
+    let content = record_batch.try_encode().map_err(|err| err.to_string())?;
+    OpenOptions::new()
+        .append(true)
+        .open(&metadata_log_file)
+        .and_then(|mut file| file.write_all(&content))
+        .map_err(|err| err.to_string())?;
+
+    init_internal_states(&MetadataLog::new(vec![record_batch]));
+    tracing::info!("Created topic {} with {} partitions", name, num_partitions);
+    Ok(Some(topic_id))
+}
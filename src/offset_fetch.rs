@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    decode::Decode,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{partition_count, topic_id, topic_name},
+    offset_for_leader_epoch::UNDEFINED_EPOCH,
+    offsets::{get_committed, group_committed, NO_COMMITTED_OFFSET},
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+lazy_static! {
+    pub static ref OFFSET_FETCH_API_INFO: ApiKey = ApiKey::new(9, 9, 9, TagBuffer::default());
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchRequestBodyV9 {
+    groups: CompactArray<OffsetFetchRequestGroup>,
+    require_stable: bool,
+    tag_buffer: TagBuffer,
+}
+
+// topics 为 null 时返回 group 提交过的所有 partition
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchRequestGroup {
+    group_id: CompactString,
+    member_id: CompactNullableString,
+    member_epoch: i32,
+    topics: CompactArray<OffsetFetchRequestTopic>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchRequestTopic {
+    name: CompactString,
+    partition_indexes: CompactArray<i32>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchResponseBodyV9 {
+    throttle_time_ms: Millis,
+    groups: CompactArray<OffsetFetchResponseGroup>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchResponseGroup {
+    group_id: CompactString,
+    topics: CompactArray<OffsetFetchResponseTopic>,
+    error_code: i16,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchResponseTopic {
+    name: CompactString,
+    partitions: CompactArray<OffsetFetchResponsePartition>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetFetchResponsePartition {
+    partition_index: i32,
+    committed_offset: i64,
+    committed_leader_epoch: i32,
+    metadata: CompactNullableString,
+    error_code: i16,
+    tag_buffer: TagBuffer,
+}
+
+impl OffsetFetchResponseBodyV9 {
+    // 每个 group 返回 group 级别的错误码
+    pub fn error(body: &OffsetFetchRequestBodyV9, error_code: i16) -> Self {
+        Self {
+            throttle_time_ms: Millis(0),
+            groups: body
+                .groups
+                .iter()
+                .flatten()
+                .map(|group| OffsetFetchResponseGroup {
+                    group_id: group.group_id.clone(),
+                    topics: CompactArray::empty(),
+                    error_code,
+                    tag_buffer: TagBuffer::default(),
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn groups(&self) -> &CompactArray<OffsetFetchResponseGroup> {
+        &self.groups
+    }
+}
+
+impl OffsetFetchResponseGroup {
+    pub fn group_id(&self) -> &str {
+        self.group_id.as_str()
+    }
+
+    pub fn topics(&self) -> &CompactArray<OffsetFetchResponseTopic> {
+        &self.topics
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+}
+
+impl OffsetFetchResponseTopic {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn partitions(&self) -> &CompactArray<OffsetFetchResponsePartition> {
+        &self.partitions
+    }
+}
+
+impl OffsetFetchResponsePartition {
+    fn new(partition_index: i32, committed_offset: i64, error_code: i16) -> Self {
+        Self {
+            partition_index,
+            committed_offset,
+            committed_leader_epoch: UNDEFINED_EPOCH,
+            metadata: CompactNullableString::new(Some(String::new())),
+            error_code,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn partition_index(&self) -> i32 {
+        self.partition_index
+    }
+
+    pub fn committed_offset(&self) -> i64 {
+        self.committed_offset
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+}
+
+// 不存在的 topic 或 partition 返回 UNKNOWN_TOPIC_OR_PARTITION, 没有提交过的返回 -1
+fn fetch_topic_offsets(
+    group_id: &str,
+    topic: &OffsetFetchRequestTopic,
+) -> OffsetFetchResponseTopic {
+    let name = topic.name.as_str();
+    let topic_id = topic_id(name);
+    let partition_count = partition_count(name).unwrap_or(0);
+    let partitions = topic
+        .partition_indexes
+        .iter()
+        .flatten()
+        .map(|&partition| match topic_id {
+            Some(topic_id) if partition >= 0 && (partition as usize) < partition_count => {
+                let offset =
+                    get_committed(group_id, &topic_id, partition).unwrap_or(NO_COMMITTED_OFFSET);
+                OffsetFetchResponsePartition::new(partition, offset, 0)
+            }
+            _ => OffsetFetchResponsePartition::new(
+                partition,
+                NO_COMMITTED_OFFSET,
+                UNKNOWN_TOPIC_OR_PARTITION,
+            ),
+        })
+        .collect();
+    OffsetFetchResponseTopic {
+        name: topic.name.clone(),
+        partitions,
+        tag_buffer: TagBuffer::default(),
+    }
+}
+
+// 已经删除的 topic 不再返回
+fn fetch_all_offsets(group_id: &str) -> Vec<OffsetFetchResponseTopic> {
+    let mut topics: BTreeMap<String, Vec<OffsetFetchResponsePartition>> = BTreeMap::new();
+    for (topic_id, partition, offset) in group_committed(group_id) {
+        if let Some(name) = topic_name(&topic_id) {
+            topics
+                .entry(name.as_str().to_string())
+                .or_default()
+                .push(OffsetFetchResponsePartition::new(partition, offset, 0));
+        }
+    }
+    topics
+        .into_iter()
+        .map(|(name, partitions)| OffsetFetchResponseTopic {
+            name: CompactString::new(name),
+            partitions: CompactArray::from_vec(partitions),
+            tag_buffer: TagBuffer::default(),
+        })
+        .collect()
+}
+
+pub fn execute_offset_fetch(
+    header: &RequestHeaderV2,
+    body: &OffsetFetchRequestBodyV9,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < OFFSET_FETCH_API_INFO.min_version
+        || request_api_version > OFFSET_FETCH_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::OffsetFetchV9(OffsetFetchResponseBodyV9::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let groups = body
+        .groups
+        .iter()
+        .flatten()
+        .map(|group| {
+            let group_id = group.group_id.as_str();
+            let topics = match group.topics.as_ref() {
+                Some(topics) => topics
+                    .iter()
+                    .map(|topic| fetch_topic_offsets(group_id, topic))
+                    .collect(),
+                None => fetch_all_offsets(group_id),
+            };
+            OffsetFetchResponseGroup {
+                group_id: group.group_id.clone(),
+                topics: CompactArray::from_vec(topics),
+                error_code: 0,
+                tag_buffer: TagBuffer::default(),
+            }
+        })
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::OffsetFetchV9(OffsetFetchResponseBodyV9 {
+            throttle_time_ms: Millis(0),
+            groups,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
@@ -0,0 +1,263 @@
+use std::path::Path;
+
+use lazy_static::lazy_static;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactString, Millis, TagBuffer},
+    decode::{Decode, DecodeResult},
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{partition_leader_epoch, partition_lock, partition_log_path, RecordBatchReader},
+    produce::CORRUPT_MESSAGE_ERROR,
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+pub const UNDEFINED_EPOCH: i32 = -1;
+pub const UNDEFINED_EPOCH_OFFSET: i64 = -1;
+
+lazy_static! {
+    pub static ref OFFSET_FOR_LEADER_EPOCH_API_INFO: ApiKey =
+        ApiKey::new(23, 4, 4, TagBuffer::default());
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetForLeaderEpochRequestBodyV4 {
+    replica_id: i32,
+    topics: CompactArray<OffsetForLeaderTopic>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetForLeaderTopic {
+    topic: CompactString,
+    partitions: CompactArray<OffsetForLeaderPartition>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetForLeaderPartition {
+    partition: i32,
+    current_leader_epoch: i32,
+    leader_epoch: i32,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetForLeaderEpochResponseBodyV4 {
+    throttle_time_ms: Millis,
+    topics: CompactArray<OffsetForLeaderTopicResult>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetForLeaderTopicResult {
+    topic: CompactString,
+    partitions: CompactArray<EpochEndOffset>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct EpochEndOffset {
+    error_code: i16,
+    partition: i32,
+    leader_epoch: i32,
+    end_offset: i64,
+    tag_buffer: TagBuffer,
+}
+
+impl OffsetForLeaderEpochResponseBodyV4 {
+    // 请求的每个 partition 都返回同一个错误码
+    pub fn error(body: &OffsetForLeaderEpochRequestBodyV4, error_code: i16) -> Self {
+        let topics = body
+            .topics
+            .iter()
+            .flatten()
+            .map(|topic| OffsetForLeaderTopicResult {
+                topic: topic.topic.clone(),
+                partitions: topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|partition| {
+                        EpochEndOffset::new(
+                            error_code,
+                            partition.partition,
+                            UNDEFINED_EPOCH,
+                            UNDEFINED_EPOCH_OFFSET,
+                        )
+                    })
+                    .collect(),
+                tag_buffer: TagBuffer::default(),
+            })
+            .collect();
+        Self {
+            throttle_time_ms: Millis(0),
+            topics,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+}
+
+impl EpochEndOffset {
+    pub fn new(error_code: i16, partition: i32, leader_epoch: i32, end_offset: i64) -> Self {
+        Self {
+            error_code,
+            partition,
+            leader_epoch,
+            end_offset,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn leader_epoch(&self) -> i32 {
+        self.leader_epoch
+    }
+
+    pub fn end_offset(&self) -> i64 {
+        self.end_offset
+    }
+}
+
+// 每个 leader epoch 的第一个 offset, 按 epoch 递增排列, 以及 log end offset
+// 没有 leader epoch (小于 0) 的 batch 不计入
+pub fn read_epoch_start_offsets(path: &Path) -> DecodeResult<(Vec<(i32, i64)>, i64)> {
+    let mut epochs: Vec<(i32, i64)> = vec![];
+    let mut log_end_offset = 0;
+    if !path.exists() {
+        return Ok((epochs, log_end_offset));
+    }
+    for record_batch in RecordBatchReader::open(path)? {
+        let record_batch = record_batch?;
+        let epoch = record_batch.partition_leader_epoch;
+        if epoch >= 0 && epochs.last().map_or(true, |(last, _)| epoch > *last) {
+            epochs.push((epoch, record_batch.base_offset));
+        }
+        log_end_offset = record_batch.next_offset();
+    }
+    Ok((epochs, log_end_offset))
+}
+
+// 与 Kafka 的 LeaderEpochFileCache::endOffsetFor 相同, 返回 (leader_epoch, end_offset)
+// 请求的是最新的 epoch 时返回 log end offset
+// 否则返回不大于请求的最大 epoch, 以及下一个 epoch 的起始 offset
+pub fn end_offset_for_epoch(
+    epochs: &[(i32, i64)],
+    latest_epoch: i32,
+    log_end_offset: i64,
+    requested_epoch: i32,
+) -> (i32, i64) {
+    if requested_epoch == UNDEFINED_EPOCH {
+        return (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET);
+    }
+    if requested_epoch == latest_epoch {
+        return (requested_epoch, log_end_offset);
+    }
+    let higher = epochs
+        .iter()
+        .find(|(epoch, _)| *epoch > requested_epoch)
+        .map(|(_, start_offset)| *start_offset)
+        // 当前 epoch 还没有写入 batch, 它从 log end offset 开始
+        .or((latest_epoch > requested_epoch).then_some(log_end_offset));
+    match higher {
+        None => (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET),
+        Some(end_offset) => {
+            let floor = epochs
+                .iter()
+                .rev()
+                .find(|(epoch, _)| *epoch <= requested_epoch)
+                .map_or(requested_epoch, |(epoch, _)| *epoch);
+            (floor, end_offset)
+        }
+    }
+}
+
+pub fn epoch_end_offset(topic: &str, partition: i32, requested_epoch: i32) -> EpochEndOffset {
+    let Some(current_epoch) = partition_leader_epoch(topic, partition) else {
+        return EpochEndOffset::new(
+            UNKNOWN_TOPIC_OR_PARTITION,
+            partition,
+            UNDEFINED_EPOCH,
+            UNDEFINED_EPOCH_OFFSET,
+        );
+    };
+    let partition_lock = partition_lock(topic, partition);
+    let _guard = partition_lock
+        .read()
+        .expect("Failed to get partition read lock");
+    match read_epoch_start_offsets(&partition_log_path(topic, partition)) {
+        Ok((epochs, log_end_offset)) => {
+            let latest_epoch = epochs
+                .last()
+                .map_or(current_epoch, |(epoch, _)| current_epoch.max(*epoch));
+            let (leader_epoch, end_offset) =
+                end_offset_for_epoch(&epochs, latest_epoch, log_end_offset, requested_epoch);
+            EpochEndOffset::new(0, partition, leader_epoch, end_offset)
+        }
+        Err(err) => {
+            tracing::error!("Corrupt log for {}-{}: {}", topic, partition, err);
+            EpochEndOffset::new(
+                CORRUPT_MESSAGE_ERROR,
+                partition,
+                UNDEFINED_EPOCH,
+                UNDEFINED_EPOCH_OFFSET,
+            )
+        }
+    }
+}
+
+pub fn execute_offset_for_leader_epoch(
+    header: &RequestHeaderV2,
+    body: &OffsetForLeaderEpochRequestBodyV4,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < OFFSET_FOR_LEADER_EPOCH_API_INFO.min_version
+        || request_api_version > OFFSET_FOR_LEADER_EPOCH_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::OffsetForLeaderEpochV4(OffsetForLeaderEpochResponseBodyV4::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let topics = body
+        .topics
+        .iter()
+        .flatten()
+        .map(|topic| OffsetForLeaderTopicResult {
+            topic: topic.topic.clone(),
+            partitions: topic
+                .partitions
+                .iter()
+                .flatten()
+                .map(|partition| {
+                    epoch_end_offset(
+                        topic.topic.as_str(),
+                        partition.partition,
+                        partition.leader_epoch,
+                    )
+                })
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        })
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::OffsetForLeaderEpochV4(OffsetForLeaderEpochResponseBodyV4 {
+            throttle_time_ms: Millis(0),
+            topics,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
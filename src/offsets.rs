@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    common_struct::{CompactArray, CompactNullableString, CompactString, Millis, TagBuffer},
+    decode::Decode,
+    describe_topic_partitions::UNKNOWN_TOPIC_OR_PARTITION,
+    encode::Encode,
+    metadata_log::{partition_count, topic_id},
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+// 没有提交过 offset 的 partition 返回 -1
+pub const NO_COMMITTED_OFFSET: i64 = -1;
+
+// (group_id, topic_id, partition)
+type CommittedOffsetKey = (String, Uuid, i32);
+
+lazy_static! {
+    pub static ref OFFSET_COMMIT_API_INFO: ApiKey = ApiKey::new(8, 9, 9, TagBuffer::default());
+    // committed offset 只保存在内存中, flush_persisted_state 不会写入磁盘, 重启后丢失
+    static ref COMMITTED_OFFSETS: Arc<Mutex<HashMap<CommittedOffsetKey, i64>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+pub fn get_committed(group_id: &str, topic_id: &Uuid, partition: i32) -> Option<i64> {
+    COMMITTED_OFFSETS
+        .lock()
+        .expect("Failed to get COMMITTED_OFFSETS lock")
+        .get(&(group_id.to_string(), *topic_id, partition))
+        .copied()
+}
+
+pub fn set_committed(group_id: &str, topic_id: Uuid, partition: i32, offset: i64) {
+    COMMITTED_OFFSETS
+        .lock()
+        .expect("Failed to get COMMITTED_OFFSETS lock")
+        .insert((group_id.to_string(), topic_id, partition), offset);
+}
+
+// group 提交过的所有 (topic_id, partition, offset), 按 topic_id 和 partition 排序
+pub fn group_committed(group_id: &str) -> Vec<(Uuid, i32, i64)> {
+    let mut committed: Vec<(Uuid, i32, i64)> = COMMITTED_OFFSETS
+        .lock()
+        .expect("Failed to get COMMITTED_OFFSETS lock")
+        .iter()
+        .filter(|((group, _, _), _)| group == group_id)
+        .map(|((_, topic_id, partition), offset)| (*topic_id, *partition, *offset))
+        .collect();
+    committed.sort();
+    committed
+}
+
+// 没有 group coordinator, generation 和 member 不做校验
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitRequestBodyV9 {
+    group_id: CompactString,
+    generation_id_or_member_epoch: i32,
+    member_id: CompactString,
+    group_instance_id: CompactNullableString,
+    topics: CompactArray<OffsetCommitRequestTopic>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitRequestTopic {
+    name: CompactString,
+    partitions: CompactArray<OffsetCommitRequestPartition>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitRequestPartition {
+    partition_index: i32,
+    committed_offset: i64,
+    committed_leader_epoch: i32,
+    committed_metadata: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitResponseBodyV9 {
+    throttle_time_ms: Millis,
+    topics: CompactArray<OffsetCommitResponseTopic>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitResponseTopic {
+    name: CompactString,
+    partitions: CompactArray<OffsetCommitResponsePartition>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct OffsetCommitResponsePartition {
+    partition_index: i32,
+    error_code: i16,
+    tag_buffer: TagBuffer,
+}
+
+impl OffsetCommitResponseBodyV9 {
+    // 请求的每个 partition 都返回同一个错误码
+    pub fn error(body: &OffsetCommitRequestBodyV9, error_code: i16) -> Self {
+        let topics = body
+            .topics
+            .iter()
+            .flatten()
+            .map(|topic| OffsetCommitResponseTopic {
+                name: topic.name.clone(),
+                partitions: topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|partition| OffsetCommitResponsePartition {
+                        partition_index: partition.partition_index,
+                        error_code,
+                        tag_buffer: TagBuffer::default(),
+                    })
+                    .collect(),
+                tag_buffer: TagBuffer::default(),
+            })
+            .collect();
+        Self {
+            throttle_time_ms: Millis(0),
+            topics,
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn topics(&self) -> &CompactArray<OffsetCommitResponseTopic> {
+        &self.topics
+    }
+}
+
+impl OffsetCommitResponseTopic {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn partitions(&self) -> &CompactArray<OffsetCommitResponsePartition> {
+        &self.partitions
+    }
+}
+
+impl OffsetCommitResponsePartition {
+    pub fn partition_index(&self) -> i32 {
+        self.partition_index
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+}
+
+// 不存在的 topic 或 partition 返回 UNKNOWN_TOPIC_OR_PARTITION, 不会写入
+fn commit_topic_offsets(
+    group_id: &str,
+    topic: &OffsetCommitRequestTopic,
+) -> OffsetCommitResponseTopic {
+    let name = topic.name.as_str();
+    let topic_id = topic_id(name);
+    let partition_count = partition_count(name).unwrap_or(0);
+    let partitions = topic
+        .partitions
+        .iter()
+        .flatten()
+        .map(|partition| {
+            let partition_index = partition.partition_index;
+            let error_code = match topic_id {
+                Some(topic_id)
+                    if partition_index >= 0 && (partition_index as usize) < partition_count =>
+                {
+                    set_committed(
+                        group_id,
+                        topic_id,
+                        partition_index,
+                        partition.committed_offset,
+                    );
+                    0
+                }
+                _ => UNKNOWN_TOPIC_OR_PARTITION,
+            };
+            OffsetCommitResponsePartition {
+                partition_index,
+                error_code,
+                tag_buffer: TagBuffer::default(),
+            }
+        })
+        .collect();
+    OffsetCommitResponseTopic {
+        name: topic.name.clone(),
+        partitions,
+        tag_buffer: TagBuffer::default(),
+    }
+}
+
+pub fn execute_offset_commit(
+    header: &RequestHeaderV2,
+    body: &OffsetCommitRequestBodyV9,
+) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < OFFSET_COMMIT_API_INFO.min_version
+        || request_api_version > OFFSET_COMMIT_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::OffsetCommitV9(OffsetCommitResponseBodyV9::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let group_id = body.group_id.as_str();
+    let topics = body
+        .topics
+        .iter()
+        .flatten()
+        .map(|topic| commit_topic_offsets(group_id, topic))
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::OffsetCommitV9(OffsetCommitResponseBodyV9 {
+            throttle_time_ms: Millis(0),
+            topics,
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
@@ -0,0 +1,583 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    api_versions::{ApiKey, UNSUPPORTED_VERSION_ERROR},
+    broker_config::BROKER_CONFIG,
+    clock::now_millis,
+    common_struct::{
+        CompactArray, CompactNullableBytes, CompactNullableString, CompactString, Millis,
+        RecordBatch, TagBuffer, TimestampType, RECORD_BATCH_MAGIC,
+    },
+    configs::topic_timestamp_type,
+    decode::Decode,
+    describe_topic_partitions::{COORDINATOR_LOAD_IN_PROGRESS, UNKNOWN_TOPIC_OR_PARTITION},
+    encode::Encode,
+    metadata_log::{
+        create_topic, decode_record_batches, is_metadata_ready, log_end_offset, partition_lock,
+        partition_log_path, partition_notify, topic_exists, TOPIC_INFO_MAP,
+    },
+    request_message::RequestHeaderV2,
+    response_message::{ResponseBody, ResponseHeader, ResponseMessage},
+};
+
+pub const CORRUPT_MESSAGE_ERROR: i16 = 2;
+pub const MESSAGE_TOO_LARGE_ERROR: i16 = 10;
+pub const OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR: i16 = 45;
+pub const DUPLICATE_SEQUENCE_NUMBER_ERROR: i16 = 46;
+pub const INVALID_PRODUCER_EPOCH_ERROR: i16 = 47;
+pub const KAFKA_STORAGE_ERROR: i16 = 56;
+
+// 与 max.in.flight.requests.per.connection 的上限相同, 重试的 batch 一定在这个范围内
+pub const MAX_RETAINED_BATCHES: usize = 5;
+
+lazy_static! {
+    pub static ref PRODUCE_API_INFO: ApiKey = ApiKey::new(0, 9, 9, TagBuffer::default());
+    // 幂等 producer 在每个 partition 上已写入的 sequence
+    pub static ref PRODUCER_REGISTRY: Mutex<ProducerRegistry> = Mutex::new(ProducerRegistry::new());
+}
+
+// (producer_id, topic, partition)
+pub type ProducerKey = (i64, String, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    Append,
+    // 重试的 batch 已经写入, 返回之前分配的 base_offset
+    Duplicate(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BatchMetadata {
+    first_sequence: i32,
+    last_sequence: i32,
+    base_offset: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProducerState {
+    producer_epoch: i16,
+    batches: VecDeque<BatchMetadata>,
+}
+
+impl ProducerState {
+    pub fn new(producer_epoch: i16) -> Self {
+        Self {
+            producer_epoch,
+            batches: VecDeque::new(),
+        }
+    }
+
+    pub fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
+
+    pub fn last_sequence(&self) -> Option<i32> {
+        self.batches.back().map(|batch| batch.last_sequence)
+    }
+
+    // 新的 epoch 从 sequence 0 开始, 同一 epoch 内 first_sequence 必须紧接 last_sequence
+    // 没有记录的 producer (例如 broker 重启后) 接受任意 sequence
+    pub fn check(
+        &self,
+        producer_epoch: i16,
+        first_sequence: i32,
+        last_sequence: i32,
+    ) -> Result<SequenceCheck, (i16, String)> {
+        if producer_epoch < self.producer_epoch {
+            return Err((
+                INVALID_PRODUCER_EPOCH_ERROR,
+                format!(
+                    "Producer epoch {} is older than {}",
+                    producer_epoch, self.producer_epoch
+                ),
+            ));
+        }
+        if producer_epoch > self.producer_epoch {
+            if first_sequence != 0 {
+                return Err((
+                    OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR,
+                    format!(
+                        "New producer epoch {} must start at sequence 0, got {}",
+                        producer_epoch, first_sequence
+                    ),
+                ));
+            }
+            return Ok(SequenceCheck::Append);
+        }
+
+        if let Some(batch) = self.batches.iter().find(|batch| {
+            batch.first_sequence == first_sequence && batch.last_sequence == last_sequence
+        }) {
+            return Ok(SequenceCheck::Duplicate(batch.base_offset));
+        }
+        let Some(expected) = self.last_sequence().map(next_sequence) else {
+            return Ok(SequenceCheck::Append);
+        };
+        if first_sequence == expected {
+            return Ok(SequenceCheck::Append);
+        }
+        // 落后于 expected 但不是保留的 batch, 说明与之前写入的 sequence 重叠
+        let error_code = if is_sequence_behind(first_sequence, expected) {
+            DUPLICATE_SEQUENCE_NUMBER_ERROR
+        } else {
+            OUT_OF_ORDER_SEQUENCE_NUMBER_ERROR
+        };
+        Err((
+            error_code,
+            format!("Expected sequence {}, got {}", expected, first_sequence),
+        ))
+    }
+
+    pub fn append(
+        &mut self,
+        producer_epoch: i16,
+        first_sequence: i32,
+        last_sequence: i32,
+        base_offset: i64,
+    ) {
+        if producer_epoch != self.producer_epoch {
+            self.producer_epoch = producer_epoch;
+            self.batches.clear();
+        }
+        if self.batches.len() == MAX_RETAINED_BATCHES {
+            self.batches.pop_front();
+        }
+        self.batches.push_back(BatchMetadata {
+            first_sequence,
+            last_sequence,
+            base_offset,
+        });
+    }
+}
+
+fn next_sequence(sequence: i32) -> i32 {
+    if sequence == i32::MAX {
+        0
+    } else {
+        sequence + 1
+    }
+}
+
+// sequence 在 0..=i32::MAX 上循环, 落后 expected 不超过半圈的视为已经写入
+fn is_sequence_behind(sequence: i32, expected: i32) -> bool {
+    let modulus = i32::MAX as i64 + 1;
+    let distance = (expected as i64 - sequence as i64).rem_euclid(modulus);
+    distance > 0 && distance <= modulus / 2
+}
+
+#[derive(Debug, Default)]
+pub struct ProducerRegistry {
+    producers: HashMap<ProducerKey, ProducerState>,
+}
+
+impl ProducerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &ProducerKey) -> Option<&ProducerState> {
+        self.producers.get(key)
+    }
+
+    pub fn insert(&mut self, key: ProducerKey, state: ProducerState) {
+        self.producers.insert(key, state);
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct ProduceRequestBodyV9 {
+    transactional_id: CompactNullableString,
+    acks: i16,
+    timeout_ms: Millis,
+    topic_data: CompactArray<TopicProduceData>,
+    tag_buffer: TagBuffer,
+}
+
+impl ProduceRequestBodyV9 {
+    pub fn transactional_id(&self) -> Option<&str> {
+        self.transactional_id.as_deref()
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct TopicProduceData {
+    name: CompactString,
+    partition_data: CompactArray<PartitionProduceData>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct PartitionProduceData {
+    index: i32,
+    // 保留原始字节, 在 produce_partition 中 decode, 一个 partition 的 batch 损坏时不影响其他 partition
+    records: CompactNullableBytes,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct ProduceResponseBodyV9 {
+    responses: CompactArray<TopicProduceResponse>,
+    throttle_time_ms: Millis,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct TopicProduceResponse {
+    name: CompactString,
+    partition_responses: CompactArray<PartitionProduceResponse>,
+    tag_buffer: TagBuffer,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct PartitionProduceResponse {
+    index: i32,
+    error_code: i16,
+    base_offset: i64,
+    log_append_time_ms: i64,
+    log_start_offset: i64,
+    record_errors: CompactArray<BatchIndexAndErrorMessage>,
+    error_message: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+impl ProduceResponseBodyV9 {
+    // 请求的每个 partition 都返回同一个错误码
+    pub fn error(body: &ProduceRequestBodyV9, error_code: i16) -> Self {
+        let responses = body
+            .topic_data
+            .iter()
+            .flatten()
+            .map(|topic| TopicProduceResponse {
+                name: topic.name.clone(),
+                partition_responses: topic
+                    .partition_data
+                    .iter()
+                    .flatten()
+                    .map(|partition| PartitionProduceResponse {
+                        index: partition.index,
+                        error_code,
+                        base_offset: -1,
+                        log_append_time_ms: -1,
+                        log_start_offset: -1,
+                        record_errors: CompactArray::empty(),
+                        error_message: CompactNullableString::new(None),
+                        tag_buffer: TagBuffer::default(),
+                    })
+                    .collect(),
+                tag_buffer: TagBuffer::default(),
+            })
+            .collect();
+        Self {
+            responses,
+            throttle_time_ms: Millis(0),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+
+    pub fn responses(&self) -> &CompactArray<TopicProduceResponse> {
+        &self.responses
+    }
+}
+
+impl TopicProduceResponse {
+    pub fn name(&self) -> &CompactString {
+        &self.name
+    }
+
+    pub fn partition_responses(&self) -> &CompactArray<PartitionProduceResponse> {
+        &self.partition_responses
+    }
+}
+
+impl PartitionProduceResponse {
+    pub fn index(&self) -> i32 {
+        self.index
+    }
+
+    pub fn error_code(&self) -> i16 {
+        self.error_code
+    }
+
+    pub fn base_offset(&self) -> i64 {
+        self.base_offset
+    }
+
+    pub fn new_error(index: i32, error_code: i16, error_message: String) -> Self {
+        Self {
+            index,
+            error_code,
+            base_offset: -1,
+            log_append_time_ms: -1,
+            log_start_offset: -1,
+            record_errors: CompactArray::empty(),
+            error_message: CompactNullableString::new(Some(error_message)),
+            tag_buffer: TagBuffer::default(),
+        }
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct BatchIndexAndErrorMessage {
+    batch_index: i32,
+    batch_index_error_message: CompactNullableString,
+    tag_buffer: TagBuffer,
+}
+
+// 出错时返回错误码和错误信息
+pub fn validate_batch(
+    batch_index: usize,
+    record_batch: &RecordBatch,
+    max_message_bytes: usize,
+) -> Result<(), (i16, String)> {
+    // 超过 message.max.bytes 的 batch 写入后, 客户端可能无法在 fetch.max.bytes 内读取
+    let batch_size = record_batch.encoded_len();
+    if batch_size > max_message_bytes {
+        return Err((
+            MESSAGE_TOO_LARGE_ERROR,
+            format!(
+                "Batch {} is {} bytes, larger than max message bytes {}",
+                batch_index, batch_size, max_message_bytes
+            ),
+        ));
+    }
+    if record_batch.magic_byte != RECORD_BATCH_MAGIC {
+        return Err((
+            CORRUPT_MESSAGE_ERROR,
+            format!(
+                "Batch {} has unsupported magic byte {}",
+                batch_index, record_batch.magic_byte
+            ),
+        ));
+    }
+    if let Err(err) = record_batch.check_record_count() {
+        return Err((
+            CORRUPT_MESSAGE_ERROR,
+            format!("Batch {}: {}", batch_index, err),
+        ));
+    }
+    // 压缩的 batch 重新 encode 后与原始字节不同, 原始字节的 crc 已在 decode 时检查
+    let crc = record_batch.compute_crc();
+    if !record_batch.is_compressed() && crc != record_batch.crc as u32 {
+        return Err((
+            CORRUPT_MESSAGE_ERROR,
+            format!(
+                "Batch {} has invalid crc {:#010x}, expected {:#010x}",
+                batch_index, record_batch.crc as u32, crc
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn partition_exists(topic: &CompactString, partition: i32) -> bool {
+    TOPIC_INFO_MAP
+        .lock()
+        .expect("Failed to get TOPIC_INFO_MAP lock")
+        .get(topic)
+        .and_then(|topic_info| topic_info.partitions_array.as_ref())
+        .is_some_and(|partitions| partitions.iter().any(|p| p.index == partition))
+}
+
+// 每个 partition 独立处理, 一个 partition 失败不影响其他 partition
+fn produce_partition(
+    topic: &CompactString,
+    partition: &PartitionProduceData,
+) -> PartitionProduceResponse {
+    let index = partition.index;
+    if !is_metadata_ready() {
+        return PartitionProduceResponse::new_error(
+            index,
+            COORDINATOR_LOAD_IN_PROGRESS,
+            "Metadata is still loading".to_string(),
+        );
+    }
+    if BROKER_CONFIG.auto_create_topics && !topic_exists(topic.as_str()) {
+        if let Err(err) = create_topic(topic.as_str(), BROKER_CONFIG.num_partitions) {
+            tracing::error!("Failed to create topic {}: {}", topic.as_str(), err);
+        }
+    }
+    if !partition_exists(topic, index) {
+        return PartitionProduceResponse::new_error(
+            index,
+            UNKNOWN_TOPIC_OR_PARTITION,
+            format!("Unknown topic partition: {}-{}", topic.as_str(), index),
+        );
+    }
+
+    let mut record_batches = match partition
+        .records
+        .get_inner()
+        .as_deref()
+        .map(decode_record_batches)
+        .transpose()
+    {
+        Ok(record_batches) => record_batches.unwrap_or_default(),
+        Err(err) => {
+            return PartitionProduceResponse::new_error(
+                index,
+                CORRUPT_MESSAGE_ERROR,
+                format!("Invalid records: {}", err),
+            )
+        }
+    };
+    for (batch_index, record_batch) in record_batches.iter().enumerate() {
+        if let Err((error_code, err)) =
+            validate_batch(batch_index, record_batch, BROKER_CONFIG.max_message_bytes)
+        {
+            return PartitionProduceResponse::new_error(index, error_code, err);
+        }
+    }
+
+    let timestamp_type = topic_timestamp_type(topic.as_str());
+    let append_time = now_millis();
+
+    let log_path = partition_log_path(topic.as_str(), index);
+    // 计算 offset 和追加写入需要串行执行, 否则并发的 produce 会分配到相同的 offset
+    let partition_lock = partition_lock(topic.as_str(), index);
+    let _guard = partition_lock
+        .write()
+        .expect("Failed to get partition write lock");
+    let base_offset = match log_end_offset(&log_path) {
+        Ok(offset) => offset,
+        Err(err) => {
+            return PartitionProduceResponse::new_error(
+                index,
+                KAFKA_STORAGE_ERROR,
+                format!("Failed to read {}: {}", log_path.to_string_lossy(), err),
+            )
+        }
+    };
+
+    // 写入成功后才更新 PRODUCER_REGISTRY, 同一请求中的多个 batch 先在副本上检查
+    let mut producer_states: HashMap<i64, ProducerState> = HashMap::new();
+    let mut response_base_offset = None;
+    let mut next_offset = base_offset;
+    let mut log_content = vec![];
+    for (batch_index, record_batch) in record_batches.iter_mut().enumerate() {
+        if record_batch.is_idempotent() {
+            let producer_id = record_batch.producer_id;
+            let state = producer_states.entry(producer_id).or_insert_with(|| {
+                PRODUCER_REGISTRY
+                    .lock()
+                    .expect("Failed to get PRODUCER_REGISTRY lock")
+                    .get(&(producer_id, topic.as_str().to_string(), index))
+                    .cloned()
+                    .unwrap_or_else(|| ProducerState::new(record_batch.producer_epoch))
+            });
+            let first_sequence = record_batch.base_sequence;
+            let last_sequence = record_batch.last_sequence();
+            match state.check(record_batch.producer_epoch, first_sequence, last_sequence) {
+                Ok(SequenceCheck::Append) => state.append(
+                    record_batch.producer_epoch,
+                    first_sequence,
+                    last_sequence,
+                    next_offset,
+                ),
+                Ok(SequenceCheck::Duplicate(prior_offset)) => {
+                    response_base_offset.get_or_insert(prior_offset);
+                    continue;
+                }
+                Err((error_code, err)) => {
+                    return PartitionProduceResponse::new_error(
+                        index,
+                        error_code,
+                        format!("Batch {}: {}", batch_index, err),
+                    )
+                }
+            }
+        }
+        response_base_offset.get_or_insert(next_offset);
+        record_batch.base_offset = next_offset;
+        // base_offset 不在 crc 的范围内, 只有修改时间戳时需要重新计算 crc
+        if timestamp_type == TimestampType::LogAppendTime {
+            record_batch.apply_timestamp_type(timestamp_type, append_time);
+            record_batch.crc = record_batch.compute_crc() as i32;
+        }
+        next_offset = record_batch.next_offset();
+        log_content.append(&mut record_batch.encode());
+    }
+
+    let write_result = log_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&log_path))
+        .and_then(|mut file| file.write_all(&log_content));
+    if let Err(err) = write_result {
+        return PartitionProduceResponse::new_error(
+            index,
+            KAFKA_STORAGE_ERROR,
+            format!("Failed to write {}: {}", log_path.to_string_lossy(), err),
+        );
+    }
+    partition_notify(topic.as_str(), index).notify_waiters();
+    let mut producer_registry = PRODUCER_REGISTRY
+        .lock()
+        .expect("Failed to get PRODUCER_REGISTRY lock");
+    for (producer_id, state) in producer_states {
+        producer_registry.insert((producer_id, topic.as_str().to_string(), index), state);
+    }
+    drop(producer_registry);
+
+    PartitionProduceResponse {
+        index,
+        error_code: 0,
+        base_offset: response_base_offset.unwrap_or(base_offset),
+        log_append_time_ms: match timestamp_type {
+            TimestampType::CreateTime => -1,
+            TimestampType::LogAppendTime => append_time,
+        },
+        log_start_offset: 0,
+        record_errors: CompactArray::empty(),
+        error_message: CompactNullableString::new(None),
+        tag_buffer: TagBuffer::default(),
+    }
+}
+
+pub fn execute_produce(header: &RequestHeaderV2, body: &ProduceRequestBodyV9) -> ResponseMessage {
+    let request_api_version = header.request_api_version;
+    let correlation_id = header.correlation_id;
+
+    if request_api_version < PRODUCE_API_INFO.min_version
+        || request_api_version > PRODUCE_API_INFO.max_version
+    {
+        return ResponseMessage::new(
+            ResponseHeader::for_request(header),
+            ResponseBody::ProduceV9(ProduceResponseBodyV9::error(
+                body,
+                UNSUPPORTED_VERSION_ERROR,
+            )),
+        );
+    }
+
+    let responses = body
+        .topic_data
+        .iter()
+        .flatten()
+        .map(|topic| TopicProduceResponse {
+            name: topic.name.clone(),
+            partition_responses: topic
+                .partition_data
+                .iter()
+                .flatten()
+                .map(|partition| produce_partition(&topic.name, partition))
+                .collect(),
+            tag_buffer: TagBuffer::default(),
+        })
+        .collect();
+
+    ResponseMessage::new(
+        ResponseHeader::new_v1(correlation_id),
+        ResponseBody::ProduceV9(ProduceResponseBodyV9 {
+            responses,
+            throttle_time_ms: Millis(0),
+            tag_buffer: TagBuffer::default(),
+        }),
+    )
+}
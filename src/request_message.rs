@@ -1,17 +1,34 @@
 use std::io::Cursor;
 
 use crate::{
-    api_versions::{ApiVersionsReqeustBodyV4, API_VERSIONS_API_INFO},
-    common_struct::{CompactString, KafkaString, TagBuffer},
-    decode::{Decode, DecodeResult},
+    alter_configs::{
+        AlterConfigsRequestBodyV2, IncrementalAlterConfigsRequestBodyV1, ALTER_CONFIGS_API_INFO,
+        INCREMENTAL_ALTER_CONFIGS_API_INFO,
+    },
+    api_versions::{
+        request_header_version, ApiVersionsReqeustBodyV4, ApiVersionsRequestBodyV0,
+        API_VERSIONS_API_INFO, FIRST_FLEXIBLE_VERSIONS,
+    },
+    common_struct::{read_bytes_until, CompactString, NullableString, TagBuffer},
+    create_topics::{CreateTopicsRequestBodyV7, CREATE_TOPICS_API_INFO},
+    decode::{decode_bounded_with, Decode, DecodeError, DecodeResult},
     describe_topic_partitions::{
         DescribeTopicPartitionsRequestBodyV0, DESCRIBE_TOPIC_PARTITIONS_API_INFO,
     },
     encode::Encode,
     fetch::{FetchRequestBodyV16, FETCH_API_INFO},
+    find_coordinator::{FindCoordinatorRequestBodyV4, FIND_COORDINATOR_API_INFO},
+    init_producer_id::{InitProducerIdRequestBodyV5, INIT_PRODUCER_ID_API_INFO},
+    metadata::{MetadataRequestBodyV12, METADATA_API_INFO},
+    offset_fetch::{OffsetFetchRequestBodyV9, OFFSET_FETCH_API_INFO},
+    offset_for_leader_epoch::{
+        OffsetForLeaderEpochRequestBodyV4, OFFSET_FOR_LEADER_EPOCH_API_INFO,
+    },
+    offsets::{OffsetCommitRequestBodyV9, OFFSET_COMMIT_API_INFO},
+    produce::{ProduceRequestBodyV9, PRODUCE_API_INFO},
 };
 
-#[derive(Debug, Encode)]
+#[derive(Debug)]
 pub struct RequestMessage {
     #[allow(dead_code)]
     pub message_size: u32,
@@ -20,38 +37,44 @@ pub struct RequestMessage {
 }
 
 impl RequestMessage {
+    // encode 的结果不包含 message_size, 这里计算后放在最前面
     pub fn as_bytes(&mut self) -> Vec<u8> {
-        if self.message_size == 0 {
-            let mut encode_header = self.header.encode();
-            let mut encode_body = self.body.encode();
+        self.message_size = self.encoded_len() as u32;
+        let mut encode_vec = Vec::with_capacity(4 + self.message_size as usize);
+        encode_vec.extend_from_slice(&self.message_size.to_be_bytes());
+        self.encode_into(&mut encode_vec);
+        encode_vec
+    }
+}
+
+// message_size 由 as_bytes 负责, 不参与 encode
+impl Encode for RequestMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut encode_vec = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut encode_vec);
+        encode_vec
+    }
 
-            self.message_size = (encode_header.len() + encode_body.len()) as u32;
-            let mut encode_vec = self.message_size.to_be_bytes().to_vec();
-            encode_vec.append(&mut encode_header);
-            encode_vec.append(&mut encode_body);
+    fn encoded_len(&self) -> usize {
+        self.header.encoded_len() + self.body.encoded_len()
+    }
 
-            encode_vec
-        } else {
-            self.encode()
-        }
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.header.encode_into(out);
+        self.body.encode_into(out);
     }
 }
 
 impl Decode for RequestMessage {
     fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self> {
         let message_size = u32::decode(buffer)?;
-        let header = RequestHeader::RequestHeaderV2(RequestHeaderV2::decode(buffer)?);
-        let body = if header.request_api_key() == API_VERSIONS_API_INFO.api_key {
-            RequestBody::ApiVersionsV4(ApiVersionsReqeustBodyV4::decode(buffer)?)
-        } else if header.request_api_key() == DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key {
-            RequestBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsRequestBodyV0::decode(
-                buffer,
-            )?)
-        } else if header.request_api_key() == FETCH_API_INFO.api_key {
-            RequestBody::FetchV16(FetchRequestBodyV16::decode(buffer)?)
-        } else {
-            unimplemented!("Unknown request api key: {}", header.request_api_key());
-        };
+        // 整个请求都在 buffer 中之后才开始 decode, 且只在 message_size 的范围内 decode:
+        // 长度字段错误时直接返回错误, 不会等待永远不会到达的字节, 也不会读到下一个请求
+        let (header, body) = decode_bounded_with(buffer, message_size as usize, |buffer| {
+            let header = RequestHeader::decode(buffer)?;
+            let body = decode_request_body(&header, buffer, message_size as u64)?;
+            Ok((header, body))
+        })?;
         Ok(RequestMessage {
             message_size,
             header,
@@ -60,8 +83,100 @@ impl Decode for RequestMessage {
     }
 }
 
+// message_end 是 body 结束的位置, 无法识别的 body 直接跳到这里
+fn decode_request_body(
+    header: &RequestHeader,
+    buffer: &mut Cursor<&[u8]>,
+    message_end: u64,
+) -> DecodeResult<RequestBody> {
+    let body = if header.request_api_key() < 0 {
+        // 负数的 api key 一定是非法请求, 不需要再和已知的 api key 比较
+        // 无法识别的 api key 跳过整个 body, 保证后续请求的解析不受影响
+        RequestBody::Unknown(read_bytes_until(buffer, message_end)?)
+    } else if header.request_api_key() != API_VERSIONS_API_INFO.api_key
+        && matches!(header, RequestHeader::RequestHeaderV1(_))
+    {
+        // 除 ApiVersions 外只实现了 flexible 版本的 body, 跳过后返回 UNSUPPORTED_VERSION
+        RequestBody::Unknown(read_bytes_until(buffer, message_end)?)
+    } else if header.request_api_key() == API_VERSIONS_API_INFO.api_key {
+        match header {
+            RequestHeader::RequestHeaderV1(_) => {
+                RequestBody::ApiVersionsV0(ApiVersionsRequestBodyV0::decode(buffer)?)
+            }
+            RequestHeader::RequestHeaderV2(_) => {
+                RequestBody::ApiVersionsV4(ApiVersionsReqeustBodyV4::decode(buffer)?)
+            }
+        }
+    } else if header.request_api_key() == DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key {
+        RequestBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsRequestBodyV0::decode(
+            buffer,
+        )?)
+    } else if header.request_api_key() == FETCH_API_INFO.api_key {
+        RequestBody::FetchV16(FetchRequestBodyV16::decode(buffer)?)
+    } else if header.request_api_key() == ALTER_CONFIGS_API_INFO.api_key {
+        RequestBody::AlterConfigsV2(AlterConfigsRequestBodyV2::decode(buffer)?)
+    } else if header.request_api_key() == INCREMENTAL_ALTER_CONFIGS_API_INFO.api_key {
+        RequestBody::IncrementalAlterConfigsV1(IncrementalAlterConfigsRequestBodyV1::decode(
+            buffer,
+        )?)
+    } else if header.request_api_key() == PRODUCE_API_INFO.api_key {
+        RequestBody::ProduceV9(ProduceRequestBodyV9::decode(buffer)?)
+    } else if header.request_api_key() == OFFSET_FOR_LEADER_EPOCH_API_INFO.api_key {
+        RequestBody::OffsetForLeaderEpochV4(OffsetForLeaderEpochRequestBodyV4::decode(buffer)?)
+    } else if header.request_api_key() == METADATA_API_INFO.api_key {
+        RequestBody::MetadataV12(MetadataRequestBodyV12::decode(buffer)?)
+    } else if header.request_api_key() == CREATE_TOPICS_API_INFO.api_key {
+        RequestBody::CreateTopicsV7(CreateTopicsRequestBodyV7::decode(buffer)?)
+    } else if header.request_api_key() == FIND_COORDINATOR_API_INFO.api_key {
+        RequestBody::FindCoordinatorV4(FindCoordinatorRequestBodyV4::decode(buffer)?)
+    } else if header.request_api_key() == OFFSET_FETCH_API_INFO.api_key {
+        RequestBody::OffsetFetchV9(OffsetFetchRequestBodyV9::decode(buffer)?)
+    } else if header.request_api_key() == OFFSET_COMMIT_API_INFO.api_key {
+        RequestBody::OffsetCommitV9(OffsetCommitRequestBodyV9::decode(buffer)?)
+    } else if header.request_api_key() == INIT_PRODUCER_ID_API_INFO.api_key {
+        RequestBody::InitProducerIdV5(InitProducerIdRequestBodyV5::decode(buffer)?)
+    } else {
+        RequestBody::Unknown(read_bytes_until(buffer, message_end)?)
+    };
+    Ok(body)
+}
+
+pub fn decodable_api_versions(request_api_key: i16) -> Option<(i16, i16)> {
+    if request_api_key == API_VERSIONS_API_INFO.api_key {
+        // v0 到 v2 使用 ApiVersionsRequestBodyV0, ApiVersionsReqeustBodyV4 的结构同样适用于 v3
+        Some((0, 4))
+    } else if request_api_key == DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key {
+        Some((0, 0))
+    } else if request_api_key == FETCH_API_INFO.api_key {
+        Some((16, 16))
+    } else if request_api_key == ALTER_CONFIGS_API_INFO.api_key {
+        Some((2, 2))
+    } else if request_api_key == INCREMENTAL_ALTER_CONFIGS_API_INFO.api_key {
+        Some((1, 1))
+    } else if request_api_key == PRODUCE_API_INFO.api_key {
+        Some((9, 9))
+    } else if request_api_key == OFFSET_FOR_LEADER_EPOCH_API_INFO.api_key {
+        Some((4, 4))
+    } else if request_api_key == METADATA_API_INFO.api_key {
+        Some((12, 12))
+    } else if request_api_key == CREATE_TOPICS_API_INFO.api_key {
+        Some((7, 7))
+    } else if request_api_key == FIND_COORDINATOR_API_INFO.api_key {
+        Some((4, 4))
+    } else if request_api_key == OFFSET_FETCH_API_INFO.api_key
+        || request_api_key == OFFSET_COMMIT_API_INFO.api_key
+    {
+        Some((9, 9))
+    } else if request_api_key == INIT_PRODUCER_ID_API_INFO.api_key {
+        Some((5, 5))
+    } else {
+        None
+    }
+}
+
 #[derive(Debug)]
 pub enum RequestHeader {
+    RequestHeaderV1(RequestHeaderV1),
     RequestHeaderV2(RequestHeaderV2),
 }
 
@@ -70,7 +185,7 @@ impl RequestHeader {
         request_api_key: i16,
         request_api_version: i16,
         correlation_id: i32,
-        client_id: KafkaString,
+        client_id: NullableString,
         tag_buffer: TagBuffer,
     ) -> Self {
         RequestHeader::RequestHeaderV2(RequestHeaderV2 {
@@ -84,59 +199,204 @@ impl RequestHeader {
 
     pub fn request_api_key(&self) -> i16 {
         match self {
+            RequestHeader::RequestHeaderV1(header) => header.request_api_key,
             RequestHeader::RequestHeaderV2(header) => header.request_api_key,
         }
     }
+
+    pub fn request_api_version(&self) -> i16 {
+        match self {
+            RequestHeader::RequestHeaderV1(header) => header.request_api_version,
+            RequestHeader::RequestHeaderV2(header) => header.request_api_version,
+        }
+    }
+
+    pub fn correlation_id(&self) -> i32 {
+        match self {
+            RequestHeader::RequestHeaderV1(header) => header.correlation_id,
+            RequestHeader::RequestHeaderV2(header) => header.correlation_id,
+        }
+    }
+}
+
+// 先读出 api key 和版本再回退, 非 flexible 版本使用没有 tag_buffer 的 v1 header
+// 未实现的 api 不知道从哪个版本开始 flexible, 按 v2 解析
+impl Decode for RequestHeader {
+    fn decode(buffer: &mut Cursor<&[u8]>) -> DecodeResult<Self> {
+        let start = buffer.position();
+        let request_api_key = i16::decode(buffer)?;
+        let request_api_version = i16::decode(buffer)?;
+        buffer.set_position(start);
+        if FIRST_FLEXIBLE_VERSIONS.contains_key(&request_api_key)
+            && request_header_version(request_api_key, request_api_version) == 1
+        {
+            Ok(RequestHeader::RequestHeaderV1(RequestHeaderV1::decode(
+                buffer,
+            )?))
+        } else {
+            Ok(RequestHeader::RequestHeaderV2(RequestHeaderV2::decode(
+                buffer,
+            )?))
+        }
+    }
 }
 
 impl Encode for RequestHeader {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match self {
-            RequestHeader::RequestHeaderV2(header) => header.encode(),
+            RequestHeader::RequestHeaderV1(header) => header.encode_into(out),
+            RequestHeader::RequestHeaderV2(header) => header.encode_into(out),
         }
     }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            RequestHeader::RequestHeaderV1(header) => header.encoded_len(),
+            RequestHeader::RequestHeaderV2(header) => header.encoded_len(),
+        }
+    }
+}
+
+// 与 v2 相比没有 tag_buffer
+#[derive(Debug, PartialEq, Decode, Encode)]
+pub struct RequestHeaderV1 {
+    pub request_api_key: i16,
+    pub request_api_version: i16,
+    pub correlation_id: i32,
+    pub client_id: NullableString,
 }
 
-#[derive(Debug, Decode, Encode)]
+#[derive(Debug, PartialEq, Decode, Encode)]
 pub struct RequestHeaderV2 {
     pub request_api_key: i16,
     pub request_api_version: i16,
     pub correlation_id: i32,
-    pub client_id: KafkaString,
+    pub client_id: NullableString, // nullable string, 部分客户端发送 null
     pub tag_buffer: TagBuffer,
 }
 
+impl RequestHeaderV2 {
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    // 只解析 message_size 和 header, 不解析 body, 用于代理根据 api key 转发请求
+    pub fn peek_from(bytes: &[u8]) -> DecodeResult<RequestHeaderV2> {
+        let mut buffer = Cursor::new(bytes);
+        let message_size = u32::decode(&mut buffer)? as usize;
+        let header_start = buffer.position() as usize;
+        // header 不能超出当前请求的范围
+        let frame_end = bytes.len().min(header_start + message_size);
+        let mut frame = Cursor::new(&bytes[header_start..frame_end]);
+        match RequestHeaderV2::decode(&mut frame) {
+            Err(err) if err.is_incomplete() && frame_end - header_start == message_size => {
+                Err(DecodeError::Other(
+                    format!(
+                        "Request header is longer than message size {}",
+                        message_size
+                    )
+                    .into(),
+                ))
+            }
+            result => result,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum RequestBody {
+    ApiVersionsV0(ApiVersionsRequestBodyV0),
     ApiVersionsV4(ApiVersionsReqeustBodyV4),
     DescribeTopicPartitionsV0(DescribeTopicPartitionsRequestBodyV0),
     FetchV16(FetchRequestBodyV16),
+    AlterConfigsV2(AlterConfigsRequestBodyV2),
+    IncrementalAlterConfigsV1(IncrementalAlterConfigsRequestBodyV1),
+    ProduceV9(ProduceRequestBodyV9),
+    OffsetForLeaderEpochV4(OffsetForLeaderEpochRequestBodyV4),
+    MetadataV12(MetadataRequestBodyV12),
+    CreateTopicsV7(CreateTopicsRequestBodyV7),
+    FindCoordinatorV4(FindCoordinatorRequestBodyV4),
+    OffsetFetchV9(OffsetFetchRequestBodyV9),
+    OffsetCommitV9(OffsetCommitRequestBodyV9),
+    InitProducerIdV5(InitProducerIdRequestBodyV5),
+    Unknown(Vec<u8>),
 }
 
 impl Encode for RequestBody {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match self {
-            RequestBody::ApiVersionsV4(body) => body.encode(),
-            RequestBody::DescribeTopicPartitionsV0(body) => body.encode(),
-            RequestBody::FetchV16(body) => body.encode(),
+            RequestBody::ApiVersionsV0(body) => body.encode_into(out),
+            RequestBody::ApiVersionsV4(body) => body.encode_into(out),
+            RequestBody::DescribeTopicPartitionsV0(body) => body.encode_into(out),
+            RequestBody::FetchV16(body) => body.encode_into(out),
+            RequestBody::AlterConfigsV2(body) => body.encode_into(out),
+            RequestBody::IncrementalAlterConfigsV1(body) => body.encode_into(out),
+            RequestBody::ProduceV9(body) => body.encode_into(out),
+            RequestBody::OffsetForLeaderEpochV4(body) => body.encode_into(out),
+            RequestBody::MetadataV12(body) => body.encode_into(out),
+            RequestBody::CreateTopicsV7(body) => body.encode_into(out),
+            RequestBody::FindCoordinatorV4(body) => body.encode_into(out),
+            RequestBody::OffsetFetchV9(body) => body.encode_into(out),
+            RequestBody::OffsetCommitV9(body) => body.encode_into(out),
+            RequestBody::InitProducerIdV5(body) => body.encode_into(out),
+            RequestBody::Unknown(body) => out.extend_from_slice(body),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            RequestBody::ApiVersionsV0(body) => body.encoded_len(),
+            RequestBody::ApiVersionsV4(body) => body.encoded_len(),
+            RequestBody::DescribeTopicPartitionsV0(body) => body.encoded_len(),
+            RequestBody::FetchV16(body) => body.encoded_len(),
+            RequestBody::AlterConfigsV2(body) => body.encoded_len(),
+            RequestBody::IncrementalAlterConfigsV1(body) => body.encoded_len(),
+            RequestBody::ProduceV9(body) => body.encoded_len(),
+            RequestBody::OffsetForLeaderEpochV4(body) => body.encoded_len(),
+            RequestBody::MetadataV12(body) => body.encoded_len(),
+            RequestBody::CreateTopicsV7(body) => body.encoded_len(),
+            RequestBody::FindCoordinatorV4(body) => body.encoded_len(),
+            RequestBody::OffsetFetchV9(body) => body.encoded_len(),
+            RequestBody::OffsetCommitV9(body) => body.encoded_len(),
+            RequestBody::InitProducerIdV5(body) => body.encoded_len(),
+            RequestBody::Unknown(body) => body.len(),
         }
     }
 }
 
+// v3 之前的请求使用 v1 header 和空的 body
 pub fn request_api_versions(request_api_version: i16) -> RequestMessage {
+    let client_id = NullableString::new(Some("myclient".to_string()));
+    let (header, body) =
+        if request_header_version(API_VERSIONS_API_INFO.api_key, request_api_version) == 1 {
+            (
+                RequestHeader::RequestHeaderV1(RequestHeaderV1 {
+                    request_api_key: API_VERSIONS_API_INFO.api_key,
+                    request_api_version,
+                    correlation_id: 0,
+                    client_id,
+                }),
+                RequestBody::ApiVersionsV0(ApiVersionsRequestBodyV0),
+            )
+        } else {
+            (
+                RequestHeader::new_v2(
+                    API_VERSIONS_API_INFO.api_key,
+                    request_api_version,
+                    0,
+                    client_id,
+                    TagBuffer::default(),
+                ),
+                RequestBody::ApiVersionsV4(ApiVersionsReqeustBodyV4 {
+                    client_id: CompactString::new("myclient".to_string()),
+                    client_software_version: CompactString::new("0.1".to_string()),
+                    tag_buffer: TagBuffer::default(),
+                }),
+            )
+        };
     RequestMessage {
         message_size: 0,
-        header: RequestHeader::new_v2(
-            API_VERSIONS_API_INFO.api_key,
-            request_api_version,
-            0,
-            KafkaString::new("myclient".to_string()),
-            TagBuffer::default(),
-        ),
-        body: RequestBody::ApiVersionsV4(ApiVersionsReqeustBodyV4 {
-            client_id: CompactString::new("myclient".to_string()),
-            client_software_version: CompactString::new("0.1".to_string()),
-            tag_buffer: TagBuffer::default(),
-        }),
+        header,
+        body,
     }
 }
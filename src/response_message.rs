@@ -1,19 +1,47 @@
 use std::io::{self, Cursor};
 
+use bytes::Buf;
+
 use crate::{
-    api_versions::{execute_api_verions, ApiVersionsResponseBodyV4, API_VERSIONS_API_INFO},
-    common_struct::TagBuffer,
-    decode::{Decode, DecodeResult},
+    acl::Session,
+    alter_configs::{
+        execute_alter_configs, execute_incremental_alter_configs, AlterConfigsResponseBody,
+        ALTER_CONFIGS_API_INFO, INCREMENTAL_ALTER_CONFIGS_API_INFO,
+    },
+    api_versions::{
+        self, execute_api_verions, execute_api_versions_v0, is_api_disabled,
+        response_header_version, ApiVersionsResponseBodyV0, ApiVersionsResponseBodyV1,
+        ApiVersionsResponseBodyV4, API_VERSIONS_API_INFO, MUTATING_API_KEYS,
+        POLICY_VIOLATION_ERROR, UNSUPPORTED_VERSION_ERROR,
+    },
+    broker_config::BROKER_CONFIG,
+    common_struct::{read_bytes_until, TagBuffer},
+    create_topics::{execute_create_topics, CreateTopicsResponseBodyV7, CREATE_TOPICS_API_INFO},
+    decode::{Decode, DecodeError, DecodeResult},
     describe_topic_partitions::{
         execute_describe_topic_partitions, DescribeTopicPartitionsResponseBodyV0,
         DESCRIBE_TOPIC_PARTITIONS_API_INFO,
     },
     encode::Encode,
     fetch::{execute_fetch, FetchResponseBodyV16, FETCH_API_INFO},
-    request_message::{RequestBody, RequestHeader, RequestMessage},
+    find_coordinator::{
+        execute_find_coordinator, FindCoordinatorResponseBodyV4, FIND_COORDINATOR_API_INFO,
+    },
+    init_producer_id::{
+        execute_init_producer_id, InitProducerIdResponseBodyV5, INIT_PRODUCER_ID_API_INFO,
+    },
+    metadata::{execute_metadata, MetadataResponseBodyV12, METADATA_API_INFO},
+    offset_fetch::{execute_offset_fetch, OffsetFetchResponseBodyV9, OFFSET_FETCH_API_INFO},
+    offset_for_leader_epoch::{
+        execute_offset_for_leader_epoch, OffsetForLeaderEpochResponseBodyV4,
+        OFFSET_FOR_LEADER_EPOCH_API_INFO,
+    },
+    offsets::{execute_offset_commit, OffsetCommitResponseBodyV9, OFFSET_COMMIT_API_INFO},
+    produce::{execute_produce, ProduceResponseBodyV9, PRODUCE_API_INFO},
+    request_message::{RequestBody, RequestHeader, RequestHeaderV2, RequestMessage},
 };
 
-#[derive(Debug, Encode)]
+#[derive(Debug)]
 pub struct ResponseMessage {
     message_size: u32,
     header: ResponseHeader,
@@ -29,33 +57,65 @@ impl ResponseMessage {
         }
     }
 
-    pub fn as_bytes(&mut self) -> Vec<u8> {
-        if self.message_size == 0 {
-            let mut encode_header = self.header.encode();
-            let mut encode_body = self.body.encode();
+    pub fn header(&self) -> &ResponseHeader {
+        &self.header
+    }
 
-            self.message_size = (encode_header.len() + encode_body.len()) as u32;
-            let mut encode_vec = self.message_size.to_be_bytes().to_vec();
-            encode_vec.append(&mut encode_header);
-            encode_vec.append(&mut encode_body);
+    pub fn body(&self) -> &ResponseBody {
+        &self.body
+    }
 
-            encode_vec
-        } else {
-            self.encode()
-        }
+    // encode 的结果不包含 message_size, 这里计算后放在最前面
+    pub fn as_bytes(&mut self) -> Vec<u8> {
+        self.message_size = self.encoded_len() as u32;
+        let mut encode_vec = Vec::with_capacity(4 + self.message_size as usize);
+        encode_vec.extend_from_slice(&self.message_size.to_be_bytes());
+        self.encode_into(&mut encode_vec);
+        encode_vec
     }
 
     pub fn decode(buffer: &mut Cursor<&[u8]>, request_api_key: i16) -> DecodeResult<Self> {
         let message_size = u32::decode(buffer)?;
+        let message_end = buffer.position() + message_size as u64;
+        if buffer.remaining() < message_size as usize {
+            return Err(DecodeError::incomplete_needed(
+                message_size as usize - buffer.remaining(),
+            ));
+        }
         let header = ResponseHeader::ResponseHeaderV1(ResponseHeaderV1::decode(buffer)?);
-        let body = if request_api_key == API_VERSIONS_API_INFO.api_key {
+        let body = if request_api_key < 0 {
+            ResponseBody::Unknown(read_bytes_until(buffer, message_end)?)
+        } else if request_api_key == API_VERSIONS_API_INFO.api_key {
             ResponseBody::ApiVersionsV4(ApiVersionsResponseBodyV4::decode(buffer)?)
         } else if request_api_key == DESCRIBE_TOPIC_PARTITIONS_API_INFO.api_key {
             ResponseBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsResponseBodyV0::decode(
                 buffer,
             )?)
+        } else if request_api_key == ALTER_CONFIGS_API_INFO.api_key {
+            ResponseBody::AlterConfigsV2(AlterConfigsResponseBody::decode(buffer)?)
+        } else if request_api_key == INCREMENTAL_ALTER_CONFIGS_API_INFO.api_key {
+            ResponseBody::IncrementalAlterConfigsV1(AlterConfigsResponseBody::decode(buffer)?)
+        } else if request_api_key == PRODUCE_API_INFO.api_key {
+            ResponseBody::ProduceV9(ProduceResponseBodyV9::decode(buffer)?)
+        } else if request_api_key == OFFSET_FOR_LEADER_EPOCH_API_INFO.api_key {
+            ResponseBody::OffsetForLeaderEpochV4(OffsetForLeaderEpochResponseBodyV4::decode(
+                buffer,
+            )?)
+        } else if request_api_key == METADATA_API_INFO.api_key {
+            ResponseBody::MetadataV12(MetadataResponseBodyV12::decode(buffer)?)
+        } else if request_api_key == CREATE_TOPICS_API_INFO.api_key {
+            ResponseBody::CreateTopicsV7(CreateTopicsResponseBodyV7::decode(buffer)?)
+        } else if request_api_key == FIND_COORDINATOR_API_INFO.api_key {
+            ResponseBody::FindCoordinatorV4(FindCoordinatorResponseBodyV4::decode(buffer)?)
+        } else if request_api_key == OFFSET_FETCH_API_INFO.api_key {
+            ResponseBody::OffsetFetchV9(OffsetFetchResponseBodyV9::decode(buffer)?)
+        } else if request_api_key == OFFSET_COMMIT_API_INFO.api_key {
+            ResponseBody::OffsetCommitV9(OffsetCommitResponseBodyV9::decode(buffer)?)
+        } else if request_api_key == INIT_PRODUCER_ID_API_INFO.api_key {
+            ResponseBody::InitProducerIdV5(InitProducerIdResponseBodyV5::decode(buffer)?)
         } else {
-            unimplemented!("Unknown request api key: {}", request_api_key);
+            // 无法识别的 api key 原样保留剩余的字节, 用于转发
+            ResponseBody::Unknown(read_bytes_until(buffer, message_end)?)
         };
         Ok(ResponseMessage {
             message_size,
@@ -65,6 +125,24 @@ impl ResponseMessage {
     }
 }
 
+// message_size 由 as_bytes 负责, 不参与 encode
+impl Encode for ResponseMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut encode_vec = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut encode_vec);
+        encode_vec
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.header.encoded_len() + self.body.encoded_len()
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.header.encode_into(out);
+        self.body.encode_into(out);
+    }
+}
+
 #[derive(Debug)]
 pub enum ResponseHeader {
     ResponseHeaderV0(ResponseHeaderV0),
@@ -82,13 +160,35 @@ impl ResponseHeader {
             tag_buffer: TagBuffer::default(),
         })
     }
+
+    // 根据请求的 api key 和版本选择 header 的版本
+    pub fn for_request(header: &RequestHeaderV2) -> Self {
+        match response_header_version(header.request_api_key, header.request_api_version) {
+            0 => ResponseHeader::new_v0(header.correlation_id),
+            _ => ResponseHeader::new_v1(header.correlation_id),
+        }
+    }
+
+    pub fn version(&self) -> i16 {
+        match self {
+            ResponseHeader::ResponseHeaderV0(_) => 0,
+            ResponseHeader::ResponseHeaderV1(_) => 1,
+        }
+    }
 }
 
 impl Encode for ResponseHeader {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
         match self {
-            ResponseHeader::ResponseHeaderV0(header) => header.encode(),
-            ResponseHeader::ResponseHeaderV1(header) => header.encode(),
+            ResponseHeader::ResponseHeaderV0(header) => header.encode_into(out),
+            ResponseHeader::ResponseHeaderV1(header) => header.encode_into(out),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            ResponseHeader::ResponseHeaderV0(header) => header.encoded_len(),
+            ResponseHeader::ResponseHeaderV1(header) => header.encoded_len(),
         }
     }
 }
@@ -98,7 +198,7 @@ pub struct ResponseHeaderV0 {
     correlation_id: i32,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct ResponseHeaderV1 {
     correlation_id: i32,
     tag_buffer: TagBuffer,
@@ -106,22 +206,140 @@ pub struct ResponseHeaderV1 {
 
 #[derive(Debug)]
 pub enum ResponseBody {
+    ApiVersionsV0(ApiVersionsResponseBodyV0),
+    ApiVersionsV1(ApiVersionsResponseBodyV1),
     ApiVersionsV4(ApiVersionsResponseBodyV4),
     DescribeTopicPartitionsV0(DescribeTopicPartitionsResponseBodyV0),
     FetchV16(FetchResponseBodyV16),
+    AlterConfigsV2(AlterConfigsResponseBody),
+    IncrementalAlterConfigsV1(AlterConfigsResponseBody),
+    ProduceV9(ProduceResponseBodyV9),
+    OffsetForLeaderEpochV4(OffsetForLeaderEpochResponseBodyV4),
+    MetadataV12(MetadataResponseBodyV12),
+    CreateTopicsV7(CreateTopicsResponseBodyV7),
+    FindCoordinatorV4(FindCoordinatorResponseBodyV4),
+    OffsetFetchV9(OffsetFetchResponseBodyV9),
+    OffsetCommitV9(OffsetCommitResponseBodyV9),
+    InitProducerIdV5(InitProducerIdResponseBodyV5),
+    Unknown(Vec<u8>),
 }
 
 impl Encode for ResponseBody {
-    fn encode(&self) -> Vec<u8> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            ResponseBody::ApiVersionsV0(inner) => inner.encode_into(out),
+            ResponseBody::ApiVersionsV1(inner) => inner.encode_into(out),
+            ResponseBody::ApiVersionsV4(inner) => inner.encode_into(out),
+            ResponseBody::DescribeTopicPartitionsV0(inner) => inner.encode_into(out),
+            ResponseBody::FetchV16(inner) => inner.encode_into(out),
+            ResponseBody::AlterConfigsV2(inner) => inner.encode_into(out),
+            ResponseBody::IncrementalAlterConfigsV1(inner) => inner.encode_into(out),
+            ResponseBody::ProduceV9(inner) => inner.encode_into(out),
+            ResponseBody::OffsetForLeaderEpochV4(inner) => inner.encode_into(out),
+            ResponseBody::MetadataV12(inner) => inner.encode_into(out),
+            ResponseBody::CreateTopicsV7(inner) => inner.encode_into(out),
+            ResponseBody::FindCoordinatorV4(inner) => inner.encode_into(out),
+            ResponseBody::OffsetFetchV9(inner) => inner.encode_into(out),
+            ResponseBody::OffsetCommitV9(inner) => inner.encode_into(out),
+            ResponseBody::InitProducerIdV5(inner) => inner.encode_into(out),
+            ResponseBody::Unknown(inner) => out.extend_from_slice(inner),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
         match self {
-            ResponseBody::ApiVersionsV4(inner) => inner.encode(),
-            ResponseBody::DescribeTopicPartitionsV0(inner) => inner.encode(),
-            ResponseBody::FetchV16(inner) => inner.encode(),
+            ResponseBody::ApiVersionsV0(inner) => inner.encoded_len(),
+            ResponseBody::ApiVersionsV1(inner) => inner.encoded_len(),
+            ResponseBody::ApiVersionsV4(inner) => inner.encoded_len(),
+            ResponseBody::DescribeTopicPartitionsV0(inner) => inner.encoded_len(),
+            ResponseBody::FetchV16(inner) => inner.encoded_len(),
+            ResponseBody::AlterConfigsV2(inner) => inner.encoded_len(),
+            ResponseBody::IncrementalAlterConfigsV1(inner) => inner.encoded_len(),
+            ResponseBody::ProduceV9(inner) => inner.encoded_len(),
+            ResponseBody::OffsetForLeaderEpochV4(inner) => inner.encoded_len(),
+            ResponseBody::MetadataV12(inner) => inner.encoded_len(),
+            ResponseBody::CreateTopicsV7(inner) => inner.encoded_len(),
+            ResponseBody::FindCoordinatorV4(inner) => inner.encoded_len(),
+            ResponseBody::OffsetFetchV9(inner) => inner.encoded_len(),
+            ResponseBody::OffsetCommitV9(inner) => inner.encoded_len(),
+            ResponseBody::InitProducerIdV5(inner) => inner.encoded_len(),
+            ResponseBody::Unknown(inner) => inner.len(),
         }
     }
 }
 
-pub async fn execute_request(request: &RequestMessage) -> io::Result<ResponseMessage> {
+// 使用请求对应 api 的响应结构返回错误, 只有 ApiVersions 请求返回 ApiVersions 的 body
+pub fn error_response(request: &RequestMessage, error_code: i16) -> ResponseMessage {
+    let correlation_id = request.header.correlation_id();
+    let header = match response_header_version(
+        request.header.request_api_key(),
+        request.header.request_api_version(),
+    ) {
+        0 => ResponseHeader::new_v0(correlation_id),
+        _ => ResponseHeader::new_v1(correlation_id),
+    };
+    let body = match (&request.header, &request.body) {
+        (RequestHeader::RequestHeaderV1(header), RequestBody::ApiVersionsV0(_)) => {
+            return api_versions::error_response_v0(header, error_code);
+        }
+        (_, RequestBody::ApiVersionsV4(_)) => {
+            return api_versions::error_response(correlation_id, error_code);
+        }
+        (_, RequestBody::DescribeTopicPartitionsV0(body)) => {
+            ResponseBody::DescribeTopicPartitionsV0(DescribeTopicPartitionsResponseBodyV0::error(
+                body, error_code,
+            ))
+        }
+        (_, RequestBody::FetchV16(_)) => {
+            ResponseBody::FetchV16(FetchResponseBodyV16::error(error_code))
+        }
+        (_, RequestBody::AlterConfigsV2(body)) => ResponseBody::AlterConfigsV2(
+            AlterConfigsResponseBody::alter_configs_error(body, error_code),
+        ),
+        (_, RequestBody::IncrementalAlterConfigsV1(body)) => {
+            ResponseBody::IncrementalAlterConfigsV1(
+                AlterConfigsResponseBody::incremental_alter_configs_error(body, error_code),
+            )
+        }
+        (_, RequestBody::ProduceV9(body)) => {
+            ResponseBody::ProduceV9(ProduceResponseBodyV9::error(body, error_code))
+        }
+        (_, RequestBody::OffsetForLeaderEpochV4(body)) => ResponseBody::OffsetForLeaderEpochV4(
+            OffsetForLeaderEpochResponseBodyV4::error(body, error_code),
+        ),
+        (_, RequestBody::MetadataV12(body)) => {
+            ResponseBody::MetadataV12(MetadataResponseBodyV12::error(body, error_code))
+        }
+        (_, RequestBody::CreateTopicsV7(body)) => {
+            ResponseBody::CreateTopicsV7(CreateTopicsResponseBodyV7::error(body, error_code))
+        }
+        (_, RequestBody::FindCoordinatorV4(body)) => {
+            ResponseBody::FindCoordinatorV4(FindCoordinatorResponseBodyV4::error(body, error_code))
+        }
+        (_, RequestBody::OffsetFetchV9(body)) => {
+            ResponseBody::OffsetFetchV9(OffsetFetchResponseBodyV9::error(body, error_code))
+        }
+        (_, RequestBody::OffsetCommitV9(body)) => {
+            ResponseBody::OffsetCommitV9(OffsetCommitResponseBodyV9::error(body, error_code))
+        }
+        (_, RequestBody::InitProducerIdV5(_)) => {
+            ResponseBody::InitProducerIdV5(InitProducerIdResponseBodyV5::error(error_code))
+        }
+        // 无法识别的 api key 或无法解析的版本没有对应的响应结构, 只返回错误码
+        (_, _) => {
+            return ResponseMessage::new(
+                ResponseHeader::new_v0(correlation_id),
+                ResponseBody::Unknown(error_code.to_be_bytes().to_vec()),
+            );
+        }
+    };
+    ResponseMessage::new(header, body)
+}
+
+pub async fn execute_request(
+    request: &RequestMessage,
+    session: &Session,
+) -> io::Result<ResponseMessage> {
     let request_api_key = request.header.request_api_key();
     let create_err = |header, body| {
         Err(io::Error::new(
@@ -135,8 +353,26 @@ Support Request Header v2, Describe Topic Partitions V0.",
             ),
         ))
     };
+    if let RequestBody::Unknown(_) = request.body {
+        tracing::warn!("Reject unknown request api key {}", request_api_key);
+        return Ok(error_response(request, UNSUPPORTED_VERSION_ERROR));
+    }
+    if is_api_disabled(request_api_key) {
+        tracing::warn!("Reject disabled request api key {}", request_api_key);
+        return Ok(error_response(request, UNSUPPORTED_VERSION_ERROR));
+    }
+    if BROKER_CONFIG.read_only && MUTATING_API_KEYS.contains(&request_api_key) {
+        tracing::warn!(
+            "Reject mutating request api key {} in read-only mode",
+            request_api_key
+        );
+        return Ok(error_response(request, POLICY_VIOLATION_ERROR));
+    }
     if request_api_key == API_VERSIONS_API_INFO.api_key {
         match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV1(header), RequestBody::ApiVersionsV0(body)) => {
+                Ok(execute_api_versions_v0(header, body))
+            }
             (RequestHeader::RequestHeaderV2(header), RequestBody::ApiVersionsV4(body)) => {
                 Ok(execute_api_verions(header, body))
             }
@@ -147,13 +383,84 @@ Support Request Header v2, Describe Topic Partitions V0.",
             (
                 RequestHeader::RequestHeaderV2(header),
                 RequestBody::DescribeTopicPartitionsV0(body),
-            ) => Ok(execute_describe_topic_partitions(header, body)),
+            ) => Ok(execute_describe_topic_partitions(header, body, session)),
             (header, body) => create_err(header, body),
         }
     } else if request_api_key == FETCH_API_INFO.api_key {
         match (&request.header, &request.body) {
             (RequestHeader::RequestHeaderV2(header), RequestBody::FetchV16(body)) => {
-                Ok(execute_fetch(header, body))
+                Ok(execute_fetch(header, body).await)
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == ALTER_CONFIGS_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::AlterConfigsV2(body)) => {
+                Ok(execute_alter_configs(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == INCREMENTAL_ALTER_CONFIGS_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (
+                RequestHeader::RequestHeaderV2(header),
+                RequestBody::IncrementalAlterConfigsV1(body),
+            ) => Ok(execute_incremental_alter_configs(header, body)),
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == PRODUCE_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::ProduceV9(body)) => {
+                Ok(execute_produce(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == OFFSET_FOR_LEADER_EPOCH_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::OffsetForLeaderEpochV4(body)) => {
+                Ok(execute_offset_for_leader_epoch(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == METADATA_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::MetadataV12(body)) => {
+                Ok(execute_metadata(header, body, session))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == CREATE_TOPICS_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::CreateTopicsV7(body)) => {
+                Ok(execute_create_topics(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == FIND_COORDINATOR_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::FindCoordinatorV4(body)) => {
+                Ok(execute_find_coordinator(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == OFFSET_FETCH_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::OffsetFetchV9(body)) => {
+                Ok(execute_offset_fetch(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == OFFSET_COMMIT_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::OffsetCommitV9(body)) => {
+                Ok(execute_offset_commit(header, body))
+            }
+            (header, body) => create_err(header, body),
+        }
+    } else if request_api_key == INIT_PRODUCER_ID_API_INFO.api_key {
+        match (&request.header, &request.body) {
+            (RequestHeader::RequestHeaderV2(header), RequestBody::InitProducerIdV5(body)) => {
+                Ok(execute_init_producer_id(header, body))
             }
             (header, body) => create_err(header, body),
         }
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    broker_config::BROKER_CONFIG,
+    common_struct::RecordBatch,
+    decode::DecodeResult,
+    metadata_log::{segment_log_path, RecordBatchReader},
+};
+
+lazy_static! {
+    // fetch 使用的 segment 缓存, 按 decode 前的文件大小计算占用
+    pub static ref SEGMENT_CACHE: Mutex<SegmentCache> =
+        Mutex::new(SegmentCache::new(BROKER_CONFIG.segment_cache_bytes));
+}
+
+// (topic, partition, segment 的 base offset)
+pub type SegmentKey = (String, i32, i64);
+
+struct CachedSegment {
+    record_batches: Arc<Vec<RecordBatch>>,
+    // 缓存时的文件大小, 文件被追加后只需要 decode 这个位置之后的 batch
+    file_size: usize,
+    last_used: u64,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SegmentCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct SegmentCache {
+    capacity: usize,
+    size: usize,
+    // 每次访问递增, 淘汰 last_used 最小的 segment
+    tick: u64,
+    segments: HashMap<SegmentKey, CachedSegment>,
+    #[cfg(feature = "metrics")]
+    stats: SegmentCacheStats,
+}
+
+impl SegmentCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            tick: 0,
+            segments: HashMap::new(),
+            #[cfg(feature = "metrics")]
+            stats: SegmentCacheStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn contains(&self, key: &SegmentKey) -> bool {
+        self.segments.contains_key(key)
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> SegmentCacheStats {
+        self.stats
+    }
+
+    // 文件大小与缓存时不一致时视为未命中
+    pub fn get(&mut self, key: &SegmentKey, file_size: usize) -> Option<Arc<Vec<RecordBatch>>> {
+        self.tick += 1;
+        let record_batches = match self.segments.get_mut(key) {
+            Some(segment) if segment.file_size == file_size => {
+                segment.last_used = self.tick;
+                Some(segment.record_batches.clone())
+            }
+            _ => None,
+        };
+        #[cfg(feature = "metrics")]
+        match record_batches {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        record_batches
+    }
+
+    // 文件在缓存之后被追加时取出缓存的 batch 和缓存时的文件大小, 由调用方补上追加的 batch 后重新插入
+    // 取出而不是复制, 没有其他 fetch 持有时可以直接在原来的 Vec 上追加
+    pub fn take_appended(
+        &mut self,
+        key: &SegmentKey,
+        file_size: usize,
+    ) -> Option<(Arc<Vec<RecordBatch>>, usize)> {
+        match self.segments.get(key) {
+            Some(segment) if segment.file_size < file_size => {
+                let segment = self.segments.remove(key)?;
+                self.size -= segment.file_size;
+                Some((segment.record_batches, segment.file_size))
+            }
+            _ => None,
+        }
+    }
+
+    // 超过容量的 segment 不缓存, 总大小超过 (而不是等于) 容量时才淘汰最久未使用的 segment
+    pub fn insert(
+        &mut self,
+        key: SegmentKey,
+        file_size: usize,
+        record_batches: Arc<Vec<RecordBatch>>,
+    ) {
+        self.remove(&key);
+        if file_size > self.capacity {
+            return;
+        }
+        while self.size + file_size > self.capacity {
+            let Some(coldest) = self
+                .segments
+                .iter()
+                .min_by_key(|(_, segment)| segment.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&coldest);
+        }
+        self.tick += 1;
+        self.size += file_size;
+        self.segments.insert(
+            key,
+            CachedSegment {
+                record_batches,
+                file_size,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &SegmentKey) {
+        if let Some(segment) = self.segments.remove(key) {
+            self.size -= segment.file_size;
+        }
+    }
+}
+
+// 命中时直接返回缓存的 batch, 文件被追加时只从磁盘 decode 追加的 batch, 否则从头逐个 batch 读取
+// 超过缓存容量的 segment 不会被缓存, 返回 None, 由调用方按 fetch 的大小限制流式读取
+// 读取文件时不持有 SEGMENT_CACHE 的锁, 避免阻塞其他 partition 的 fetch
+pub fn cached_segment(
+    topic: &str,
+    partition: i32,
+    base_offset: i64,
+) -> DecodeResult<Option<Arc<Vec<RecordBatch>>>> {
+    let path = segment_log_path(topic, partition, base_offset);
+    let file_size = segment_file_size(&path);
+    let key = (topic.to_string(), partition, base_offset);
+    let (mut record_batches, cached_size) = {
+        let mut segment_cache = SEGMENT_CACHE
+            .lock()
+            .expect("Failed to get SEGMENT_CACHE lock");
+        if let Some(record_batches) = segment_cache.get(&key, file_size) {
+            return Ok(Some(record_batches));
+        }
+        if file_size > segment_cache.capacity() {
+            segment_cache.remove(&key);
+            return Ok(None);
+        }
+        segment_cache
+            .take_appended(&key, file_size)
+            .unwrap_or_default()
+    };
+
+    let appended =
+        RecordBatchReader::open_at(&path, cached_size as u64)?.collect::<DecodeResult<Vec<_>>>()?;
+    Arc::make_mut(&mut record_batches).extend(appended);
+    SEGMENT_CACHE
+        .lock()
+        .expect("Failed to get SEGMENT_CACHE lock")
+        .insert(key, file_size, record_batches.clone());
+    Ok(Some(record_batches))
+}
+
+fn segment_file_size(path: &Path) -> usize {
+    fs::metadata(path)
+        .map(|metadata| metadata.len() as usize)
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "metrics")]
+pub fn segment_cache_stats() -> SegmentCacheStats {
+    SEGMENT_CACHE
+        .lock()
+        .expect("Failed to get SEGMENT_CACHE lock")
+        .stats()
+}